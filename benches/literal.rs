@@ -0,0 +1,72 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Literal heavy benchmark: incompressible input misses the primary context on nearly
+//! every byte, so almost all the time goes into the literal path of the byte coder.
+//!
+//! Run with `cargo bench --bench literal`.
+
+use srx::{compress_with, decompress, AnyResult, Config};
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const INPUT_SIZE: usize = 8 << 20;
+const ROUNDS: usize = 3;
+
+fn random_bytes(length: usize) -> Vec<u8> {
+	// xorshift, good enough to defeat the model
+	let mut state: u64 = 0x9E3779B97F4A7C15;
+	(0..length)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			(state >> 32) as u8
+		})
+		.collect()
+}
+
+fn best_of<T>(mut run: impl FnMut() -> AnyResult<T>) -> AnyResult<Duration> {
+	let mut best: Duration = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let start: Instant = Instant::now();
+		run()?;
+		best = best.min(start.elapsed());
+	}
+	Ok(best)
+}
+
+fn report(name: &str, duration: Duration) {
+	let speed: f64 = INPUT_SIZE as f64 / duration.as_secs_f64() / (1 << 20) as f64;
+	println!(
+		"{:>12}: {:8.3} ms ({:.2} MiB/s)",
+		name,
+		duration.as_secs_f64() * 1000.0,
+		speed
+	);
+}
+
+fn main() -> AnyResult<()> {
+	let input: Vec<u8> = random_bytes(INPUT_SIZE);
+	let config: Config = Config::default();
+	let compressed: Vec<u8> = compress_with(&input, &config)?;
+	report("compress", best_of(|| compress_with(&input, &config))?);
+	report("decompress", best_of(|| decompress(&compressed))?);
+	Ok(())
+}
@@ -0,0 +1,86 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Message buffer benchmark: compresses the same text through message pipes of every size from
+//! the smallest allowed one up, to show where smaller buffers start to cost speed.
+//!
+//! Run with `cargo bench --bench message_buffer`.
+
+use srx::{encode_with, AnyResult, Config, IO_BUFFER_SIZE, MIN_MESSAGE_BUFFER_SIZE};
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const INPUT_SIZE: usize = 8 << 20;
+const ROUNDS: usize = 3;
+
+fn text(length: usize) -> Vec<u8> {
+	// the license with a counter in every line, compressible but not a plain repeat
+	let license: &str = include_str!("../LICENSE");
+	let mut text: Vec<u8> = Vec::with_capacity(length + license.len());
+	let mut count: usize = 0;
+	while text.len() < length {
+		for line in license.lines() {
+			text.extend_from_slice(format!("{} {}\n", count, line).as_bytes());
+			count += 1;
+		}
+	}
+	text.truncate(length);
+	text
+}
+
+fn best_of<const MESSAGE_BUFFER_SIZE: usize>(input: &[u8]) -> AnyResult<Duration> {
+	let mut best: Duration = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let start: Instant = Instant::now();
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			input,
+			Vec::new(),
+			&Config::default(),
+		)?;
+		best = best.min(start.elapsed());
+	}
+	Ok(best)
+}
+
+fn report(size: usize, duration: Duration) {
+	let speed: f64 = INPUT_SIZE as f64 / duration.as_secs_f64() / (1 << 20) as f64;
+	println!(
+		"{:>8} messages: {:8.3} ms ({:.2} MiB/s)",
+		size,
+		duration.as_secs_f64() * 1000.0,
+		speed
+	);
+}
+
+fn main() -> AnyResult<()> {
+	let input: Vec<u8> = text(INPUT_SIZE);
+	report(
+		MIN_MESSAGE_BUFFER_SIZE,
+		best_of::<MIN_MESSAGE_BUFFER_SIZE>(&input)?,
+	);
+	report(16, best_of::<16>(&input)?);
+	report(64, best_of::<64>(&input)?);
+	report(256, best_of::<256>(&input)?);
+	report(0x400, best_of::<0x400>(&input)?);
+	report(0x1000, best_of::<0x1000>(&input)?);
+	report(0x4000, best_of::<0x4000>(&input)?);
+	report(0x10000, best_of::<0x10000>(&input)?);
+	report(0x40000, best_of::<0x40000>(&input)?);
+	Ok(())
+}
@@ -0,0 +1,107 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Mapped input benchmark: compresses the same 100 MiB file with srx c, reading it and then
+//! mapping it in memory, to show what --mmap saves over the read calls of the reader stage.
+//!
+//! Run with `cargo bench --bench mmap`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const INPUT_SIZE: usize = 100 << 20;
+const ROUNDS: usize = 3;
+
+fn text(length: usize) -> Vec<u8> {
+	// the license with a counter in every line, compressible but not a plain repeat
+	let license: &str = include_str!("../LICENSE");
+	let mut text: Vec<u8> = Vec::with_capacity(length + license.len());
+	let mut count: usize = 0;
+	while text.len() < length {
+		for line in license.lines() {
+			text.extend_from_slice(format!("{} {}\n", count, line).as_bytes());
+			count += 1;
+		}
+	}
+	text.truncate(length);
+	text
+}
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("srx-bench-mmap-{}-{}", std::process::id(), name))
+}
+
+fn compress(policy: &str, input_path: &Path, output_path: &Path) -> io::Result<Duration> {
+	let start: Instant = Instant::now();
+	let status = Command::new(env!("CARGO_BIN_EXE_srx"))
+		.args(["c", "--force", policy])
+		.arg(input_path)
+		.arg(output_path)
+		.output()?
+		.status;
+	let duration: Duration = start.elapsed();
+	if !status.success() {
+		return Err(io::Error::other(format!("srx c {} failed!", policy)));
+	}
+	Ok(duration)
+}
+
+// the best of the rounds of each, taken in turns so that the load of the machine changing in
+// between does not favor either
+fn best_of(input_path: &Path, output_path: &Path) -> io::Result<(Duration, Duration)> {
+	let (mut read, mut mapped): (Duration, Duration) = (Duration::MAX, Duration::MAX);
+	for _ in 0..ROUNDS {
+		read = read.min(compress("--mmap=never", input_path, output_path)?);
+		mapped = mapped.min(compress("--mmap=always", input_path, output_path)?);
+	}
+	Ok((read, mapped))
+}
+
+fn report(policy: &str, duration: Duration) {
+	let speed: f64 = INPUT_SIZE as f64 / duration.as_secs_f64() / (1 << 20) as f64;
+	println!(
+		"{:>14}: {:8.3} ms ({:.2} MiB/s)",
+		policy,
+		duration.as_secs_f64() * 1000.0,
+		speed
+	);
+}
+
+fn main() -> io::Result<()> {
+	let input_path: PathBuf = temp_path("input");
+	let output_path: PathBuf = temp_path("output");
+	fs::write(&input_path, text(INPUT_SIZE))?;
+	let result: io::Result<(Duration, Duration)> = best_of(&input_path, &output_path);
+	for path in [&input_path, &output_path] {
+		let _ = fs::remove_file(path);
+	}
+	let (read, mapped): (Duration, Duration) = result?;
+	report("--mmap=never", read);
+	report("--mmap=always", mapped);
+	println!(
+		"{:>14}: {:+.2}%",
+		"speedup",
+		(read.as_secs_f64() / mapped.as_secs_f64() - 1.0) * 100.0
+	);
+	Ok(())
+}
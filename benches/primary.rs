@@ -0,0 +1,98 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Primary context benchmark: the hash reduction on its own with `% SIZE` and with
+//! `& (SIZE - 1)`, then the whole matching loop of the primary context.
+//!
+//! Run with `cargo bench --features internals --bench primary`.
+
+use srx::internals::{Byte, HistoryState, PrimaryContext, PrimaryTable};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const SIZE: usize = 1 << 24;
+const INPUT_SIZE: usize = 32 << 20;
+const ROUNDS: usize = 3;
+
+fn text_like_bytes(length: usize) -> Vec<u8> {
+	// a few repeating words, so that the loop sees both matches and misses
+	const WORDS: &[&[u8]] = &[
+		b"the ", b"quick ", b"brown ", b"fox ", b"jumps ", b"over ", b"lazy ", b"dog\n",
+	];
+	let mut state: u32 = 1;
+	let mut output: Vec<u8> = Vec::with_capacity(length + 8);
+	while output.len() < length {
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		output.extend_from_slice(WORDS[(state >> 29) as usize]);
+	}
+	output.truncate(length);
+	output
+}
+
+fn best_of(mut run: impl FnMut() -> usize) -> Duration {
+	let mut best: Duration = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let start: Instant = Instant::now();
+		black_box(run());
+		best = best.min(start.elapsed());
+	}
+	best
+}
+
+fn report(name: &str, duration: Duration) {
+	let speed: f64 = INPUT_SIZE as f64 / duration.as_secs_f64() / (1 << 20) as f64;
+	println!(
+		"{:>10}: {:8.3} ms ({:.2} MiB/s)",
+		name,
+		duration.as_secs_f64() * 1000.0,
+		speed
+	);
+}
+
+// -----------------------------------------------
+
+fn hash_modulo<const SIZE: usize>(input: &[u8]) -> usize {
+	input.iter().fold(0, |hash: usize, &byte: &u8| {
+		(black_box(hash) * (5 << 5) + byte as usize + 1) % SIZE
+	})
+}
+
+fn hash_mask<const SIZE: usize>(input: &[u8]) -> usize {
+	input.iter().fold(0, |hash: usize, &byte: &u8| {
+		(black_box(hash) * (5 << 5) + byte as usize + 1) & (SIZE - 1)
+	})
+}
+
+fn matching(context: &mut PrimaryContext, input: &[u8]) -> usize {
+	for &byte in input {
+		let state: HistoryState = context.get_history().get_state();
+		context.matching(state, Byte::from(byte));
+	}
+	context.hash_value()
+}
+
+fn main() {
+	let input: Vec<u8> = text_like_bytes(INPUT_SIZE);
+	assert_eq!(hash_modulo::<SIZE>(&input), hash_mask::<SIZE>(&input));
+	report("modulo", best_of(|| hash_modulo::<SIZE>(&input)));
+	report("mask", best_of(|| hash_mask::<SIZE>(&input)));
+	let mut context: PrimaryContext = PrimaryContext::new(SIZE);
+	report("matching", best_of(|| matching(&mut context, &input)));
+}
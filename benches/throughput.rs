@@ -0,0 +1,167 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Throughput benchmark: compresses and decompresses 8 MiB of text and binary records with the
+//! default config, and fails when either is slower than the saved baseline by more than
+//! TOLERANCE, to catch a change slowing the codec down before it is merged.
+//!
+//! Run with `cargo bench --bench throughput -- --save-baseline` on the commit to compare with,
+//! then with `cargo bench --bench throughput` on the change. The baseline is kept in
+//! target/throughput.baseline, SRX_BENCH_BASELINE=<file> keeps it elsewhere, and
+//! SRX_BENCH_INPUT=<file> measures a real file instead of the generated one.
+
+use srx::{compress, decompress, AnyError, AnyResult};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const GENERATED_SIZE: usize = 8 << 20;
+const ROUNDS: usize = 5;
+// slower than the baseline by more than this fails, the rounds of a loaded machine vary by less
+const TOLERANCE: f64 = 0.10;
+
+fn text(length: usize) -> Vec<u8> {
+	// the license with a counter in every line, compressible but not a plain repeat
+	let license: &str = include_str!("../LICENSE");
+	let mut text: Vec<u8> = Vec::with_capacity(length + license.len());
+	let mut count: usize = 0;
+	while text.len() < length {
+		for line in license.lines() {
+			text.extend_from_slice(format!("{} {}\n", count, line).as_bytes());
+			count += 1;
+		}
+	}
+	text.truncate(length);
+	text
+}
+
+fn records(length: usize) -> Vec<u8> {
+	// records of 16 bytes: a counter, a few flags and a random field, like a table of a binary
+	let mut state: u32 = 1;
+	let mut output: Vec<u8> = Vec::with_capacity(length + 16);
+	let mut count: u32 = 0;
+	while output.len() < length {
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		output.extend_from_slice(&count.to_le_bytes());
+		output.extend_from_slice(&[(state >> 30) as u8, 0, 0, (state >> 28) as u8 & 1]);
+		output.extend_from_slice(
+			&u64::from(state)
+				.wrapping_mul(0x9E3779B97F4A7C15)
+				.to_le_bytes(),
+		);
+		count += 1;
+	}
+	output.truncate(length);
+	output
+}
+
+fn best_of<T>(mut run: impl FnMut() -> AnyResult<T>) -> AnyResult<Duration> {
+	let mut best: Duration = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let start: Instant = Instant::now();
+		run()?;
+		best = best.min(start.elapsed());
+	}
+	Ok(best)
+}
+
+fn speed(size: usize, duration: Duration) -> f64 {
+	size as f64 / duration.as_secs_f64() / (1 << 20) as f64
+}
+
+// -----------------------------------------------
+
+fn baseline_path() -> PathBuf {
+	match std::env::var_os("SRX_BENCH_BASELINE") {
+		Some(path) => PathBuf::from(path),
+		None => PathBuf::from("target/throughput.baseline"),
+	}
+}
+
+// a line of name and speed in MiB/s for each measure
+fn read_baseline() -> AnyResult<Option<Vec<(String, f64)>>> {
+	let text: String = match fs::read_to_string(baseline_path()) {
+		Ok(text) => text,
+		Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(error) => return Err(error.into()),
+	};
+	let mut baseline: Vec<(String, f64)> = Vec::new();
+	for line in text.lines() {
+		let (name, speed): (&str, &str) = line
+			.split_once(' ')
+			.ok_or_else(|| AnyError::from_string("The baseline is not readable!"))?;
+		let speed: f64 = speed
+			.parse()
+			.map_err(|_| AnyError::from_string("The baseline is not readable!"))?;
+		baseline.push((name.to_string(), speed));
+	}
+	Ok(Some(baseline))
+}
+
+fn main() -> AnyResult<()> {
+	let input: Vec<u8> = match std::env::var_os("SRX_BENCH_INPUT") {
+		Some(path) => fs::read(path)?,
+		None => [text(GENERATED_SIZE / 2), records(GENERATED_SIZE / 2)].concat(),
+	};
+	let compressed: Vec<u8> = compress(&input)?;
+	let speeds: [(&str, f64); 2] = [
+		(
+			"compress",
+			speed(input.len(), best_of(|| compress(&input))?),
+		),
+		(
+			"decompress",
+			speed(input.len(), best_of(|| decompress(&compressed))?),
+		),
+	];
+	if std::env::args().any(|argument| argument == "--save-baseline") {
+		let text: String = speeds
+			.iter()
+			.map(|(name, speed)| format!("{} {}\n", name, speed))
+			.collect();
+		fs::write(baseline_path(), text)?;
+	}
+	let baseline: Vec<(String, f64)> = read_baseline()?.unwrap_or_default();
+	let mut slower: Vec<&str> = Vec::new();
+	for (name, speed) in speeds {
+		match baseline.iter().find(|(saved, _)| saved == name) {
+			Some(&(_, saved)) => {
+				println!(
+					"{:>12}: {:8.2} MiB/s ({:+.2}% against {:.2} MiB/s)",
+					name,
+					speed,
+					(speed / saved - 1.0) * 100.0,
+					saved
+				);
+				if speed < saved * (1.0 - TOLERANCE) {
+					slower.push(name);
+				}
+			}
+			None => println!("{:>12}: {:8.2} MiB/s", name, speed),
+		}
+	}
+	if !slower.is_empty() {
+		return Err(AnyError::from_string(format!(
+			"Slower than the baseline: {}!",
+			slower.join(", ")
+		)));
+	}
+	Ok(())
+}
@@ -0,0 +1,121 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Serves a body compressed on the fly with `Content-Encoding: srx` and chunked transfer, a
+//! chunk going out every time the body is flushed, with nothing but the standard library:
+//!
+//! ```text
+//! cargo run --release --example http_body
+//! curl -s http://127.0.0.1:8080/ -o body.srx && srx d body.srx body.txt
+//! ```
+
+use srx::{AnyResult, SrxWriter};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+// -----------------------------------------------
+
+// Frames what is written to it as the chunks of a chunked body. The chunks are sent on flush,
+// so that each flush of the SrxWriter above reaches the client without ending the response.
+struct ChunkedBody<W: Write> {
+	writer: W,
+	chunk: Vec<u8>,
+}
+
+impl<W: Write> ChunkedBody<W> {
+	fn new(writer: W) -> Self {
+		Self {
+			writer,
+			chunk: Vec::new(),
+		}
+	}
+
+	// the last chunk, empty, ends the body
+	fn finish(mut self) -> io::Result<W> {
+		self.flush()?;
+		self.writer.write_all(b"0\r\n\r\n")?;
+		self.writer.flush()?;
+		Ok(self.writer)
+	}
+}
+
+impl<W: Write> Write for ChunkedBody<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		self.chunk.extend_from_slice(buffer);
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		// an empty chunk would end the body
+		if !self.chunk.is_empty() {
+			write!(self.writer, "{:x}\r\n", self.chunk.len())?;
+			self.writer.write_all(&self.chunk)?;
+			self.writer.write_all(b"\r\n")?;
+			self.chunk.clear();
+		}
+		self.writer.flush()
+	}
+}
+
+// -----------------------------------------------
+
+fn respond(stream: TcpStream) -> AnyResult<()> {
+	// the request itself does not matter, only its end
+	let mut request: BufReader<TcpStream> = BufReader::new(stream.try_clone()?);
+	let mut line: String = String::new();
+	while request.read_line(&mut line)? > 2 {
+		line.clear();
+	}
+
+	let mut stream: TcpStream = stream;
+	stream.write_all(
+		b"HTTP/1.1 200 OK\r\n\
+		Content-Type: text/plain\r\n\
+		Content-Encoding: srx\r\n\
+		Transfer-Encoding: chunked\r\n\
+		Connection: close\r\n\r\n",
+	)?;
+	let mut body: SrxWriter<ChunkedBody<TcpStream>> = SrxWriter::new(ChunkedBody::new(stream))?;
+	for second in 0..5 {
+		for line in 0..1000 {
+			writeln!(
+				body,
+				"second {}, line {}: the same words again and again",
+				second, line
+			)?;
+		}
+		// what was written so far goes out now, all but the last few coded bytes
+		body.flush()?;
+		thread::sleep(Duration::from_secs(1));
+	}
+	body.finish()?.finish()?;
+	Ok(())
+}
+
+fn main() -> AnyResult<()> {
+	let listener: TcpListener = TcpListener::bind("127.0.0.1:8080")?;
+	println!("Listening on http://127.0.0.1:8080/");
+	for stream in listener.incoming() {
+		if let Err(error) = respond(stream?) {
+			println!("Error occurred! {}", error);
+		}
+	}
+	Ok(())
+}
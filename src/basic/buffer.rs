@@ -16,7 +16,34 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::alloc::{alloc_zeroed, handle_alloc_error, Layout};
 use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+// -----------------------------------------------
+
+// a type whose default is all zero bytes, so that a table of it is allocated zeroed and its
+// pages only mapped when first touched. Unsafe to implement: all zero bytes must be a valid
+// value of the type, and its default one.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait Zeroed: Copy + Default {}
+
+// a slice of len default values, allocated zeroed
+pub fn zeroed_slice<T: Zeroed>(len: usize) -> Box<[T]> {
+	let layout: Layout = Layout::array::<T>(len).expect("The table is too large!");
+	if layout.size() == 0 {
+		return vec![T::default(); len].into_boxed_slice();
+	}
+	// SAFETY: the layout is not empty, the memory comes from the global allocator with the
+	// layout of a slice of len T, and zeroed is a valid value of T, see Zeroed
+	unsafe {
+		let pointer: *mut T = alloc_zeroed(layout) as *mut T;
+		if pointer.is_null() {
+			handle_alloc_error(layout);
+		}
+		Box::from_raw(ptr::slice_from_raw_parts_mut(pointer, len))
+	}
+}
 
 // -----------------------------------------------
 
@@ -20,12 +20,14 @@
 pub struct Byte(usize);
 
 impl From<Byte> for u8 {
+	#[inline]
 	fn from(value: Byte) -> Self {
 		value.0 as u8
 	}
 }
 
 impl From<u8> for Byte {
+	#[inline]
 	fn from(value: u8) -> Self {
 		Byte(value as usize)
 	}
@@ -35,12 +37,14 @@ macro_rules! impl_from_unsigned_for_byte {
     ($($t:ty),*) => {
         $(
             impl From<Byte> for $t {
+				#[inline]
 				fn from(value: Byte) -> Self {
 					value.0 as $t
 				}
             }
 
             impl From<$t> for Byte {
+				#[inline]
 				fn from(value: $t) -> Self {
 					debug_assert!(value <= 255, "Unexpected value for Byte!");
 					Byte(value as usize)
@@ -54,12 +58,14 @@ macro_rules! impl_from_signed_for_byte {
     ($($t:ty),*) => {
         $(
             impl From<Byte> for $t {
+				#[inline]
 				fn from(value: Byte) -> Self {
 					value.0 as $t
 				}
             }
 
             impl From<$t> for Byte {
+				#[inline]
 				fn from(value: $t) -> Self {
 					debug_assert!(value >= 0 && value <= 255, "Unexpected value for Byte!");
 					Byte(value as usize)
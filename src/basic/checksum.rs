@@ -0,0 +1,141 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "sha256")]
+use sha2::Digest;
+use std::hash::Hasher;
+
+// CRC-32 as used by zip and gzip (reflected, polynomial 0xEDB88320)
+const CRC32_TABLE: [u32; 256] = {
+	let mut table: [u32; 256] = [0; 256];
+	let mut index: usize = 0;
+	while index < 256 {
+		let mut value: u32 = index as u32;
+		let mut bit: usize = 0;
+		while bit < 8 {
+			value = if value & 1 != 0 {
+				(value >> 1) ^ 0xEDB88320
+			} else {
+				value >> 1
+			};
+			bit += 1;
+		}
+		table[index] = value;
+		index += 1;
+	}
+	table
+};
+
+// -----------------------------------------------
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+	pub fn new() -> Self {
+		Self(0xFFFFFFFF)
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		let mut value: u32 = self.0;
+		for &byte in data {
+			value = (value >> 8) ^ CRC32_TABLE[((value ^ byte as u32) & 0xFF) as usize];
+		}
+		self.0 = value;
+	}
+
+	pub fn value(&self) -> u32 {
+		!self.0
+	}
+
+	pub fn of(data: &[u8]) -> u32 {
+		let mut crc: Crc32 = Crc32::new();
+		crc.update(data);
+		crc.value()
+	}
+}
+
+impl Default for Crc32 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// -----------------------------------------------
+
+// XXH64 with a seed of 0, from the twox-hash crate
+#[derive(Clone, Debug)]
+pub struct XxHash64(twox_hash::XxHash64);
+
+impl XxHash64 {
+	pub fn new() -> Self {
+		Self(twox_hash::XxHash64::with_seed(0))
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		self.0.write(data);
+	}
+
+	pub fn value(&self) -> u64 {
+		self.0.finish()
+	}
+
+	pub fn of(data: &[u8]) -> u64 {
+		twox_hash::XxHash64::oneshot(0, data)
+	}
+}
+
+impl Default for XxHash64 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// -----------------------------------------------
+
+// SHA-256 (FIPS 180-4) from the sha2 crate, for tamper evidence where a CRC or xxHash would
+// only catch accidents
+#[cfg(feature = "sha256")]
+#[derive(Clone, Debug)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha256")]
+impl Sha256 {
+	pub fn new() -> Self {
+		Self(sha2::Sha256::new())
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		self.0.update(data);
+	}
+
+	pub fn value(&self) -> [u8; 32] {
+		self.0.clone().finalize().into()
+	}
+
+	pub fn of(data: &[u8]) -> [u8; 32] {
+		sha2::Sha256::digest(data).into()
+	}
+}
+
+#[cfg(feature = "sha256")]
+impl Default for Sha256 {
+	fn default() -> Self {
+		Self::new()
+	}
+}
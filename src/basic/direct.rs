@@ -0,0 +1,148 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::basic::{AnyError, AnyResult, Buffer, Closable, Consumer, Producer, Reader, Writer};
+
+// -----------------------------------------------
+
+// The ends of a stage driven on the calling thread instead of through a pipe, for builds
+// without threads or inputs too small for them, and the reader of SrxDecoder in all builds:
+// the reader fills its buffer straight from a producer when it runs out, and the writer hands
+// its buffer straight to a consumer when it is full.
+
+pub struct DirectReader<T: Copy + Send + 'static, P: Producer<T>, const SIZE: usize> {
+	producer: P,
+	buffer: Buffer<T, SIZE>,
+	length: usize,
+	index: usize,
+	ended: bool,
+}
+
+impl<T: Copy + Default + Send + 'static, P: Producer<T>, const SIZE: usize>
+	DirectReader<T, P, SIZE>
+{
+	pub fn new(producer: P) -> Self {
+		Self {
+			producer,
+			buffer: Buffer::new(),
+			length: 0,
+			index: 0,
+			ended: false,
+		}
+	}
+}
+
+impl<T: Copy + Send + 'static, P: Producer<T>, const SIZE: usize> DirectReader<T, P, SIZE> {
+	#[cold]
+	fn fill(&mut self) -> AnyResult<()> {
+		debug_assert!(!self.ended && self.index == self.length);
+		self.length = self.producer.produce(&mut self.buffer)?;
+		self.index = 0;
+		self.ended = self.length == 0;
+		Ok(())
+	}
+}
+
+impl<T: Copy + Send + 'static, P: Producer<T>, const SIZE: usize> Reader<T>
+	for DirectReader<T, P, SIZE>
+{
+	fn read(&mut self) -> AnyResult<Option<T>> {
+		if !self.ended && self.index == self.length {
+			self.fill()?;
+		}
+		if self.ended {
+			return Ok(None);
+		}
+		let value: T = self.buffer[self.index];
+		self.index += 1;
+		Ok(Some(value))
+	}
+}
+
+impl<T: Copy + Send + 'static, P: Producer<T>, const SIZE: usize> Closable<P>
+	for DirectReader<T, P, SIZE>
+{
+	// the input is read to its end, as the reader thread of a pipeline would have
+	fn close(mut self) -> AnyResult<P> {
+		while !self.ended {
+			self.index = self.length;
+			self.fill()?;
+		}
+		Ok(self.producer)
+	}
+}
+
+// -----------------------------------------------
+
+pub struct DirectWriter<T: Copy + Send + 'static, C: Consumer<T>, const SIZE: usize> {
+	consumer: C,
+	buffer: Buffer<T, SIZE>,
+	index: usize,
+}
+
+impl<T: Copy + Default + Send + 'static, C: Consumer<T>, const SIZE: usize>
+	DirectWriter<T, C, SIZE>
+{
+	pub fn new(consumer: C) -> Self {
+		Self {
+			consumer,
+			buffer: Buffer::new(),
+			index: 0,
+		}
+	}
+}
+
+impl<T: Copy + Send + 'static, C: Consumer<T>, const SIZE: usize> DirectWriter<T, C, SIZE> {
+	#[cold]
+	fn flush(&mut self) -> AnyResult<()> {
+		let mut consumed: usize = 0;
+		while consumed < self.index {
+			match self.consumer.consume(&self.buffer[consumed..self.index])? {
+				0 => return Err(AnyError::from_string("Consumer did not consume anything!")),
+				length => consumed += length,
+			}
+		}
+		self.index = 0;
+		Ok(())
+	}
+}
+
+impl<T: Copy + Send + 'static, C: Consumer<T>, const SIZE: usize> Writer<T>
+	for DirectWriter<T, C, SIZE>
+{
+	fn write(&mut self, value: T) -> AnyResult<()> {
+		debug_assert!(self.index < SIZE);
+		self.buffer[self.index] = value;
+		self.index += 1;
+		if self.index == SIZE {
+			self.flush()?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: Copy + Send + 'static, C: Consumer<T>, const SIZE: usize> Closable<C>
+	for DirectWriter<T, C, SIZE>
+{
+	fn close(mut self) -> AnyResult<C> {
+		if self.index > 0 {
+			self.flush()?;
+		}
+		Ok(self.consumer)
+	}
+}
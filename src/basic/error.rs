@@ -17,9 +17,16 @@
  */
 
 use std::any::Any;
+use std::array::TryFromSliceError;
 use std::error::Error;
+use std::ffi::NulError;
 use std::fmt;
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::num::ParseIntError;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+use std::sync::mpsc::{RecvError, SendError};
 
 // -----------------------------------------------
 
@@ -27,11 +34,23 @@ pub type AnyResult<T> = Result<T, AnyError>;
 
 // -----------------------------------------------
 
+// The failures of srx, the ones worth telling apart by kind as variants of their own, each with
+// the message it is displayed as, and the rest as a string or an error of std. A panic is kept
+// as its message, so that an AnyError is Send and Sync like the errors of std.
 #[derive(Debug)]
 pub enum AnyError {
 	String(String),
-	Error(Box<dyn Error + Send>),
-	Box(Box<dyn Any + Send>),
+	Error(Box<dyn Error + Send + Sync>),
+	// reading or writing failed below the codec, the error given back as it was
+	Io(io::Error),
+	// not a header of srx, or one of a newer srx
+	BadHeader(String),
+	// the output is not the one the checksum of the header or of the stream was taken of
+	ChecksumMismatch(String),
+	// the input ends before the header, the stream or what follows it does
+	TruncatedInput(String),
+	// the other end of a pipe or a channel is gone, usually because its stage failed first
+	ChannelClosed,
 }
 
 impl AnyError {
@@ -44,24 +63,137 @@ impl AnyError {
 	#[cold]
 	#[inline(always)]
 	pub fn from_box(any: Box<dyn Any + Send>) -> Self {
-		Self::Box(any)
+		// the payload of a panic is its message, unless it was raised with something else
+		match any.downcast::<String>() {
+			Ok(string) => Self::String(*string),
+			Err(any) => match any.downcast::<&'static str>() {
+				Ok(string) => Self::from_string(*string),
+				Err(_) => Self::from_string("Panicked with a payload that is not a message!"),
+			},
+		}
+	}
+
+	// any other error, for the ones without a From of their own
+	#[cold]
+	#[inline(always)]
+	pub fn from_error<E: Error + Send + Sync + 'static>(error: E) -> Self {
+		Self::Error(Box::new(error))
+	}
+
+	#[cold]
+	#[inline(always)]
+	pub fn bad_header<S: Into<String>>(into_string: S) -> Self {
+		Self::BadHeader(into_string.into())
+	}
+
+	#[cold]
+	#[inline(always)]
+	pub fn checksum_mismatch<S: Into<String>>(into_string: S) -> Self {
+		Self::ChecksumMismatch(into_string.into())
+	}
+
+	#[cold]
+	#[inline(always)]
+	pub fn truncated_input<S: Into<String>>(into_string: S) -> Self {
+		Self::TruncatedInput(into_string.into())
+	}
+
+	#[cold]
+	#[inline(always)]
+	pub fn channel_closed() -> Self {
+		Self::ChannelClosed
+	}
+
+	pub fn is_channel_closed(&self) -> bool {
+		matches!(self, Self::ChannelClosed)
 	}
 }
 
 impl Display for AnyError {
 	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
 		match self {
-			AnyError::String(value) => Display::fmt(value, formatter),
+			AnyError::String(value)
+			| AnyError::BadHeader(value)
+			| AnyError::ChecksumMismatch(value)
+			| AnyError::TruncatedInput(value) => Display::fmt(value, formatter),
 			AnyError::Error(value) => Display::fmt(value, formatter),
-			AnyError::Box(value) => Debug::fmt(value, formatter),
+			AnyError::Io(value) => Display::fmt(value, formatter),
+			AnyError::ChannelClosed => formatter.write_str("Broken pipe!"),
 		}
 	}
 }
 
-impl<E: Error + Send + 'static> From<E> for AnyError {
+// An AnyError is an error too, for callers returning a Box<dyn Error> or an error of their own.
+// Which is why the errors of std get a From each instead of all of them at once: a From of any
+// error would take in AnyError itself, which already has one.
+impl Error for AnyError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			AnyError::Error(error) => Some(error.as_ref()),
+			AnyError::Io(error) => Some(error),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for AnyError {
 	#[cold]
 	#[inline(always)]
-	fn from(e: E) -> Self {
-		Self::Error(Box::new(e))
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+// the other errors of std taken in with ?, see from_error
+macro_rules! from_errors {
+	($($error:ty),*) => {
+		$(impl From<$error> for AnyError {
+			#[cold]
+			#[inline(always)]
+			fn from(error: $error) -> Self {
+				Self::from_error(error)
+			}
+		})*
+	};
+}
+
+from_errors!(
+	TryFromSliceError,
+	NulError,
+	ParseIntError,
+	Utf8Error,
+	FromUtf8Error
+);
+
+// a channel failing to send or receive only ever means that its other end is gone
+impl<T> From<SendError<T>> for AnyError {
+	#[cold]
+	#[inline(always)]
+	fn from(_: SendError<T>) -> Self {
+		Self::ChannelClosed
+	}
+}
+
+impl From<RecvError> for AnyError {
+	#[cold]
+	#[inline(always)]
+	fn from(_: RecvError) -> Self {
+		Self::ChannelClosed
+	}
+}
+
+// for the implementations of Read and Write, an io::Error from below is given back as it was
+impl From<AnyError> for io::Error {
+	#[cold]
+	fn from(error: AnyError) -> Self {
+		match error {
+			AnyError::Io(error) => error,
+			AnyError::Error(error) => match error.downcast::<io::Error>() {
+				Ok(error) => *error,
+				Err(error) => io::Error::other(error.to_string()),
+			},
+			AnyError::ChannelClosed => io::Error::from(io::ErrorKind::BrokenPipe),
+			error => io::Error::other(error.to_string()),
+		}
 	}
 }
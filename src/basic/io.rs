@@ -17,6 +17,10 @@
  */
 
 use crate::basic::AnyResult;
+use std::io;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
 
 // -----------------------------------------------
 
@@ -38,10 +42,91 @@ pub trait Writer<T> {
 
 // -----------------------------------------------
 
+// Takes a prefix of the buffer and returns its length. Taking less than all of it is fine, the
+// rest is offered again by the next call, but an empty prefix is only allowed for an empty
+// buffer: the pipes would otherwise never make progress.
 pub trait Consumer<T> {
 	fn consume(&mut self, buffer: &[T]) -> AnyResult<usize>;
 }
 
+// lending a consumer keeps it with the caller when the one it was lent to fails
+impl<T, C: Consumer<T> + ?Sized> Consumer<T> for &mut C {
+	fn consume(&mut self, buffer: &[T]) -> AnyResult<usize> {
+		(**self).consume(buffer)
+	}
+}
+
+// Takes every buffer whole and only counts it, for timing a decoder without its output.
+#[derive(Default)]
+pub struct NullConsumer {
+	consumed: u64,
+}
+
+impl NullConsumer {
+	pub fn consumed(&self) -> u64 {
+		self.consumed
+	}
+}
+
+impl<T> Consumer<T> for NullConsumer {
+	fn consume(&mut self, buffer: &[T]) -> AnyResult<usize> {
+		self.consumed += buffer.len() as u64;
+		Ok(buffer.len())
+	}
+}
+
+// -----------------------------------------------
+
+// A nonblocking handle that would block has nothing ready yet: yield a few times, then sleep
+// for twice as long on each further attempt, up to about a millisecond.
+fn back_off(attempt: &mut u32) {
+	if *attempt < 4 {
+		thread::yield_now();
+	} else {
+		thread::sleep(Duration::from_micros(1 << (*attempt - 4).min(10)));
+	}
+	*attempt += 1;
+}
+
+// Every output of the codec goes through here instead of a bare write, which may take only part
+// of the buffer. Interrupted writes are retried, so are the ones that would block after backing
+// off, and a writer taking nothing is an error instead of an endless loop.
+pub fn write_fully<W: Write + ?Sized>(writer: &mut W, mut buffer: &[u8]) -> io::Result<()> {
+	let mut attempt: u32 = 0;
+	while !buffer.is_empty() {
+		match writer.write(buffer) {
+			Ok(0) => {
+				return Err(io::Error::new(
+					io::ErrorKind::WriteZero,
+					"The output does not accept any more bytes!",
+				))
+			}
+			Ok(length) => {
+				buffer = &buffer[length..];
+				attempt = 0;
+			}
+			Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+			Err(error) if error.kind() == io::ErrorKind::WouldBlock => back_off(&mut attempt),
+			Err(error) => return Err(error),
+		}
+	}
+	Ok(())
+}
+
+// Every input of the codec goes through here instead of a bare read, retried the same way as
+// write_fully, so that zero still means the end of the input.
+pub fn read_retrying<R: Read + ?Sized>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+	let mut attempt: u32 = 0;
+	loop {
+		match reader.read(buffer) {
+			Ok(length) => return Ok(length),
+			Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+			Err(error) if error.kind() == io::ErrorKind::WouldBlock => back_off(&mut attempt),
+			Err(error) => return Err(error),
+		}
+	}
+}
+
 // -----------------------------------------------
 
 pub trait Producer<T> {
@@ -50,12 +135,46 @@ pub trait Producer<T> {
 
 // -----------------------------------------------
 
+#[cfg(feature = "threads")]
 pub trait ToConsumer<T> {
 	fn consume<C: Consumer<T>>(&mut self, consumer: &mut C) -> AnyResult<usize>;
 }
 
 // -----------------------------------------------
 
+#[cfg(feature = "threads")]
 pub trait FromProducer<T> {
 	fn produce<P: Producer<T>>(&mut self, producer: &mut P) -> AnyResult<usize>;
 }
+
+// -----------------------------------------------
+
+// in-memory ends, for driving a single stage without the pipes, mostly in tests
+
+impl<T> Writer<T> for Vec<T> {
+	fn write(&mut self, value: T) -> AnyResult<()> {
+		self.push(value);
+		Ok(())
+	}
+}
+
+impl<T> Closable<Vec<T>> for Vec<T> {
+	fn close(self) -> AnyResult<Vec<T>> {
+		Ok(self)
+	}
+}
+
+impl<T: Copy> Reader<T> for &[T] {
+	fn read(&mut self) -> AnyResult<Option<T>> {
+		Ok(self.split_first().map(|(&value, rest)| {
+			*self = rest;
+			value
+		}))
+	}
+}
+
+impl<T> Closable<()> for &[T] {
+	fn close(self) -> AnyResult<()> {
+		Ok(())
+	}
+}
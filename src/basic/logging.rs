@@ -0,0 +1,33 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+// log!(Warn, "format", arguments...) hands a message to the log crate at that level, for the
+// logger of the embedding application, and is nothing at all without the log feature (the
+// arguments are still checked, so that what is only logged does not look unused)
+macro_rules! log {
+	($level:ident, $($argument:tt)+) => {
+		#[cfg(feature = "log")]
+		::log::log!(::log::Level::$level, $($argument)+);
+		#[cfg(not(feature = "log"))]
+		if false {
+			let _ = format_args!($($argument)+);
+		}
+	};
+}
+
+pub(crate) use log;
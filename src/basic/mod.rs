@@ -18,12 +18,37 @@
 
 mod buffer;
 mod byte;
+mod checksum;
+mod direct;
 mod error;
 mod io;
+mod logging;
+#[cfg(feature = "threads")]
 mod pipe;
+mod sparse;
+#[cfg(test)]
+mod test;
+mod volume;
 
-pub use self::buffer::Buffer;
+pub use self::buffer::{zeroed_slice, Buffer, Zeroed};
 pub use self::byte::Byte;
+#[cfg(feature = "sha256")]
+pub use self::checksum::Sha256;
+pub use self::checksum::{Crc32, XxHash64};
+pub use self::direct::DirectReader;
+pub use self::direct::DirectWriter;
 pub use self::error::{AnyError, AnyResult};
-pub use self::io::{Closable, Consumer, FromProducer, Producer, Reader, ToConsumer, Writer};
+pub use self::io::{
+	read_retrying, write_fully, Closable, Consumer, NullConsumer, Producer, Reader, Writer,
+};
+#[cfg(feature = "threads")]
+pub use self::io::{FromProducer, ToConsumer};
+pub(crate) use self::logging::log;
+#[cfg(all(test, feature = "threads"))]
+pub(crate) use self::pipe::with_jitter;
+#[cfg(feature = "threads")]
 pub use self::pipe::{pipe, PipedReader, PipedWriter};
+#[cfg(feature = "pipe-stats")]
+pub use self::pipe::{pipe_stats, PipeStats};
+pub use self::sparse::{SparseReader, SparseWriter};
+pub use self::volume::{volume_path, VolumeReader, VolumeWriter};
@@ -19,7 +19,16 @@
 use super::buffer::Buffer;
 use super::error::{AnyError, AnyResult};
 use super::io::{Closable, Consumer, FromProducer, Producer, Reader, ToConsumer, Writer};
+use super::logging::log;
+#[cfg(any(test, feature = "pipe-stats"))]
+use std::cell::Cell;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+#[cfg(feature = "pipe-stats")]
+use std::sync::mpsc::{TryRecvError, TrySendError};
+#[cfg(test)]
+use std::thread;
+#[cfg(test)]
+use std::time::Duration;
 
 // -----------------------------------------------
 
@@ -28,6 +37,134 @@ type ReaderToWriter<T, const SIZE: usize> = Buffer<T, SIZE>;
 
 // -----------------------------------------------
 
+#[cfg(test)]
+thread_local! {
+	// the seed of the delays injected by the pipes created on this thread, see with_jitter
+	static JITTER_SEED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// makes the pipes created by the body on this thread sleep or yield at random before each
+// of their syncs, from the seed, so tests can run the stages under many interleavings
+#[cfg(test)]
+pub(crate) fn with_jitter<T>(seed: u64, body: impl FnOnce() -> T) -> T {
+	let previous: Option<u64> = JITTER_SEED.replace(Some(seed));
+	let result: T = body();
+	JITTER_SEED.set(previous);
+	result
+}
+
+#[cfg(test)]
+struct Jitter(Option<u64>);
+
+#[cfg(test)]
+impl Jitter {
+	// splitmix64, a different stream for each pipe and each side of it
+	fn split(seed: &mut u64) -> u64 {
+		*seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+		let mut value: u64 = *seed;
+		value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+		value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+		(value ^ (value >> 31)) | 1
+	}
+
+	fn new_pair() -> (Jitter, Jitter) {
+		match JITTER_SEED.get() {
+			None => (Jitter(None), Jitter(None)),
+			Some(mut seed) => {
+				let pair: (Jitter, Jitter) = (
+					Jitter(Some(Self::split(&mut seed))),
+					Jitter(Some(Self::split(&mut seed))),
+				);
+				JITTER_SEED.set(Some(seed));
+				pair
+			}
+		}
+	}
+
+	fn delay(&mut self) {
+		if let Some(state) = &mut self.0 {
+			// xorshift64
+			*state ^= *state << 13;
+			*state ^= *state >> 7;
+			*state ^= *state << 17;
+			match *state % 8 {
+				0..=3 => {}
+				4..=6 => thread::yield_now(),
+				_ => thread::sleep(Duration::from_micros(*state >> 56)),
+			}
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// the syncs of the pipes used on a thread, and how many of their sends and receives had to
+// wait for the other side: a full pipe for a writer, an empty one for a reader
+#[cfg(feature = "pipe-stats")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct PipeStats {
+	pub syncs: u64,
+	pub stalls: u64,
+}
+
+#[cfg(feature = "pipe-stats")]
+impl PipeStats {
+	pub fn since(&self, start: PipeStats) -> PipeStats {
+		PipeStats {
+			syncs: self.syncs - start.syncs,
+			stalls: self.stalls - start.stalls,
+		}
+	}
+}
+
+#[cfg(feature = "pipe-stats")]
+thread_local! {
+	// the syncs of the pipes on this thread so far, counted by each side as it syncs
+	static PIPE_STATS: Cell<PipeStats> = const { Cell::new(PipeStats { syncs: 0, stalls: 0 }) };
+}
+
+// the syncs of the pipes used on the calling thread since it started
+#[cfg(feature = "pipe-stats")]
+pub fn pipe_stats() -> PipeStats {
+	PIPE_STATS.get()
+}
+
+#[cfg(feature = "pipe-stats")]
+fn count(syncs: u64, stalls: u64) {
+	let stats: PipeStats = PIPE_STATS.get();
+	PIPE_STATS.set(PipeStats {
+		syncs: stats.syncs + syncs,
+		stalls: stats.stalls + stalls,
+	});
+}
+
+// a send counted as a stall if it can not go without waiting, a plain send otherwise
+fn send<T>(sender: &SyncSender<T>, value: T) -> Result<(), ()> {
+	#[cfg(feature = "pipe-stats")]
+	let value: T = match sender.try_send(value) {
+		Ok(()) => return Ok(()),
+		Err(TrySendError::Disconnected(_)) => return Err(()),
+		Err(TrySendError::Full(value)) => {
+			count(0, 1);
+			value
+		}
+	};
+	sender.send(value).map_err(|_| ())
+}
+
+// a receive counted as a stall if it can not go without waiting, a plain receive otherwise
+fn recv<T>(receiver: &Receiver<T>) -> Result<T, ()> {
+	#[cfg(feature = "pipe-stats")]
+	match receiver.try_recv() {
+		Ok(value) => return Ok(value),
+		Err(TryRecvError::Disconnected) => return Err(()),
+		Err(TryRecvError::Empty) => count(0, 1),
+	}
+	receiver.recv().map_err(|_| ())
+}
+
+// -----------------------------------------------
+
 pub fn pipe<T: Default + Copy + Send + 'static, const SIZE: usize>(
 ) -> (PipedWriter<T, SIZE>, PipedReader<T, SIZE>) {
 	let (writer_sender, reader_receiver): (
@@ -38,12 +175,16 @@ pub fn pipe<T: Default + Copy + Send + 'static, const SIZE: usize>(
 		SyncSender<ReaderToWriter<T, SIZE>>,
 		Receiver<ReaderToWriter<T, SIZE>>,
 	) = sync_channel(1);
+	#[cfg(test)]
+	let (writer_jitter, reader_jitter): (Jitter, Jitter) = Jitter::new_pair();
 	(
 		PipedWriter {
 			sender: writer_sender,
 			receiver: writer_receiver,
 			buffer: Some(Buffer::new()),
 			index: 0,
+			#[cfg(test)]
+			jitter: writer_jitter,
 		},
 		PipedReader {
 			sender: reader_sender,
@@ -51,6 +192,8 @@ pub fn pipe<T: Default + Copy + Send + 'static, const SIZE: usize>(
 			buffer: Some(Buffer::new()),
 			index: 0,
 			length: 0,
+			#[cfg(test)]
+			jitter: reader_jitter,
 		},
 	)
 }
@@ -62,6 +205,8 @@ pub struct PipedWriter<T: Copy + Send + 'static, const SIZE: usize> {
 	receiver: Receiver<ReaderToWriter<T, SIZE>>,
 	buffer: Option<Buffer<T, SIZE>>,
 	index: usize,
+	#[cfg(test)]
+	jitter: Jitter,
 }
 
 impl<T: Copy + Send + 'static, const SIZE: usize> PipedWriter<T, SIZE> {
@@ -69,9 +214,13 @@ impl<T: Copy + Send + 'static, const SIZE: usize> PipedWriter<T, SIZE> {
 	fn sync(&mut self) -> AnyResult<()> {
 		debug_assert!(self.buffer.is_some());
 		debug_assert!(self.index > 0 && self.index <= SIZE);
+		#[cfg(test)]
+		self.jitter.delay();
+		#[cfg(feature = "pipe-stats")]
+		count(1, 0);
 		let buffer: Buffer<T, SIZE> = self.buffer.take().unwrap();
-		self.sender.send((buffer, self.index))?;
-		self.buffer = Some(self.receiver.recv()?);
+		send(&self.sender, (buffer, self.index)).map_err(|_| AnyError::channel_closed())?;
+		self.buffer = Some(recv(&self.receiver).map_err(|_| AnyError::channel_closed())?);
 		self.index = 0;
 		Ok(())
 	}
@@ -80,7 +229,7 @@ impl<T: Copy + Send + 'static, const SIZE: usize> PipedWriter<T, SIZE> {
 impl<T: Copy + Send + 'static, const SIZE: usize> Writer<T> for PipedWriter<T, SIZE> {
 	fn write(&mut self, value: T) -> AnyResult<()> {
 		match &mut self.buffer {
-			None => Err(AnyError::from_string("Broken pipe!")),
+			None => Err(AnyError::channel_closed()),
 			Some(buffer) => {
 				debug_assert!(self.index < SIZE);
 				buffer[self.index] = value;
@@ -99,7 +248,7 @@ impl<T: Copy + Send + 'static, const SIZE: usize> Writer<T> for PipedWriter<T, S
 impl<T: Copy + Send + 'static, const SIZE: usize> FromProducer<T> for PipedWriter<T, SIZE> {
 	fn produce<P: Producer<T>>(&mut self, producer: &mut P) -> AnyResult<usize> {
 		match &mut self.buffer {
-			None => Err(AnyError::from_string("Broken pipe!")),
+			None => Err(AnyError::channel_closed()),
 			Some(buffer) => {
 				debug_assert!(self.index < SIZE);
 				let sliced_buffer: &mut [T] = &mut buffer[self.index..SIZE];
@@ -120,10 +269,17 @@ impl<T: Copy + Send + 'static, const SIZE: usize> Closable<()> for PipedWriter<T
 	fn close(mut self) -> AnyResult<()> {
 		if self.buffer.is_some() && self.index > 0 {
 			debug_assert!(self.index <= SIZE);
-			self.sync()
-		} else {
-			Ok(())
+			self.sync()?;
+		}
+		// an empty buffer tells the reader that the stream ended here, while a writer dropped
+		// without closing (its thread panicked) breaks the pipe. The reader may already be gone
+		// if it stopped before the end, which is fine.
+		if let Some(buffer) = self.buffer.take() {
+			if send(&self.sender, (buffer, 0)).is_err() {
+				log!(Trace, "The reader of a pipe stopped before its end");
+			}
 		}
+		Ok(())
 	}
 }
 
@@ -135,23 +291,41 @@ pub struct PipedReader<T: Copy + Send + 'static, const SIZE: usize> {
 	buffer: Option<Buffer<T, SIZE>>,
 	length: usize,
 	index: usize,
+	#[cfg(test)]
+	jitter: Jitter,
 }
 
 impl<T: Copy + Send + 'static, const SIZE: usize> PipedReader<T, SIZE> {
 	#[cold]
-	fn sync(&mut self) {
+	fn sync(&mut self) -> AnyResult<()> {
 		debug_assert!(self.buffer.is_some() && self.index == self.length && self.length <= SIZE);
+		#[cfg(test)]
+		self.jitter.delay();
+		#[cfg(feature = "pipe-stats")]
+		count(1, 0);
 		// take the old buffer and set it to None
 		let old_buffer: Buffer<T, SIZE> = self.buffer.take().unwrap();
 		// receive the new buffer
-		if let Ok((new_buffer, length)) = self.receiver.recv() {
-			debug_assert!(length > 0 && length <= SIZE);
-			// set the new buffer and its length
-			self.buffer = Some(new_buffer);
-			self.length = length;
-			self.index = 0;
-			// send the old buffer away, maybe print something to log if error?
-			let _error_ignored_ = self.sender.send(old_buffer);
+		match recv(&self.receiver) {
+			// the writer is closed, this is the end of the stream
+			Ok((_, 0)) => Ok(()),
+			Ok((new_buffer, length)) => {
+				debug_assert!(length <= SIZE);
+				// set the new buffer and its length
+				self.buffer = Some(new_buffer);
+				self.length = length;
+				self.index = 0;
+				// send the old buffer away, the writer may be closed already
+				if send(&self.sender, old_buffer).is_err() {
+					log!(
+						Trace,
+						"A buffer of a pipe was not recycled, its writer is gone"
+					);
+				}
+				Ok(())
+			}
+			// the writer is gone without closing
+			Err(_) => Err(AnyError::channel_closed()),
 		}
 	}
 }
@@ -160,7 +334,7 @@ impl<T: Copy + Send + 'static, const SIZE: usize> Reader<T> for PipedReader<T, S
 	fn read(&mut self) -> AnyResult<Option<T>> {
 		debug_assert!(self.index <= self.length && self.length <= SIZE);
 		if self.buffer.is_some() && self.index == self.length {
-			self.sync();
+			self.sync()?;
 		}
 		match &mut self.buffer {
 			None => Ok(None),
@@ -179,7 +353,7 @@ impl<T: Copy + Send + 'static, const SIZE: usize> ToConsumer<T> for PipedReader<
 	fn consume<C: Consumer<T>>(&mut self, consumer: &mut C) -> AnyResult<usize> {
 		debug_assert!(self.index <= self.length && self.length <= SIZE);
 		if self.buffer.is_some() && self.index == self.length {
-			self.sync();
+			self.sync()?;
 		}
 		match &mut self.buffer {
 			None => Ok(0),
@@ -187,7 +361,9 @@ impl<T: Copy + Send + 'static, const SIZE: usize> ToConsumer<T> for PipedReader<
 				debug_assert!(self.index < self.length && self.length <= SIZE);
 				let sliced_buffer: &[T] = &buffer[self.index..self.length];
 				let consumed_length: usize = consumer.consume(sliced_buffer)?;
-				if consumed_length <= sliced_buffer.len() {
+				if consumed_length == 0 {
+					Err(AnyError::from_string("Consumer did not consume anything!"))
+				} else if consumed_length <= sliced_buffer.len() {
 					self.index += consumed_length;
 					debug_assert!(self.index <= SIZE);
 					Ok(consumed_length)
@@ -0,0 +1,260 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::io::write_fully;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+// -----------------------------------------------
+
+// zero runs shorter than this are written out, longer ones become holes
+const HOLE_BLOCK_SIZE: usize = 0x1000;
+
+// -----------------------------------------------
+
+#[cfg(any(
+	target_os = "linux",
+	target_os = "android",
+	target_os = "freebsd",
+	target_os = "macos"
+))]
+mod hole {
+	use std::fs::File;
+	use std::io;
+	use std::os::unix::io::AsRawFd;
+
+	fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+		// SAFETY: lseek only reads the descriptor, which stays open for the lifetime of file
+		let result: libc::off_t =
+			unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+		if result >= 0 {
+			Ok(Some(result as u64))
+		} else {
+			let error: io::Error = io::Error::last_os_error();
+			match error.raw_os_error() {
+				// no data (or no hole) after the offset
+				Some(libc::ENXIO) => Ok(None),
+				_ => Err(error),
+			}
+		}
+	}
+
+	pub fn next_data(file: &File, offset: u64) -> io::Result<Option<u64>> {
+		seek(file, offset, libc::SEEK_DATA)
+	}
+
+	pub fn next_hole(file: &File, offset: u64) -> io::Result<Option<u64>> {
+		seek(file, offset, libc::SEEK_HOLE)
+	}
+}
+
+#[cfg(not(any(
+	target_os = "linux",
+	target_os = "android",
+	target_os = "freebsd",
+	target_os = "macos"
+)))]
+mod hole {
+	use std::fs::File;
+	use std::io;
+
+	pub fn next_data(_file: &File, offset: u64) -> io::Result<Option<u64>> {
+		Ok(Some(offset))
+	}
+
+	pub fn next_hole(_file: &File, _offset: u64) -> io::Result<Option<u64>> {
+		Ok(None)
+	}
+}
+
+// -----------------------------------------------
+
+// A reader that produces the holes of a sparse file as zeros without reading them from
+// the disk. Where holes can not be detected, the whole file is read as usual.
+pub struct SparseReader {
+	file: File,
+	length: u64,
+	position: u64,
+	region_end: u64,
+	in_hole: bool,
+	bytes_read: u64,
+}
+
+impl SparseReader {
+	pub fn new(file: File) -> io::Result<Self> {
+		let length: u64 = file.metadata()?.len();
+		Ok(Self {
+			file,
+			length,
+			position: 0,
+			region_end: 0,
+			in_hole: false,
+			bytes_read: 0,
+		})
+	}
+
+	// number of bytes produced so far, holes included
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	// number of bytes actually read from the file
+	pub fn bytes_read(&self) -> u64 {
+		self.bytes_read
+	}
+
+	pub fn into_inner(self) -> File {
+		self.file
+	}
+
+	#[cold]
+	fn next_region(&mut self) -> io::Result<()> {
+		debug_assert!(self.position == self.region_end);
+		// past the known length, read whatever is there until the real end of file
+		let data_start: Option<u64> = if self.position < self.length {
+			// when holes can not be queried, everything is data
+			hole::next_data(&self.file, self.position).unwrap_or(Some(self.position))
+		} else {
+			Some(self.position)
+		};
+		match data_start {
+			Some(data_start) if data_start > self.position => {
+				self.in_hole = true;
+				self.region_end = data_start;
+			}
+			None => {
+				// a hole until the end of file
+				self.in_hole = true;
+				self.region_end = self.length;
+			}
+			Some(_) => {
+				self.in_hole = false;
+				self.region_end = match hole::next_hole(&self.file, self.position) {
+					Ok(Some(hole_start))
+						if hole_start > self.position && hole_start < self.length =>
+					{
+						hole_start
+					}
+					_ => u64::MAX,
+				};
+				// seeking for data and holes moved the file offset
+				self.file.seek(SeekFrom::Start(self.position))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Read for SparseReader {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		if self.position == self.region_end {
+			self.next_region()?;
+		}
+		let available: usize = (self.region_end - self.position).min(buffer.len() as u64) as usize;
+		let length: usize = if self.in_hole {
+			buffer[..available].fill(0);
+			available
+		} else {
+			let length: usize = self.file.read(&mut buffer[..available])?;
+			self.bytes_read += length as u64;
+			length
+		};
+		self.position += length as u64;
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+// A writer that seeks over long zero runs instead of writing them, leaving holes in the
+// file. The file only gets its final length in finish(), which must be called. A pipe or a
+// socket can not be seeked in, the zeros are written to it as they are.
+pub struct SparseWriter {
+	file: File,
+	seekable: bool,
+	position: u64,
+	pending_hole: u64,
+}
+
+impl SparseWriter {
+	pub fn new(file: File) -> Self {
+		let seekable: bool = file
+			.metadata()
+			.is_ok_and(|metadata| metadata.file_type().is_file());
+		Self {
+			file,
+			seekable,
+			position: 0,
+			pending_hole: 0,
+		}
+	}
+
+	// number of bytes written so far, holes included
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	fn write_data(&mut self, data: &[u8]) -> io::Result<()> {
+		if !data.is_empty() {
+			if self.pending_hole > 0 {
+				self.file
+					.seek(SeekFrom::Current(self.pending_hole as i64))?;
+				self.pending_hole = 0;
+			}
+			write_fully(&mut self.file, data)?;
+		}
+		Ok(())
+	}
+
+	pub fn finish(mut self) -> io::Result<File> {
+		if self.pending_hole > 0 {
+			// extending the file with set_len does not allocate the trailing hole
+			self.file.set_len(self.position)?;
+			self.pending_hole = 0;
+		}
+		self.file.flush()?;
+		Ok(self.file)
+	}
+}
+
+impl Write for SparseWriter {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		// consecutive data blocks are written together, in as few calls as possible
+		let mut data_start: usize = 0;
+		let mut offset: usize = 0;
+		for block in buffer.chunks(HOLE_BLOCK_SIZE) {
+			if self.seekable
+				&& block.len() == HOLE_BLOCK_SIZE
+				&& block.iter().all(|&byte| byte == 0)
+			{
+				self.write_data(&buffer[data_start..offset])?;
+				self.pending_hole += block.len() as u64;
+				data_start = offset + block.len();
+			}
+			offset += block.len();
+		}
+		self.write_data(&buffer[data_start..])?;
+		self.position += buffer.len() as u64;
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
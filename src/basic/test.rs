@@ -0,0 +1,354 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "sha256")]
+use crate::basic::Sha256;
+#[cfg(feature = "threads")]
+use crate::basic::{pipe, Closable, PipedReader, PipedWriter, Reader, Writer};
+#[cfg(feature = "pipe-stats")]
+use crate::basic::{pipe_stats, PipeStats};
+use crate::basic::{
+	volume_path, zeroed_slice, AnyResult, Crc32, SparseReader, SparseWriter, VolumeReader,
+	VolumeWriter, XxHash64, Zeroed,
+};
+use crate::codec::{decode, encode, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::process;
+#[cfg(feature = "threads")]
+use std::thread;
+#[cfg(feature = "pipe-stats")]
+use std::time::Duration;
+
+// -----------------------------------------------
+
+const SPARSE_LENGTH: u64 = 32 << 20;
+const DATA_OFFSET: u64 = 16 << 20;
+const DATA: &[u8] = b"some data in the middle of a lot of nothing";
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("srx-test-{}-{}", process::id(), name))
+}
+
+#[cfg(unix)]
+fn is_sparse(file: &File) -> AnyResult<bool> {
+	let metadata: fs::Metadata = file.metadata()?;
+	Ok(metadata.blocks() * 512 < metadata.len())
+}
+
+fn compress(reader: impl Read + Send) -> AnyResult<Vec<u8>> {
+	let (_, output): (_, Vec<u8>) =
+		encode::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(reader, Vec::new())?;
+	Ok(output)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sparse_round_trip() -> AnyResult<()> {
+	let sparse_path: PathBuf = temp_path("sparse");
+	let output_path: PathBuf = temp_path("sparse-output");
+
+	// a file that is mostly a hole, and its dense equivalent
+	let mut sparse_file: File = File::create(&sparse_path)?;
+	sparse_file.set_len(SPARSE_LENGTH)?;
+	sparse_file.seek(SeekFrom::Start(DATA_OFFSET))?;
+	sparse_file.write_all(DATA)?;
+	drop(sparse_file);
+	let mut dense: Vec<u8> = vec![0; SPARSE_LENGTH as usize];
+	dense[DATA_OFFSET as usize..DATA_OFFSET as usize + DATA.len()].copy_from_slice(DATA);
+
+	// the holes must compress exactly like the zeros they stand for
+	let sparse_file: File = File::open(&sparse_path)?;
+	let input_is_sparse: bool = is_sparse(&sparse_file)?;
+	let (sparse_reader, compressed): (SparseReader, Vec<u8>) =
+		encode::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			SparseReader::new(sparse_file)?,
+			Vec::new(),
+		)?;
+	assert_eq!(compressed, compress(dense.as_slice())?);
+	assert_eq!(sparse_reader.position(), SPARSE_LENGTH);
+	if input_is_sparse {
+		assert!(sparse_reader.bytes_read() < SPARSE_LENGTH / 2);
+	}
+
+	// decompressing must restore the content, leaving the zeros as holes
+	let (_, sparse_writer): (&[u8], SparseWriter) = decode::<_, _, IO_BUFFER_SIZE>(
+		compressed.as_slice(),
+		SparseWriter::new(File::create(&output_path)?),
+	)?;
+	assert_eq!(sparse_writer.position(), SPARSE_LENGTH);
+	let output_file: File = sparse_writer.finish()?;
+	if input_is_sparse {
+		assert!(is_sparse(&output_file)?);
+	}
+	drop(output_file);
+	assert!(fs::read(&output_path)? == dense);
+
+	fs::remove_file(&sparse_path)?;
+	fs::remove_file(&output_path)?;
+	Ok(())
+}
+
+// a pipe can not be seeked in, its zeros are written instead of left as holes
+#[cfg(unix)]
+#[test]
+fn test_sparse_writer_pipe() -> AnyResult<()> {
+	use std::os::unix::io::FromRawFd;
+
+	let mut fds: [libc::c_int; 2] = [0; 2];
+	// SAFETY: pipe writes two new descriptors into fds, each then owned by one File
+	assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+	let (mut reader, writer): (File, File) =
+		unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+	let data: Vec<u8> = [vec![0; 3 * 0x1000], DATA.to_vec(), vec![0; 0x2000]].concat();
+	let expected: Vec<u8> = data.clone();
+	let writing = std::thread::spawn(move || -> io::Result<u64> {
+		let mut sparse_writer: SparseWriter = SparseWriter::new(writer);
+		sparse_writer.write_all(&data)?;
+		let position: u64 = sparse_writer.position();
+		sparse_writer.finish()?;
+		Ok(position)
+	});
+	let mut received: Vec<u8> = Vec::new();
+	reader.read_to_end(&mut received)?;
+	assert_eq!(writing.join().unwrap()?, expected.len() as u64);
+	assert!(received == expected);
+	Ok(())
+}
+
+#[test]
+fn test_volume_round_trip() -> AnyResult<()> {
+	let base: PathBuf = temp_path("volumes.srx");
+	let data: Vec<u8> = DATA.repeat(1000);
+	let compressed: Vec<u8> = compress(data.as_slice())?;
+	let volume_size: u64 = compressed.len() as u64 / 3 + 1;
+
+	// split into three volumes, the last one shorter
+	let (_, writer): (&[u8], VolumeWriter) = encode::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		VolumeWriter::new(&base, volume_size)?,
+	)?;
+	assert_eq!(writer.total(), compressed.len() as u64);
+	assert_eq!(writer.finish()?, 3);
+	let sizes: Vec<u64> = (1..=3)
+		.map(|index| Ok(fs::metadata(volume_path(&base, index))?.len()))
+		.collect::<AnyResult<_>>()?;
+	assert_eq!(
+		sizes,
+		[
+			volume_size,
+			volume_size,
+			compressed.len() as u64 - 2 * volume_size
+		]
+	);
+	assert!(!volume_path(&base, 4).exists());
+
+	// reassembled from the first one
+	let reader: VolumeReader = VolumeReader::open(&volume_path(&base, 1), volume_size)?;
+	assert_eq!(reader.remaining_size()?, compressed.len() as u64);
+	let (reader, output): (VolumeReader, Vec<u8>) =
+		decode::<_, _, IO_BUFFER_SIZE>(reader, Vec::new())?;
+	assert_eq!(output, data);
+	assert_eq!(reader.total(), compressed.len() as u64);
+
+	// a missing volume is named in the error
+	fs::rename(volume_path(&base, 2), &base)?;
+	let mut reader: VolumeReader = VolumeReader::open(&volume_path(&base, 1), volume_size)?;
+	let error: io::Error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+	assert!(
+		error.to_string().contains("volumes.srx.002 is missing"),
+		"{}",
+		error
+	);
+	fs::rename(&base, volume_path(&base, 2))?;
+	// only the first volume can be opened
+	assert!(VolumeReader::open(&volume_path(&base, 2), volume_size).is_err());
+
+	// a full last volume is followed by an empty one
+	let mut writer: VolumeWriter = VolumeWriter::new(&base, volume_size)?;
+	writer.write_all(&compressed[..2 * volume_size as usize])?;
+	assert_eq!(writer.finish()?, 3);
+	assert_eq!(fs::metadata(volume_path(&base, 3))?.len(), 0);
+	let mut reader: VolumeReader = VolumeReader::open(&volume_path(&base, 1), volume_size)?;
+	let mut reassembled: Vec<u8> = Vec::new();
+	reader.read_to_end(&mut reassembled)?;
+	assert!(reassembled == compressed[..2 * volume_size as usize]);
+
+	for index in 1..=3 {
+		fs::remove_file(volume_path(&base, index))?;
+	}
+	Ok(())
+}
+
+#[cfg(feature = "threads")]
+#[test]
+fn test_pipe_closed_or_dropped() -> AnyResult<()> {
+	for close in [true, false] {
+		let (mut writer, mut reader): (PipedWriter<u8, 4>, PipedReader<u8, 4>) = pipe();
+		let writer_thread: thread::JoinHandle<AnyResult<()>> = thread::spawn(move || {
+			for value in 0..10 {
+				writer.write(value)?;
+			}
+			// without closing, the writer is dropped like in a stage that panicked
+			match close {
+				true => writer.close(),
+				false => Ok(()),
+			}
+		});
+		let mut values: Vec<u8> = Vec::new();
+		let end: AnyResult<Option<u8>> = loop {
+			match reader.read() {
+				Ok(Some(value)) => values.push(value),
+				end => break end,
+			}
+		};
+		writer_thread.join().unwrap()?;
+		assert_eq!(
+			values,
+			(0..8)
+				.chain(if close { 8..10 } else { 0..0 })
+				.collect::<Vec<u8>>()
+		);
+		match close {
+			true => assert!(matches!(end, Ok(None))),
+			false => assert!(end.unwrap_err().is_channel_closed()),
+		}
+	}
+	Ok(())
+}
+
+#[cfg(feature = "threads")]
+#[test]
+fn test_pipe_end_of_stream() -> AnyResult<()> {
+	// the last buffer exactly full, partially full, and nothing written at all
+	for count in [8u8, 6, 0] {
+		let (mut writer, mut reader): (PipedWriter<u8, 4>, PipedReader<u8, 4>) = pipe();
+		let writer_thread: thread::JoinHandle<AnyResult<()>> = thread::spawn(move || {
+			for value in 0..count {
+				writer.write(value)?;
+			}
+			writer.close()
+		});
+		let mut values: Vec<u8> = Vec::new();
+		while let Some(value) = reader.read()? {
+			values.push(value);
+		}
+		writer_thread.join().unwrap()?;
+		assert_eq!(values, (0..count).collect::<Vec<u8>>(), "{} written", count);
+		// the end stays the end
+		assert!(matches!(reader.read(), Ok(None)), "{} written", count);
+		reader.close()?;
+	}
+	Ok(())
+}
+
+#[cfg(feature = "pipe-stats")]
+#[test]
+fn test_pipe_stats() -> AnyResult<()> {
+	let (mut writer, mut reader): (PipedWriter<u8, 4>, PipedReader<u8, 4>) = pipe();
+	let writer_thread: thread::JoinHandle<AnyResult<PipeStats>> = thread::spawn(move || {
+		let start: PipeStats = pipe_stats();
+		for value in 0..100 {
+			writer.write(value)?;
+		}
+		writer.close()?;
+		Ok(pipe_stats().since(start))
+	});
+	// the writer fills its first buffer long before, then waits for the reader to give one back
+	thread::sleep(Duration::from_millis(20));
+	let start: PipeStats = pipe_stats();
+	while reader.read()?.is_some() {}
+	let reading: PipeStats = pipe_stats().since(start);
+	let writing: PipeStats = writer_thread.join().unwrap()?;
+	// a sync for each full buffer, and one more for the reader to find the end
+	assert_eq!(writing.syncs, 25);
+	assert_eq!(reading.syncs, 26);
+	assert!(writing.stalls > 0 && writing.stalls <= 2 * writing.syncs + 1);
+	assert!(reading.stalls <= 2 * reading.syncs);
+	Ok(())
+}
+
+#[test]
+fn test_crc32() {
+	// the check value of the catalogue of parametrised CRC algorithms
+	assert_eq!(Crc32::of(b""), 0);
+	assert_eq!(Crc32::of(b"123456789"), 0xCBF43926);
+	let mut crc: Crc32 = Crc32::new();
+	crc.update(b"1234");
+	crc.update(b"56789");
+	assert_eq!(crc.value(), 0xCBF43926);
+}
+
+#[test]
+fn test_xxhash64() {
+	// the values of the reference implementation with seed 0
+	assert_eq!(XxHash64::of(b""), 0xEF46DB3751D8E999);
+	assert_eq!(XxHash64::of(b"abc"), 0x44BC2CF5AD770999);
+	let text: &[u8] = b"Nobody inspects the spammish repetition";
+	assert_eq!(XxHash64::of(text), 0xFBCEA83C8A378BF1);
+	// split across and inside the 32-byte stripes
+	for split in [1, 7, 32, 33] {
+		let mut hash: XxHash64 = XxHash64::new();
+		hash.update(&text[..split]);
+		hash.update(&text[split..]);
+		assert_eq!(hash.value(), 0xFBCEA83C8A378BF1, "{}", split);
+	}
+}
+
+#[cfg(feature = "sha256")]
+#[test]
+fn test_sha256() {
+	assert_eq!(
+		Sha256::of(b"abc"),
+		*b"\xba\x78\x16\xbf\x8f\x01\xcf\xea\x41\x41\x40\xde\x5d\xae\x22\x23\
+		\xb0\x03\x61\xa3\x96\x17\x7a\x9c\xb4\x10\xff\x61\xf2\x00\x15\xad"
+	);
+	// many blocks, fed in pieces not aligned to them
+	let text: Vec<u8> = b"The quick brown fox jumps over the lazy dog".repeat(100);
+	let mut hash: Sha256 = Sha256::new();
+	for piece in text.chunks(37) {
+		hash.update(piece);
+	}
+	assert_eq!(hash.value(), Sha256::of(&text));
+	assert_eq!(
+		hash.value(),
+		*b"\x4f\xcc\x26\xa7\x51\x07\xa5\xfb\x70\xab\x05\xfa\x42\x7d\xc2\xc6\
+		\xca\x15\x92\x9b\x4e\x1d\x75\x97\xb8\x99\xcf\x6a\xdb\xab\x8b\x08"
+	);
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+struct Pair {
+	count: u32,
+	last: u16,
+}
+
+// SAFETY: the fields are integers, their default is zero
+unsafe impl Zeroed for Pair {}
+
+#[test]
+fn test_zeroed() {
+	for len in [0, 1, 1000, 1 << 20] {
+		assert_eq!(*zeroed_slice::<Pair>(len), *vec![Pair::default(); len]);
+	}
+}
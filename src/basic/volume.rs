@@ -0,0 +1,223 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::error::{AnyError, AnyResult};
+use super::io::{write_fully, Consumer};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// -----------------------------------------------
+
+const FIRST_VOLUME_SUFFIX: &str = ".001";
+
+// the path of the volume with the given index (from 1) of the output at base
+pub fn volume_path(base: &Path, index: u32) -> PathBuf {
+	let mut path: OsString = base.as_os_str().to_owned();
+	path.push(format!(".{:03}", index));
+	PathBuf::from(path)
+}
+
+// -----------------------------------------------
+
+// Splits its output into volumes of volume_size bytes next to the base path, base.001,
+// base.002 and so on. The next volume is only created when a byte goes into it, and a shorter
+// volume marks the end: if the last one is full, finish() adds an empty one after it.
+pub struct VolumeWriter {
+	base: PathBuf,
+	volume_size: u64,
+	file: File,
+	index: u32,
+	position: u64,
+	total: u64,
+}
+
+impl VolumeWriter {
+	pub fn new(base: &Path, volume_size: u64) -> AnyResult<Self> {
+		if volume_size == 0 {
+			return Err(AnyError::from_string("The volume size must not be zero!"));
+		}
+		Ok(Self {
+			base: base.to_path_buf(),
+			volume_size,
+			file: File::create(volume_path(base, 1))?,
+			index: 1,
+			position: 0,
+			total: 0,
+		})
+	}
+
+	// the number of bytes written into all the volumes
+	pub fn total(&self) -> u64 {
+		self.total
+	}
+
+	// returns the number of volumes, the terminating empty one included
+	pub fn finish(mut self) -> AnyResult<u32> {
+		self.file.flush()?;
+		if self.position == self.volume_size {
+			self.next_volume()?;
+		}
+		Ok(self.index)
+	}
+
+	#[cold]
+	fn next_volume(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		self.index += 1;
+		self.file = File::create(volume_path(&self.base, self.index))?;
+		self.position = 0;
+		Ok(())
+	}
+}
+
+// takes at most the rest of the current volume, the next call rolls over to the next one
+impl Consumer<u8> for VolumeWriter {
+	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
+		Ok(Write::write(self, buffer)?)
+	}
+}
+
+impl Write for VolumeWriter {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		if buffer.is_empty() {
+			return Ok(0);
+		}
+		if self.position == self.volume_size {
+			self.next_volume()?;
+		}
+		let length: usize = (self.volume_size - self.position).min(buffer.len() as u64) as usize;
+		write_fully(&mut self.file, &buffer[..length])?;
+		self.position += length as u64;
+		self.total += length as u64;
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+// -----------------------------------------------
+
+// Reads the volumes written by VolumeWriter back as a single stream, starting from the path
+// of the first one, which must end with .001. After a full volume the next one must exist.
+pub struct VolumeReader {
+	base: PathBuf,
+	volume_size: u64,
+	file: File,
+	index: u32,
+	position: u64,
+	total: u64,
+	is_last: bool,
+}
+
+impl VolumeReader {
+	pub fn open(first: &Path, volume_size: u64) -> AnyResult<Self> {
+		let base: PathBuf = first
+			.to_str()
+			.and_then(|path: &str| path.strip_suffix(FIRST_VOLUME_SUFFIX))
+			.map(PathBuf::from)
+			.ok_or_else(|| {
+				AnyError::from_string(format!(
+					"The first volume must be named with {}!",
+					FIRST_VOLUME_SUFFIX
+				))
+			})?;
+		if volume_size == 0 {
+			return Err(AnyError::from_string("The volume size must not be zero!"));
+		}
+		Ok(Self {
+			base,
+			volume_size,
+			file: File::open(first)?,
+			index: 1,
+			position: 0,
+			total: 0,
+			is_last: false,
+		})
+	}
+
+	// the number of bytes read from all the volumes
+	pub fn total(&self) -> u64 {
+		self.total
+	}
+
+	// the size of this volume and of the ones after it that exist
+	pub fn remaining_size(&self) -> io::Result<u64> {
+		let mut size: u64 = self.file.metadata()?.len();
+		let mut index: u32 = self.index + 1;
+		while let Ok(metadata) = volume_path(&self.base, index).metadata() {
+			size += metadata.len();
+			index += 1;
+		}
+		Ok(size)
+	}
+
+	#[cold]
+	fn next_volume(&mut self) -> io::Result<()> {
+		let path: PathBuf = volume_path(&self.base, self.index + 1);
+		self.file = File::open(&path).map_err(|error: io::Error| {
+			if error.kind() == io::ErrorKind::NotFound {
+				io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("Volume {} is missing!", path.display()),
+				)
+			} else {
+				error
+			}
+		})?;
+		self.index += 1;
+		self.position = 0;
+		Ok(())
+	}
+}
+
+impl Read for VolumeReader {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		if self.is_last || buffer.is_empty() {
+			return Ok(0);
+		}
+		if self.position == self.volume_size {
+			self.next_volume()?;
+		}
+		// a byte more than the volume can hold is read to catch oversized volumes
+		let available: usize =
+			(self.volume_size - self.position + 1).min(buffer.len() as u64) as usize;
+		let length: usize = self.file.read(&mut buffer[..available])?;
+		if length == 0 {
+			// a volume shorter than the others is the last one
+			self.is_last = true;
+			return Ok(0);
+		}
+		self.position += length as u64;
+		if self.position > self.volume_size {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"Volume {} is larger than the volume size!",
+					volume_path(&self.base, self.index).display()
+				),
+			));
+		}
+		self.total += length as u64;
+		Ok(length)
+	}
+}
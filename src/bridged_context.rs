@@ -17,30 +17,172 @@
  */
 
 use crate::basic::Byte;
-use crate::primary_context::{ByteHistory, HistoryState, PrimaryContext};
+use crate::primary_context::{ByteHistory, FixedPrimaryContext, HistoryState, PrimaryContext};
 use crate::secondary_context::SecondaryContext;
 
 // -----------------------------------------------
 
 pub const PRIMARY_CONTEXT_SIZE: usize = 1 << 24;
-pub const SECONDARY_CONTEXT_SIZE: usize = 0x4000 * 256 + (1024 + 32) * 768;
+// literals of the standard model, then the bits: the whole secondary table of every model but
+// the text one, whose literals keyed by words come after them
+const TEXT_LITERAL_BASE: usize = 0x4000 * 256 + (1024 + 32) * 768;
+// the largest secondary table, the one of the text model
+pub const SECONDARY_CONTEXT_SIZE: usize = TEXT_LITERAL_BASE + 0x10000 * 256;
 
 // -----------------------------------------------
 
-pub type BridgedPrimaryContext = PrimaryContext<PRIMARY_CONTEXT_SIZE>;
-pub type BridgedSecondaryContext = SecondaryContext<SECONDARY_CONTEXT_SIZE>;
+pub type BridgedPrimaryContext = PrimaryContext;
+// the primary context of a config whose table neither grows nor has tags, see with_primary
+pub type FixedBridgedPrimaryContext = FixedPrimaryContext<PRIMARY_CONTEXT_SIZE>;
+pub type BridgedSecondaryContext = SecondaryContext;
+
+// -----------------------------------------------
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Model {
+	#[default]
+	Standard,
+	// literals also keyed by the current word, for natural language and source code
+	Text,
+	// the standard model, with a tag on every primary entry so that colliding contexts replace
+	// each other instead of mixing
+	Tagged,
+	// the standard model with every literal in a single context, for high-entropy binaries
+	Flat,
+	// the standard model with the second and third bytes mixed by xor_mix
+	XorMix,
+	// the standard model with the second and third bytes mixed by rotate_mix
+	RotateMix,
+	// the tagged model with buckets of 2 or 4 primary entries, so that colliding contexts
+	// each keep an entry of their own until more than that land on the bucket
+	TwoWay,
+	FourWay,
+}
+
+impl Model {
+	pub const ALL: &'static [Model] = &[
+		Model::Standard,
+		Model::Text,
+		Model::Tagged,
+		Model::Flat,
+		Model::XorMix,
+		Model::RotateMix,
+		Model::TwoWay,
+		Model::FourWay,
+	];
+
+	pub fn id(&self) -> u8 {
+		match self {
+			Model::Standard => 0,
+			Model::Text => 1,
+			Model::Tagged => 2,
+			Model::Flat => 3,
+			Model::XorMix => 4,
+			Model::RotateMix => 5,
+			Model::TwoWay => 6,
+			Model::FourWay => 7,
+		}
+	}
+
+	pub fn from_id(id: u8) -> Option<Self> {
+		Self::ALL.iter().copied().find(|model| model.id() == id)
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Model::Standard => "standard",
+			Model::Text => "text",
+			Model::Tagged => "tagged",
+			Model::Flat => "flat",
+			Model::XorMix => "xor-mix",
+			Model::RotateMix => "rotate-mix",
+			Model::TwoWay => "two-way",
+			Model::FourWay => "four-way",
+		}
+	}
+
+	// the entries of a bucket of the primary table, None when it is not tagged
+	pub fn ways(&self) -> Option<usize> {
+		match self {
+			Model::Tagged => Some(1),
+			Model::TwoWay => Some(2),
+			Model::FourWay => Some(4),
+			Model::Standard | Model::Text | Model::Flat | Model::XorMix | Model::RotateMix => None,
+		}
+	}
+
+	// the entries of the secondary table, only the text model has the literals keyed by words
+	pub fn secondary_context_size(&self) -> usize {
+		match self {
+			Model::Text => SECONDARY_CONTEXT_SIZE,
+			Model::Standard
+			| Model::Tagged
+			| Model::Flat
+			| Model::XorMix
+			| Model::RotateMix
+			| Model::TwoWay
+			| Model::FourWay => TEXT_LITERAL_BASE,
+		}
+	}
+
+	// how the second and third bytes of the history key the second and third bits
+	#[inline]
+	fn mix(&self, second: u8, third: u8) -> (u8, u8) {
+		match self {
+			Model::XorMix => xor_mix(second, third),
+			Model::RotateMix => rotate_mix(second, third),
+			Model::Standard
+			| Model::Text
+			| Model::Tagged
+			| Model::Flat
+			| Model::TwoWay
+			| Model::FourWay => additive_mix(second, third),
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// The contexts of the second and third bits from the second and third bytes of the history,
+// each mix giving a byte for each of them. The additive mix is the hand-tuned default.
+#[inline]
+fn additive_mix(second: u8, third: u8) -> (u8, u8) {
+	(
+		second.wrapping_add(third),
+		second.wrapping_mul(2).wrapping_sub(third),
+	)
+}
+
+#[inline]
+fn xor_mix(second: u8, third: u8) -> (u8, u8) {
+	(second ^ third, second ^ third.rotate_left(1))
+}
+
+#[inline]
+fn rotate_mix(second: u8, third: u8) -> (u8, u8) {
+	(second.rotate_left(4) ^ third, third.rotate_left(4) ^ second)
+}
 
 // -----------------------------------------------
 
 pub struct BridgedContextInfo {
 	bit_context: usize,
+	// the second and third bytes mixed by the model
+	mixed: (u8, u8),
 	literal_context: usize,
 	current_history: ByteHistory,
 	current_state: HistoryState,
 }
 
 impl BridgedContextInfo {
-	pub fn new(current_history: ByteHistory, previous_byte: Byte, hash_value: usize) -> Self {
+	#[inline]
+	pub fn new(
+		model: Model,
+		current_history: ByteHistory,
+		previous_byte: Byte,
+		hash_value: usize,
+		word_hash: usize,
+	) -> Self {
 		let current_state: HistoryState = current_history.get_state();
 		let match_count: usize = current_state.match_count();
 		Self {
@@ -54,46 +196,64 @@ impl BridgedContextInfo {
 						31
 					}
 				} * 768,
-			literal_context: (hash_value & 0x3FFF) * 256,
+			mixed: model.mix(
+				current_history.second_byte().into(),
+				current_history.third_byte().into(),
+			),
+			literal_context: match model {
+				Model::Standard
+				| Model::Tagged
+				| Model::XorMix
+				| Model::RotateMix
+				| Model::TwoWay
+				| Model::FourWay => (hash_value & 0x3FFF) * 256,
+				Model::Text => {
+					TEXT_LITERAL_BASE + ((word_hash ^ usize::from(previous_byte)) & 0xFFFF) * 256
+				}
+				// the first of the standard literal contexts, so the rest of the region is unused
+				Model::Flat => 0,
+			},
 			current_history,
 			current_state,
 		}
 	}
 
+	#[inline]
 	pub fn first_context(&self) -> usize {
 		return self.bit_context + usize::from(self.current_history.first_byte());
 	}
 
+	#[inline]
 	pub fn second_context(&self) -> usize {
-		return self.bit_context
-			+ 0x100 + ((usize::from(self.current_history.second_byte())
-			+ usize::from(self.current_history.third_byte()))
-			& 0xFF);
+		self.bit_context + 0x100 + usize::from(self.mixed.0)
 	}
 
+	#[inline]
 	pub fn third_context(&self) -> usize {
-		return self.bit_context
-			+ 0x200 + ((usize::from(self.current_history.second_byte()) * 2)
-			.wrapping_sub(usize::from(self.current_history.third_byte()))
-			& 0xFF);
+		self.bit_context + 0x200 + usize::from(self.mixed.1)
 	}
 
+	#[inline]
 	pub fn literal_context(&self) -> usize {
 		self.literal_context
 	}
 
+	#[inline]
 	pub fn first_byte(&self) -> Byte {
 		self.current_history.first_byte()
 	}
 
+	#[inline]
 	pub fn second_byte(&self) -> Byte {
 		self.current_history.second_byte()
 	}
 
+	#[inline]
 	pub fn third_byte(&self) -> Byte {
 		self.current_history.third_byte()
 	}
 
+	#[inline]
 	pub fn current_state(&self) -> HistoryState {
 		self.current_state
 	}
@@ -0,0 +1,64 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::stdin::STDIN_PATH;
+use srx::{read_header, AnyError, AnyResult, Header, RESYNC_MARKER, SRX_HEADER};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// -----------------------------------------------
+
+// the sizes of a default header zeroed in place, with the original size of a file or without it
+const ZEROED_HEADER_SIZES: [usize; 2] = [12, 4];
+
+// The header, or with --assume-srx a default one standing for a header that is not there: one
+// zeroed in place is skipped, one stripped off leaves the payload at the start. True when the
+// header was assumed.
+pub fn read_or_assume_header<R: BufRead>(
+	reader: &mut R,
+	assume_srx: bool,
+) -> AnyResult<(Header, bool)> {
+	let start: &[u8] = reader.fill_buf()?;
+	if !assume_srx || start.starts_with(&SRX_HEADER[..2]) {
+		return Ok((read_header(reader)?, false));
+	}
+	let zeroed: usize = ZEROED_HEADER_SIZES
+		.into_iter()
+		.find(|size| start.len() >= *size && start[..*size].iter().all(|byte| *byte == 0))
+		.unwrap_or(0);
+	reader.consume(zeroed);
+	Ok((Header::default(), true))
+}
+
+// Whether the file starts like one srx wrote, for srx auto: a header that reads back, or the
+// marker of a stream of resync segments. Anything else is a file to compress, which srx c
+// compresses even if it happens to start like that.
+pub fn is_srx(path: &Path) -> AnyResult<bool> {
+	if path == Path::new(STDIN_PATH) {
+		return Err(AnyError::from_string(
+			"The start of the standard input can not be read before deciding, use c or d!",
+		));
+	}
+	let mut reader: BufReader<File> = BufReader::new(File::open(path)?);
+	let start: &[u8] = reader.fill_buf()?;
+	if start.starts_with(&RESYNC_MARKER) {
+		return Ok(true);
+	}
+	Ok(start.starts_with(&SRX_HEADER[..2]) && read_header(&mut reader).is_ok())
+}
@@ -0,0 +1,218 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::assume::is_srx;
+use super::compare::{compare, print_comparisons, read_sample};
+use super::estimate::{estimate, Estimate, ESTIMATE_SAMPLE_SIZE};
+use super::options::Options;
+use super::output::Discard;
+use super::preflight::Preflight;
+use super::profile::load_profile;
+use super::progress::{CountingWriter, Progress};
+use super::ratio::RatioOnly;
+use super::recompress::{recompress, Foreign, ForeignOutput};
+use super::run::{compress_file, decompress_path, header_profile, read_decoded_header};
+use super::stdin::{STDIN_PATH, STDOUT_PATH};
+use super::tune::tune;
+use super::verify::verify;
+use srx::{
+	decode_blocks, decode_to_consumer_with_config, split_stored_tail, time_stages, AnyError,
+	AnyResult, Config, Header, NullConsumer, Profile, StageTimes, RESYNC_MARKER,
+};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Sink;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+pub fn run_compare(input_path: &Path) -> AnyResult<()> {
+	let sample: Vec<u8> = read_sample(input_path)?;
+	print_comparisons(sample.len(), &compare(&sample)?);
+	Ok(())
+}
+
+// the whole file stays in memory for the session
+pub fn run_tune(input_path: &Path) -> AnyResult<()> {
+	let data: Vec<u8> = fs::read(input_path)?;
+	tune(&data, io::stdin().lock(), io::stdout().lock())?;
+	Ok(())
+}
+
+pub fn run_estimate(input_path: &Path, options: &Options) -> AnyResult<()> {
+	let estimate: Estimate = estimate(input_path, &options.config, ESTIMATE_SAMPLE_SIZE)?;
+	match options.ratio_only {
+		true => println!("{}", RatioOnly::from(&estimate).json()),
+		false => println!("{}", estimate),
+	}
+	Ok(())
+}
+
+// compresses the file as srx c would, into nothing, only counting the bytes
+pub fn run_ratio_only(
+	input_path: &Path,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<RatioOnly> {
+	if input_path == Path::new(STDIN_PATH) || input_path.is_dir() {
+		return Err(AnyError::from_string("--ratio-only needs an input file!"));
+	}
+	if options.volume_size.is_some()
+		|| options.resync.is_some()
+		|| options.profile_out.is_some()
+		|| options.map_context.is_some()
+	{
+		return Err(AnyError::from_string(
+			"--ratio-only writes nothing, no volume, resync segment, profile or context map!",
+		));
+	}
+	let reader: File = File::open(input_path)?;
+	let written: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+	let writer: CountingWriter<Sink> = CountingWriter::new(io::sink(), written.clone());
+	let (input_size, _, _): (u64, CountingWriter<Sink>, u64) =
+		compress_file(input_path, reader, writer, options, progress)?;
+	Ok(RatioOnly {
+		input: input_size,
+		output: written.load(Ordering::Relaxed),
+		confidence: 1.0,
+	})
+}
+
+// The whole file is read before the timer starts and the output is only counted, so the time is
+// the one of the model and the coder alone. Nothing is checked or restored.
+pub fn run_benchmark(input_path: &Path, options: &Options) -> AnyResult<(u64, u64, Duration)> {
+	let data: Vec<u8> = fs::read(input_path)?;
+	if data.first() == Some(&RESYNC_MARKER[0]) {
+		return Err(AnyError::from_string(
+			"--benchmark decodes a single payload, not resync segments!",
+		));
+	}
+	let mut payload: &[u8] = &data;
+	let header: Header = read_decoded_header(&mut payload, options)?;
+	let warm_start: Option<Profile> = header_profile(&header, load_profile(options)?, options)?;
+	if header.volume_size.is_some() {
+		return Err(AnyError::from_string(
+			"--benchmark decodes a single file, not volumes!",
+		));
+	}
+	// the stored tail is part of the output without being decoded
+	let (payload, tail): (&[u8], &[u8]) = match header.stored_tail {
+		true => split_stored_tail(payload)?,
+		false => (payload, &[]),
+	};
+	let config: Config = header
+		.config
+		.with_buffers(options.config.buffers)
+		.with_strict_end(options.config.strict_end);
+	let start: Instant = Instant::now();
+	let decoded: u64 = match &header.blocks {
+		// the outputs of the blocks are held until written, into nothing here
+		Some(blocks) => {
+			decode_blocks(payload, io::sink(), blocks, &config, warm_start.as_ref())?;
+			blocks.iter().map(|block| block.original_size).sum()
+		}
+		None => {
+			let (_, consumer): (&[u8], NullConsumer) = decode_to_consumer_with_config(
+				payload,
+				NullConsumer::default(),
+				&config,
+				warm_start.as_ref(),
+			)?;
+			consumer.consumed()
+		}
+	};
+	let duration: Duration = start.elapsed();
+	Ok((data.len() as u64, decoded + tail.len() as u64, duration))
+}
+
+pub fn run_preflight(input_path: &Path, options: &Options) -> AnyResult<bool> {
+	let preflight: Preflight = Preflight::new(input_path, options.memory_limit)?;
+	println!("{}", preflight);
+	Ok(!preflight.exceeds_limit())
+}
+
+// A gzip or zstd input is decompressed on the fly into srx, and a srx one decoded on the fly
+// into the format the output is named after. Either way, the sizes are the decompressed size of
+// the data and the size of the output.
+pub fn run_recompress(
+	input_path: &Path,
+	output_path: &Path,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	if input_path == Path::new(STDIN_PATH) || output_path == Path::new(STDOUT_PATH) {
+		return Err(AnyError::from_string(
+			"Recompressing needs the paths of the input and the output, not the standard streams!",
+		));
+	}
+	let start: Instant = Instant::now();
+	let (input_size, output_size): (u64, u64) = match Foreign::of_input(input_path)? {
+		Some(format) => recompress(input_path, output_path, format, options)?,
+		None if is_srx(input_path)? => {
+			let output: ForeignOutput =
+				ForeignOutput::create(output_path, Foreign::of_output(output_path))?;
+			let reader: File = File::open(input_path)?;
+			let (_, decoded_size): (u64, u64) =
+				decompress_path(input_path, reader, output, None, options, progress)?;
+			(decoded_size, fs::metadata(output_path)?.len())
+		}
+		None => return Err(AnyError::from_string("Not a gzip, zstd or srx file!")),
+	};
+	Ok((input_size, output_size, start.elapsed()))
+}
+
+// decompresses the file into nothing, its size and checksum checked as srx d would
+pub fn run_test(input_path: &Path, options: &Options, progress: &Progress) -> AnyResult<u64> {
+	if input_path == Path::new(STDIN_PATH) || input_path.is_dir() {
+		return Err(AnyError::from_string("srx t needs a compressed file!"));
+	}
+	let reader: File = File::open(input_path)?;
+	let (_, output_size): (u64, u64) = decompress_path(
+		input_path,
+		reader,
+		Discard::default(),
+		None,
+		options,
+		progress,
+	)?;
+	Ok(output_size)
+}
+
+pub fn run_verify(archive_path: &Path, reference_path: &Path) -> AnyResult<bool> {
+	let (size, mismatch): (u64, Option<u64>) = verify(archive_path, reference_path)?;
+	match mismatch {
+		None => println!("Verified: all {} bytes match.", size),
+		Some(offset) => println!("Mismatch! The first difference is at offset {}.", offset),
+	}
+	Ok(mismatch.is_none())
+}
+
+// the stages are only timed for --flat-profile
+pub fn time_stages_if<T>(enabled: bool, body: impl FnOnce() -> T) -> (T, Option<StageTimes>) {
+	match enabled {
+		true => {
+			let (result, times): (T, StageTimes) = time_stages(body);
+			(result, Some(times))
+		}
+		false => (body(), None),
+	}
+}
@@ -0,0 +1,111 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{compress_with, decompress, AnyError, AnyResult, Config};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+// only the head of huge files is compared, the ratio of a 64 MiB sample is a good enough hint
+pub const SAMPLE_SIZE: u64 = 64 << 20;
+
+// -----------------------------------------------
+
+pub struct Comparison {
+	pub config: Config,
+	pub compressed: Vec<u8>,
+	pub duration: Duration,
+}
+
+impl Comparison {
+	fn ratio(&self, sample_size: usize) -> f64 {
+		self.compressed.len() as f64 / sample_size.max(1) as f64
+	}
+
+	fn ratio_per_second(&self, sample_size: usize) -> f64 {
+		// compression factor gained per second, higher is better
+		sample_size as f64 / self.compressed.len() as f64 / self.duration.as_secs_f64().max(0.001)
+	}
+}
+
+// -----------------------------------------------
+
+pub fn read_sample(path: &Path) -> AnyResult<Vec<u8>> {
+	let mut sample: Vec<u8> = Vec::new();
+	File::open(path)?
+		.take(SAMPLE_SIZE)
+		.read_to_end(&mut sample)?;
+	Ok(sample)
+}
+
+pub fn compare(sample: &[u8]) -> AnyResult<Vec<Comparison>> {
+	Config::all()
+		.map(|config| {
+			let start: Instant = Instant::now();
+			let compressed: Vec<u8> = compress_with(sample, &config)?;
+			let duration: Duration = start.elapsed();
+			if decompress(&compressed)? != sample {
+				return Err(AnyError::from_string(format!(
+					"Config {} does not round-trip!",
+					config.name()
+				)));
+			}
+			Ok(Comparison {
+				config,
+				compressed,
+				duration,
+			})
+		})
+		.collect()
+}
+
+pub fn print_comparisons(sample_size: usize, comparisons: &[Comparison]) {
+	let best_ratio: Option<usize> =
+		(0..comparisons.len()).min_by_key(|&index| comparisons[index].compressed.len());
+	let best_speed: Option<usize> = (0..comparisons.len()).max_by(|&left, &right| {
+		comparisons[left]
+			.ratio_per_second(sample_size)
+			.total_cmp(&comparisons[right].ratio_per_second(sample_size))
+	});
+	println!(
+		"{:<16} {:>14} {:>9} {:>10}",
+		"config", "size", "ratio", "seconds"
+	);
+	for (index, comparison) in comparisons.iter().enumerate() {
+		println!(
+			"{:<16} {:>14} {:>8.2}% {:>10.2}{}{}",
+			comparison.config.name(),
+			comparison.compressed.len(),
+			comparison.ratio(sample_size) * 100.0,
+			comparison.duration.as_secs_f64(),
+			if best_ratio == Some(index) {
+				"  [best ratio]"
+			} else {
+				""
+			},
+			if best_speed == Some(index) {
+				"  [best ratio/second]"
+			} else {
+				""
+			},
+		);
+	}
+}
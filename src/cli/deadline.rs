@@ -0,0 +1,87 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{AnyError, AnyResult};
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Instant;
+
+// -----------------------------------------------
+
+// Ends the stream early once the deadline has passed, as if the input ended there. The encoder
+// then finishes a valid payload with what it has read, and the rest of the input is left in the
+// inner reader, to be stored as it is after the payload.
+pub struct DeadlineReader<R: Read> {
+	reader: R,
+	deadline: Option<Instant>,
+	expired: bool,
+}
+
+impl<R: Read> DeadlineReader<R> {
+	pub fn new(reader: R, deadline: Option<Instant>) -> Self {
+		Self {
+			reader,
+			deadline,
+			expired: false,
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		// once expired, stays so, the encoder never sees the stream going on again
+		if !self.expired
+			&& self
+				.deadline
+				.is_some_and(|deadline| Instant::now() >= deadline)
+		{
+			self.expired = true;
+		}
+		match self.expired {
+			true => Ok(0),
+			false => self.reader.read(buffer),
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// The size of the payload starting at payload_start in a file with a stored tail, and a reader
+// of the tail after it. The size of the tail is in the last 8 bytes, see srx::split_stored_tail.
+pub fn open_stored_tail(path: &Path, payload_start: u64) -> AnyResult<(u64, io::Take<File>)> {
+	let mut file: File = File::open(path)?;
+	let total: u64 = file.metadata()?.len();
+	let truncated = || AnyError::truncated_input("Truncated SRX stored tail!");
+	let trailer_start: u64 = total.checked_sub(8).ok_or_else(truncated)?;
+	let mut trailer: [u8; 8] = [0; 8];
+	file.seek(SeekFrom::Start(trailer_start))?;
+	file.read_exact(&mut trailer)?;
+	let tail_size: u64 = u64::from_le_bytes(trailer);
+	let tail_start: u64 = trailer_start
+		.checked_sub(tail_size)
+		.filter(|tail_start| *tail_start >= payload_start)
+		.ok_or_else(truncated)?;
+	file.seek(SeekFrom::Start(tail_start))?;
+	Ok((tail_start - payload_start, file.take(tail_size)))
+}
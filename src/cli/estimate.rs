@@ -0,0 +1,99 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{encode_with_config, AnyError, AnyResult, Config};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// -----------------------------------------------
+
+// a megabyte from the start, the middle and the end of the file is read in well under a second
+pub const ESTIMATE_SAMPLE_SIZE: u64 = 1 << 20;
+const ESTIMATE_SAMPLES: u64 = 3;
+
+// -----------------------------------------------
+
+pub struct Estimate {
+	pub file_size: u64,
+	pub sampled: u64,
+	pub compressed: u64,
+}
+
+impl Estimate {
+	pub fn ratio(&self) -> f64 {
+		self.compressed as f64 / self.sampled.max(1) as f64
+	}
+
+	pub fn estimated_size(&self) -> u64 {
+		(self.ratio() * self.file_size as f64).round() as u64
+	}
+}
+
+impl Display for Estimate {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		write!(
+			formatter,
+			"Estimated: {} -> about {} ({:.2}%), from {} sampled bytes",
+			self.file_size,
+			self.estimated_size(),
+			self.ratio() * 100.0,
+			self.sampled
+		)
+	}
+}
+
+// -----------------------------------------------
+
+// Compresses samples of sample_size bytes spread over the file, each with fresh contexts, and
+// takes their overall ratio as the one of the whole file. The samples are seeked to, so the
+// input must be a file and not a pipe. A file too small for them is compressed whole.
+pub fn estimate(path: &Path, config: &Config, sample_size: u64) -> AnyResult<Estimate> {
+	let mut file: File = File::open(path)?;
+	if !file.metadata()?.is_file() {
+		return Err(AnyError::from_string(
+			"Estimating needs a file to seek in, not a pipe!",
+		));
+	}
+	let file_size: u64 = file.metadata()?.len();
+	let (offsets, length): (Vec<u64>, u64) = if file_size <= sample_size * ESTIMATE_SAMPLES {
+		(vec![0], file_size)
+	} else {
+		let offsets: Vec<u64> = (0..ESTIMATE_SAMPLES)
+			.map(|index| (file_size - sample_size) * index / (ESTIMATE_SAMPLES - 1))
+			.collect();
+		(offsets, sample_size)
+	};
+	let mut estimate: Estimate = Estimate {
+		file_size,
+		sampled: 0,
+		compressed: 0,
+	};
+	for offset in offsets {
+		file.seek(SeekFrom::Start(offset))?;
+		let mut sample: Vec<u8> = Vec::new();
+		(&mut file).take(length).read_to_end(&mut sample)?;
+		let (_, compressed): (&[u8], Vec<u8>) =
+			encode_with_config(sample.as_slice(), Vec::new(), config)?;
+		estimate.sampled += sample.len() as u64;
+		estimate.compressed += compressed.len() as u64;
+	}
+	Ok(estimate)
+}
@@ -0,0 +1,122 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{AnyError, AnyResult};
+use std::fs::File;
+#[cfg(unix)]
+use std::io;
+use std::io::Seek;
+#[cfg(unix)]
+use std::io::Write;
+
+// -----------------------------------------------
+
+// Takes over a descriptor set up by a parent process, after checking that it is open for
+// reading, or for writing. The file closes it when dropped, so a descriptor must only be taken
+// once: the input and the output can not be the same one.
+#[cfg(unix)]
+pub fn take_fd(fd: i32, writable: bool) -> AnyResult<File> {
+	use std::os::unix::io::FromRawFd;
+
+	// SAFETY: F_GETFL only reads the flags of the descriptor, failing if it is not open
+	let flags: libc::c_int = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+	if flags < 0 {
+		return Err(AnyError::from_string(format!(
+			"File descriptor {} is not open!",
+			fd
+		)));
+	}
+	let allowed: bool = match flags & libc::O_ACCMODE {
+		libc::O_RDWR => true,
+		libc::O_WRONLY => writable,
+		_ => !writable,
+	};
+	if !allowed {
+		return Err(AnyError::from_string(format!(
+			"File descriptor {} is not open for {}!",
+			fd,
+			if writable { "writing" } else { "reading" }
+		)));
+	}
+	// SAFETY: the descriptor is open and nothing else in srx owns it
+	Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+pub fn take_fd(_fd: i32, _writable: bool) -> AnyResult<File> {
+	Err(AnyError::from_string(
+		"File descriptors are only supported on Unix!",
+	))
+}
+
+// The standard input, for - given as the input path when decompressing, as a file of its own
+// which can be closed without closing the standard input.
+#[cfg(unix)]
+pub fn take_stdin() -> AnyResult<File> {
+	duplicate(0)
+}
+
+// The standard output, for - given as the output path. The output gets a descriptor of its
+// own, and the standard output is pointed to the standard error from then on, so that the
+// messages of srx, the report included, do not end up in the middle of it.
+#[cfg(unix)]
+pub fn take_stdout() -> AnyResult<File> {
+	io::stdout().flush()?;
+	let file: File = duplicate(1)?;
+	// SAFETY: dup2 only replaces descriptor 1, which nothing in srx owns
+	if unsafe { libc::dup2(2, 1) } < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(file)
+}
+
+#[cfg(unix)]
+fn duplicate(fd: i32) -> AnyResult<File> {
+	use std::os::unix::io::FromRawFd;
+
+	// SAFETY: dup only reads the descriptor, the new one being owned by the file alone
+	let duplicated: libc::c_int = unsafe { libc::dup(fd) };
+	if duplicated < 0 {
+		return Err(io::Error::last_os_error().into());
+	}
+	Ok(unsafe { File::from_raw_fd(duplicated) })
+}
+
+#[cfg(not(unix))]
+pub fn take_stdin() -> AnyResult<File> {
+	Err(AnyError::from_string(
+		"Decompressing the standard input is only supported on Unix!",
+	))
+}
+
+#[cfg(not(unix))]
+pub fn take_stdout() -> AnyResult<File> {
+	Err(AnyError::from_string(
+		"Writing to the standard output is only supported on Unix!",
+	))
+}
+
+// what is left to read of a regular file, nothing being known about a pipe or a socket
+pub fn remaining_size(file: &mut File) -> Option<u64> {
+	let length: u64 = file
+		.metadata()
+		.ok()
+		.filter(|metadata| metadata.file_type().is_file())?
+		.len();
+	length.checked_sub(file.stream_position().ok()?)
+}
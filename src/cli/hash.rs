@@ -0,0 +1,79 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::options::{ContentHash, Options};
+use srx::{AnyResult, Checksum, ChecksumHasher, Crc32};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+// -----------------------------------------------
+
+// the hash to write into the header, reading the whole input once more if srx computes it
+pub fn content_hash(path: &Path, options: &Options) -> AnyResult<Option<Vec<u8>>> {
+	match &options.content_hash {
+		None => Ok(None),
+		Some(ContentHash::Given(hash)) => Ok(Some(hash.clone())),
+		Some(ContentHash::Crc32) => {
+			let mut crc: Crc32 = Crc32::new();
+			read_all(path, |buffer| crc.update(buffer))?;
+			// big-endian, so that it prints like the usual CRC-32 strings
+			Ok(Some(crc.value().to_be_bytes().to_vec()))
+		}
+	}
+}
+
+// the checksum the decoder verifies its output against, reading the whole input once more
+pub fn checksum(path: &Path, options: &Options) -> AnyResult<Option<Checksum>> {
+	match options.checksum_algo {
+		None => Ok(None),
+		Some(algo) => {
+			let mut hasher: ChecksumHasher = ChecksumHasher::new(algo)?;
+			read_all(path, |buffer| hasher.update(buffer))?;
+			Ok(Some(hasher.finish()))
+		}
+	}
+}
+
+fn read_all(path: &Path, mut consume: impl FnMut(&[u8])) -> AnyResult<()> {
+	let mut file: File = File::open(path)?;
+	let mut buffer: Vec<u8> = vec![0; 1 << 16];
+	loop {
+		match file.read(&mut buffer) {
+			Ok(0) => return Ok(()),
+			Ok(length) => consume(&buffer[..length]),
+			Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+			Err(error) => return Err(error.into()),
+		}
+	}
+}
+
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) || !text.is_ascii() {
+		return None;
+	}
+	(0..text.len())
+		.step_by(2)
+		.map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok())
+		.collect()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,53 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::options::Options;
+use srx::AnyResult;
+use std::path::Path;
+
+// -----------------------------------------------
+
+// how many bytes from the start of the input are mapped when --map-bytes is not given
+#[cfg(feature = "map-context")]
+pub const DEFAULT_MAP_BYTES: u64 = 1 << 16;
+
+// -----------------------------------------------
+
+#[cfg(feature = "map-context")]
+pub fn write_context_map(input_path: &Path, map_path: &Path, options: &Options) -> AnyResult<u64> {
+	use srx::map_contexts;
+	use std::fs::File;
+
+	map_contexts(
+		File::open(input_path)?,
+		File::create(map_path)?,
+		&options.config,
+		options.map_bytes.unwrap_or(DEFAULT_MAP_BYTES),
+	)
+}
+
+#[cfg(not(feature = "map-context"))]
+pub fn write_context_map(
+	_input_path: &Path,
+	_map_path: &Path,
+	_options: &Options,
+) -> AnyResult<u64> {
+	Err(srx::AnyError::from_string(
+		"The context map is not supported by this build!",
+	))
+}
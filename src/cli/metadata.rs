@@ -0,0 +1,104 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::options::Options;
+use super::owner::{restore_owner, store_owner, OWNER_RECORD};
+use super::xattrs::{restore_xattrs, store_xattrs};
+use srx::{AnyError, AnyResult};
+use std::path::Path;
+
+// -----------------------------------------------
+
+// The metadata block of the header is a sequence of (u32 LE name length, name, u32 LE value
+// length, value) records. Extended attributes are stored under their own names, which always
+// have a namespace (user., security., ...), while what srx keeps about the file itself goes
+// under srx. names, which are no valid attribute names.
+pub type Record = (Vec<u8>, Vec<u8>);
+
+const SRX_PREFIX: &[u8] = b"srx.";
+
+// -----------------------------------------------
+
+pub fn encode_records(records: &[Record]) -> Vec<u8> {
+	let mut metadata: Vec<u8> = Vec::new();
+	for (name, value) in records {
+		metadata.extend_from_slice(&(name.len() as u32).to_le_bytes());
+		metadata.extend_from_slice(name);
+		metadata.extend_from_slice(&(value.len() as u32).to_le_bytes());
+		metadata.extend_from_slice(value);
+	}
+	metadata
+}
+
+pub fn decode_records(mut metadata: &[u8]) -> AnyResult<Vec<Record>> {
+	fn field<'a>(metadata: &mut &'a [u8]) -> AnyResult<&'a [u8]> {
+		let error = || AnyError::from_string("Corrupted metadata!");
+		let (length, rest): (&[u8], &[u8]) = metadata.split_at_checked(4).ok_or_else(error)?;
+		let length: usize = u32::from_le_bytes(length.try_into()?) as usize;
+		let (value, rest): (&[u8], &[u8]) = rest.split_at_checked(length).ok_or_else(error)?;
+		*metadata = rest;
+		Ok(value)
+	}
+	let mut records: Vec<Record> = Vec::new();
+	while !metadata.is_empty() {
+		let name: &[u8] = field(&mut metadata)?;
+		let value: &[u8] = field(&mut metadata)?;
+		records.push((name.to_vec(), value.to_vec()));
+	}
+	Ok(records)
+}
+
+// -----------------------------------------------
+
+// the metadata to store in the header for the options given, None if there is nothing to store
+pub fn store_metadata(path: &Path, options: &Options) -> AnyResult<Option<Vec<u8>>> {
+	if !options.xattrs && !options.owner {
+		return Ok(None);
+	}
+	let mut records: Vec<Record> = Vec::new();
+	if options.xattrs {
+		records.extend(store_xattrs(path)?);
+	}
+	if options.owner {
+		records.extend(store_owner(path)?);
+	}
+	Ok(Some(encode_records(&records)))
+}
+
+// restores what the options ask for and the metadata has, returns how many records could not
+// be applied. Like they are only stored when asked for, they are only restored when asked for.
+pub fn restore_metadata(path: &Path, metadata: &[u8], options: &Options) -> AnyResult<usize> {
+	let records: Vec<Record> = decode_records(metadata)?;
+	let mut skipped: usize = 0;
+	if options.xattrs {
+		let attributes: Vec<Record> = records
+			.iter()
+			.filter(|(name, _)| !name.starts_with(SRX_PREFIX))
+			.cloned()
+			.collect();
+		skipped += restore_xattrs(path, &attributes)?;
+	}
+	if options.owner {
+		for (_, value) in records.iter().filter(|(name, _)| name == OWNER_RECORD) {
+			if !restore_owner(path, value)? {
+				skipped += 1;
+			}
+		}
+	}
+	Ok(skipped)
+}
@@ -0,0 +1,187 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{AnyError, AnyResult};
+use std::fs::File;
+use std::io;
+
+// -----------------------------------------------
+
+// below this, reading is as fast as mapping and needs no system call to set up
+pub const MMAP_THRESHOLD: u64 = 1 << 20;
+
+// -----------------------------------------------
+
+// when the input of the compression is mapped in memory instead of read
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum MmapPolicy {
+	Never,
+	// regular, non-sparse files of at least MMAP_THRESHOLD bytes, reading them on any error
+	#[default]
+	Auto,
+	// failing if the file can not be mapped
+	Always,
+}
+
+impl MmapPolicy {
+	pub fn parse(text: &str) -> Option<Self> {
+		match text {
+			"never" => Some(Self::Never),
+			"auto" => Some(Self::Auto),
+			"always" => Some(Self::Always),
+			_ => None,
+		}
+	}
+}
+
+// -----------------------------------------------
+
+#[cfg(unix)]
+mod platform {
+	use std::fs::File;
+	use std::io;
+	use std::os::unix::fs::MetadataExt;
+	use std::os::unix::io::AsRawFd;
+	use std::{ptr, slice};
+
+	// A read only private mapping of a whole file. If the file is truncated while mapped, reading
+	// the missing pages raises SIGBUS, which no policy can guard against: files that are still
+	// being written should be compressed with --mmap=never.
+	pub struct Mapped {
+		pointer: *mut libc::c_void,
+		length: usize,
+	}
+
+	// the mapping is read only, and only unmapped on drop
+	unsafe impl Send for Mapped {}
+	unsafe impl Sync for Mapped {}
+
+	impl Mapped {
+		pub fn new(file: &File) -> io::Result<Self> {
+			let metadata: std::fs::Metadata = file.metadata()?;
+			if !metadata.is_file() {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"Only regular files can be mapped!",
+				));
+			}
+			if metadata.len() == 0 {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"Empty files can not be mapped!",
+				));
+			}
+			let length: usize = usize::try_from(metadata.len()).map_err(|_| {
+				io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"The file is larger than the address space!",
+				)
+			})?;
+			// SAFETY: a fresh mapping of a valid descriptor, checked for failure below
+			let pointer: *mut libc::c_void = unsafe {
+				libc::mmap(
+					ptr::null_mut(),
+					length,
+					libc::PROT_READ,
+					libc::MAP_PRIVATE,
+					file.as_raw_fd(),
+					0,
+				)
+			};
+			if pointer == libc::MAP_FAILED {
+				return Err(io::Error::last_os_error());
+			}
+			// SAFETY: the range was just mapped, the advice is only a hint
+			unsafe { libc::madvise(pointer, length, libc::MADV_SEQUENTIAL) };
+			Ok(Self { pointer, length })
+		}
+
+		pub fn as_slice(&self) -> &[u8] {
+			// SAFETY: the mapping lives as long as self and is never written
+			unsafe { slice::from_raw_parts(self.pointer as *const u8, self.length) }
+		}
+	}
+
+	impl Drop for Mapped {
+		fn drop(&mut self) {
+			// SAFETY: unmapping exactly what new mapped, once
+			unsafe { libc::munmap(self.pointer, self.length) };
+		}
+	}
+
+	// holes are skipped by the sparse reader, while mapping would read them as zero pages
+	pub fn is_sparse(file: &File) -> io::Result<bool> {
+		let metadata: std::fs::Metadata = file.metadata()?;
+		Ok(metadata.blocks() * 512 < metadata.len())
+	}
+}
+
+#[cfg(not(unix))]
+mod platform {
+	use std::fs::File;
+	use std::io;
+
+	pub struct Mapped(());
+
+	impl Mapped {
+		pub fn new(_file: &File) -> io::Result<Self> {
+			Err(io::Error::new(
+				io::ErrorKind::Unsupported,
+				"Memory mapping is not supported on this platform!",
+			))
+		}
+
+		pub fn as_slice(&self) -> &[u8] {
+			&[]
+		}
+	}
+
+	pub fn is_sparse(_file: &File) -> io::Result<bool> {
+		Ok(false)
+	}
+}
+
+pub use self::platform::Mapped;
+
+// -----------------------------------------------
+
+// None when the file is to be read instead
+pub fn map_input(file: &File, policy: MmapPolicy) -> AnyResult<Option<Mapped>> {
+	match policy {
+		MmapPolicy::Never => Ok(None),
+		MmapPolicy::Always => match Mapped::new(file) {
+			Ok(mapped) => Ok(Some(mapped)),
+			Err(error) => Err(AnyError::from_string(format!(
+				"The input can not be mapped: {}",
+				error
+			))),
+		},
+		MmapPolicy::Auto => {
+			let worth_it = || -> io::Result<bool> {
+				let metadata: std::fs::Metadata = file.metadata()?;
+				Ok(metadata.is_file()
+					&& metadata.len() >= MMAP_THRESHOLD
+					&& !platform::is_sparse(file)?)
+			};
+			match worth_it() {
+				Ok(true) => Ok(Mapped::new(file).ok()),
+				_ => Ok(None),
+			}
+		}
+	}
+}
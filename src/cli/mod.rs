@@ -0,0 +1,57 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod assume;
+mod command;
+mod compare;
+mod deadline;
+mod estimate;
+mod fd;
+mod hash;
+mod map;
+mod metadata;
+mod mmap;
+mod options;
+mod output;
+mod owner;
+mod preflight;
+mod preset;
+mod profile;
+mod progress;
+mod ratio;
+mod recompress;
+mod report;
+mod run;
+mod stdin;
+mod stream;
+#[cfg(test)]
+mod test;
+mod tune;
+mod verify;
+mod xattrs;
+
+pub use self::assume::is_srx;
+pub use self::command::{
+	run_benchmark, run_compare, run_estimate, run_preflight, run_ratio_only, run_recompress,
+	run_test, run_tune, run_verify, time_stages_if,
+};
+pub use self::options::Options;
+pub use self::progress::{show_progress, Progress};
+pub use self::report::{Report, StageReport};
+pub use self::run::{check_no_metadata, check_output, run, EXIT_REFUSED};
+pub use self::stream::run_fds;
@@ -0,0 +1,227 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::hash::parse_hex;
+use super::mmap::MmapPolicy;
+use super::preset::Preset;
+use srx::{Buffers, ChecksumAlgo, Config, Growth, Model, MAX_BLOCKS, MAX_LANES, MAX_SEGMENT_SIZE};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// -----------------------------------------------
+
+// the hash of the input to write into the header
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ContentHash {
+	// a CRC-32 computed by srx
+	Crc32,
+	// computed by the caller, stored as is
+	Given(Vec<u8>),
+}
+
+// -----------------------------------------------
+
+// the --options of the compress/decompress commands
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Options {
+	pub xattrs: bool,
+	pub owner: bool,
+	// only used when compressing, refuse what would make the output depend on more than the
+	// content of the input
+	pub no_metadata: bool,
+	// overwrite an existing output file instead of refusing to
+	pub force: bool,
+	pub progress_file: Option<PathBuf>,
+	// keep a line with the percentage done and the speed on the standard error
+	pub progress: bool,
+	// only print what decompressing would take
+	pub preflight: bool,
+	// only used when decompressing, a missing or zeroed header stands for the default one
+	pub assume_srx: bool,
+	// only print the ratio as JSON, writing no output
+	pub ratio_only: bool,
+	// only used when decompressing, decode from memory into nothing and report the speed
+	pub benchmark: bool,
+	// only used when compressing a file, decode the output as it is written and compare it
+	pub verify: bool,
+	// print how long each stage of the pipeline worked after the report
+	pub flat_profile: bool,
+	// in bytes, refuse to compress or decompress with a config needing more
+	pub memory_limit: Option<u64>,
+	// in bytes, compress into independently decodable segments between resync markers
+	pub resync: Option<usize>,
+	// only used when compressing a file, compress it in this many blocks at once
+	pub blocks: Option<usize>,
+	// in bytes, only used when compressing a file, split the output into volumes of this size
+	pub volume_size: Option<u64>,
+	// the size of the input piped to the standard input, written into the header
+	pub stdin_size: Option<u64>,
+	// descriptors set up by a parent process, read and written instead of the paths (Unix)
+	pub input_fd: Option<i32>,
+	pub output_fd: Option<i32>,
+	pub content_hash: Option<ContentHash>,
+	// only used when compressing a file, the decoder verifies its output against it
+	pub checksum_algo: Option<ChecksumAlgo>,
+	// only used when compressing, a CSV of the contexts of the first map_bytes of the input
+	pub map_context: Option<PathBuf>,
+	pub map_bytes: Option<u64>,
+	// only used when compressing a file, print how often the primary contexts collide
+	pub collision_report: bool,
+	// only used when compressing a file, print how the bytes matched and the bits they took
+	pub self_profile: bool,
+	// only used when compressing a file, print how well the predictions match the bits
+	pub calibration: bool,
+	// only used when compressing a file
+	pub mmap: MmapPolicy,
+	// only used when compressing a file, what is left of it when the time is up is stored as it is
+	pub max_time: Option<Duration>,
+	// the contexts start from this profile, needed again to decompress
+	pub profile_in: Option<PathBuf>,
+	// only used when compressing, where to save the profile of the contexts at the end
+	pub profile_out: Option<PathBuf>,
+	// the contexts start from a profile built into srx, found back from the header when
+	// decompressing
+	pub preset: Option<Preset>,
+	// only used when compressing, decompression takes it from the header but the buffers
+	pub config: Config,
+}
+
+impl Options {
+	// None if any of the options is unknown or malformed
+	pub fn parse<S: AsRef<str>>(options: &[S]) -> Option<Self> {
+		let mut parsed: Options = Options::default();
+		for option in options {
+			match option.as_ref().split_once('=') {
+				None if option.as_ref() == "--xattrs" => parsed.xattrs = true,
+				None if option.as_ref() == "--owner" => parsed.owner = true,
+				None if option.as_ref() == "--text" => parsed.config.model = Model::Text,
+				None if option.as_ref() == "--tagged" => parsed.config.model = Model::Tagged,
+				None if option.as_ref() == "--flat" => parsed.config.model = Model::Flat,
+				None if option.as_ref() == "--xor-mix" => parsed.config.model = Model::XorMix,
+				None if option.as_ref() == "--rotate-mix" => parsed.config.model = Model::RotateMix,
+				None if option.as_ref() == "--two-way" => parsed.config.model = Model::TwoWay,
+				None if option.as_ref() == "--four-way" => parsed.config.model = Model::FourWay,
+				None if option.as_ref() == "--grow" => {
+					parsed.config = parsed.config.with_growth(Some(Growth::default()));
+				}
+				None if option.as_ref() == "--preflight" => parsed.preflight = true,
+				None if option.as_ref() == "--assume-srx" => parsed.assume_srx = true,
+				None if option.as_ref() == "--ratio-only" => parsed.ratio_only = true,
+				None if option.as_ref() == "--benchmark" => parsed.benchmark = true,
+				None if option.as_ref() == "--verify" => parsed.verify = true,
+				None if option.as_ref() == "--no-metadata" => parsed.no_metadata = true,
+				None if matches!(option.as_ref(), "--force" | "-f") => parsed.force = true,
+				None if option.as_ref() == "--progress" => parsed.progress = true,
+				None if option.as_ref() == "--flat-profile" => parsed.flat_profile = true,
+				None if option.as_ref() == "--collision-report" => parsed.collision_report = true,
+				None if option.as_ref() == "--self-profile" => parsed.self_profile = true,
+				None if option.as_ref() == "--calibration" => parsed.calibration = true,
+				None if option.as_ref() == "--content-hash" => {
+					parsed.content_hash = Some(ContentHash::Crc32);
+				}
+				Some(("--content-hash", hex)) => {
+					let hash: Vec<u8> = parse_hex(hex)?;
+					if hash.is_empty() || hash.len() > u8::MAX as usize {
+						return None;
+					}
+					parsed.content_hash = Some(ContentHash::Given(hash));
+				}
+				Some(("--checksum-algo", name)) => {
+					parsed.checksum_algo = Some(ChecksumAlgo::from_name(name)?);
+				}
+				Some(("--max-memory", mebibytes)) => {
+					let mebibytes: u64 = mebibytes.parse().ok()?;
+					parsed.memory_limit = Some(mebibytes.checked_mul(1 << 20)?);
+				}
+				Some(("--resync", mebibytes)) => {
+					let mebibytes: usize = mebibytes.parse().ok()?;
+					let size: usize = mebibytes.checked_mul(1 << 20)?;
+					if size == 0 || size > MAX_SEGMENT_SIZE {
+						return None;
+					}
+					parsed.resync = Some(size);
+				}
+				Some(("--blocks", blocks)) => {
+					let blocks: usize = blocks.parse().ok()?;
+					if blocks == 0 || blocks > MAX_BLOCKS {
+						return None;
+					}
+					parsed.blocks = Some(blocks);
+				}
+				Some(("--volume-size", bytes)) => {
+					let size: u64 = bytes.parse().ok()?;
+					if size == 0 {
+						return None;
+					}
+					parsed.volume_size = Some(size);
+				}
+				Some(("--max-time", milliseconds)) => {
+					parsed.max_time = Some(Duration::from_millis(milliseconds.parse().ok()?));
+				}
+				Some(("--stdin-size", bytes)) => parsed.stdin_size = Some(bytes.parse().ok()?),
+				Some(("--input-fd", fd)) => {
+					parsed.input_fd = Some(fd.parse().ok().filter(|fd| *fd >= 0)?);
+				}
+				Some(("--output-fd", fd)) => {
+					parsed.output_fd = Some(fd.parse().ok().filter(|fd| *fd >= 0)?);
+				}
+				Some(("--map-context", path)) if !path.is_empty() => {
+					parsed.map_context = Some(PathBuf::from(path));
+				}
+				None if option.as_ref() == "--no-mmap" => parsed.mmap = MmapPolicy::Never,
+				Some(("--mmap", policy)) => parsed.mmap = MmapPolicy::parse(policy)?,
+				Some(("--profile-in", path)) if !path.is_empty() => {
+					parsed.profile_in = Some(PathBuf::from(path));
+				}
+				Some(("--profile-out", path)) if !path.is_empty() => {
+					parsed.profile_out = Some(PathBuf::from(path));
+				}
+				// the model of the preset, unless another one is given after it
+				Some(("--preset", name)) => {
+					let preset: Preset = Preset::parse(name)?;
+					parsed.config.model = preset.model();
+					parsed.preset = Some(preset);
+				}
+				Some(("--map-bytes", bytes)) => parsed.map_bytes = Some(bytes.parse().ok()?),
+				Some(("--progress-file", path)) if !path.is_empty() => {
+					parsed.progress_file = Some(PathBuf::from(path));
+				}
+				Some(("--buffers", name)) => {
+					parsed.config = parsed.config.with_buffers(Buffers::from_name(name)?);
+				}
+				Some(("--integrity", level)) => {
+					let strict_end: bool = match level {
+						"strict" => true,
+						"lenient" => false,
+						_ => return None,
+					};
+					parsed.config = parsed.config.with_strict_end(strict_end);
+				}
+				Some(("--lanes", lanes)) => {
+					let lanes: u8 = lanes.parse().ok()?;
+					if lanes == 0 || lanes as usize > MAX_LANES {
+						return None;
+					}
+					parsed.config = parsed.config.with_lanes(lanes);
+				}
+				_ => return None,
+			}
+		}
+		Some(parsed)
+	}
+}
@@ -0,0 +1,63 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::SparseWriter;
+use std::io;
+use std::io::Write;
+
+// -----------------------------------------------
+
+// Where a decompression writes to: a file, its holes left out, or nowhere at all for srx t.
+pub trait Output: Write + Send {
+	// the output is complete, returns its size
+	fn finish(self) -> io::Result<u64>;
+}
+
+impl Output for SparseWriter {
+	fn finish(self) -> io::Result<u64> {
+		let size: u64 = self.position();
+		SparseWriter::finish(self)?;
+		Ok(size)
+	}
+}
+
+// -----------------------------------------------
+
+// Drops everything written to it, only counting it, so that a file can be decoded without the
+// space its output would take.
+#[derive(Default, Debug)]
+pub struct Discard {
+	size: u64,
+}
+
+impl Write for Discard {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		self.size += buffer.len() as u64;
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Output for Discard {
+	fn finish(self) -> io::Result<u64> {
+		Ok(self.size)
+	}
+}
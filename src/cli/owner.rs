@@ -0,0 +1,89 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::metadata::Record;
+use srx::{AnyError, AnyResult};
+use std::path::Path;
+
+// -----------------------------------------------
+
+// the owner is stored as a u32 LE uid then a u32 LE gid under this record name
+pub const OWNER_RECORD: &[u8] = b"srx.owner";
+
+// -----------------------------------------------
+
+#[cfg(unix)]
+mod platform {
+	use srx::AnyResult;
+	use std::fs;
+	use std::io;
+	use std::os::unix::fs::{chown, MetadataExt};
+	use std::path::Path;
+
+	pub fn read(path: &Path) -> AnyResult<Option<(u32, u32)>> {
+		let metadata: fs::Metadata = fs::metadata(path)?;
+		Ok(Some((metadata.uid(), metadata.gid())))
+	}
+
+	pub fn write(path: &Path, uid: u32, gid: u32) -> AnyResult<bool> {
+		match chown(path, Some(uid), Some(gid)) {
+			Ok(()) => Ok(true),
+			// only root may give a file away
+			Err(error) if error.kind() == io::ErrorKind::PermissionDenied => Ok(false),
+			Err(error) => Err(error.into()),
+		}
+	}
+}
+
+#[cfg(not(unix))]
+mod platform {
+	use srx::AnyResult;
+	use std::path::Path;
+
+	// there is no uid/gid to keep, the option is ignored
+	pub fn read(_path: &Path) -> AnyResult<Option<(u32, u32)>> {
+		Ok(None)
+	}
+
+	pub fn write(_path: &Path, _uid: u32, _gid: u32) -> AnyResult<bool> {
+		Ok(true)
+	}
+}
+
+// -----------------------------------------------
+
+// the owner of the file as a record, None where files have no uid/gid
+pub fn store_owner(path: &Path) -> AnyResult<Option<Record>> {
+	Ok(platform::read(path)?.map(|(uid, gid): (u32, u32)| {
+		let value: Vec<u8> = [uid.to_le_bytes(), gid.to_le_bytes()].concat();
+		(OWNER_RECORD.to_vec(), value)
+	}))
+}
+
+// gives the file to the stored owner, returns false if that was not permitted
+pub fn restore_owner(path: &Path, value: &[u8]) -> AnyResult<bool> {
+	let (uid, gid): (&[u8], &[u8]) = value
+		.split_at_checked(4)
+		.filter(|(_, gid)| gid.len() == 4)
+		.ok_or_else(|| AnyError::from_string("Corrupted owner!"))?;
+	platform::write(
+		path,
+		u32::from_le_bytes(uid.try_into()?),
+		u32::from_le_bytes(gid.try_into()?),
+	)
+}
@@ -0,0 +1,140 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::hash::to_hex;
+use srx::{read_header, AnyError, AnyResult, Config, Header};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// -----------------------------------------------
+
+// What decompressing a file would take, from its header alone.
+pub struct Preflight {
+	pub header: Header,
+	pub memory: u64,
+	pub memory_limit: Option<u64>,
+}
+
+impl Preflight {
+	pub fn new(path: &Path, memory_limit: Option<u64>) -> AnyResult<Self> {
+		let header: Header = read_header(&mut BufReader::new(File::open(path)?))?;
+		Ok(Self {
+			memory: estimated_memory(&header.config, header_blocks(&header)),
+			header,
+			memory_limit,
+		})
+	}
+
+	pub fn exceeds_limit(&self) -> bool {
+		self.memory_limit.is_some_and(|limit| self.memory > limit)
+	}
+}
+
+impl Display for Preflight {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		writeln!(formatter, "Config: {}", self.header.config.name())?;
+		match self.header.original_size {
+			Some(size) => writeln!(formatter, "Original size: {} bytes", size)?,
+			None => writeln!(formatter, "Original size: not recorded")?,
+		}
+		match &self.header.content_hash {
+			Some(hash) => writeln!(formatter, "Content hash: {}", to_hex(hash))?,
+			None => writeln!(formatter, "Content hash: not recorded")?,
+		}
+		if let Some(checksum) = &self.header.checksum {
+			writeln!(
+				formatter,
+				"Checksum: {} {}",
+				checksum.algo.name(),
+				to_hex(&checksum.digest)
+			)?;
+		}
+		if let Some(volume_size) = self.header.volume_size {
+			writeln!(formatter, "Volume size: {} bytes", volume_size)?;
+		}
+		if self.header.stored_tail {
+			writeln!(
+				formatter,
+				"Stored tail: compressed with a time budget, the end may be stored as it is"
+			)?;
+		}
+		if let Some(blocks) = &self.header.blocks {
+			writeln!(formatter, "Blocks: {}, decompressed at once", blocks.len())?;
+		}
+		if let Some(profile_id) = self.header.profile_id {
+			writeln!(
+				formatter,
+				"Profile: {:08x}, needed to decompress",
+				profile_id
+			)?;
+		}
+		write!(
+			formatter,
+			"Memory needed: {:.2} MiB",
+			mebibytes(self.memory)
+		)?;
+		if let Some(limit) = self.memory_limit {
+			write!(
+				formatter,
+				"\nMemory limit: {:.2} MiB{}",
+				mebibytes(limit),
+				if self.exceeds_limit() {
+					", exceeded!"
+				} else {
+					""
+				}
+			)?;
+		}
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+// the blocks are compressed or decompressed at once, each with contexts of its own
+fn estimated_memory(config: &Config, blocks: usize) -> u64 {
+	config.estimated_memory().saturating_mul(blocks as u64)
+}
+
+// the number of blocks a file is decompressed in, a single payload being one
+pub fn header_blocks(header: &Header) -> usize {
+	header.blocks.as_ref().map_or(1, Vec::len)
+}
+
+pub fn check_memory_limit(
+	config: &Config,
+	blocks: usize,
+	memory_limit: Option<u64>,
+) -> AnyResult<()> {
+	let memory: u64 = estimated_memory(config, blocks);
+	match memory_limit {
+		Some(limit) if memory > limit => Err(AnyError::from_string(format!(
+			"Needs {:.2} MiB of memory, more than the limit of {:.2} MiB!",
+			mebibytes(memory),
+			mebibytes(limit)
+		))),
+		_ => Ok(()),
+	}
+}
+
+fn mebibytes(bytes: u64) -> f64 {
+	bytes as f64 / (1 << 20) as f64
+}
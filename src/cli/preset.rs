@@ -0,0 +1,89 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{encode_with_config_profile, AnyResult, Config, Model, Profile, DEFAULT_PROFILE_ENTRIES};
+use std::io;
+
+// -----------------------------------------------
+
+// A model and a small sample of a kind of file, built into srx. The secondary contexts start
+// from the profile of the sample compressed with the model, so the preset is only a profile
+// that needs no file: the header records its id like any other profile, and decompression
+// finds the preset back from it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Preset {
+	Json,
+	Log,
+	Protobuf,
+}
+
+impl Preset {
+	pub const ALL: [Preset; 3] = [Preset::Json, Preset::Log, Preset::Protobuf];
+
+	pub fn parse(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|preset| preset.name() == name)
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Preset::Json => "json",
+			Preset::Log => "log",
+			Preset::Protobuf => "protobuf",
+		}
+	}
+
+	pub fn model(&self) -> Model {
+		match self {
+			Preset::Json | Preset::Log => Model::Text,
+			Preset::Protobuf => Model::default(),
+		}
+	}
+
+	// changing a sample changes the profile, and the files compressed with it can no longer
+	// be decompressed: a changed sample must be added as a new preset
+	fn sample(&self) -> &'static [u8] {
+		match self {
+			Preset::Json => include_bytes!("presets/json.dict"),
+			Preset::Log => include_bytes!("presets/log.dict"),
+			Preset::Protobuf => include_bytes!("presets/protobuf.dict"),
+		}
+	}
+
+	// the same whatever the options, the lanes and the growth of the table do not change it
+	pub fn profile(&self) -> AnyResult<Profile> {
+		let (_, _, profile): (&[u8], io::Sink, Option<Profile>) = encode_with_config_profile(
+			self.sample(),
+			io::sink(),
+			&Config::new(self.model()),
+			None,
+			Some(DEFAULT_PROFILE_ENTRIES),
+		)?;
+		Ok(profile.unwrap_or_default())
+	}
+}
+
+// the profile of the preset with this id, if the profile recorded in a header is one of them
+pub fn preset_profile(profile_id: u32) -> AnyResult<Option<Profile>> {
+	for preset in Preset::ALL {
+		let profile: Profile = preset.profile()?;
+		if profile.id() == profile_id {
+			return Ok(Some(profile));
+		}
+	}
+	Ok(None)
+}
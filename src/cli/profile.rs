@@ -0,0 +1,41 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::options::Options;
+use srx::{AnyError, AnyResult, Profile};
+use std::fs;
+use std::path::Path;
+
+// -----------------------------------------------
+
+// the profile given by --profile-in or --preset, to start the contexts from
+pub fn load_profile(options: &Options) -> AnyResult<Option<Profile>> {
+	match (&options.profile_in, options.preset) {
+		(None, None) => Ok(None),
+		(Some(path), None) => Ok(Some(Profile::from_bytes(&fs::read(path)?)?)),
+		(None, Some(preset)) => Ok(Some(preset.profile()?)),
+		(Some(_), Some(_)) => Err(AnyError::from_string(
+			"A preset is a profile of its own, it can not be given with --profile-in!",
+		)),
+	}
+}
+
+pub fn save_profile(path: &Path, profile: &Profile) -> AnyResult<()> {
+	fs::write(path, profile.to_bytes())?;
+	Ok(())
+}
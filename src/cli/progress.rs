@@ -0,0 +1,336 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// the original size of a file is assumed to be this many times its compressed size when its
+// header does not tell it, about what text compresses to, until the decoder says better
+pub const ASSUMED_EXPANSION: u64 = 4;
+
+// -----------------------------------------------
+
+// Calls back with the number of bytes read so far, counting from start, after every read.
+pub struct ProgressReader<R: Read, F: FnMut(u64)> {
+	reader: R,
+	done: u64,
+	callback: F,
+}
+
+impl<R: Read, F: FnMut(u64)> ProgressReader<R, F> {
+	pub fn new(reader: R, start: u64, callback: F) -> Self {
+		Self {
+			reader,
+			done: start,
+			callback,
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	// calls back once more without reading, once the output caught up with the input
+	pub fn report(&mut self) {
+		(self.callback)(self.done);
+	}
+}
+
+impl<R: Read, F: FnMut(u64)> Read for ProgressReader<R, F> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let length: usize = self.reader.read(buffer)?;
+		self.done += length as u64;
+		(self.callback)(self.done);
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+// Counts the bytes written through it into a counter shared with the progress callback, which
+// runs on the thread reading the input.
+pub struct CountingWriter<W: Write> {
+	writer: W,
+	written: Arc<AtomicU64>,
+}
+
+impl<W: Write> CountingWriter<W> {
+	pub fn new(writer: W, written: Arc<AtomicU64>) -> Self {
+		Self { writer, written }
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let length: usize = self.writer.write(buffer)?;
+		self.written.fetch_add(length as u64, Ordering::Relaxed);
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+// The final size of the output if the rest of the input compresses like what was read so far.
+// The output lags behind the input by the buffers of the pipeline, so the estimate starts low
+// and refines as the compression goes on. Nothing is estimated before both sides moved, nor
+// without knowing the size of the input.
+pub fn estimate_output(done: u64, total: u64, written: u64) -> Option<u64> {
+	if done == 0 || written == 0 || total == 0 {
+		return None;
+	}
+	Some((written as u128 * total.max(done) as u128 / done as u128) as u64)
+}
+
+// The original size of a file whose header does not tell it, from the compressed bytes read
+// and the bytes decoded so far: assumed from ASSUMED_EXPANSION until the decoder wrote some,
+// then refined like estimate_output, and exact once everything is read and written.
+pub fn estimate_original(done: u64, total: u64, written: u64) -> u64 {
+	match estimate_output(done, total, written) {
+		Some(estimate) => estimate,
+		None => (total.max(done) * ASSUMED_EXPANSION).max(written),
+	}
+}
+
+// -----------------------------------------------
+
+// Keeps a file holding a single JSON line with the latest progress, for GUIs to poll. The
+// file is rewritten at most every PROGRESS_INTERVAL, except for the final update.
+pub struct ProgressFile {
+	path: PathBuf,
+	total: u64,
+	stage: &'static str,
+	interval: Duration,
+	last_write: Option<(Instant, u64, Option<u64>)>,
+}
+
+impl ProgressFile {
+	pub fn new(path: &Path, total: u64, stage: &'static str) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			total,
+			stage,
+			interval: PROGRESS_INTERVAL,
+			last_write: None,
+		}
+	}
+
+	#[cfg(test)]
+	pub fn with_interval(self, interval: Duration) -> Self {
+		Self { interval, ..self }
+	}
+
+	// the estimate of the final output size is only there when compressing, see estimate_output,
+	// or when decompressing a file whose header does not tell it, see estimate_original
+	pub fn line(&self, done: u64, estimate: Option<u64>) -> String {
+		match estimate {
+			None => format!(
+				"{{\"done\":{},\"total\":{},\"stage\":\"{}\"}}\n",
+				done, self.total, self.stage
+			),
+			Some(estimate) => format!(
+				"{{\"done\":{},\"total\":{},\"stage\":\"{}\",\"estimate\":{}}}\n",
+				done, self.total, self.stage, estimate
+			),
+		}
+	}
+
+	// the estimate may still change once all the input is done, the output catching up
+	pub fn update(&mut self, done: u64, estimate: Option<u64>) {
+		let done: u64 = done.min(self.total);
+		if let Some((time, last_done, last_estimate)) = self.last_write {
+			if (done, estimate) == (last_done, last_estimate)
+				|| (done < self.total && time.elapsed() < self.interval)
+			{
+				return;
+			}
+		}
+		// progress is only informative, failing to report it must not fail the compression
+		let _error_ignored_ = fs::write(&self.path, self.line(done, estimate));
+		self.last_write = Some((Instant::now(), done, estimate));
+	}
+}
+
+// -----------------------------------------------
+
+// The bytes read and written so far, shared by the progress callback and the CountingWriter of
+// a run with main, which tells how far the run went when it fails halfway. Clones share them.
+#[derive(Clone, Default)]
+pub struct Progress {
+	done: Arc<AtomicU64>,
+	total: Arc<AtomicU64>,
+	written: Arc<AtomicU64>,
+	// the size of the output when known ahead, the original size of a decompressed file
+	expected: Arc<AtomicU64>,
+}
+
+impl Progress {
+	// the counter to give to a CountingWriter
+	pub fn written(&self) -> Arc<AtomicU64> {
+		self.written.clone()
+	}
+
+	// nothing to tell if the run failed before reading anything
+	pub fn summary(&self) -> Option<String> {
+		let done: u64 = self.done.load(Ordering::Relaxed);
+		let total: u64 = self.total.load(Ordering::Relaxed);
+		let written: u64 = self.written.load(Ordering::Relaxed);
+		if done == 0 {
+			return None;
+		}
+		let mut summary: String = format!("{} bytes of the input were processed", done);
+		if total > 0 {
+			summary += &format!(
+				" out of {} ({:.2}%)",
+				total,
+				done.min(total) as f64 * 100.0 / total as f64
+			);
+		}
+		if written > 0 {
+			summary += &format!(", {} bytes written", written);
+		}
+		summary += " before the error.";
+		Some(summary)
+	}
+
+	// The line of --progress: the part of the output written out of its expected size, or of the
+	// input read out of its total, or the bytes read alone when neither is known, and how fast.
+	// The widths are fixed so that a shorter line leaves nothing of the previous one.
+	pub fn line(&self, elapsed: Duration) -> String {
+		let (done, total): (u64, u64) = match self.expected.load(Ordering::Relaxed) {
+			0 => (
+				self.done.load(Ordering::Relaxed),
+				self.total.load(Ordering::Relaxed),
+			),
+			expected => (self.written.load(Ordering::Relaxed), expected),
+		};
+		let speed: f64 = done as f64 / (1 << 20) as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+		match total {
+			0 => format!("\r{:>16} bytes, {:>10.2} MiB/s", done, speed),
+			total => format!(
+				"\r{:>6.2}%, {:>10.2} MiB/s",
+				done.min(total) as f64 * 100.0 / total as f64,
+				speed
+			),
+		}
+	}
+}
+
+// Runs the body while another thread redraws the line of the progress on the standard error
+// every PROGRESS_INTERVAL, from the counters the run keeps. The reader gets ahead of the coder by
+// the buffers between them, so the thread polls instead of waiting for reads to call back. The
+// last line, drawn once the body returned, is ended for the report to go after it.
+pub fn show_progress<T>(progress: &Progress, enabled: bool, body: impl FnOnce() -> T) -> T {
+	if !enabled {
+		return body();
+	}
+	let finished: AtomicBool = AtomicBool::new(false);
+	thread::scope(|scope| {
+		let drawer = scope.spawn(|| {
+			let start: Instant = Instant::now();
+			let mut stderr: io::Stderr = io::stderr();
+			let mut drawn: bool = false;
+			loop {
+				let last: bool = finished.load(Ordering::Acquire);
+				// nothing to show for a run that failed before reading anything
+				if progress.done.load(Ordering::Relaxed) > 0 {
+					// only informative, failing to show it must not fail the run
+					let _error_ignored_ = write!(stderr, "{}", progress.line(start.elapsed()));
+					drawn = true;
+				}
+				if last {
+					break;
+				}
+				thread::park_timeout(PROGRESS_INTERVAL);
+			}
+			if drawn {
+				let _error_ignored_ = writeln!(stderr);
+			}
+		});
+		let result: T = body();
+		finished.store(true, Ordering::Release);
+		drawer.thread().unpark();
+		let _error_ignored_ = drawer.join();
+		result
+	})
+}
+
+// the callback for ProgressReader, keeping the progress for the summary on failure, and keeping
+// the progress file if there is one, estimating the final output size from the bytes counted
+// by a CountingWriter on the written counter of the progress, if any
+pub fn progress_callback(
+	path: Option<&Path>,
+	total: u64,
+	stage: &'static str,
+	progress: &Progress,
+) -> impl FnMut(u64) + Send {
+	estimating_callback(path, total, stage, progress, estimate_output)
+}
+
+// the callback for the ProgressReader of a decompression, estimating the original size only
+// when the header does not tell it, from a CountingWriter on the output
+pub fn decompress_callback(
+	path: Option<&Path>,
+	total: u64,
+	original_size: Option<u64>,
+	progress: &Progress,
+) -> impl FnMut(u64) + Send {
+	let estimate = move |done: u64, total: u64, written: u64| match original_size {
+		Some(_) => None,
+		None => Some(estimate_original(done, total, written)),
+	};
+	progress
+		.expected
+		.store(original_size.unwrap_or_default(), Ordering::Relaxed);
+	estimating_callback(path, total, "decompress", progress, estimate)
+}
+
+fn estimating_callback(
+	path: Option<&Path>,
+	total: u64,
+	stage: &'static str,
+	progress: &Progress,
+	estimate: impl Fn(u64, u64, u64) -> Option<u64> + Send,
+) -> impl FnMut(u64) + Send {
+	let mut progress_file: Option<ProgressFile> =
+		path.map(|path: &Path| ProgressFile::new(path, total, stage));
+	let progress: Progress = progress.clone();
+	progress.total.store(total, Ordering::Relaxed);
+	move |done: u64| {
+		progress.done.store(done, Ordering::Relaxed);
+		if let Some(progress_file) = &mut progress_file {
+			let written: u64 = progress.written.load(Ordering::Relaxed);
+			progress_file.update(done, estimate(done, total, written));
+		}
+	}
+}
@@ -0,0 +1,68 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::estimate::Estimate;
+
+// -----------------------------------------------
+
+// The only output of --ratio-only, a single JSON line for storage tiering policies deciding
+// whether some data is worth keeping compressed, without keeping the compressed data itself.
+pub struct RatioOnly {
+	// the size of the input
+	pub input: u64,
+	// the size srx c would write, or its estimate
+	pub output: u64,
+	// the share of the input that was compressed to get the output size, 1 unless estimated
+	// from samples
+	pub confidence: f64,
+}
+
+impl RatioOnly {
+	// the output size over the input size, 0 for an empty input like Report::percentage
+	pub fn ratio(&self) -> f64 {
+		if self.input == 0 {
+			0.0
+		} else {
+			self.output as f64 / self.input as f64
+		}
+	}
+
+	pub fn json(&self) -> String {
+		format!(
+			"{{\"input\":{},\"output\":{},\"ratio\":{:.6},\"confidence\":{:.6}}}",
+			self.input,
+			self.output,
+			self.ratio(),
+			self.confidence
+		)
+	}
+}
+
+impl From<&Estimate> for RatioOnly {
+	fn from(estimate: &Estimate) -> Self {
+		Self {
+			input: estimate.file_size,
+			output: estimate.estimated_size(),
+			confidence: if estimate.file_size == 0 {
+				1.0
+			} else {
+				estimate.sampled as f64 / estimate.file_size as f64
+			},
+		}
+	}
+}
@@ -0,0 +1,227 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::options::Options;
+use super::output::Output;
+use srx::{AnyError, AnyResult, Consumer};
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+// -----------------------------------------------
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+// The formats srx recompress turns into srx and back, told by the magic of the input, or by the
+// extension of the output for an input of srx.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Foreign {
+	Gzip,
+	Zstd,
+}
+
+impl Foreign {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Foreign::Gzip => "gzip",
+			Foreign::Zstd => "zstd",
+		}
+	}
+
+	// None for an input that is neither, which may be one of srx
+	pub fn of_input(input_path: &Path) -> AnyResult<Option<Self>> {
+		let mut start: Vec<u8> = Vec::with_capacity(ZSTD_MAGIC.len());
+		File::open(input_path)?
+			.take(ZSTD_MAGIC.len() as u64)
+			.read_to_end(&mut start)?;
+		Ok(if start.starts_with(&GZIP_MAGIC) {
+			Some(Foreign::Gzip)
+		} else if start.starts_with(&ZSTD_MAGIC) {
+			Some(Foreign::Zstd)
+		} else {
+			None
+		})
+	}
+
+	// zstd for a .zst or .zstd output, gzip for any other
+	pub fn of_output(output_path: &Path) -> Self {
+		match output_path
+			.extension()
+			.and_then(|extension| extension.to_str())
+		{
+			Some("zst" | "zstd") => Foreign::Zstd,
+			_ => Foreign::Gzip,
+		}
+	}
+
+	pub fn unsupported(&self) -> AnyError {
+		AnyError::from_string(format!(
+			"Recompressing {} is not supported by this build!",
+			self.name()
+		))
+	}
+}
+
+// -----------------------------------------------
+
+// Returns the decompressed size of the input and the size of the output.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub fn recompress(
+	input_path: &Path,
+	output_path: &Path,
+	format: Foreign,
+	options: &Options,
+) -> AnyResult<(u64, u64)> {
+	match format {
+		#[cfg(feature = "gzip")]
+		Foreign::Gzip => recompress_with(
+			input_path,
+			output_path,
+			options,
+			srx::recompress_gzip::<_, _, { srx::IO_BUFFER_SIZE }, { srx::MESSAGE_BUFFER_SIZE }>,
+		),
+		#[cfg(feature = "zstd")]
+		Foreign::Zstd => recompress_with(
+			input_path,
+			output_path,
+			options,
+			srx::recompress_zstd::<_, _, { srx::IO_BUFFER_SIZE }, { srx::MESSAGE_BUFFER_SIZE }>,
+		),
+		#[allow(unreachable_patterns)]
+		format => Err(format.unsupported()),
+	}
+}
+
+#[cfg(not(any(feature = "gzip", feature = "zstd")))]
+pub fn recompress(
+	_input_path: &Path,
+	_output_path: &Path,
+	format: Foreign,
+	_options: &Options,
+) -> AnyResult<(u64, u64)> {
+	Err(format.unsupported())
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn recompress_with(
+	input_path: &Path,
+	output_path: &Path,
+	options: &Options,
+	recompress: impl FnOnce(
+		File,
+		BufWriter<File>,
+		&srx::Config,
+	) -> AnyResult<(File, BufWriter<File>, u64)>,
+) -> AnyResult<(u64, u64)> {
+	use super::preflight::check_memory_limit;
+	use srx::{write_header, Header};
+	use std::io::Seek;
+
+	check_memory_limit(&options.config, 1, options.memory_limit)?;
+	let reader: File = File::open(input_path)?;
+	let writer: File = File::create(output_path)?;
+	// the decompressed size is only known at the end, after the header
+	let header: Header = Header {
+		config: options.config,
+		..Header::default()
+	};
+	let mut writer: BufWriter<File> = BufWriter::new(writer);
+	write_header(&mut writer, &header)?;
+	let (_, done_writer, decompressed_size): (File, BufWriter<File>, u64) =
+		recompress(reader, writer, &options.config)?;
+	let mut done_writer: File = done_writer
+		.into_inner()
+		.map_err(|error| error.into_error())?;
+	Ok((decompressed_size, done_writer.stream_position()?))
+}
+
+// -----------------------------------------------
+
+// the consumers compressing into the formats, taken back from the output at the end
+trait ForeignConsumer: Consumer<u8> + Send {
+	fn finish(self: Box<Self>) -> AnyResult<BufWriter<File>>;
+}
+
+#[cfg(feature = "gzip")]
+impl ForeignConsumer for srx::GzipConsumer<BufWriter<File>> {
+	fn finish(self: Box<Self>) -> AnyResult<BufWriter<File>> {
+		srx::GzipConsumer::finish(*self)
+	}
+}
+
+#[cfg(feature = "zstd")]
+impl ForeignConsumer for srx::ZstdConsumer<BufWriter<File>> {
+	fn finish(self: Box<Self>) -> AnyResult<BufWriter<File>> {
+		srx::ZstdConsumer::finish(*self)
+	}
+}
+
+// The output of srx recompress for an input of srx, compressed into the format again as it is
+// decoded. Its size is the one of the decoded data, which the header recorded.
+pub struct ForeignOutput {
+	consumer: Box<dyn ForeignConsumer>,
+	size: u64,
+}
+
+impl ForeignOutput {
+	#[cfg(any(feature = "gzip", feature = "zstd"))]
+	pub fn create(output_path: &Path, format: Foreign) -> AnyResult<Self> {
+		let consumer: Box<dyn ForeignConsumer> = match format {
+			#[cfg(feature = "gzip")]
+			Foreign::Gzip => Box::new(srx::GzipConsumer::new(BufWriter::new(File::create(
+				output_path,
+			)?))),
+			#[cfg(feature = "zstd")]
+			Foreign::Zstd => Box::new(srx::ZstdConsumer::new(BufWriter::new(File::create(
+				output_path,
+			)?))?),
+			#[allow(unreachable_patterns)]
+			format => return Err(format.unsupported()),
+		};
+		Ok(Self { consumer, size: 0 })
+	}
+
+	#[cfg(not(any(feature = "gzip", feature = "zstd")))]
+	pub fn create(_output_path: &Path, format: Foreign) -> AnyResult<Self> {
+		Err(format.unsupported())
+	}
+}
+
+impl Write for ForeignOutput {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let length: usize = self.consumer.consume(buffer)?;
+		self.size += length as u64;
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Output for ForeignOutput {
+	fn finish(self) -> io::Result<u64> {
+		self.consumer
+			.finish()?
+			.into_inner()
+			.map_err(|error| error.into_error())?;
+		Ok(self.size)
+	}
+}
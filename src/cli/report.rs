@@ -0,0 +1,243 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "pipe-stats")]
+use srx::PipeStats;
+use srx::{Calibration, Outcomes, Stage, StageTimes, CALIBRATION_BINS};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+// -----------------------------------------------
+
+// the timer can read zero for tiny inputs, clamp it so the speed stays finite
+const MINIMUM_SECONDS: f64 = 0.001;
+
+// -----------------------------------------------
+
+pub struct Report {
+	input_size: u64,
+	output_size: u64,
+	duration: Duration,
+	is_compress: bool,
+}
+
+impl Report {
+	pub fn new(input_size: u64, output_size: u64, duration: Duration, is_compress: bool) -> Self {
+		Self {
+			input_size,
+			output_size,
+			duration,
+			is_compress,
+		}
+	}
+
+	// (compressed size, uncompressed size), the sizes themselves are kept exact as u64
+	fn sizes(&self) -> (u64, u64) {
+		if self.is_compress {
+			(self.output_size, self.input_size)
+		} else {
+			(self.input_size, self.output_size)
+		}
+	}
+
+	pub fn percentage(&self) -> f64 {
+		let (compressed_size, uncompressed_size): (u64, u64) = self.sizes();
+		if uncompressed_size == 0 {
+			0.0
+		} else {
+			compressed_size as f64 / uncompressed_size as f64 * 100.0
+		}
+	}
+
+	pub fn seconds(&self) -> f64 {
+		self.duration.as_secs_f64()
+	}
+
+	pub fn speed(&self) -> f64 {
+		let (_, uncompressed_size): (u64, u64) = self.sizes();
+		uncompressed_size as f64 / self.seconds().max(MINIMUM_SECONDS) / (1 << 20) as f64
+	}
+}
+
+impl Display for Report {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		write!(
+			formatter,
+			"{} -> {} ({:.2}%) in {:.2} seconds ({:.2} MiB/s)",
+			self.input_size,
+			self.output_size,
+			self.percentage(),
+			self.seconds(),
+			self.speed()
+		)
+	}
+}
+
+// -----------------------------------------------
+
+// what --flat-profile prints after the report, the share of each stage being of the whole
+// run, so that on more cores than stages the shares add up to more than 100%
+pub struct StageReport {
+	times: StageTimes,
+	duration: Duration,
+}
+
+impl StageReport {
+	pub fn new(times: StageTimes, duration: Duration) -> Self {
+		Self { times, duration }
+	}
+}
+
+impl Display for StageReport {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		if self.times.total().is_zero() {
+			// built without threads, or nothing got as far as the pipeline
+			return write!(formatter, "Stages: none timed");
+		}
+		write!(formatter, "Stages:")?;
+		let seconds: f64 = self.duration.as_secs_f64().max(MINIMUM_SECONDS);
+		let mut separator: &str = " ";
+		for &stage in Stage::ALL {
+			let time: Duration = self.times.get(stage);
+			if !time.is_zero() {
+				write!(
+					formatter,
+					"{}{} {:.2}s ({:.1}%",
+					separator,
+					stage.name(),
+					time.as_secs_f64(),
+					time.as_secs_f64() / seconds * 100.0
+				)?;
+				#[cfg(feature = "pipe-stats")]
+				{
+					let pipes: PipeStats = self.times.pipe_stats(stage);
+					write!(
+						formatter,
+						", {} stalls in {} syncs",
+						pipes.stalls, pipes.syncs
+					)?;
+				}
+				write!(formatter, ")")?;
+				separator = ", ";
+			}
+		}
+		write!(formatter, " of {:.2} seconds", self.duration.as_secs_f64())
+	}
+}
+
+// -----------------------------------------------
+
+// what --self-profile prints before compressing: how the bytes matched their ranks, then the
+// bits given to the coder for each decision with the share of zeros, a share far from 50%
+// telling a branch that is easy to predict
+pub struct OutcomeReport {
+	outcomes: Outcomes,
+}
+
+impl OutcomeReport {
+	pub fn new(outcomes: Outcomes) -> Self {
+		Self { outcomes }
+	}
+}
+
+fn percentage(part: u64, whole: u64) -> f64 {
+	match whole {
+		0 => 0.0,
+		_ => part as f64 / whole as f64 * 100.0,
+	}
+}
+
+impl Display for OutcomeReport {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		let outcomes: &Outcomes = &self.outcomes;
+		let bytes: u64 = outcomes.bytes();
+		writeln!(
+			formatter,
+			"Outcomes: first {:.2}%, second {:.2}%, third {:.2}%, literal {:.2}% of {} bytes",
+			percentage(outcomes.first, bytes),
+			percentage(outcomes.second, bytes),
+			percentage(outcomes.third, bytes),
+			percentage(outcomes.literal, bytes),
+			bytes
+		)?;
+		let (zeros, ones): (u64, u64) = outcomes.bits();
+		write!(
+			formatter,
+			"Bits: {} to the coder, {:.2}% zeros",
+			zeros + ones,
+			percentage(zeros, zeros + ones)
+		)?;
+		for (name, (zeros, ones)) in [
+			("first", outcomes.first_bits()),
+			("second", outcomes.second_bits()),
+			("third", outcomes.third_bits()),
+			("literal", outcomes.literal_bits()),
+		] {
+			write!(
+				formatter,
+				", {} {} with {:.2}% zeros",
+				name,
+				zeros + ones,
+				percentage(zeros, zeros + ones)
+			)?;
+		}
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+// one row for each bin of predictions, the empty ones included
+pub struct CalibrationReport {
+	calibration: Calibration,
+}
+
+impl CalibrationReport {
+	pub fn new(calibration: Calibration) -> Self {
+		Self { calibration }
+	}
+}
+
+impl Display for CalibrationReport {
+	fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+		write!(
+			formatter,
+			"Calibration: {} bits, predicted against observed probability of a one",
+			self.calibration.bits()
+		)?;
+		for (index, bin) in self.calibration.bins.iter().enumerate() {
+			write!(
+				formatter,
+				"\n  {:.4}-{:.4}: {} bits",
+				index as f64 / CALIBRATION_BINS as f64,
+				(index + 1) as f64 / CALIBRATION_BINS as f64,
+				bin.bits
+			)?;
+			if bin.bits > 0 {
+				write!(
+					formatter,
+					", predicted {:.4}, observed {:.4}",
+					bin.predicted(),
+					bin.observed()
+				)?;
+			}
+		}
+		Ok(())
+	}
+}
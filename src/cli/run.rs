@@ -0,0 +1,800 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::assume::read_or_assume_header;
+use super::deadline::{open_stored_tail, DeadlineReader};
+use super::fd::take_stdout;
+use super::hash::{checksum, content_hash};
+use super::map::write_context_map;
+use super::metadata::{restore_metadata, store_metadata};
+use super::mmap::{map_input, Mapped};
+use super::options::Options;
+use super::output::Output;
+use super::preflight::{check_memory_limit, header_blocks};
+use super::preset::preset_profile;
+use super::profile::{load_profile, save_profile};
+use super::progress::{
+	decompress_callback, progress_callback, CountingWriter, Progress, ProgressReader,
+};
+use super::report::{CalibrationReport, OutcomeReport};
+use super::stdin::{STDIN_PATH, STDOUT_PATH};
+use super::stream::{run_fds, run_stdin};
+use super::verify::compress_verified;
+use srx::{
+	calibrate, check_profile, count_collisions, count_outcomes, decode_blocks, decode_resync,
+	decode_with_config_profile, encode_blocks, encode_resync, encode_with_config_profile,
+	read_header, volume_path, write_header, AnyError, AnyResult, Block, Buffers, Calibration,
+	Checksum, ChecksumWriter, Collisions, Config, Header, Outcomes, Profile, Resynced,
+	SparseReader, SparseWriter, VolumeReader, VolumeWriter, COLLISION_SAMPLE_BITS,
+	DEFAULT_PROFILE_ENTRIES, RESYNC_MARKER,
+};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, Take, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+pub fn run(
+	input_path: &Path,
+	output_path: &Path,
+	is_compress: bool,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	if is_compress && input_path == Path::new(STDIN_PATH) {
+		return run_stdin(output_path, options, progress);
+	}
+	// decompressed like a descriptor, the standard input having neither a size nor a position
+	if input_path == Path::new(STDIN_PATH) {
+		return run_fds(
+			Some(input_path),
+			Some(output_path),
+			false,
+			options,
+			progress,
+		);
+	}
+	// opening a directory fails with an obscure error or succeeds and fails on the first read
+	if input_path.is_dir() {
+		return Err(AnyError::from_string(if is_compress {
+			"Input is a directory, srx compresses a single file, archive it first, with tar for example!"
+		} else {
+			"Input is a directory, not a compressed file!"
+		}));
+	}
+	if options.stdin_size.is_some() {
+		return Err(AnyError::from_string(
+			"--stdin-size only applies when compressing the standard input!",
+		));
+	}
+	if let (true, Some(map_path)) = (is_compress, &options.map_context) {
+		// a separate pass over the input, the compression itself is left alone
+		write_context_map(input_path, map_path, options)?;
+	}
+	if is_compress && options.collision_report {
+		// a separate pass over the input too
+		let collisions: Collisions = count_collisions(File::open(input_path)?, &options.config)?;
+		println!(
+			"Collisions: {:.2}% of {} lookups of 1 in {} entries of the primary table found another context",
+			collisions.rate() * 100.0,
+			collisions.lookups,
+			1 << COLLISION_SAMPLE_BITS
+		);
+	}
+	if is_compress && options.self_profile {
+		// and another one, the encoder counts nothing
+		let outcomes: Outcomes = count_outcomes(File::open(input_path)?, &options.config)?;
+		println!("{}", OutcomeReport::new(outcomes));
+	}
+	if is_compress && options.calibration {
+		// the secondary contexts too this time, from the same profile as the encoder
+		let warm_start: Option<Profile> = load_profile(options)?;
+		let calibration: Calibration = calibrate(
+			File::open(input_path)?,
+			&options.config,
+			warm_start.as_ref(),
+		)?;
+		println!("{}", CalibrationReport::new(calibration));
+	}
+
+	if options.verify
+		&& (options.resync.is_some() || options.volume_size.is_some() || options.max_time.is_some())
+	{
+		return Err(AnyError::from_string(
+			"--verify decodes a single payload, not resync segments, volumes or a stored tail!",
+		));
+	}
+	if let (true, Some(volume_size)) = (is_compress, options.volume_size) {
+		return run_volumes(input_path, output_path, volume_size, options, progress);
+	}
+
+	// open file, the output being written next to its path and renamed to it once complete
+	let reader: File = File::open(input_path)?;
+	let (temp_path, writer): (Option<PathBuf>, File) = match create_temp_output(output_path)? {
+		Some((temp_path, writer)) => (Some(temp_path), writer),
+		None => (None, create_output(output_path)?),
+	};
+	let writing_path: &Path = temp_path.as_deref().unwrap_or(output_path);
+	let result: AnyResult<(u64, u64, Duration)> = run_file(
+		input_path,
+		reader,
+		writer,
+		writing_path,
+		is_compress,
+		options,
+		progress,
+	);
+	let Some(temp_path) = &temp_path else {
+		return result;
+	};
+	let result: AnyResult<(u64, u64, Duration)> = result.and_then(|sizes| {
+		fs::rename(temp_path, output_path)?;
+		Ok(sizes)
+	});
+	// neither a truncated output nor a clobbered previous one is left, nor the temporary file
+	if result.is_err() {
+		let _ = fs::remove_file(temp_path);
+	}
+	result
+}
+
+// compresses or decompresses the opened files, the output being written to writing_path
+pub fn run_file(
+	input_path: &Path,
+	reader: File,
+	writer: File,
+	writing_path: &Path,
+	is_compress: bool,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	// start the timer
+	let start: Instant = Instant::now();
+
+	// do the compression/decompression, holes of sparse files are neither read nor written
+	let (input_size, output_size): (u64, u64) = if is_compress && options.resync.is_some() {
+		check_memory_limit(&options.config, 1, options.memory_limit)?;
+		if options.xattrs
+			|| options.owner
+			|| options.content_hash.is_some()
+			|| options.checksum_algo.is_some()
+			|| options.profile_in.is_some()
+			|| options.profile_out.is_some()
+			|| options.preset.is_some()
+			|| options.max_time.is_some()
+			|| options.blocks.is_some()
+		{
+			return Err(AnyError::from_string(
+				"Resync segments do not carry the extended attributes, owner, content hash, checksum, profile, stored tail or blocks!",
+			));
+		}
+		let total: u64 = reader.metadata()?.len();
+		let progress_reader = ProgressReader::new(
+			SparseReader::new(reader)?,
+			0,
+			progress_callback(
+				options.progress_file.as_deref(),
+				total,
+				"compress",
+				progress,
+			),
+		);
+		let (done_reader, done_writer) = encode_resync(
+			progress_reader,
+			CountingWriter::new(writer, progress.written()),
+			&options.config,
+			options.resync.unwrap_or_default(),
+		)?;
+		(
+			done_reader.into_inner().position(),
+			output_size(done_writer.into_inner(), &progress.written())?,
+		)
+	} else if is_compress {
+		// all of the output is counted, the header included, see output_size
+		let written: Arc<AtomicU64> = Arc::default();
+		let writer: CountingWriter<File> = CountingWriter::new(writer, written.clone());
+		let (input_size, done_writer, tail_size): (u64, CountingWriter<File>, u64) =
+			match options.verify {
+				true => {
+					// a second set of contexts decodes alongside, the limit is shared by both
+					let halved: Options = Options {
+						memory_limit: options.memory_limit.map(|limit| limit / 2),
+						..options.clone()
+					};
+					let warm_start: Option<Profile> = load_profile(options)?;
+					compress_verified(input_path, warm_start.as_ref(), writer, |tee| {
+						compress_file(input_path, reader, tee, &halved, progress)
+					})
+					.inspect_err(|_| remove_output(writing_path))?
+				}
+				false => compress_file(input_path, reader, writer, options, progress)?,
+			};
+		if tail_size > 0 {
+			println!(
+				"Warning: the time budget ran out, the last {} bytes were stored uncompressed!",
+				tail_size
+			);
+		}
+		(input_size, output_size(done_writer.into_inner(), &written)?)
+	} else {
+		decompress_path(
+			input_path,
+			reader,
+			SparseWriter::new(writer),
+			Some(writing_path),
+			options,
+			progress,
+		)?
+	};
+
+	// stop the timer
+	let duration: Duration = start.elapsed();
+
+	// oke
+	Ok((input_size, output_size, duration))
+}
+
+// Decompresses the file, a single payload, the volumes it starts or a stream of resync segments,
+// returning the sizes of the input and the output. The metadata is restored if there is a path.
+pub fn decompress_path<W: Output>(
+	input_path: &Path,
+	reader: File,
+	output: W,
+	output_path: Option<&Path>,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64)> {
+	// the header is read along with the start of the payload
+	let total: u64 = reader.metadata()?.len();
+	let mut reader: BufReader<File> = BufReader::new(reader);
+	let warm_start: Option<Profile> = load_profile(options)?;
+	if reader.fill_buf()?.first() == Some(&RESYNC_MARKER[0]) {
+		check_profile(None, warm_start.as_ref())?;
+		return run_resync(reader, output, total, options, progress);
+	}
+	let header: Header = read_decoded_header(&mut reader, options)?;
+	let warm_start: Option<Profile> = header_profile(&header, warm_start, options)?;
+	match header.volume_size {
+		// the payload stops where the stored tail starts, the decoder reads up to the end
+		None if header.stored_tail => {
+			let start: u64 = reader.stream_position()?;
+			let (payload_size, tail): (u64, Take<File>) = open_stored_tail(input_path, start)?;
+			let progress_reader = ProgressReader::new(
+				reader.take(payload_size),
+				start,
+				decompress_callback(
+					options.progress_file.as_deref(),
+					total,
+					header.original_size,
+					progress,
+				),
+			);
+			let (_, output_size) = decompress_file(
+				progress_reader,
+				tail,
+				output_writer(output, progress),
+				header,
+				warm_start.as_ref(),
+				output_path,
+				options,
+			)?;
+			Ok((total, output_size))
+		}
+		None => {
+			let start: u64 = reader.stream_position()?;
+			let progress_reader = ProgressReader::new(
+				reader,
+				start,
+				decompress_callback(
+					options.progress_file.as_deref(),
+					total,
+					header.original_size,
+					progress,
+				),
+			);
+			let (mut done_reader, output_size) = decompress_file(
+				progress_reader,
+				io::empty(),
+				output_writer(output, progress),
+				header,
+				warm_start.as_ref(),
+				output_path,
+				options,
+			)?;
+			// the estimate of the original size, if any, is now the size of the output
+			done_reader.report();
+			let mut done_reader: BufReader<File> = done_reader.into_inner();
+			Ok((done_reader.stream_position()?, output_size))
+		}
+		// the payload goes on in the next volumes, read as one stream from the start again
+		Some(_) if header.stored_tail => Err(AnyError::from_string(
+			"A split file with a stored tail is not supported!",
+		)),
+		Some(volume_size) => {
+			let volumes: VolumeReader = VolumeReader::open(input_path, volume_size)?;
+			let total: u64 = volumes.remaining_size()?;
+			let mut reader: BufReader<VolumeReader> = BufReader::new(volumes);
+			read_header(&mut reader)?;
+			let start: u64 = reader.get_ref().total() - reader.buffer().len() as u64;
+			let progress_reader = ProgressReader::new(
+				reader,
+				start,
+				decompress_callback(
+					options.progress_file.as_deref(),
+					total,
+					header.original_size,
+					progress,
+				),
+			);
+			let (done_reader, output_size) = decompress_file(
+				progress_reader,
+				io::empty(),
+				output_writer(output, progress),
+				header,
+				warm_start.as_ref(),
+				output_path,
+				options,
+			)?;
+			Ok((done_reader.into_inner().get_ref().total(), output_size))
+		}
+	}
+}
+
+// The output is written next to its path and renamed to it once complete, so that a run that
+// fails or is killed leaves neither a truncated output nor a clobbered previous one. The standard
+// output, a pipe, a device or a symbolic link are written to directly, without a temporary file.
+// The temporary file takes a name of its own, only created if no file has it yet, so that no file
+// of the user is ever truncated or removed in its place.
+pub fn create_temp_output(output_path: &Path) -> AnyResult<Option<(PathBuf, File)>> {
+	static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+	if output_path == Path::new(STDOUT_PATH)
+		|| fs::symlink_metadata(output_path).is_ok_and(|metadata| !metadata.is_file())
+	{
+		return Ok(None);
+	}
+	let Some(name) = output_path.file_name() else {
+		return Ok(None);
+	};
+	loop {
+		let mut temp_name: OsString = name.to_os_string();
+		temp_name.push(format!(
+			".{}-{}.tmp",
+			process::id(),
+			TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+		));
+		let temp_path: PathBuf = output_path.with_file_name(temp_name);
+		match OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&temp_path)
+		{
+			Ok(file) => return Ok(Some((temp_path, file))),
+			// left by another run, or a file of the user, the next name is tried
+			Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+			Err(error) => return Err(error.into()),
+		}
+	}
+}
+
+// the exit status of a run refused before anything was read or written, see check_output
+pub const EXIT_REFUSED: i32 = 2;
+
+// An existing output file is only overwritten with --force, and never when it is the input
+// itself, which would be lost. The standard output, a pipe or a device is written to whatever
+// it held.
+pub fn check_output(
+	input_path: Option<&Path>,
+	output_path: Option<&Path>,
+	is_compress: bool,
+	options: &Options,
+) -> AnyResult<()> {
+	let Some(output_path) = output_path.filter(|path| *path != Path::new(STDOUT_PATH)) else {
+		return Ok(());
+	};
+	// a split output starts with its first volume
+	let output_path: PathBuf = match (is_compress, options.volume_size) {
+		(true, Some(_)) => volume_path(output_path, 1),
+		_ => output_path.to_path_buf(),
+	};
+	if let (Some(input_path), Ok(output)) = (input_path, fs::canonicalize(&output_path)) {
+		if fs::canonicalize(input_path).is_ok_and(|input| input == output) {
+			return Err(AnyError::from_string(
+				"The input and the output are the same file!",
+			));
+		}
+	}
+	if !options.force && fs::metadata(&output_path).is_ok_and(|metadata| metadata.is_file()) {
+		return Err(AnyError::from_string(format!(
+			"{} already exists, give --force to overwrite it!",
+			output_path.display()
+		)));
+	}
+	Ok(())
+}
+
+// the file to write the output to, - standing for the standard output
+pub fn create_output(output_path: &Path) -> AnyResult<File> {
+	match output_path == Path::new(STDOUT_PATH) {
+		true => take_stdout(),
+		false => Ok(File::create(output_path)?),
+	}
+}
+
+// An output that failed its verification is not left to be taken for a good one, unless it is
+// not a file of its own: the standard output, a pipe or a device.
+pub fn remove_output(output_path: &Path) {
+	if output_path != Path::new(STDOUT_PATH)
+		&& fs::metadata(output_path).is_ok_and(|metadata| metadata.is_file())
+	{
+		// the error of the verification is the one to tell
+		let _ = fs::remove_file(output_path);
+	}
+}
+
+// The size of the compressed output, the position of a regular file or the bytes counted on
+// their way to a pipe or a device, which has no position, or one telling nothing: /dev/null
+// stays at 0 whatever is written to it.
+pub fn output_size(mut file: File, written: &AtomicU64) -> AnyResult<u64> {
+	match file.metadata()?.is_file() {
+		true => Ok(file.stream_position()?),
+		false => Ok(written.load(Ordering::Relaxed)),
+	}
+}
+
+// a split output is written volume by volume, from the start of the first one
+pub fn run_volumes(
+	input_path: &Path,
+	output_path: &Path,
+	volume_size: u64,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	if options.resync.is_some() {
+		return Err(AnyError::from_string(
+			"Resync segments can not be split into volumes!",
+		));
+	}
+	if output_path == Path::new(STDOUT_PATH) {
+		return Err(AnyError::from_string(
+			"The volumes are files of their own, named after the output, not the standard output!",
+		));
+	}
+	if options.max_time.is_some() {
+		return Err(AnyError::from_string(
+			"A stored tail can not be split into volumes!",
+		));
+	}
+	let reader: File = File::open(input_path)?;
+	let writer: VolumeWriter = VolumeWriter::new(output_path, volume_size)?;
+	let start: Instant = Instant::now();
+	let (input_size, done_writer, _): (u64, VolumeWriter, u64) =
+		compress_file(input_path, reader, writer, options, progress)?;
+	let output_size: u64 = done_writer.total();
+	done_writer.finish()?;
+	Ok((input_size, output_size, start.elapsed()))
+}
+
+// returns the size of the input, the writer after the end of the output and the size of the
+// part of the input stored as it is when the time budget ran out
+pub fn compress_file<W: Write + Send>(
+	input_path: &Path,
+	reader: File,
+	writer: W,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, W, u64)> {
+	check_memory_limit(
+		&options.config,
+		options.blocks.unwrap_or(1),
+		options.memory_limit,
+	)?;
+	if let Some(count) = options.blocks {
+		return compress_blocks(input_path, reader, writer, count, options, progress);
+	}
+	let deadline: Option<Instant> = options.max_time.map(|max_time| Instant::now() + max_time);
+	// all the input read ahead before the deadline is still compressed after it, the less the
+	// sooner it stops, unless other buffers are asked for
+	let config: Config = match deadline {
+		Some(_) if options.config.buffers == Buffers::default() => {
+			options.config.with_buffers(Buffers::Small)
+		}
+		_ => options.config,
+	};
+	let total: u64 = reader.metadata()?.len();
+	let config: Config = threadless_if_small(config, Some(total), options);
+	let warm_start: Option<Profile> = load_profile(options)?;
+	let profile_entries: Option<usize> = options
+		.profile_out
+		.as_ref()
+		.map(|_| DEFAULT_PROFILE_ENTRIES);
+	let header: Header = Header {
+		config: options.config,
+		original_size: Some(total),
+		content_hash: content_hash(input_path, options)?,
+		checksum: checksum(input_path, options)?,
+		profile_id: warm_start.as_ref().map(Profile::id),
+		volume_size: options.volume_size,
+		metadata: store_metadata(input_path, options)?,
+		// whether the budget runs out is only known at the end, long after the header
+		stored_tail: deadline.is_some(),
+		blocks: None,
+	};
+	// the header is buffered and goes out with the first block of the payload
+	let mut writer: BufWriter<W> = BufWriter::new(writer);
+	write_header(&mut writer, &header)?;
+	// the payload is counted for the estimate of the final size
+	let writer: CountingWriter<BufWriter<W>> = CountingWriter::new(writer, progress.written());
+	let callback = progress_callback(
+		options.progress_file.as_deref(),
+		total,
+		"compress",
+		progress,
+	);
+	// what the encoder has not read when the budget runs out follows the payload as it is
+	let (input_size, mut done_writer, profile, tail_size): (
+		u64,
+		CountingWriter<BufWriter<W>>,
+		Option<Profile>,
+		u64,
+	) = match map_input(&reader, options.mmap)? {
+		// the whole file is a single slice, taken without any read call
+		Some(mapped) => {
+			let deadline_reader = DeadlineReader::new(mapped.as_slice(), deadline);
+			let progress_reader = ProgressReader::new(deadline_reader, 0, callback);
+			let (done_reader, mut done_writer, profile) = encode_with_config_profile(
+				progress_reader,
+				writer,
+				&config,
+				warm_start.as_ref(),
+				profile_entries,
+			)?;
+			let tail: &[u8] = done_reader.into_inner().into_inner();
+			done_writer.write_all(tail)?;
+			let input_size: u64 = mapped.as_slice().len() as u64;
+			(input_size, done_writer, profile, tail.len() as u64)
+		}
+		None => {
+			let deadline_reader = DeadlineReader::new(SparseReader::new(reader)?, deadline);
+			let progress_reader = ProgressReader::new(deadline_reader, 0, callback);
+			let (done_reader, mut done_writer, profile) = encode_with_config_profile(
+				progress_reader,
+				writer,
+				&config,
+				warm_start.as_ref(),
+				profile_entries,
+			)?;
+			let mut tail: SparseReader = done_reader.into_inner().into_inner();
+			let tail_size: u64 = io::copy(&mut tail, &mut done_writer)?;
+			(tail.position(), done_writer, profile, tail_size)
+		}
+	};
+	if header.stored_tail {
+		done_writer.write_all(&tail_size.to_le_bytes())?;
+	}
+	if let (Some(path), Some(profile)) = (&options.profile_out, &profile) {
+		save_profile(path, profile)?;
+	}
+	let done_writer: W = done_writer
+		.into_inner()
+		.into_inner()
+		.map_err(|error| error.into_error())?;
+	Ok((input_size, done_writer, tail_size))
+}
+
+// The whole file is mapped or read into memory and compressed in blocks at once, written after
+// the header holding their index once they are all done: the progress only tells the reading.
+pub fn compress_blocks<W: Write + Send>(
+	input_path: &Path,
+	reader: File,
+	writer: W,
+	count: usize,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, W, u64)> {
+	if options.max_time.is_some() || options.profile_out.is_some() {
+		return Err(AnyError::from_string(
+			"Blocks can not be compressed with a time budget or into a profile!",
+		));
+	}
+	let total: u64 = reader.metadata()?.len();
+	let warm_start: Option<Profile> = load_profile(options)?;
+	let mut callback = progress_callback(
+		options.progress_file.as_deref(),
+		total,
+		"compress",
+		progress,
+	);
+	let mapped: Option<Mapped> = map_input(&reader, options.mmap)?;
+	let mut read: Vec<u8> = Vec::new();
+	let data: &[u8] = match &mapped {
+		Some(mapped) => mapped.as_slice(),
+		None => {
+			ProgressReader::new(SparseReader::new(reader)?, 0, &mut callback)
+				.read_to_end(&mut read)?;
+			&read
+		}
+	};
+	let (blocks, payloads): (Vec<Block>, Vec<Vec<u8>>) =
+		encode_blocks(data, count, &options.config, warm_start.as_ref())?;
+	callback(data.len() as u64);
+	let header: Header = Header {
+		config: options.config,
+		original_size: Some(data.len() as u64),
+		content_hash: content_hash(input_path, options)?,
+		checksum: checksum(input_path, options)?,
+		profile_id: warm_start.as_ref().map(Profile::id),
+		volume_size: options.volume_size,
+		metadata: store_metadata(input_path, options)?,
+		stored_tail: false,
+		blocks: Some(blocks),
+	};
+	let mut writer: BufWriter<W> = BufWriter::new(writer);
+	write_header(&mut writer, &header)?;
+	let mut writer: CountingWriter<BufWriter<W>> = CountingWriter::new(writer, progress.written());
+	for payload in &payloads {
+		writer.write_all(payload)?;
+	}
+	let writer: W = writer
+		.into_inner()
+		.into_inner()
+		.map_err(|error| error.into_error())?;
+	Ok((data.len() as u64, writer, 0))
+}
+
+// the profile the contexts of the file start from, after checking that it can be decompressed
+pub fn header_profile(
+	header: &Header,
+	mut warm_start: Option<Profile>,
+	options: &Options,
+) -> AnyResult<Option<Profile>> {
+	// compressed with a preset, which needs no --preset to be decompressed
+	if let (None, Some(profile_id)) = (&warm_start, header.profile_id) {
+		warm_start = preset_profile(profile_id)?;
+	}
+	check_profile(header.profile_id, warm_start.as_ref())?;
+	check_memory_limit(&header.config, header_blocks(header), options.memory_limit)?;
+	Ok(warm_start)
+}
+
+// the output to decompress to, counted for the progress and the summary on failure
+pub fn output_writer<W: Output>(output: W, progress: &Progress) -> CountingWriter<W> {
+	CountingWriter::new(output, progress.written())
+}
+
+// returns the reader after the end of the payload and the size of the output, the stored tail
+// being written after the decoded payload, and the metadata restored if there is a path. The
+// writer counts the output for the progress, see output_writer.
+pub fn decompress_file<R: Read + Send, T: Read, W: Output>(
+	reader: R,
+	mut tail: T,
+	writer: CountingWriter<W>,
+	header: Header,
+	warm_start: Option<&Profile>,
+	output_path: Option<&Path>,
+	options: &Options,
+) -> AnyResult<(R, u64)> {
+	// the output is hashed on its way to the file if the header recorded a checksum
+	let checksum_writer: ChecksumWriter<CountingWriter<W>> = ChecksumWriter::new(
+		writer,
+		header.checksum.as_ref().map(|checksum| checksum.algo),
+	)?;
+	// the buffers and the end check are not part of the stream, the ones asked for are used
+	let config: Config = header
+		.config
+		.with_buffers(options.config.buffers)
+		.with_strict_end(options.config.strict_end);
+	let config: Config = threadless_if_small(config, header.original_size, options);
+	let (done_reader, mut done_writer) = match &header.blocks {
+		Some(blocks) => decode_blocks(reader, checksum_writer, blocks, &config, warm_start)?,
+		None => decode_with_config_profile(reader, checksum_writer, &config, warm_start)?,
+	};
+	io::copy(&mut tail, &mut done_writer)?;
+	let (done_writer, actual): (CountingWriter<W>, Option<Checksum>) = done_writer.into_inner();
+	let output_size: u64 = done_writer.into_inner().finish()?;
+	header.check_size(output_size)?;
+	if let (Some(expected), Some(actual)) = (&header.checksum, &actual) {
+		expected.check(actual)?;
+	}
+	// the standard output is not a file to give the attributes or the owner to
+	let output_path: Option<&Path> = output_path.filter(|path| *path != Path::new(STDOUT_PATH));
+	if let (Some(metadata), Some(output_path)) = (header.metadata, output_path) {
+		let skipped: usize = restore_metadata(output_path, &metadata, options)?;
+		if skipped > 0 {
+			println!(
+				"Warning: {} extended attribute(s) or owner could not be restored!",
+				skipped
+			);
+		}
+	}
+	Ok((done_reader, output_size))
+}
+
+// An input smaller than a buffer of the pipes is coded on the calling thread: the reader hands
+// it over whole and the writer takes the output at the end, so the stages would mostly take
+// turns, after starting their threads. Unless the stages are to be timed.
+pub fn threadless_if_small(config: Config, size: Option<u64>, options: &Options) -> Config {
+	let buffer_size: u64 = config.buffers.io_buffer_size() as u64;
+	config.with_threadless(!options.flat_profile && size.is_some_and(|size| size < buffer_size))
+}
+
+// nothing but the content of the input may go into the output, the file and the clock being
+// left out: the header holds no time, so there is no SOURCE_DATE_EPOCH to clamp it to
+pub fn check_no_metadata(options: &Options) -> AnyResult<()> {
+	if options.no_metadata && (options.xattrs || options.owner || options.max_time.is_some()) {
+		return Err(AnyError::from_string(
+			"--no-metadata keeps the output to the content of the input, without extended attributes, owner or time budget!",
+		));
+	}
+	Ok(())
+}
+
+// the header of a file to decompress, told when --assume-srx had to stand for it
+pub fn read_decoded_header<R: BufRead>(reader: &mut R, options: &Options) -> AnyResult<Header> {
+	let (header, assumed): (Header, bool) = read_or_assume_header(reader, options.assume_srx)?;
+	if assumed {
+		println!("Warning: no valid SRX header, decoding with the default model anyway!");
+	}
+	Ok(header)
+}
+
+// a stream of resync segments has no header, its segments are decoded one by one
+pub fn run_resync<W: Output>(
+	reader: BufReader<File>,
+	output: W,
+	total: u64,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64)> {
+	if options.config.strict_end {
+		return Err(AnyError::from_string(
+			"--integrity=strict checks the end of a single payload, not of resync segments!",
+		));
+	}
+	let progress_reader = ProgressReader::new(
+		reader,
+		0,
+		progress_callback(
+			options.progress_file.as_deref(),
+			total,
+			"decompress",
+			progress,
+		),
+	);
+	let (resynced, done_writer): (Resynced, W) = decode_resync(progress_reader, output)?;
+	done_writer.finish()?;
+	if resynced.damaged > 0 {
+		let lost: u64 = resynced
+			.lost
+			.iter()
+			.map(|range| range.end - range.start)
+			.sum();
+		println!(
+			"Warning: {} damaged segment(s) skipped, {} byte(s) lost and written as zeros!",
+			resynced.damaged, lost
+		);
+	}
+	// the scan reads the whole input
+	Ok((total, resynced.size))
+}
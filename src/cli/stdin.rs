@@ -0,0 +1,73 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io;
+use std::io::Read;
+
+// -----------------------------------------------
+
+// the input path standing for the standard input, and the output path for the standard output
+pub const STDIN_PATH: &str = "-";
+pub const STDOUT_PATH: &str = "-";
+
+// -----------------------------------------------
+
+// Counts the bytes read, and when the size is known beforehand, fails the stream at the end if
+// it was not exactly that long. The header was written with that size long before the end.
+pub struct SizedReader<R: Read> {
+	reader: R,
+	expected: Option<u64>,
+	position: u64,
+}
+
+impl<R: Read> SizedReader<R> {
+	pub fn new(reader: R, expected: Option<u64>) -> Self {
+		Self {
+			reader,
+			expected,
+			position: 0,
+		}
+	}
+
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+}
+
+impl<R: Read> Read for SizedReader<R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let length: usize = self.reader.read(buffer)?;
+		self.position += length as u64;
+		match self.expected {
+			Some(expected)
+				if self.position > expected || (length == 0 && self.position < expected) =>
+			{
+				Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!(
+						"The input is {} {} bytes, but --stdin-size says {}!",
+						if length == 0 { "only" } else { "at least" },
+						self.position,
+						expected
+					),
+				))
+			}
+			_ => Ok(length),
+		}
+	}
+}
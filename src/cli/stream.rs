@@ -0,0 +1,242 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::fd::{remaining_size, take_fd, take_stdin};
+use super::options::{ContentHash, Options};
+use super::preflight::check_memory_limit;
+use super::profile::{load_profile, save_profile};
+use super::progress::{
+	decompress_callback, progress_callback, CountingWriter, Progress, ProgressReader,
+};
+use super::run::{
+	create_output, decompress_file, header_profile, output_writer, read_decoded_header,
+};
+use super::stdin::{SizedReader, STDIN_PATH};
+use srx::{
+	encode_with_config_profile, write_header, AnyError, AnyResult, Header, Profile, SparseWriter,
+	DEFAULT_PROFILE_ENTRIES, RESYNC_MARKER,
+};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+// nothing can be stored about a pipe, and its size is only known if the caller tells it
+pub fn run_stdin(
+	output_path: &Path,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	check_stream_options(options, "The standard input")?;
+	let writer: File = create_output(output_path)?;
+	let start: Instant = Instant::now();
+	let (input_size, output_size): (u64, u64) = compress_stream(
+		SizedReader::new(io::stdin(), options.stdin_size),
+		options.stdin_size,
+		writer,
+		options,
+		progress,
+	)?;
+	Ok((input_size, output_size, start.elapsed()))
+}
+
+// the options that need a path, to read the input again or to find its attributes
+pub fn check_stream_options(options: &Options, input: &str) -> AnyResult<()> {
+	if options.xattrs
+		|| options.owner
+		|| options.resync.is_some()
+		|| options.content_hash == Some(ContentHash::Crc32)
+		|| options.checksum_algo.is_some()
+		|| options.volume_size.is_some()
+		|| options.map_context.is_some()
+		|| options.collision_report
+		|| options.self_profile
+		|| options.calibration
+		|| options.max_time.is_some()
+		|| options.verify
+		|| options.blocks.is_some()
+	{
+		return Err(AnyError::from_string(format!(
+			"{} has no extended attributes, owner, resync, computed hash, checksum, volume, context map, collision report, self profile, calibration, time budget, verify or blocks support!",
+			input
+		)));
+	}
+	Ok(())
+}
+
+// returns the size of the input and the size of the output, the header included, neither of
+// them being seekable
+pub fn compress_stream<R: Read + Send>(
+	reader: SizedReader<R>,
+	original_size: Option<u64>,
+	writer: File,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64)> {
+	check_memory_limit(&options.config, 1, options.memory_limit)?;
+	let warm_start: Option<Profile> = load_profile(options)?;
+	let header: Header = Header {
+		config: options.config,
+		original_size,
+		content_hash: match &options.content_hash {
+			Some(ContentHash::Given(hash)) => Some(hash.clone()),
+			_ => None,
+		},
+		profile_id: warm_start.as_ref().map(Profile::id),
+		checksum: None,
+		volume_size: None,
+		metadata: None,
+		stored_tail: false,
+		blocks: None,
+	};
+	let mut header_bytes: Vec<u8> = Vec::new();
+	write_header(&mut header_bytes, &header)?;
+	let mut writer: BufWriter<File> = BufWriter::new(writer);
+	writer.write_all(&header_bytes)?;
+	let progress_reader = ProgressReader::new(
+		reader,
+		0,
+		progress_callback(
+			options.progress_file.as_deref(),
+			original_size.unwrap_or_default(),
+			"compress",
+			progress,
+		),
+	);
+	let written: Arc<AtomicU64> = progress.written();
+	let (done_reader, done_writer, profile) = encode_with_config_profile(
+		progress_reader,
+		CountingWriter::new(writer, written.clone()),
+		&options.config,
+		warm_start.as_ref(),
+		options
+			.profile_out
+			.as_ref()
+			.map(|_| DEFAULT_PROFILE_ENTRIES),
+	)?;
+	if let (Some(path), Some(profile)) = (&options.profile_out, &profile) {
+		save_profile(path, profile)?;
+	}
+	let done_reader: SizedReader<R> = done_reader.into_inner();
+	done_writer
+		.into_inner()
+		.into_inner()
+		.map_err(|error| error.into_error())?;
+	Ok((
+		done_reader.position(),
+		header_bytes.len() as u64 + written.load(Ordering::Relaxed),
+	))
+}
+
+// Descriptors set up by a parent process, each taken over instead of a path, either side
+// possibly being a pipe: neither can be seeked in, and nothing is known about them but the size
+// of a regular file.
+pub fn run_fds(
+	input_path: Option<&Path>,
+	output_path: Option<&Path>,
+	is_compress: bool,
+	options: &Options,
+	progress: &Progress,
+) -> AnyResult<(u64, u64, Duration)> {
+	// two files owning the same descriptor would close it twice
+	if options.input_fd.is_some() && options.input_fd == options.output_fd {
+		return Err(AnyError::from_string(
+			"The input and the output can not be the same file descriptor!",
+		));
+	}
+	// the report goes to the standard output, the errors to the standard error
+	if matches!(options.output_fd, Some(1 | 2)) {
+		return Err(AnyError::from_string(
+			"The standard output and error are left to the messages of srx!",
+		));
+	}
+	if options.stdin_size.is_some() {
+		return Err(AnyError::from_string(
+			"--stdin-size only applies when compressing the standard input!",
+		));
+	}
+	if is_compress {
+		check_stream_options(options, "A file descriptor")?;
+	}
+	let mut reader: File = match (options.input_fd, input_path) {
+		(Some(fd), _) => take_fd(fd, false)?,
+		(None, Some(input_path)) if input_path == Path::new(STDIN_PATH) => take_stdin()?,
+		(None, Some(input_path)) => File::open(input_path)?,
+		(None, None) => return Err(AnyError::from_string("No input given!")),
+	};
+	let writer: File = match (options.output_fd, output_path) {
+		(Some(fd), _) => take_fd(fd, true)?,
+		(None, Some(output_path)) => create_output(output_path)?,
+		(None, None) => return Err(AnyError::from_string("No output given!")),
+	};
+	let start: Instant = Instant::now();
+	let total: Option<u64> = remaining_size(&mut reader);
+	if is_compress {
+		let (input_size, output_size): (u64, u64) = compress_stream(
+			SizedReader::new(reader, None),
+			total,
+			writer,
+			options,
+			progress,
+		)?;
+		return Ok((input_size, output_size, start.elapsed()));
+	}
+	// the bytes read are counted, the position of a pipe is not known
+	let mut reader: BufReader<SizedReader<File>> = BufReader::new(SizedReader::new(reader, None));
+	if reader.fill_buf()?.first() == Some(&RESYNC_MARKER[0]) {
+		return Err(AnyError::from_string(
+			"A stream of resync segments can not be read from a file descriptor!",
+		));
+	}
+	let header: Header = read_decoded_header(&mut reader, options)?;
+	let warm_start: Option<Profile> = header_profile(&header, load_profile(options)?, options)?;
+	if header.volume_size.is_some() || header.stored_tail {
+		return Err(AnyError::from_string(
+			"A split file, or one with a stored tail, can not be read from a file descriptor!",
+		));
+	}
+	let header_size: u64 = reader.get_ref().position() - reader.buffer().len() as u64;
+	let progress_reader = ProgressReader::new(
+		reader,
+		header_size,
+		decompress_callback(
+			options.progress_file.as_deref(),
+			total.unwrap_or_default(),
+			header.original_size,
+			progress,
+		),
+	);
+	let (mut done_reader, output_size) = decompress_file(
+		progress_reader,
+		io::empty(),
+		output_writer(SparseWriter::new(writer), progress),
+		header,
+		warm_start.as_ref(),
+		output_path,
+		options,
+	)?;
+	// the estimate of the original size, if any, is now the size of the output
+	done_reader.report();
+	let input_size: u64 = done_reader.into_inner().into_inner().position();
+	Ok((input_size, output_size, start.elapsed()))
+}
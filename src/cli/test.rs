@@ -0,0 +1,1974 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::assume::is_srx;
+use super::command::{run_benchmark, run_ratio_only, run_test};
+use super::compare::{compare, Comparison};
+use super::estimate::{estimate, Estimate};
+use super::mmap::{map_input, MmapPolicy, MMAP_THRESHOLD};
+use super::options::{ContentHash, Options};
+use super::preflight::Preflight;
+use super::preset::{preset_profile, Preset};
+use super::progress::{
+	decompress_callback, estimate_original, estimate_output, progress_callback, show_progress,
+	CountingWriter, Progress, ProgressFile, ProgressReader, ASSUMED_EXPANSION,
+};
+use super::ratio::RatioOnly;
+use super::report::Report;
+use super::run::{check_no_metadata, compress_file, remove_output, run};
+use super::stdin::{SizedReader, STDIN_PATH, STDOUT_PATH};
+use super::stream::run_fds;
+use super::tune::tune;
+use super::verify::{compress_verified, verify, TeeWriter};
+use srx::{
+	compress_with, decode_with, decompress, encode_with, read_header, write_header, AnyError,
+	AnyResult, Buffers, Checksum, ChecksumAlgo, Config, Crc32, Growth, Header, Model, Profile,
+	IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE,
+};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// -----------------------------------------------
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("srx-test-{}-{}", std::process::id(), name))
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_report_zero_duration() {
+	for is_compress in [true, false] {
+		let report: Report = Report::new(12, 20, Duration::ZERO, is_compress);
+		assert!(report.percentage().is_finite());
+		assert!(report.speed().is_finite());
+		let text: String = report.to_string();
+		assert!(!text.contains("inf") && !text.contains("NaN"), "{}", text);
+	}
+}
+
+#[test]
+fn test_report_sub_millisecond() {
+	let report: Report = Report::new(1000, 100, Duration::from_micros(250), true);
+	assert_eq!(
+		report.to_string(),
+		"1000 -> 100 (10.00%) in 0.00 seconds (0.95 MiB/s)"
+	);
+}
+
+#[test]
+fn test_report_empty_input() {
+	for is_compress in [true, false] {
+		let report: Report = Report::new(0, 0, Duration::ZERO, is_compress);
+		assert_eq!(
+			report.to_string(),
+			"0 -> 0 (0.00%) in 0.00 seconds (0.00 MiB/s)"
+		);
+	}
+}
+
+#[test]
+fn test_report_exact_sizes() {
+	// sizes above 2^53 can not be represented exactly by f64, they must be printed as u64
+	let size: u64 = (1 << 60) + 1;
+	let report: Report = Report::new(size, size - 1, Duration::from_secs(1), true);
+	assert!(report
+		.to_string()
+		.starts_with("1152921504606846977 -> 1152921504606846976 "));
+}
+
+#[test]
+fn test_compare_all_configs() -> AnyResult<()> {
+	let sample: Vec<u8> = (0..50_000u32)
+		.map(|index| b"compare every config "[(index % 21) as usize] ^ (index / 4096) as u8)
+		.collect();
+	let comparisons: Vec<Comparison> = compare(&sample)?;
+	assert_eq!(comparisons.len(), Config::all().count());
+	for (comparison, config) in comparisons.iter().zip(Config::all()) {
+		assert_eq!(comparison.config, config);
+		assert!(comparison.compressed.len() < sample.len());
+		assert_eq!(decompress(&comparison.compressed)?, sample);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_options_parse() {
+	assert_eq!(Options::parse::<&str>(&[]), Some(Options::default()));
+	let options: Options = Options::parse(&["--lanes=8", "--xattrs", "--text", "--owner"]).unwrap();
+	assert!(options.xattrs && options.owner);
+	assert_eq!(options.config, Config::new(Model::Text).with_lanes(8));
+	let options: Options = Options::parse(&["--preflight", "--max-memory=512"]).unwrap();
+	assert!(options.preflight);
+	assert_eq!(options.memory_limit, Some(512 << 20));
+	let options: Options = Options::parse(&["--tagged"]).unwrap();
+	assert_eq!(options.config, Config::new(Model::Tagged));
+	let options: Options = Options::parse(&["--buffers=small"]).unwrap();
+	assert_eq!(
+		options.config,
+		Config::default().with_buffers(Buffers::Small)
+	);
+	let options: Options = Options::parse(&["--flat"]).unwrap();
+	assert_eq!(options.config, Config::new(Model::Flat));
+	assert!(Options::parse(&["--no-metadata"]).unwrap().no_metadata);
+	let options: Options = Options::parse(&["--integrity=strict"]).unwrap();
+	assert_eq!(options.config, Config::default().with_strict_end(true));
+	let options: Options = Options::parse(&["--integrity=strict", "--integrity=lenient"]).unwrap();
+	assert_eq!(options.config, Config::default());
+	let options: Options = Options::parse(&["--xor-mix"]).unwrap();
+	assert_eq!(options.config, Config::new(Model::XorMix));
+	let options: Options = Options::parse(&["--rotate-mix"]).unwrap();
+	assert_eq!(options.config, Config::new(Model::RotateMix));
+	let options: Options = Options::parse(&["--grow"]).unwrap();
+	assert_eq!(
+		options.config,
+		Config::default().with_growth(Some(Growth::default()))
+	);
+	let options: Options = Options::parse(&["--resync=4"]).unwrap();
+	assert_eq!(options.resync, Some(4 << 20));
+	let options: Options = Options::parse(&["--volume-size=1000000"]).unwrap();
+	assert_eq!(options.volume_size, Some(1_000_000));
+	let options: Options = Options::parse(&["--stdin-size=12345"]).unwrap();
+	assert_eq!(options.stdin_size, Some(12345));
+	let options: Options = Options::parse(&["--content-hash"]).unwrap();
+	assert_eq!(options.content_hash, Some(ContentHash::Crc32));
+	let options: Options = Options::parse(&["--content-hash=00fF10"]).unwrap();
+	assert_eq!(
+		options.content_hash,
+		Some(ContentHash::Given(vec![0x00, 0xFF, 0x10]))
+	);
+	let options: Options = Options::parse(&["--checksum-algo=xxhash"]).unwrap();
+	assert_eq!(options.checksum_algo, Some(ChecksumAlgo::XxHash64));
+	let options: Options =
+		Options::parse(&["--map-context=/tmp/map.csv", "--map-bytes=100"]).unwrap();
+	assert_eq!(options.map_context, Some(PathBuf::from("/tmp/map.csv")));
+	assert_eq!(options.map_bytes, Some(100));
+	assert_eq!(Options::default().mmap, MmapPolicy::Auto);
+	let options: Options = Options::parse(&["--mmap=always"]).unwrap();
+	assert_eq!(options.mmap, MmapPolicy::Always);
+	let options: Options = Options::parse(&["--no-mmap"]).unwrap();
+	assert_eq!(options.mmap, MmapPolicy::Never);
+	let options: Options =
+		Options::parse(&["--profile-in=/tmp/in.prof", "--profile-out=/tmp/out.prof"]).unwrap();
+	assert_eq!(options.profile_in, Some(PathBuf::from("/tmp/in.prof")));
+	assert_eq!(options.profile_out, Some(PathBuf::from("/tmp/out.prof")));
+	let options: Options = Options::parse(&["--progress-file=/tmp/progress"]).unwrap();
+	assert_eq!(options.progress_file, Some(PathBuf::from("/tmp/progress")));
+	assert!(
+		Options::parse(&["--collision-report"])
+			.unwrap()
+			.collision_report
+	);
+	assert!(Options::parse(&["--self-profile"]).unwrap().self_profile);
+	assert!(Options::parse(&["--calibration"]).unwrap().calibration);
+	assert!(Options::parse(&["--force"]).unwrap().force);
+	assert!(Options::parse(&["-f"]).unwrap().force);
+	assert!(Options::parse(&["--progress"]).unwrap().progress);
+	assert_eq!(Options::parse(&["--blocks=4"]).unwrap().blocks, Some(4));
+	let options: Options = Options::parse(&["--max-time=250"]).unwrap();
+	assert_eq!(options.max_time, Some(Duration::from_millis(250)));
+	let options: Options = Options::parse(&["--preset=json"]).unwrap();
+	assert_eq!(options.preset, Some(Preset::Json));
+	assert_eq!(options.config, Config::new(Model::Text));
+	let options: Options = Options::parse(&["--preset=log", "--lanes=2", "--tagged"]).unwrap();
+	assert_eq!(options.preset, Some(Preset::Log));
+	assert_eq!(options.config, Config::new(Model::Tagged).with_lanes(2));
+	for invalid in [
+		"--progress-file=",
+		"--progress-file",
+		"--lanes=0",
+		"--lanes=17",
+		"--blocks=0",
+		"--blocks=257",
+		"--blocks",
+		"--lanes=",
+		"--lanes",
+		"--xattrs=1",
+		"--no-metadata=1",
+		"--force=1",
+		"--progress=1",
+		"--grow=1",
+		"--flat=1",
+		"--xor-mix=1",
+		"--buffers=huge",
+		"--integrity=loose",
+		"--integrity",
+		"--buffers",
+		"--max-memory=",
+		"--max-memory=-1",
+		"--resync=0",
+		"--resync=1025",
+		"--resync",
+		"--stdin-size=",
+		"--max-time=",
+		"--max-time=-1",
+		"--volume-size=0",
+		"--volume-size=",
+		"--content-hash=",
+		"--map-context=",
+		"--mmap=",
+		"--mmap=sometimes",
+		"--profile-in=",
+		"--profile-out",
+		"--preset=",
+		"--collision-report=1",
+		"--preset=xml",
+		"--preset",
+		"--no-mmap=1",
+		"--map-bytes=x",
+		"--content-hash=abc",
+		"--content-hash=xy",
+		"--checksum-algo=",
+		"--checksum-algo=md5",
+		"--checksum-algo",
+		"--stdin-size=-1",
+		"--unknown",
+	] {
+		assert_eq!(Options::parse(&[invalid]), None, "{}", invalid);
+	}
+}
+
+#[test]
+fn test_tune_commands() -> AnyResult<()> {
+	let data: Vec<u8> = b"some content to tune the config on".repeat(500);
+	let commands: &[u8] = b"run\nmodel text\nlanes 2\nmodel nothing\nlanes 0\n\
+		buffers small\nshow\n\ngrow on\nmodel tagged\nrun\nmodel standard\nfoo\nquit\nrun\n";
+	let mut output: Vec<u8> = Vec::new();
+	let config: Config = tune(&data, commands, &mut output)?;
+	assert_eq!(
+		config,
+		Config::default()
+			.with_lanes(2)
+			.with_buffers(Buffers::Small)
+			.with_growth(Some(Growth::default()))
+	);
+	let output: String = String::from_utf8(output).unwrap();
+	let lines: Vec<&str> = output.lines().collect();
+	// a compression for each run, none after quit
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&Config::default(),
+	)?;
+	assert!(lines[0].starts_with("17000 bytes loaded"));
+	assert!(lines[1].starts_with(&format!("> standard: 17000 -> {} (", expected.len())));
+	assert_eq!(lines[2], "> > > Unknown model nothing!");
+	assert_eq!(lines[3], "> The lanes must be between 1 and 16!");
+	assert_eq!(lines[4], "> > text x2, small buffers");
+	assert!(lines[5].starts_with("> text x2: 17000 -> "));
+	// a config that can not compress is reported, the session goes on
+	assert_eq!(
+		lines[6],
+		"> > > Error occurred! The primary table of the tagged model can not grow!"
+	);
+	assert_eq!(
+		lines[7],
+		"> > model NAME    standard, text, tagged, flat, xor-mix, rotate-mix, two-way or"
+	);
+	// the help, then the prompt that quit answered
+	assert_eq!(lines.len(), 16);
+	assert_eq!(lines[15], "> ");
+	Ok(())
+}
+
+#[test]
+fn test_estimate() -> AnyResult<()> {
+	// homogeneous: the same kind of pseudo-random words all along the file
+	let mut seed: u32 = 12345;
+	let data: Vec<u8> = (0..1 << 20)
+		.map(|index: u32| {
+			seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+			if index % 7 == 6 {
+				b' '
+			} else {
+				b'a' + ((seed >> 16) % 8) as u8
+			}
+		})
+		.collect();
+	let path: PathBuf = temp_path("estimate");
+	fs::write(&path, &data)?;
+	let config: Config = Config::default();
+	let (_, compressed): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&config,
+	)?;
+	let sampled: Estimate = estimate(&path, &config, 256 << 10)?;
+	assert_eq!(sampled.file_size, data.len() as u64);
+	assert_eq!(sampled.sampled, 3 * (256 << 10));
+	// each sample starts with empty contexts, so too small samples are pessimistic
+	let ratio: f64 = compressed.len() as f64 / data.len() as f64;
+	assert!(
+		(sampled.ratio() / ratio - 1.0).abs() < 0.05,
+		"{} vs {}",
+		sampled.ratio(),
+		ratio
+	);
+	// too small to be sampled, compressed whole
+	let whole: Estimate = estimate(&path, &config, 1 << 20)?;
+	assert_eq!(whole.sampled, data.len() as u64);
+	assert_eq!(whole.compressed, compressed.len() as u64);
+	assert_eq!(whole.estimated_size(), compressed.len() as u64);
+	fs::remove_file(&path)?;
+	// nothing to seek in
+	assert!(estimate(&std::env::temp_dir(), &config, 64 << 10).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_ratio_only() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("ratio-only-input");
+	let compressed_path: PathBuf = temp_path("ratio-only-compressed");
+	fs::write(&input_path, b"a ratio and nothing else, ".repeat(40000))?;
+	let options: Options = Options::parse(&["--ratio-only"]).unwrap();
+	assert!(options.ratio_only);
+	let ratio_only: RatioOnly = run_ratio_only(&input_path, &options, &Progress::default())?;
+	let (_, compressed_size, _): (u64, u64, Duration) = run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert_eq!(ratio_only.input, fs::metadata(&input_path)?.len());
+	assert_eq!(ratio_only.output, fs::metadata(&compressed_path)?.len());
+	assert_eq!(ratio_only.output, compressed_size);
+	assert_eq!(ratio_only.confidence, 1.0);
+	// exactly these fields, in this order, with a ratio the tiering policies can compare
+	let json: String = ratio_only.json();
+	let fields: Vec<(&str, &str)> = json
+		.strip_prefix('{')
+		.and_then(|json: &str| json.strip_suffix('}'))
+		.unwrap()
+		.split(',')
+		.map(|field: &str| field.split_once(':').unwrap())
+		.collect();
+	let keys: Vec<&str> = fields.iter().map(|(key, _)| *key).collect();
+	assert_eq!(
+		keys,
+		["\"input\"", "\"output\"", "\"ratio\"", "\"confidence\""]
+	);
+	let ratio: f64 = fields[2].1.parse().unwrap();
+	assert!(ratio > 0.0 && ratio < 1.0, "{}", json);
+	assert!((ratio - ratio_only.ratio()).abs() < 1e-6);
+	// an estimate only compressed a part of the input
+	let sampled: RatioOnly = RatioOnly::from(&estimate(&input_path, &Config::default(), 64 << 10)?);
+	assert_eq!(sampled.input, ratio_only.input);
+	assert!(sampled.confidence > 0.0 && sampled.confidence < 1.0);
+	// nothing to read the whole of, or something else to write
+	assert!(run_ratio_only(&std::env::temp_dir(), &options, &Progress::default()).is_err());
+	let options: Options = Options::parse(&["--ratio-only", "--resync=1"]).unwrap();
+	assert!(run_ratio_only(&input_path, &options, &Progress::default()).is_err());
+	for path in [input_path, compressed_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_preset() -> AnyResult<()> {
+	// records like the ones of an API, but none of the sample
+	let mut seed: u32 = 54321;
+	let mut next = |range: u32| {
+		seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+		(seed >> 16) % range
+	};
+	let mut data: String = String::from("[\n");
+	for id in 0..300 {
+		data += &format!(
+			" {{\"id\": {}, \"name\": \"{}\", \"status\": \"{}\", \"enabled\": {}, \
+			\"score\": {}.{}, \"created_at\": \"2024-01-{:02}T10:{:02}:00Z\"}},\n",
+			id,
+			["mona", "li", "sam"][next(3) as usize],
+			["active", "failed", "queued"][next(3) as usize],
+			next(2) == 0,
+			next(50),
+			next(100),
+			next(28) + 1,
+			next(60)
+		);
+	}
+	data += "]\n";
+	let input_path: PathBuf = temp_path("preset-input");
+	let compressed_path: PathBuf = temp_path("preset-compressed");
+	let output_path: PathBuf = temp_path("preset-output");
+	fs::write(&input_path, &data)?;
+	let compress = |options: &Options| -> AnyResult<u64> {
+		let (_, compressed_size, _) = run(
+			&input_path,
+			&compressed_path,
+			true,
+			options,
+			&Progress::default(),
+		)?;
+		Ok(compressed_size)
+	};
+	let plain: u64 = compress(&Options::default())?;
+	let text: u64 = compress(&Options::parse(&["--text"]).unwrap())?;
+	let preset: u64 = compress(&Options::parse(&["--preset=json"]).unwrap())?;
+	assert!(
+		preset < text && text < plain,
+		"{} {} {}",
+		preset,
+		text,
+		plain
+	);
+	// the header is enough to find the preset back
+	let header: Header = read_header(&mut File::open(&compressed_path)?)?;
+	assert_eq!(header.config, Config::new(Model::Text));
+	assert_eq!(header.profile_id, Some(Preset::Json.profile()?.id()));
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert_eq!(fs::read(&output_path)?, data.as_bytes());
+	// every preset is told apart by its profile
+	for preset in Preset::ALL {
+		let profile: Profile = preset.profile()?;
+		assert!(!profile.is_empty(), "{}", preset.name());
+		assert_eq!(preset_profile(profile.id())?, Some(profile));
+		assert_eq!(Preset::parse(preset.name()), Some(preset));
+	}
+	assert_eq!(preset_profile(0)?, None);
+	// a preset and a profile file are two profiles
+	let options: Options = Options::parse(&["--preset=json", "--profile-in=/tmp/in.prof"]).unwrap();
+	assert!(compress(&options).is_err());
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_max_time() -> AnyResult<()> {
+	// 16 MiB of words, far more than srx compresses in 50 ms
+	let mut seed: u32 = 2024;
+	let mut data: Vec<u8> = Vec::with_capacity(16 << 20);
+	while data.len() < 16 << 20 {
+		seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+		data.extend_from_slice(
+			["lorem ", "ipsum ", "dolor\n", "sit ", "amet, "][(seed >> 16) as usize % 5].as_bytes(),
+		);
+	}
+	let input_path: PathBuf = temp_path("max-time-input");
+	let compressed_path: PathBuf = temp_path("max-time-compressed");
+	let output_path: PathBuf = temp_path("max-time-output");
+	fs::write(&input_path, &data)?;
+	let stored_tail = || -> AnyResult<u64> {
+		let compressed: Vec<u8> = fs::read(&compressed_path)?;
+		let trailer: [u8; 8] = compressed[compressed.len() - 8..].try_into()?;
+		Ok(u64::from_le_bytes(trailer))
+	};
+	// nothing compressed, the budget runs out all along, or not at all
+	for (max_time, mmap) in [
+		("--max-time=0", "--mmap=always"),
+		("--max-time=50", "--mmap=always"),
+		("--max-time=50", "--mmap=never"),
+		("--max-time=600000", "--mmap=never"),
+	] {
+		let options: Options = Options::parse(&[max_time, mmap, "--checksum-algo=crc32"]).unwrap();
+		let (input_size, _, _): (u64, u64, Duration) = run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		assert_eq!(input_size, data.len() as u64, "{}", max_time);
+		match max_time {
+			"--max-time=0" => assert_eq!(stored_tail()?, data.len() as u64),
+			"--max-time=50" => assert!(stored_tail()? > 0, "{}", mmap),
+			_ => assert_eq!(stored_tail()?, 0),
+		}
+		assert!(read_header(&mut File::open(&compressed_path)?)?.stored_tail);
+		assert!(Preflight::new(&compressed_path, None)?
+			.to_string()
+			.contains("Stored tail"));
+		run(
+			&compressed_path,
+			&output_path,
+			false,
+			&Options::default(),
+			&Progress::default(),
+		)?;
+		assert!(fs::read(&output_path)? == data, "{} {}", max_time, mmap);
+		assert_eq!(
+			verify(&compressed_path, &input_path)?,
+			(data.len() as u64, None)
+		);
+	}
+	// without a budget there is no stored tail at all
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert!(!read_header(&mut File::open(&compressed_path)?)?.stored_tail);
+	// the stored tail needs the end of the one file
+	let options: Options = Options::parse(&["--max-time=50", "--volume-size=1000000"]).unwrap();
+	assert!(run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default()
+	)
+	.is_err());
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+// fails every read, like a disk gone in the middle of the input
+struct FailingReader;
+
+impl Read for FailingReader {
+	fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+		Err(io::Error::other("the disk is gone"))
+	}
+}
+
+#[test]
+fn test_progress_summary_on_error() {
+	let progress: Progress = Progress::default();
+	assert_eq!(progress.summary(), None);
+	let data: Vec<u8> = b"half of the input then nothing ".repeat(2048);
+	let total: u64 = data.len() as u64 * 2;
+	let reader = ProgressReader::new(
+		data.as_slice().chain(FailingReader),
+		0,
+		progress_callback(None, total, "compress", &progress),
+	);
+	let result = encode_with::<_, _, 0x1000, 0x1000>(
+		reader,
+		CountingWriter::new(Vec::new(), progress.written()),
+		&Config::default(),
+	);
+	assert!(result.is_err());
+	let summary: String = progress.summary().unwrap();
+	assert!(
+		summary.starts_with("63488 bytes of the input were processed out of 126976 (50.00%)"),
+		"{}",
+		summary
+	);
+	assert!(summary.ends_with(" before the error."), "{}", summary);
+}
+
+#[test]
+fn test_progress_line() {
+	const MIB: u64 = 1 << 20;
+	let second: Duration = Duration::from_secs(1);
+	// the part of the input read, or the bytes alone when its size is not known
+	let progress: Progress = Progress::default();
+	progress_callback(None, 4 * MIB, "compress", &progress)(2 * MIB);
+	assert_eq!(progress.line(second * 2), "\r 50.00%,       1.00 MiB/s");
+	let progress: Progress = Progress::default();
+	progress_callback(None, 0, "compress", &progress)(3 * MIB);
+	assert_eq!(
+		progress.line(second),
+		"\r         3145728 bytes,       3.00 MiB/s"
+	);
+	// the part of the original size written when decompressing, the input being read ahead
+	let progress: Progress = Progress::default();
+	decompress_callback(None, MIB, Some(8 * MIB), &progress)(MIB);
+	progress.written().store(2 * MIB, Ordering::Relaxed);
+	assert_eq!(progress.line(second), "\r 25.00%,       2.00 MiB/s");
+	// the line is drawn next to the run, whose result is given back
+	assert_eq!(show_progress(&progress, false, || 1), 1);
+	assert_eq!(show_progress(&progress, true, || 2), 2);
+}
+
+#[test]
+fn test_progress_file_monotonic() -> AnyResult<()> {
+	let path: PathBuf = temp_path("progress");
+	let data: Vec<u8> = vec![7; 100_000];
+	let mut progress_file: ProgressFile =
+		ProgressFile::new(&path, data.len() as u64, "compress").with_interval(Duration::ZERO);
+	let mut seen: Vec<u64> = Vec::new();
+	let mut reader = ProgressReader::new(data.as_slice(), 0, |done: u64| {
+		progress_file.update(done, None);
+		let line: String = fs::read_to_string(&path).unwrap();
+		let done: u64 = line["{\"done\":".len()..line.find(',').unwrap()]
+			.parse()
+			.unwrap();
+		if seen.last() != Some(&done) {
+			seen.push(done);
+		}
+	});
+	io::copy(&mut reader, &mut io::sink())?;
+	assert!(seen.len() > 1, "{:?}", seen);
+	assert!(
+		seen.windows(2).all(|pair: &[u64]| pair[0] < pair[1]),
+		"{:?}",
+		seen
+	);
+	assert_eq!(seen.last(), Some(&(data.len() as u64)));
+	assert_eq!(
+		fs::read_to_string(&path)?,
+		"{\"done\":100000,\"total\":100000,\"stage\":\"compress\"}\n"
+	);
+	fs::remove_file(&path)?;
+	Ok(())
+}
+
+#[test]
+fn test_estimate_output() -> AnyResult<()> {
+	assert_eq!(estimate_output(0, 100, 0), None);
+	assert_eq!(estimate_output(10, 100, 0), None);
+	assert_eq!(estimate_output(10, 0, 5), None);
+	assert_eq!(estimate_output(10, 100, 5), Some(50));
+	// more input than announced, the estimate is what is already out
+	assert_eq!(estimate_output(200, 100, 80), Some(80));
+
+	// on an input compressing the same all along, the estimate gets closer and closer
+	let mut state: u32 = 1;
+	let data: Vec<u8> = (0..1 << 20)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			(state >> 28) as u8
+		})
+		.collect();
+	let written: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+	let mut estimates: Vec<u64> = Vec::new();
+	let reader = ProgressReader::new(data.as_slice(), 0, |done: u64| {
+		let written: u64 = written.load(Ordering::Relaxed);
+		estimates.extend(estimate_output(done, data.len() as u64, written));
+	});
+	let (_, writer): (_, CountingWriter<Vec<u8>>) = encode_with::<_, _, 0x1000, 0x1000>(
+		reader,
+		CountingWriter::new(Vec::new(), written.clone()),
+		&Config::default(),
+	)?;
+	let actual: u64 = writer.into_inner().len() as u64;
+	assert_eq!(written.load(Ordering::Relaxed), actual);
+	let error = |estimate: &u64| estimate.abs_diff(actual) as f64 / actual as f64;
+	let half: usize = estimates.len() / 2;
+	assert!(half > 0, "{:?}", estimates);
+	let early: f64 = estimates[..half].iter().map(error).sum::<f64>() / half as f64;
+	let late: f64 =
+		estimates[half..].iter().map(error).sum::<f64>() / (estimates.len() - half) as f64;
+	assert!(late <= early, "{} then {}", early, late);
+	assert!(
+		error(estimates.last().unwrap()) < 0.05,
+		"{:?} of {}",
+		estimates.last(),
+		actual
+	);
+	Ok(())
+}
+
+#[test]
+fn test_estimate_original() -> AnyResult<()> {
+	// assumed before anything is decoded, even of an input of unknown size
+	assert_eq!(estimate_original(0, 100, 0), 400);
+	assert_eq!(estimate_original(10, 100, 0), 400);
+	assert_eq!(estimate_original(10, 0, 0), 40);
+	assert_eq!(estimate_original(10, 0, 100), 100);
+	// then measured, the size of the output once everything is read
+	assert_eq!(estimate_original(10, 100, 25), 250);
+	assert_eq!(estimate_original(100, 100, 357), 357);
+
+	// the estimates of a decoding get closer to the original size as it goes, ending on it
+	let data: Vec<u8> = b"an older file with no size in its header, ".repeat(50_000);
+	let (_, compressed): (&[u8], Vec<u8>) =
+		encode_with::<_, _, 0x1000, 0x1000>(data.as_slice(), Vec::new(), &Config::default())?;
+	let total: u64 = compressed.len() as u64;
+	let written: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+	let mut estimates: Vec<u64> = Vec::new();
+	let mut reader = ProgressReader::new(compressed.as_slice(), 0, |done: u64| {
+		estimates.push(estimate_original(
+			done,
+			total,
+			written.load(Ordering::Relaxed),
+		));
+	});
+	let (_, writer): (_, CountingWriter<Vec<u8>>) = decode_with::<_, _, 0x1000>(
+		&mut reader,
+		CountingWriter::new(Vec::new(), written.clone()),
+		&Config::default(),
+	)?;
+	reader.report();
+	assert!(writer.into_inner() == data);
+	let actual: u64 = data.len() as u64;
+	assert_eq!(estimates[0], total * ASSUMED_EXPANSION);
+	assert_eq!(estimates.last(), Some(&actual));
+	let error = |estimate: &u64| estimate.abs_diff(actual) as f64 / actual as f64;
+	let half: usize = estimates.len() / 2;
+	let early: f64 = estimates[..half].iter().map(error).sum::<f64>() / half as f64;
+	let late: f64 =
+		estimates[half..].iter().map(error).sum::<f64>() / (estimates.len() - half) as f64;
+	assert!(late <= early, "{} then {}", early, late);
+	Ok(())
+}
+
+#[test]
+fn test_progress_file_estimate() -> AnyResult<()> {
+	let compressed_path: PathBuf = temp_path("estimate-compressed");
+	let output_path: PathBuf = temp_path("estimate-output");
+	let progress_path: PathBuf = temp_path("estimate-progress");
+	let data: Vec<u8> = b"compressed from a pipe of unknown size, ".repeat(1000);
+	// the header of a file compressed from the standard input without --stdin-size
+	let mut compressed: Vec<u8> = Vec::new();
+	write_header(&mut compressed, &Header::default())?;
+	let (_, compressed): (&[u8], Vec<u8>) =
+		encode_with::<_, _, 0x1000, 0x1000>(data.as_slice(), compressed, &Config::default())?;
+	fs::write(&compressed_path, &compressed)?;
+	let options: Options = Options {
+		progress_file: Some(progress_path.clone()),
+		..Options::default()
+	};
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default(),
+	)?;
+	assert_eq!(
+		fs::read_to_string(&progress_path)?,
+		format!(
+			"{{\"done\":{0},\"total\":{0},\"stage\":\"decompress\",\"estimate\":{1}}}\n",
+			compressed.len(),
+			data.len()
+		)
+	);
+	for path in [compressed_path, output_path, progress_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_progress_file_run() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("progress-input");
+	let compressed_path: PathBuf = temp_path("progress-compressed");
+	let output_path: PathBuf = temp_path("progress-output");
+	let progress_path: PathBuf = temp_path("progress-run");
+	fs::write(
+		&input_path,
+		b"some content to report the progress of".repeat(1000),
+	)?;
+	let options: Options = Options {
+		progress_file: Some(progress_path.clone()),
+		..Options::default()
+	};
+	let (input_size, compressed_size, _): (u64, u64, Duration) = run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	assert_eq!(
+		fs::read_to_string(&progress_path)?,
+		format!(
+			"{{\"done\":{0},\"total\":{0},\"stage\":\"compress\"}}\n",
+			input_size
+		)
+	);
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default(),
+	)?;
+	assert_eq!(
+		fs::read_to_string(&progress_path)?,
+		format!(
+			"{{\"done\":{0},\"total\":{0},\"stage\":\"decompress\"}}\n",
+			compressed_size
+		)
+	);
+	for path in [input_path, compressed_path, output_path, progress_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_buffered_run_output() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("buffered-input");
+	let compressed_path: PathBuf = temp_path("buffered-compressed");
+	let output_path: PathBuf = temp_path("buffered-output");
+
+	// data runs split by blocks of zeros, which the sparse writer skips over
+	let mut input: Vec<u8> = Vec::new();
+	for index in 0..64 {
+		input.extend(
+			format!("line {} of some buffered content\n", index)
+				.repeat(300)
+				.bytes(),
+		);
+		input.resize(input.len() + 0x1000 * (index % 3), 0);
+	}
+	fs::write(&input_path, &input)?;
+
+	// buffering must not change a single byte of the output
+	for config in [
+		Config::default(),
+		Config::new(Model::Text),
+		Config::default().with_lanes(4),
+	] {
+		let options: Options = Options {
+			config,
+			..Options::default()
+		};
+		run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		assert_eq!(fs::read(&compressed_path)?, compress_with(&input, &config)?);
+		run(
+			&compressed_path,
+			&output_path,
+			false,
+			&options,
+			&Progress::default(),
+		)?;
+		assert!(fs::read(&output_path)? == input);
+	}
+
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_verify() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("verify-input");
+	let compressed_path: PathBuf = temp_path("verify-compressed");
+	let reference_path: PathBuf = temp_path("verify-reference");
+	let input: Vec<u8> = b"some content to verify against a reference".repeat(5000);
+	fs::write(&input_path, &input)?;
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+
+	// a matching pair
+	assert_eq!(
+		verify(&compressed_path, &input_path)?,
+		(input.len() as u64, None)
+	);
+
+	// a pair differing at a known offset, past the first buffer
+	let mut reference: Vec<u8> = input.clone();
+	reference[123_456] ^= 1;
+	fs::write(&reference_path, &reference)?;
+	assert_eq!(
+		verify(&compressed_path, &reference_path)?,
+		(input.len() as u64, Some(123_456))
+	);
+
+	// a reference that is shorter or longer differs where the shorter one ends
+	fs::write(&reference_path, &input[..100_000])?;
+	assert_eq!(verify(&compressed_path, &reference_path)?.1, Some(100_000));
+	fs::write(&reference_path, [input.as_slice(), b"more"].concat())?;
+	assert_eq!(
+		verify(&compressed_path, &reference_path)?.1,
+		Some(input.len() as u64)
+	);
+
+	for path in [input_path, compressed_path, reference_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_directory_input() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("directory-input");
+	let output_path: PathBuf = temp_path("directory-output");
+	fs::create_dir_all(&input_path)?;
+	for (is_compress, message) in [
+		(
+			true,
+			"Input is a directory, srx compresses a single file, archive it first, with tar for example!",
+		),
+		(false, "Input is a directory, not a compressed file!"),
+	] {
+		let error: AnyError = run(
+			&input_path,
+			&output_path,
+			is_compress,
+			&Options::default(),
+			&Progress::default(),
+		)
+		.unwrap_err();
+		assert_eq!(error.to_string(), message);
+		// refused before the output is created
+		assert!(!output_path.exists());
+	}
+	fs::remove_dir(input_path)?;
+	Ok(())
+}
+
+#[test]
+fn test_preflight() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("preflight-input");
+	let compressed_path: PathBuf = temp_path("preflight-compressed");
+	let output_path: PathBuf = temp_path("preflight-output");
+	fs::write(
+		&input_path,
+		b"some content to check before decompressing".repeat(500),
+	)?;
+	let config: Config = Config::new(Model::Text).with_lanes(2);
+	let options: Options = Options {
+		config,
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+
+	// the declared numbers are the ones of the actual decompression
+	let preflight: Preflight = Preflight::new(&compressed_path, None)?;
+	let (_, output_size, _): (u64, u64, Duration) = run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert_eq!(preflight.header.config, config);
+	assert_eq!(preflight.header.original_size, Some(output_size));
+	assert_eq!(preflight.memory, config.estimated_memory());
+	assert!(!preflight.exceeds_limit());
+	// two lanes take twice the memory of one
+	assert_eq!(
+		preflight.memory,
+		Config::new(Model::Text).estimated_memory() * 2
+	);
+
+	// a limit below that is reported, and refused when decompressing
+	let limit: Option<u64> = Some(preflight.memory - 1);
+	assert!(Preflight::new(&compressed_path, limit)?.exceeds_limit());
+	let options: Options = Options {
+		memory_limit: limit,
+		..Options::default()
+	};
+	assert!(run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default()
+	)
+	.is_err());
+
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[cfg(all(feature = "xattrs", target_os = "linux"))]
+#[test]
+fn test_xattrs_round_trip() -> AnyResult<()> {
+	use super::xattrs::{restore_xattrs, store_xattrs};
+
+	let input_path: PathBuf = temp_path("xattrs-input");
+	let compressed_path: PathBuf = temp_path("xattrs-compressed");
+	let output_path: PathBuf = temp_path("xattrs-output");
+
+	let attribute: (Vec<u8>, Vec<u8>) = (b"user.test".to_vec(), b"some value".to_vec());
+	fs::write(&input_path, b"some content with an attribute")?;
+	assert_eq!(
+		restore_xattrs(&input_path, std::slice::from_ref(&attribute))?,
+		0
+	);
+
+	let options: Options = Options {
+		xattrs: true,
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default(),
+	)?;
+	assert_eq!(fs::read(&output_path)?, fs::read(&input_path)?);
+	assert!(store_xattrs(&output_path)?.contains(&attribute));
+
+	// without the option, the attributes are left alone
+	fs::remove_file(&output_path)?;
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert!(!store_xattrs(&output_path)?.contains(&attribute));
+
+	fs::remove_file(&input_path)?;
+	fs::remove_file(&compressed_path)?;
+	fs::remove_file(&output_path)?;
+	Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_owner_round_trip() -> AnyResult<()> {
+	use super::metadata::{decode_records, encode_records, restore_metadata, Record};
+	use super::owner::OWNER_RECORD;
+	use std::os::unix::fs::MetadataExt;
+
+	let input_path: PathBuf = temp_path("owner-input");
+	let compressed_path: PathBuf = temp_path("owner-compressed");
+	let output_path: PathBuf = temp_path("owner-output");
+	fs::write(&input_path, b"some content with an owner")?;
+	let (uid, gid): (u32, u32) = (
+		fs::metadata(&input_path)?.uid(),
+		fs::metadata(&input_path)?.gid(),
+	);
+
+	// the owner is stored in the header
+	let options: Options = Options {
+		owner: true,
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let header: Header = read_header(&mut fs::File::open(&compressed_path)?)?;
+	let owner: Record = (
+		OWNER_RECORD.to_vec(),
+		[uid.to_le_bytes(), gid.to_le_bytes()].concat(),
+	);
+	assert_eq!(decode_records(&header.metadata.unwrap())?, vec![owner]);
+
+	// restoring it to its own owner is always permitted
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default(),
+	)?;
+	assert_eq!(fs::read(&output_path)?, fs::read(&input_path)?);
+	assert_eq!(fs::metadata(&output_path)?.uid(), uid);
+
+	// giving the file to root is only permitted to root, and skipped otherwise
+	let root: Vec<u8> = encode_records(&[(OWNER_RECORD.to_vec(), vec![0; 8])]);
+	let skipped: usize = restore_metadata(&output_path, &root, &options)?;
+	if uid == 0 {
+		assert_eq!((skipped, fs::metadata(&output_path)?.uid()), (0, 0));
+	} else {
+		assert_eq!((skipped, fs::metadata(&output_path)?.uid()), (1, uid));
+	}
+	// and the owner is left alone without the option
+	assert_eq!(
+		restore_metadata(&output_path, &root, &Options::default())?,
+		0
+	);
+
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+// a pipe set up like a parent process would, each end owned by a file
+#[cfg(unix)]
+fn pipe() -> (File, File) {
+	use std::os::unix::io::FromRawFd;
+
+	let mut fds: [libc::c_int; 2] = [0; 2];
+	// SAFETY: pipe writes two new descriptors into fds, each then owned by one file
+	assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+	unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fds() -> AnyResult<()> {
+	use std::io::Write;
+	use std::os::unix::io::{AsRawFd, IntoRawFd};
+
+	let input: Vec<u8> = b"handed down by a supervisor, ".repeat(5000);
+	let compressed_path: PathBuf = temp_path("fds-compressed");
+	// compressed from the read end of a pipe into a file given by its path
+	let (reader, mut writer): (File, File) = pipe();
+	let piped: Vec<u8> = input.clone();
+	let feeding = std::thread::spawn(move || writer.write_all(&piped));
+	let options: Options =
+		Options::parse(&[format!("--input-fd={}", reader.into_raw_fd())]).unwrap();
+	let (input_size, output_size, _): (u64, u64, Duration) = run_fds(
+		None,
+		Some(&compressed_path),
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	feeding.join().unwrap()?;
+	assert_eq!(input_size, input.len() as u64);
+	assert_eq!(output_size, fs::metadata(&compressed_path)?.len());
+	// a pipe has no size to record
+	assert_eq!(
+		read_header(&mut File::open(&compressed_path)?)?.original_size,
+		None
+	);
+
+	// decompressed from a file descriptor into the write end of a pipe
+	let (mut reader, writer): (File, File) = pipe();
+	let draining = std::thread::spawn(move || -> io::Result<Vec<u8>> {
+		let mut output: Vec<u8> = Vec::new();
+		reader.read_to_end(&mut output)?;
+		Ok(output)
+	});
+	let options: Options = Options::parse(&[
+		format!("--input-fd={}", File::open(&compressed_path)?.into_raw_fd()),
+		format!("--output-fd={}", writer.into_raw_fd()),
+	])
+	.unwrap();
+	let (input_size, output_size, _): (u64, u64, Duration) =
+		run_fds(None, None, false, &options, &Progress::default())?;
+	assert_eq!(input_size, fs::metadata(&compressed_path)?.len());
+	assert_eq!(output_size, input.len() as u64);
+	assert!(draining.join().unwrap()? == input);
+
+	// a descriptor taken for the input and the output would be closed twice, and one open the
+	// other way round is refused before being taken over
+	let (reader, writer): (File, File) = pipe();
+	for (input_fd, output_fd) in [
+		(reader.as_raw_fd(), reader.as_raw_fd()),
+		(writer.as_raw_fd(), reader.as_raw_fd()),
+	] {
+		let options: Options = Options::parse(&[
+			format!("--input-fd={}", input_fd),
+			format!("--output-fd={}", output_fd),
+		])
+		.unwrap();
+		assert!(run_fds(None, None, false, &options, &Progress::default()).is_err());
+	}
+	// the options needing the path of the input
+	let options: Options = Options::parse(&[
+		format!("--input-fd={}", reader.as_raw_fd()),
+		"--checksum-algo=crc32".to_string(),
+	])
+	.unwrap();
+	assert!(run_fds(
+		None,
+		Some(&compressed_path),
+		true,
+		&options,
+		&Progress::default()
+	)
+	.is_err());
+	assert!(Options::parse(&["--input-fd=-1"]).is_none());
+	fs::remove_file(compressed_path)?;
+	Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fifo_output() -> AnyResult<()> {
+	use std::ffi::CString;
+	use std::os::unix::ffi::OsStrExt;
+
+	let input: Vec<u8> = b"written to a FIFO, which has no position, ".repeat(5000);
+	let input_path: PathBuf = temp_path("fifo-input");
+	let compressed_path: PathBuf = temp_path("fifo-compressed");
+	let fifo_path: PathBuf = temp_path("fifo");
+	fs::write(&input_path, &input)?;
+	let fifo: CString = CString::new(fifo_path.as_os_str().as_bytes()).unwrap();
+	// SAFETY: mkfifo only reads the path, a valid C string
+	assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+	// the reader opens the other side, which the writer waits for
+	let drain = |path: PathBuf| {
+		std::thread::spawn(move || -> io::Result<Vec<u8>> {
+			let mut output: Vec<u8> = Vec::new();
+			File::open(path)?.read_to_end(&mut output)?;
+			Ok(output)
+		})
+	};
+
+	// compressed into the FIFO, the bytes counted on their way
+	let options: Options = Options::default();
+	let draining = drain(fifo_path.clone());
+	let (input_size, output_size, _): (u64, u64, Duration) = run(
+		&input_path,
+		&fifo_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let compressed: Vec<u8> = draining.join().unwrap()?;
+	assert_eq!(input_size, input.len() as u64);
+	assert_eq!(output_size, compressed.len() as u64);
+	fs::write(&compressed_path, &compressed)?;
+
+	// decompressed into it
+	let draining = drain(fifo_path.clone());
+	let (input_size, output_size, _): (u64, u64, Duration) = run(
+		&compressed_path,
+		&fifo_path,
+		false,
+		&options,
+		&Progress::default(),
+	)?;
+	assert!(draining.join().unwrap()? == input);
+	assert_eq!(input_size, compressed.len() as u64);
+	assert_eq!(output_size, input.len() as u64);
+
+	// a device stays at 0 whatever is written to it
+	let null: PathBuf = PathBuf::from("/dev/null");
+	let (_, output_size, _): (u64, u64, Duration) =
+		run(&input_path, &null, true, &options, &Progress::default())?;
+	assert_eq!(output_size, compressed.len() as u64);
+
+	for path in [input_path, compressed_path, fifo_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_benchmark() -> AnyResult<()> {
+	let input: Vec<u8> = b"decoded into nothing, only counted, ".repeat(5000);
+	let input_path: PathBuf = temp_path("benchmark-input");
+	let compressed_path: PathBuf = temp_path("benchmark-compressed");
+	let output_path: PathBuf = temp_path("benchmark-output");
+	fs::write(&input_path, &input)?;
+	// the stored tail counts as output too
+	for compress_options in [&[][..], &["--max-time=0"][..]] {
+		let options: Options = Options::parse(compress_options).unwrap();
+		run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		let options: Options = Options::default();
+		let (input_size, output_size, _): (u64, u64, Duration) = run(
+			&compressed_path,
+			&output_path,
+			false,
+			&options,
+			&Progress::default(),
+		)?;
+		assert_eq!(output_size, input.len() as u64);
+		let options: Options = Options::parse(&["--benchmark", "--buffers=small"]).unwrap();
+		let (benchmark_input, benchmark_output, _): (u64, u64, Duration) =
+			run_benchmark(&compressed_path, &options)?;
+		assert_eq!(
+			(benchmark_input, benchmark_output),
+			(input_size, output_size)
+		);
+	}
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_assume_srx() -> AnyResult<()> {
+	let input: Vec<u8> = b"the header was lost on the way, ".repeat(5000);
+	let input_path: PathBuf = temp_path("assume-input");
+	let compressed_path: PathBuf = temp_path("assume-compressed");
+	let damaged_path: PathBuf = temp_path("assume-damaged");
+	let output_path: PathBuf = temp_path("assume-output");
+	fs::write(&input_path, &input)?;
+	let options: Options = Options::default();
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let compressed: Vec<u8> = fs::read(&compressed_path)?;
+	// the header of a file compressed with the defaults only holds the size of the input
+	let header_size: usize = 12;
+	assert_eq!(
+		read_header(&mut &compressed[..])?,
+		Header {
+			original_size: Some(input.len() as u64),
+			..Header::default()
+		}
+	);
+	let zeroed: Vec<u8> = [&[0; 12][..], &compressed[header_size..]].concat();
+	let assume_srx: Options = Options::parse(&["--assume-srx"]).unwrap();
+	for damaged in [&compressed[header_size..], &zeroed[..]] {
+		fs::write(&damaged_path, damaged)?;
+		assert!(run(
+			&damaged_path,
+			&output_path,
+			false,
+			&options,
+			&Progress::default()
+		)
+		.is_err());
+		run(
+			&damaged_path,
+			&output_path,
+			false,
+			&assume_srx,
+			&Progress::default(),
+		)?;
+		assert!(fs::read(&output_path)? == input);
+	}
+	// a valid header is still read as it is
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&assume_srx,
+		&Progress::default(),
+	)?;
+	assert!(fs::read(&output_path)? == input);
+	for path in [input_path, compressed_path, damaged_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_stdin_size() -> AnyResult<()> {
+	let input: Vec<u8> = b"piped from somewhere else, ".repeat(5000);
+	let compress = |expected: Option<u64>| {
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			SizedReader::new(input.as_slice(), expected),
+			Vec::new(),
+			&Config::default(),
+		)
+	};
+
+	// the right size, or none at all, goes through untouched
+	for expected in [Some(input.len() as u64), None] {
+		let (reader, _): (SizedReader<&[u8]>, Vec<u8>) = compress(expected)?;
+		assert_eq!(reader.position(), input.len() as u64);
+	}
+
+	// a wrong size only shows at the end of the input, after the header went out with it
+	for expected in [input.len() as u64 - 1, input.len() as u64 + 1, 0] {
+		match compress(Some(expected)) {
+			Ok(_) => panic!("A wrong size of {} was not noticed!", expected),
+			Err(error) => assert!(error.to_string().contains("--stdin-size"), "{}", error),
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_content_hash() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("hash-input");
+	let compressed_path: PathBuf = temp_path("hash-compressed");
+	let output_path: PathBuf = temp_path("hash-output");
+	let input: Vec<u8> = b"the same content twice is compressed once. ".repeat(1000);
+	fs::write(&input_path, &input)?;
+
+	// the given hash is stored as is, and shown without decompressing anything
+	let given: Vec<u8> = (0..32).collect();
+	let options: Options = Options {
+		content_hash: Some(ContentHash::Given(given.clone())),
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let preflight: Preflight = Preflight::new(&compressed_path, None)?;
+	assert_eq!(preflight.header.content_hash.as_ref(), Some(&given));
+	assert!(preflight.to_string().contains(
+		"Content hash: 000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+	));
+
+	// the decoder does not rely on it, a wrong hash changes nothing of the output
+	let mut compressed: Vec<u8> = fs::read(&compressed_path)?;
+	let position: usize = compressed
+		.windows(given.len())
+		.position(|window| window == given.as_slice())
+		.unwrap();
+	compressed[position] ^= 0xFF;
+	fs::write(&compressed_path, &compressed)?;
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert!(fs::read(&output_path)? == input);
+
+	// or srx computes a CRC-32 of the input
+	let options: Options = Options {
+		content_hash: Some(ContentHash::Crc32),
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let preflight: Preflight = Preflight::new(&compressed_path, None)?;
+	assert_eq!(
+		preflight.header.content_hash,
+		Some(Crc32::of(&input).to_be_bytes().to_vec())
+	);
+
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_checksum_algo() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("checksum-input");
+	let compressed_path: PathBuf = temp_path("checksum-compressed");
+	let output_path: PathBuf = temp_path("checksum-output");
+	let input: Vec<u8> = b"a CRC-32 of the input is checked on decompression. ".repeat(1000);
+	fs::write(&input_path, &input)?;
+	let options: Options = Options {
+		checksum_algo: Some(ChecksumAlgo::Crc32),
+		..Options::default()
+	};
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let digest: Vec<u8> = Checksum::of(ChecksumAlgo::Crc32, &input)?.digest;
+	let preflight: Preflight = Preflight::new(&compressed_path, None)?;
+	assert_eq!(
+		preflight
+			.header
+			.checksum
+			.as_ref()
+			.map(|checksum| &checksum.digest),
+		Some(&digest)
+	);
+	run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert!(fs::read(&output_path)? == input);
+
+	// an output not matching the recorded checksum fails the decompression
+	let mut compressed: Vec<u8> = fs::read(&compressed_path)?;
+	let position: usize = compressed
+		.windows(digest.len())
+		.position(|window| window == digest.as_slice())
+		.unwrap();
+	compressed[position] ^= 0xFF;
+	fs::write(&compressed_path, &compressed)?;
+	let error: AnyError = run(
+		&compressed_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)
+	.unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"The crc32 checksum of the output does not match the one recorded!"
+	);
+	assert!(decompress(&compressed).is_err());
+
+	for path in [input_path, compressed_path, output_path] {
+		let _ = fs::remove_file(path);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_run_test() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("t-input");
+	let compressed_path: PathBuf = temp_path("t-compressed");
+	let input: Vec<u8> = b"tested without writing the output anywhere. ".repeat(2000);
+	fs::write(&input_path, &input)?;
+	// a single payload, a stream of resync segments and one with a checksum to check
+	for options in [
+		Options::default(),
+		Options {
+			resync: Some(16 << 10),
+			..Options::default()
+		},
+		Options {
+			checksum_algo: Some(ChecksumAlgo::Crc32),
+			..Options::default()
+		},
+	] {
+		run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		let size: u64 = run_test(&compressed_path, &Options::default(), &Progress::default())?;
+		assert_eq!(size, input.len() as u64);
+	}
+
+	// the checksum is checked, nothing being written
+	let digest: Vec<u8> = Checksum::of(ChecksumAlgo::Crc32, &input)?.digest;
+	let mut compressed: Vec<u8> = fs::read(&compressed_path)?;
+	let position: usize = compressed
+		.windows(digest.len())
+		.position(|window| window == digest.as_slice())
+		.unwrap();
+	compressed[position] ^= 0xFF;
+	fs::write(&compressed_path, &compressed)?;
+	assert!(run_test(&compressed_path, &Options::default(), &Progress::default()).is_err());
+	// neither the standard input nor a directory
+	for path in [Path::new(STDIN_PATH), &std::env::temp_dir()] {
+		assert!(run_test(path, &Options::default(), &Progress::default()).is_err());
+	}
+
+	for path in [input_path, compressed_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_blocks() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("blocks-input");
+	let compressed_path: PathBuf = temp_path("blocks-compressed");
+	let output_path: PathBuf = temp_path("blocks-output");
+	let input: Vec<u8> = b"compressed in blocks, each with contexts of its own. ".repeat(40_000);
+	fs::write(&input_path, &input)?;
+	// mapped or read, verified while written, and checked against a checksum
+	for compress_options in [
+		&["--blocks=3"][..],
+		&["--blocks=2", "--mmap=never"],
+		&["--blocks=4", "--verify", "--checksum-algo=crc32"],
+	] {
+		let options: Options = Options::parse(compress_options).unwrap();
+		let (input_size, _, _): (u64, u64, Duration) = run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		assert_eq!(input_size, input.len() as u64);
+		let blocks: usize = options.blocks.unwrap();
+		let preflight: Preflight = Preflight::new(&compressed_path, None)?;
+		assert_eq!(preflight.header.blocks.as_ref().map(Vec::len), Some(blocks));
+		assert_eq!(
+			preflight.memory,
+			Config::default().estimated_memory() * blocks as u64
+		);
+		// every way of decoding a file knows the blocks
+		run(
+			&compressed_path,
+			&output_path,
+			false,
+			&Options::default(),
+			&Progress::default(),
+		)?;
+		assert!(fs::read(&output_path)? == input, "{:?}", compress_options);
+		assert!(decompress(&fs::read(&compressed_path)?)? == input);
+		assert_eq!(
+			verify(&compressed_path, &input_path)?,
+			(input.len() as u64, None)
+		);
+		let tested: u64 = run_test(&compressed_path, &Options::default(), &Progress::default())?;
+		assert_eq!(tested, input.len() as u64);
+		let (_, benchmarked, _): (u64, u64, Duration) =
+			run_benchmark(&compressed_path, &Options::parse(&["--benchmark"]).unwrap())?;
+		assert_eq!(benchmarked, input.len() as u64);
+	}
+	// all the blocks need their contexts at once
+	let limit: u64 = Config::default().estimated_memory() * 3 - 1;
+	let options: Options = Options {
+		memory_limit: Some(limit),
+		..Options::default()
+	};
+	assert!(run(
+		&compressed_path,
+		&output_path,
+		false,
+		&options,
+		&Progress::default()
+	)
+	.is_err());
+	// nothing that needs a single payload
+	for compress_options in [
+		&["--blocks=2", "--max-time=1000"][..],
+		&["--blocks=2", "--resync=1"],
+		&["--blocks=2", "--profile-out=/tmp/blocks.prof"],
+	] {
+		let options: Options = Options::parse(compress_options).unwrap();
+		assert!(run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default()
+		)
+		.is_err());
+	}
+
+	for path in [input_path, compressed_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_mmap_policy() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("mmap-input");
+	let empty_path: PathBuf = temp_path("mmap-empty");
+	let compressed_path: PathBuf = temp_path("mmap-compressed");
+	let input: Vec<u8> =
+		b"mapped or read, the output is the same. ".repeat(MMAP_THRESHOLD as usize / 40 + 1);
+	fs::write(&input_path, &input)?;
+	fs::write(&empty_path, b"")?;
+
+	// a regular file large enough is mapped by auto, and compressed the same either way
+	assert!(map_input(&File::open(&input_path)?, MmapPolicy::Auto)?.is_some());
+	assert!(map_input(&File::open(&input_path)?, MmapPolicy::Never)?.is_none());
+	let expected: Vec<u8> = compress_with(&input, &Config::default())?;
+	for mmap in [MmapPolicy::Never, MmapPolicy::Auto, MmapPolicy::Always] {
+		let options: Options = Options {
+			mmap,
+			..Options::default()
+		};
+		let (input_size, _, _): (u64, u64, Duration) = run(
+			&input_path,
+			&compressed_path,
+			true,
+			&options,
+			&Progress::default(),
+		)?;
+		assert_eq!(input_size, input.len() as u64);
+		assert!(fs::read(&compressed_path)? == expected, "{:?}", mmap);
+	}
+
+	// what can not be mapped is read under auto, and an error under always
+	let mut unmappable: Vec<PathBuf> = vec![empty_path.clone()];
+	if cfg!(unix) {
+		unmappable.push(PathBuf::from("/dev/null"));
+	}
+	for path in &unmappable {
+		assert!(map_input(&File::open(path)?, MmapPolicy::Auto)?.is_none());
+		let auto: Options = Options::default();
+		run(path, &compressed_path, true, &auto, &Progress::default())?;
+		assert_eq!(decompress(&fs::read(&compressed_path)?)?, b"");
+		let always: Options = Options {
+			mmap: MmapPolicy::Always,
+			..Options::default()
+		};
+		match run(path, &compressed_path, true, &always, &Progress::default()) {
+			Ok(_) => panic!("{} was mapped!", path.display()),
+			Err(error) => assert!(error.to_string().contains("can not be mapped"), "{}", error),
+		}
+	}
+
+	for path in [input_path, empty_path, compressed_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_compress_verified() -> AnyResult<()> {
+	let input: Vec<u8> = b"checked on the other core as it is written, ".repeat(20000);
+	let input_path: PathBuf = temp_path("verified-input");
+	let compressed_path: PathBuf = temp_path("verified-compressed");
+	fs::write(&input_path, &input)?;
+	let options: Options = Options::parse(&["--verify"]).unwrap();
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&options,
+		&Progress::default(),
+	)?;
+	let compressed_size: u64 = fs::metadata(&compressed_path)?.len();
+	assert_eq!(decompress(fs::read(&compressed_path)?.as_slice())?, input);
+	// the file is written right but the copy the decoder reads is not
+	let corrupted: AnyResult<(u64, File, u64)> = compress_verified(
+		&input_path,
+		None,
+		File::create(&compressed_path)?,
+		|mut tee: TeeWriter<File>| {
+			tee.corrupt_at(compressed_size / 2);
+			compress_file(
+				&input_path,
+				File::open(&input_path)?,
+				tee,
+				&options,
+				&Progress::default(),
+			)
+		},
+	);
+	assert!(corrupted
+		.unwrap_err()
+		.to_string()
+		.starts_with("Verification failed"));
+	assert_eq!(decompress(fs::read(&compressed_path)?.as_slice())?, input);
+	// resync segments are not checked
+	assert!(run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::parse(&["--verify", "--resync=1"]).unwrap(),
+		&Progress::default(),
+	)
+	.is_err());
+	// the output of a failed verification is removed, unless it is not a file
+	remove_output(&compressed_path);
+	assert!(!compressed_path.exists());
+	for path in [Path::new(STDOUT_PATH), Path::new("/dev/null")] {
+		remove_output(path);
+	}
+	#[cfg(unix)]
+	assert!(Path::new("/dev/null").exists());
+	fs::remove_file(input_path)?;
+	Ok(())
+}
+
+#[test]
+fn test_atomic_output() -> AnyResult<()> {
+	let input_path: PathBuf = temp_path("atomic-input");
+	let output_path: PathBuf = temp_path("atomic-output");
+	// a file of the user, named as a temporary file of the output could be
+	let user_path: PathBuf = temp_path("atomic-output.tmp");
+	let input: Vec<u8> = b"written next to the output and renamed to it. ".repeat(1000);
+	fs::write(&input_path, &input)?;
+	fs::write(&output_path, b"a previous good output")?;
+	fs::write(&user_path, b"a file of the user")?;
+	// the files next to the output besides the user's, none once a run is over
+	let leftovers = || -> AnyResult<Vec<PathBuf>> {
+		let prefix: String = format!("{}.", output_path.file_name().unwrap().to_string_lossy());
+		let mut leftovers: Vec<PathBuf> = Vec::new();
+		for entry in fs::read_dir(std::env::temp_dir())? {
+			let path: PathBuf = entry?.path();
+			let name: String = path.file_name().unwrap().to_string_lossy().into_owned();
+			if name.starts_with(&prefix) && path != user_path {
+				leftovers.push(path);
+			}
+		}
+		Ok(leftovers)
+	};
+	// a failed run leaves the previous output as it was, and nothing next to it
+	assert!(run(
+		&input_path,
+		&output_path,
+		false,
+		&Options::default(),
+		&Progress::default(),
+	)
+	.is_err());
+	assert_eq!(fs::read(&output_path)?, b"a previous good output");
+	assert_eq!(leftovers()?, Vec::<PathBuf>::new());
+	assert_eq!(fs::read(&user_path)?, b"a file of the user");
+	// a complete one replaces it
+	run(
+		&input_path,
+		&output_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert_eq!(decompress(fs::read(&output_path)?.as_slice())?, input);
+	assert_eq!(leftovers()?, Vec::<PathBuf>::new());
+	assert_eq!(fs::read(&user_path)?, b"a file of the user");
+	for path in [input_path, output_path, user_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_no_metadata() -> AnyResult<()> {
+	let input: Vec<u8> = b"the same content, touched at another time ".repeat(2000);
+	let paths: Vec<PathBuf> = (0..2)
+		.map(|index| temp_path(&format!("no-metadata-input-{}", index)))
+		.collect();
+	let compressed_path: PathBuf = temp_path("no-metadata-compressed");
+	for (path, seconds) in paths.iter().zip([1_000_000_000, 1_700_000_000]) {
+		fs::write(path, &input)?;
+		File::options()
+			.write(true)
+			.open(path)?
+			.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))?;
+	}
+	let options: Options = Options::parse(&["--no-metadata", "--content-hash"]).unwrap();
+	let mut outputs: Vec<Vec<u8>> = Vec::new();
+	for path in &paths {
+		check_no_metadata(&options)?;
+		run(path, &compressed_path, true, &options, &Progress::default())?;
+		outputs.push(fs::read(&compressed_path)?);
+	}
+	assert!(outputs[0] == outputs[1]);
+	assert_eq!(decompress(&outputs[0])?, input);
+	// what depends on the file or the clock is refused
+	for refused in ["--xattrs", "--owner", "--max-time=1000"] {
+		let options: Options = Options::parse(&["--no-metadata", refused]).unwrap();
+		assert!(check_no_metadata(&options).is_err(), "{}", refused);
+	}
+	for path in paths.into_iter().chain([compressed_path]) {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_is_srx() -> AnyResult<()> {
+	let input: Vec<u8> = b"compressed or not, srx auto tells ".repeat(1000);
+	let input_path: PathBuf = temp_path("auto-input");
+	let compressed_path: PathBuf = temp_path("auto-compressed");
+	fs::write(&input_path, &input)?;
+	assert!(!is_srx(&input_path)?);
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert!(is_srx(&compressed_path)?);
+	let resync: Options = Options::parse(&["--resync=1"]).unwrap();
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&resync,
+		&Progress::default(),
+	)?;
+	assert!(is_srx(&compressed_path)?);
+
+	// a file starting like an SRX file is taken for one, but still compressed by c
+	let lookalike: Vec<u8> = [&b"sRx\x00"[..], &input].concat();
+	fs::write(&input_path, &lookalike)?;
+	assert!(is_srx(&input_path)?);
+	run(
+		&input_path,
+		&compressed_path,
+		true,
+		&Options::default(),
+		&Progress::default(),
+	)?;
+	assert_eq!(decompress(&fs::read(&compressed_path)?)?, lookalike);
+
+	// the standard input would have to be read twice
+	assert!(is_srx(&PathBuf::from("-")).is_err());
+	for path in [input_path, compressed_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
+
+#[cfg(all(feature = "gzip", feature = "zstd"))]
+#[test]
+fn test_recompress_both_ways() -> AnyResult<()> {
+	use super::command::run_recompress;
+	use super::recompress::Foreign;
+	use std::io::Write;
+
+	let original: Vec<u8> = (0u32..200_000).map(|index| (index % 251) as u8).collect();
+	let plain_path: PathBuf = temp_path("recompress.bin");
+	let gzip_path: PathBuf = temp_path("recompress.gz");
+	let srx_path: PathBuf = temp_path("recompress.srx");
+	let zstd_path: PathBuf = temp_path("recompress.zst");
+	fs::write(&plain_path, &original)?;
+	let mut encoder: flate2::write::GzEncoder<Vec<u8>> =
+		flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+	encoder.write_all(&original)?;
+	fs::write(&gzip_path, encoder.finish()?)?;
+
+	// the input is told by its magic, the output of a srx input by its extension
+	assert_eq!(Foreign::of_input(&plain_path)?, None);
+	assert_eq!(Foreign::of_input(&gzip_path)?, Some(Foreign::Gzip));
+	assert_eq!(Foreign::of_output(&zstd_path), Foreign::Zstd);
+	assert_eq!(Foreign::of_output(&gzip_path), Foreign::Gzip);
+	let recompress = |input_path: &Path, output_path: &Path| -> AnyResult<(u64, u64)> {
+		let (input_size, output_size, _): (u64, u64, Duration) = run_recompress(
+			input_path,
+			output_path,
+			&Options::default(),
+			&Progress::default(),
+		)?;
+		Ok((input_size, output_size))
+	};
+	let error: AnyError = recompress(&plain_path, &srx_path).unwrap_err();
+	assert_eq!(error.to_string(), "Not a gzip, zstd or srx file!");
+
+	// gzip to srx, srx to zstd, then zstd to the same srx again
+	let (input_size, _): (u64, u64) = recompress(&gzip_path, &srx_path)?;
+	assert_eq!(input_size, original.len() as u64);
+	let compressed: Vec<u8> = fs::read(&srx_path)?;
+	assert_eq!(decompress(&compressed)?, original);
+	let (decoded_size, zstd_size): (u64, u64) = recompress(&srx_path, &zstd_path)?;
+	assert_eq!(decoded_size, original.len() as u64);
+	assert_eq!(zstd_size, fs::metadata(&zstd_path)?.len());
+	assert_eq!(Foreign::of_input(&zstd_path)?, Some(Foreign::Zstd));
+	assert_eq!(zstd::decode_all(File::open(&zstd_path)?)?, original);
+	recompress(&zstd_path, &srx_path)?;
+	assert_eq!(fs::read(&srx_path)?, compressed);
+	for path in [plain_path, gzip_path, srx_path, zstd_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
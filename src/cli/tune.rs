@@ -0,0 +1,107 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{encode_with_config, AnyResult, Buffers, Config, Growth, Model, MAX_LANES};
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+const TUNE_HELP: &str = "\
+	model NAME    standard, text, tagged, flat, xor-mix, rotate-mix, two-way or\n\
+	\x20             four-way\n\
+	lanes K       1 to 16 interleaved streams\n\
+	grow on|off   grow the primary table with the input\n\
+	buffers SIZE  small, default or large\n\
+	run           compress the input with the current config (or an empty line)\n\
+	show          print the current config\n\
+	quit          leave (or the end of the input)";
+
+// -----------------------------------------------
+
+// Reads commands from the input until quit or its end, changing the config and compressing
+// the data already in memory with it, so a tuning session pays for reading the file and
+// starting the process only once. Mistakes in the commands and configs that do not compress
+// are reported on the output and the session goes on.
+pub fn tune<R: BufRead, W: Write>(data: &[u8], input: R, mut output: W) -> AnyResult<Config> {
+	let mut config: Config = Config::default();
+	writeln!(
+		output,
+		"{} bytes loaded, type help for the commands",
+		data.len()
+	)?;
+	write!(output, "> ")?;
+	output.flush()?;
+	for line in input.lines() {
+		let line: String = line?;
+		let mut words = line.split_whitespace();
+		match (words.next(), words.next(), words.next()) {
+			(None, _, _) | (Some("run"), None, _) => match compress(data, &config) {
+				Ok((size, duration)) => writeln!(
+					output,
+					"{}: {} -> {} ({:.2}%) in {:.2} seconds",
+					config.name(),
+					data.len(),
+					size,
+					size as f64 / data.len().max(1) as f64 * 100.0,
+					duration.as_secs_f64()
+				)?,
+				Err(error) => writeln!(output, "Error occurred! {}", error)?,
+			},
+			(Some("model"), Some(name), None) => {
+				match Model::ALL.iter().find(|model| model.name() == name) {
+					Some(&model) => config.model = model,
+					None => writeln!(output, "Unknown model {}!", name)?,
+				}
+			}
+			(Some("lanes"), Some(lanes), None) => match lanes.parse::<u8>() {
+				Ok(lanes) if lanes >= 1 && lanes as usize <= MAX_LANES => {
+					config = config.with_lanes(lanes)
+				}
+				_ => writeln!(output, "The lanes must be between 1 and {}!", MAX_LANES)?,
+			},
+			(Some("grow"), Some("on"), None) => {
+				config = config.with_growth(Some(Growth::default()))
+			}
+			(Some("grow"), Some("off"), None) => config = config.with_growth(None),
+			(Some("buffers"), Some(name), None) => match Buffers::from_name(name) {
+				Some(buffers) => config = config.with_buffers(buffers),
+				None => writeln!(output, "Unknown buffers {}!", name)?,
+			},
+			(Some("show"), None, _) => writeln!(
+				output,
+				"{}, {} buffers",
+				config.name(),
+				config.buffers.name()
+			)?,
+			(Some("quit"), None, _) => break,
+			_ => writeln!(output, "{}", TUNE_HELP)?,
+		}
+		write!(output, "> ")?;
+		output.flush()?;
+	}
+	writeln!(output)?;
+	Ok(config)
+}
+
+// the size of the payload, without the header which is the same for every config
+fn compress(data: &[u8], config: &Config) -> AnyResult<(usize, Duration)> {
+	let start: Instant = Instant::now();
+	let (_, compressed): (&[u8], Vec<u8>) = encode_with_config(data, Vec::new(), config)?;
+	Ok((compressed.len(), start.elapsed()))
+}
@@ -0,0 +1,303 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::deadline::open_stored_tail;
+use srx::{
+	check_profile, decode_blocks, decode_with_config, decode_with_config_profile, read_header,
+	AnyError, AnyResult, Header, Profile, VolumeReader,
+};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, Write};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use std::thread::ScopedJoinHandle;
+
+// -----------------------------------------------
+
+// the chunks of the output on their way to the decoder of --verify, the compression waits for
+// it when they are all taken
+const TEE_CHUNKS: usize = 16;
+
+// -----------------------------------------------
+
+// Compares everything written to it against a reference, remembering the first offset where
+// they differ. Once they differ, the rest is only counted.
+pub struct VerifyWriter<R: Read> {
+	reference: R,
+	position: u64,
+	expected: Vec<u8>,
+	mismatch: Option<u64>,
+	// fail at the first mismatch instead of counting the rest, which may never end when the
+	// output is garbage decoded from a broken stream
+	stopping: bool,
+}
+
+impl<R: Read> VerifyWriter<R> {
+	pub fn new(reference: R) -> Self {
+		Self {
+			reference,
+			position: 0,
+			expected: Vec::new(),
+			mismatch: None,
+			stopping: false,
+		}
+	}
+
+	pub fn stopping(reference: R) -> Self {
+		Self {
+			stopping: true,
+			..Self::new(reference)
+		}
+	}
+
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	// the first differing offset, which is the end of the shorter one if one is a prefix of the other
+	pub fn finish(mut self) -> io::Result<Option<u64>> {
+		if self.mismatch.is_none() && self.reference.read(&mut [0; 1])? > 0 {
+			self.mismatch = Some(self.position);
+		}
+		Ok(self.mismatch)
+	}
+
+	fn compare(&mut self, buffer: &[u8]) -> io::Result<()> {
+		self.expected.resize(buffer.len(), 0);
+		let mut length: usize = 0;
+		while length < buffer.len() {
+			match self.reference.read(&mut self.expected[length..]) {
+				Ok(0) => break,
+				Ok(read) => length += read,
+				Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+				Err(error) => return Err(error),
+			}
+		}
+		self.mismatch = buffer
+			.iter()
+			.zip(&self.expected[..length])
+			.position(|(actual, expected)| actual != expected)
+			.or((length < buffer.len()).then_some(length))
+			.map(|offset| self.position + offset as u64);
+		Ok(())
+	}
+}
+
+impl<R: Read> Write for VerifyWriter<R> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		if self.mismatch.is_none() {
+			self.compare(buffer)?;
+		}
+		if let (true, Some(offset)) = (self.stopping, self.mismatch) {
+			return Err(io::Error::other(mismatch_message(offset)));
+		}
+		self.position += buffer.len() as u64;
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+// Decodes the archive without writing it anywhere, returning the decoded size and the first
+// offset where it differs from the reference file.
+pub fn verify(archive_path: &Path, reference_path: &Path) -> AnyResult<(u64, Option<u64>)> {
+	let mut reader: BufReader<File> = BufReader::new(File::open(archive_path)?);
+	let header: Header = read_header(&mut reader)?;
+	check_profile(header.profile_id, None)?;
+	// the payload of a split archive goes on in the next volumes, read from the start again,
+	// and the one of an archive with a stored tail stops where the tail starts
+	let mut tail: Box<dyn Read> = Box::new(io::empty());
+	let reader: Box<dyn Read + Send> = match header.volume_size {
+		None if header.stored_tail => {
+			let (payload_size, stored_tail): (u64, io::Take<File>) =
+				open_stored_tail(archive_path, reader.stream_position()?)?;
+			tail = Box::new(stored_tail);
+			Box::new(reader.take(payload_size))
+		}
+		None => Box::new(reader),
+		Some(_) if header.stored_tail => {
+			return Err(AnyError::from_string(
+				"A split file with a stored tail is not supported!",
+			));
+		}
+		Some(volume_size) => {
+			let mut volumes: BufReader<VolumeReader> =
+				BufReader::new(VolumeReader::open(archive_path, volume_size)?);
+			read_header(&mut volumes)?;
+			Box::new(volumes)
+		}
+	};
+	let verify_writer: VerifyWriter<BufReader<File>> =
+		VerifyWriter::new(BufReader::new(File::open(reference_path)?));
+	let (_, mut done_writer) = match &header.blocks {
+		Some(blocks) => decode_blocks(reader, verify_writer, blocks, &header.config, None)?,
+		None => decode_with_config(reader, verify_writer, &header.config)?,
+	};
+	io::copy(&mut tail, &mut done_writer)?;
+	let size: u64 = done_writer.position();
+	Ok((size, done_writer.finish()?))
+}
+
+// -----------------------------------------------
+
+// Writes to the writer and sends a copy of what it took to the decoder of --verify. Once the
+// decoder gave up, its answer is known and the copy stops.
+pub struct TeeWriter<W: Write> {
+	writer: W,
+	sender: Option<SyncSender<Vec<u8>>>,
+	// flips a bit of the copy at this offset, for testing that it is caught
+	#[cfg(test)]
+	corrupt_at: Option<u64>,
+	#[cfg(test)]
+	position: u64,
+}
+
+impl<W: Write> TeeWriter<W> {
+	fn new(writer: W, sender: SyncSender<Vec<u8>>) -> Self {
+		Self {
+			writer,
+			sender: Some(sender),
+			#[cfg(test)]
+			corrupt_at: None,
+			#[cfg(test)]
+			position: 0,
+		}
+	}
+
+	#[cfg(test)]
+	pub fn corrupt_at(&mut self, offset: u64) {
+		self.corrupt_at = Some(offset);
+	}
+
+	// the decoder sees the end of the output
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let length: usize = self.writer.write(buffer)?;
+		#[allow(unused_mut)]
+		let mut copy: Vec<u8> = buffer[..length].to_vec();
+		#[cfg(test)]
+		{
+			if let Some(offset) = self.corrupt_at {
+				if let Some(byte) = offset
+					.checked_sub(self.position)
+					.and_then(|index| copy.get_mut(index as usize))
+				{
+					*byte ^= 0x10;
+				}
+			}
+			self.position += length as u64;
+		}
+		if let Some(sender) = &self.sender {
+			if sender.send(copy).is_err() {
+				self.sender = None;
+			}
+		}
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+// the copy of the output as the decoder of --verify reads it, ending when the tee is gone
+struct ChannelReader {
+	receiver: Receiver<Vec<u8>>,
+	chunk: Vec<u8>,
+	position: usize,
+}
+
+impl Read for ChannelReader {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		while self.position == self.chunk.len() {
+			match self.receiver.recv() {
+				Ok(chunk) => (self.chunk, self.position) = (chunk, 0),
+				Err(_) => return Ok(0),
+			}
+		}
+		let length: usize = buffer.len().min(self.chunk.len() - self.position);
+		buffer[..length].copy_from_slice(&self.chunk[self.position..self.position + length]);
+		self.position += length;
+		Ok(length)
+	}
+}
+
+fn mismatch_message(offset: u64) -> String {
+	format!(
+		"Verification failed, the output differs from the input at offset {}!",
+		offset
+	)
+}
+
+// Runs the compression with its output decoded on a thread of its own as it is written, and
+// compared with the input read again, so that the verification takes the time of the slower
+// of the two instead of both. A failed compression is reported as it is, whatever the decoder
+// made of the part it saw.
+pub fn compress_verified<W: Write + Send>(
+	input_path: &Path,
+	warm_start: Option<&Profile>,
+	writer: W,
+	compress: impl FnOnce(TeeWriter<W>) -> AnyResult<(u64, TeeWriter<W>, u64)>,
+) -> AnyResult<(u64, W, u64)> {
+	let reference: BufReader<File> = BufReader::new(File::open(input_path)?);
+	let (sender, receiver): (SyncSender<Vec<u8>>, Receiver<Vec<u8>>) = sync_channel(TEE_CHUNKS);
+	thread::scope(|scope| {
+		let verifier: ScopedJoinHandle<AnyResult<()>> = scope.spawn(move || {
+			let mut reader: BufReader<ChannelReader> = BufReader::new(ChannelReader {
+				receiver,
+				chunk: Vec::new(),
+				position: 0,
+			});
+			let header: Header = read_header(&mut reader)?;
+			let verify_writer: VerifyWriter<BufReader<File>> = VerifyWriter::stopping(reference);
+			let (_, done_writer) = match &header.blocks {
+				// the blocks are only written once they are all compressed
+				Some(blocks) => {
+					decode_blocks(reader, verify_writer, blocks, &header.config, warm_start)?
+				}
+				None => {
+					decode_with_config_profile(reader, verify_writer, &header.config, warm_start)?
+				}
+			};
+			match done_writer.finish()? {
+				None => Ok(()),
+				Some(offset) => Err(AnyError::from_string(mismatch_message(offset))),
+			}
+		});
+		// the tee is dropped or taken apart either way, which ends the copy
+		let compressed: AnyResult<(u64, W, u64)> = compress(TeeWriter::new(writer, sender))
+			.map(|(input_size, tee, tail_size)| (input_size, tee.into_inner(), tail_size));
+		let verified: AnyResult<()> = verifier
+			.join()
+			.unwrap_or_else(|_| Err(AnyError::from_string("The verification panicked!")));
+		let compressed: (u64, W, u64) = compressed?;
+		verified?;
+		Ok(compressed)
+	})
+}
@@ -0,0 +1,138 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::metadata::Record;
+use srx::AnyResult;
+use std::path::Path;
+
+// -----------------------------------------------
+
+#[cfg(all(feature = "xattrs", target_os = "linux"))]
+mod platform {
+	use srx::AnyResult;
+	use std::ffi::CString;
+	use std::io;
+	use std::os::unix::ffi::OsStrExt;
+	use std::path::Path;
+
+	fn path_string(path: &Path) -> AnyResult<CString> {
+		Ok(CString::new(path.as_os_str().as_bytes())?)
+	}
+
+	// calls the size query/fill pair until the buffer is large enough
+	fn read_sized(mut call: impl FnMut(&mut [u8]) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+		loop {
+			let size: libc::ssize_t = call(&mut []);
+			if size < 0 {
+				return Err(io::Error::last_os_error());
+			}
+			let mut buffer: Vec<u8> = vec![0; size as usize];
+			let size: libc::ssize_t = call(&mut buffer);
+			if size >= 0 {
+				buffer.truncate(size as usize);
+				return Ok(buffer);
+			}
+			let error: io::Error = io::Error::last_os_error();
+			if error.raw_os_error() != Some(libc::ERANGE) {
+				return Err(error);
+			}
+			// the attribute grew in the meantime, try again
+		}
+	}
+
+	pub fn read(path: &Path) -> AnyResult<Vec<(Vec<u8>, Vec<u8>)>> {
+		let path: CString = path_string(path)?;
+		// SAFETY: the pointers and sizes come from live buffers, a zero size only queries
+		let names: Vec<u8> = read_sized(|buffer: &mut [u8]| unsafe {
+			libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len())
+		})?;
+		let mut attributes: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+		for name in names
+			.split(|&byte| byte == 0)
+			.filter(|name| !name.is_empty())
+		{
+			let name_string: CString = CString::new(name)?;
+			// SAFETY: same as above
+			let value: Vec<u8> = read_sized(|buffer: &mut [u8]| unsafe {
+				libc::getxattr(
+					path.as_ptr(),
+					name_string.as_ptr(),
+					buffer.as_mut_ptr().cast(),
+					buffer.len(),
+				)
+			})?;
+			attributes.push((name.to_vec(), value));
+		}
+		Ok(attributes)
+	}
+
+	pub fn write(path: &Path, attributes: &[(Vec<u8>, Vec<u8>)]) -> AnyResult<usize> {
+		let path: CString = path_string(path)?;
+		let mut skipped: usize = 0;
+		for (name, value) in attributes {
+			let name: CString = CString::new(name.as_slice())?;
+			// SAFETY: the pointers and sizes come from live buffers
+			let result: libc::c_int = unsafe {
+				libc::setxattr(
+					path.as_ptr(),
+					name.as_ptr(),
+					value.as_ptr().cast(),
+					value.len(),
+					0,
+				)
+			};
+			if result != 0 {
+				let error: io::Error = io::Error::last_os_error();
+				match error.raw_os_error() {
+					// not allowed here (e.g. security.* without privileges, or no support)
+					Some(libc::EPERM | libc::EACCES | libc::ENOTSUP) => skipped += 1,
+					_ => return Err(error.into()),
+				}
+			}
+		}
+		Ok(skipped)
+	}
+}
+
+#[cfg(not(all(feature = "xattrs", target_os = "linux")))]
+mod platform {
+	use srx::{AnyError, AnyResult};
+	use std::path::Path;
+
+	const UNSUPPORTED: &str = "Extended attributes are not supported by this build!";
+
+	pub fn read(_path: &Path) -> AnyResult<Vec<(Vec<u8>, Vec<u8>)>> {
+		Err(AnyError::from_string(UNSUPPORTED))
+	}
+
+	pub fn write(_path: &Path, _attributes: &[(Vec<u8>, Vec<u8>)]) -> AnyResult<usize> {
+		Err(AnyError::from_string(UNSUPPORTED))
+	}
+}
+
+// -----------------------------------------------
+
+// the extended attributes of the file, stored as records under their own names
+pub fn store_xattrs(path: &Path) -> AnyResult<Vec<Record>> {
+	platform::read(path)
+}
+
+// applies the stored attributes to the file, returns how many of them could not be set
+pub fn restore_xattrs(path: &Path, attributes: &[Record]) -> AnyResult<usize> {
+	platform::write(path, attributes)
+}
@@ -0,0 +1,197 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::decode_with_config_profile;
+use super::encoder::encode_with_config_profile;
+use super::memory::PREALLOCATED_SIZE_LIMIT;
+use super::profile::Profile;
+#[cfg(feature = "threads")]
+use super::shared::thread_join;
+use super::shared::BoundedWriter;
+use crate::basic::{write_fully, AnyError, AnyResult};
+use std::io::{Read, Write};
+#[cfg(feature = "threads")]
+use std::thread;
+#[cfg(feature = "threads")]
+use std::thread::ScopedJoinHandle;
+
+// -----------------------------------------------
+
+// every block has contexts of its own, and they are all in memory at once
+pub const MAX_BLOCKS: usize = 256;
+
+// -----------------------------------------------
+
+// The sizes of one of the blocks of a file compressed in blocks, see Header::blocks. The payloads
+// of the blocks follow the header one after the other, the offset of one being the sum of the
+// compressed sizes of the ones before it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Block {
+	pub original_size: u64,
+	pub compressed_size: u64,
+}
+
+// -----------------------------------------------
+
+fn encode_block(data: &[u8], config: &Config, warm_start: Option<&Profile>) -> AnyResult<Vec<u8>> {
+	let (_, payload, _): (&[u8], Vec<u8>, Option<Profile>) = encode_with_config_profile(
+		data,
+		Vec::with_capacity(data.len() / 2),
+		config,
+		warm_start,
+		None,
+	)?;
+	Ok(payload)
+}
+
+// Compresses the data in count blocks of the same size give or take a byte (fewer of them when
+// there are fewer bytes), each from fresh contexts or from the warm start, and all of them at
+// once on threads of their own. Every block starting over costs some ratio, the more the smaller
+// they are. Returns the index of the blocks and their payloads, in order.
+pub fn encode_blocks(
+	data: &[u8],
+	count: usize,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(Vec<Block>, Vec<Vec<u8>>)> {
+	if count == 0 || count > MAX_BLOCKS {
+		return Err(AnyError::from_string(format!(
+			"The number of blocks must be from 1 to {}!",
+			MAX_BLOCKS
+		)));
+	}
+	config.check()?;
+	// an empty input still gets a block, an empty payload being no srx stream
+	let count: usize = count.min(data.len()).max(1);
+	let (size, longer): (usize, usize) = (data.len() / count, data.len() % count);
+	let mut chunks: Vec<&[u8]> = Vec::with_capacity(count);
+	let mut rest: &[u8] = data;
+	for index in 0..count {
+		let (chunk, after): (&[u8], &[u8]) = rest.split_at(size + (index < longer) as usize);
+		chunks.push(chunk);
+		rest = after;
+	}
+	#[cfg(feature = "threads")]
+	let payloads: Vec<Vec<u8>> = thread::scope(|scope| {
+		let threads: Vec<ScopedJoinHandle<AnyResult<Vec<u8>>>> = chunks
+			.iter()
+			.map(|chunk| scope.spawn(move || encode_block(chunk, config, warm_start)))
+			.collect();
+		// every thread is joined before anything is reported, see root_cause
+		let payloads: Vec<AnyResult<Vec<u8>>> = threads.into_iter().map(thread_join).collect();
+		payloads.into_iter().collect::<AnyResult<Vec<Vec<u8>>>>()
+	})?;
+	#[cfg(not(feature = "threads"))]
+	let payloads: Vec<Vec<u8>> = chunks
+		.iter()
+		.map(|chunk| encode_block(chunk, config, warm_start))
+		.collect::<AnyResult<Vec<Vec<u8>>>>()?;
+	let blocks: Vec<Block> = chunks
+		.iter()
+		.zip(&payloads)
+		.map(|(chunk, payload)| Block {
+			original_size: chunk.len() as u64,
+			compressed_size: payload.len() as u64,
+		})
+		.collect();
+	Ok((blocks, payloads))
+}
+
+// -----------------------------------------------
+
+fn read_block<R: Read>(reader: &mut R, block: &Block) -> AnyResult<Vec<u8>> {
+	let mut payload: Vec<u8> = Vec::new();
+	reader
+		.take(block.compressed_size)
+		.read_to_end(&mut payload)?;
+	if payload.len() as u64 != block.compressed_size {
+		return Err(AnyError::truncated_input("Truncated SRX block!"));
+	}
+	Ok(payload)
+}
+
+fn decode_block(
+	payload: &[u8],
+	block: &Block,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<Vec<u8>> {
+	let limit: usize = usize::try_from(block.original_size).unwrap_or(usize::MAX);
+	let capacity: u64 = block.original_size.min(PREALLOCATED_SIZE_LIMIT);
+	let (_, writer): (&[u8], BoundedWriter) = decode_with_config_profile(
+		payload,
+		BoundedWriter::new(capacity as usize, limit),
+		config,
+		warm_start,
+	)?;
+	if writer.output.len() as u64 != block.original_size {
+		return Err(AnyError::from_string(format!(
+			"A block decoded to {} bytes, not the {} bytes of the index!",
+			writer.output.len(),
+			block.original_size
+		)));
+	}
+	Ok(writer.output)
+}
+
+// Decodes the blocks of the index, reading their payloads one after the other and decoding each
+// on threads of its own as soon as it is read, and writes their outputs in order. The output of
+// a block is held in memory until the ones before it are written. Returns the reader after the
+// last payload.
+pub fn decode_blocks<R: Read, W: Write>(
+	mut reader: R,
+	mut writer: W,
+	blocks: &[Block],
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, W)> {
+	#[cfg(feature = "threads")]
+	thread::scope(|scope| {
+		let mut threads: Vec<ScopedJoinHandle<AnyResult<Vec<u8>>>> = Vec::new();
+		let mut read: AnyResult<()> = Ok(());
+		for block in blocks {
+			match read_block(&mut reader, block) {
+				Ok(payload) => threads
+					.push(scope.spawn(move || decode_block(&payload, block, config, warm_start))),
+				Err(error) => {
+					read = Err(error);
+					break;
+				}
+			}
+		}
+		// every thread is joined before anything is reported, see root_cause
+		let mut written: AnyResult<()> = Ok(());
+		for thread in threads {
+			let output: AnyResult<Vec<u8>> = thread_join(thread);
+			if written.is_ok() {
+				written = output.and_then(|output| Ok(write_fully(&mut writer, &output)?));
+			}
+		}
+		written.and(read)
+	})?;
+	#[cfg(not(feature = "threads"))]
+	for block in blocks {
+		let payload: Vec<u8> = read_block(&mut reader, block)?;
+		write_fully(
+			&mut writer,
+			&decode_block(&payload, block, config, warm_start)?,
+		)?;
+	}
+	Ok((reader, writer))
+}
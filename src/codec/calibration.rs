@@ -0,0 +1,129 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::encoder::{Message, PackedMessage, PrimaryContextEncoder};
+use super::lanes::ManyLanes;
+use super::literal::literal_batch;
+use super::profile::Profile;
+use crate::basic::{AnyResult, Byte, Writer};
+use crate::bridged_context::{BridgedPrimaryContext, BridgedSecondaryContext};
+use crate::secondary_context::{prediction_to_f64, Bit, StateInfo};
+use std::io::{BufReader, Read};
+
+// -----------------------------------------------
+
+// the predictions are binned by their 4 high bits
+pub const CALIBRATION_BINS: usize = 16;
+
+// The bits given to the coder with a prediction within the bin, and how many of them were ones
+// against how many the predictions expected, the sum of their probabilities of a one.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct CalibrationBin {
+	pub bits: u64,
+	pub ones: u64,
+	pub expected: f64,
+}
+
+impl CalibrationBin {
+	// the mean probability of a one the bits were predicted with, and the frequency of the ones
+	// among them, both close for a well calibrated model
+	pub fn predicted(&self) -> f64 {
+		self.expected / self.bits.max(1) as f64
+	}
+
+	pub fn observed(&self) -> f64 {
+		self.ones as f64 / self.bits.max(1) as f64
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Calibration {
+	pub bins: [CalibrationBin; CALIBRATION_BINS],
+}
+
+impl Calibration {
+	pub fn bits(&self) -> u64 {
+		self.bins.iter().map(|bin| bin.bits).sum()
+	}
+
+	fn add(&mut self, prediction: u32, bit: Bit) {
+		let bin: &mut CalibrationBin = &mut self.bins[(prediction >> 28) as usize];
+		bin.bits += 1;
+		bin.ones += u64::from(bit);
+		bin.expected += prediction_to_f64(prediction);
+	}
+}
+
+// -----------------------------------------------
+
+// the secondary stage, tallying the predictions instead of coding the bits with them
+struct Calibrator {
+	contexts: Vec<BridgedSecondaryContext>,
+	calibration: Calibration,
+}
+
+impl Calibrator {
+	fn bit(&mut self, lane: usize, context_index: usize, bit: Bit) {
+		let context: &mut BridgedSecondaryContext = &mut self.contexts[lane];
+		let current_state: StateInfo = context.get_info(context_index);
+		context.update(current_state, context_index, bit);
+		self.calibration.add(current_state.prediction(), bit);
+	}
+
+	fn byte(&mut self, lane: usize, context_index: usize, byte: Byte) {
+		let (offsets, bits): ([usize; 8], [Bit; 8]) = literal_batch(byte);
+		for (offset, bit) in offsets.into_iter().zip(bits) {
+			self.bit(lane, context_index + offset, bit);
+		}
+	}
+}
+
+impl Writer<PackedMessage> for &mut Calibrator {
+	fn write(&mut self, message: PackedMessage) -> AnyResult<()> {
+		match message.get() {
+			Message::Bit(context_index, bit) => self.bit(message.lane(), context_index, bit),
+			Message::Byte(context_index, byte) => self.byte(message.lane(), context_index, byte),
+		}
+		Ok(())
+	}
+}
+
+// Unlike the outcomes, the predictions depend on the secondary contexts, so both stages are
+// replayed in a pass of their own, every bit the encoder would code being tallied, the end of
+// the stream included. The encoder itself does not count anything.
+pub fn calibrate<R: Read>(
+	reader: R,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<Calibration> {
+	config.check()?;
+	let mut primary: PrimaryContextEncoder<ManyLanes<BridgedPrimaryContext>> =
+		PrimaryContextEncoder::new(*config);
+	let mut calibrator: Calibrator = Calibrator {
+		contexts: (0..config.lanes)
+			.map(|_| config.secondary_context(warm_start))
+			.collect(),
+		calibration: Calibration::default(),
+	};
+	for current_byte in BufReader::new(reader).bytes() {
+		primary.byte(&mut &mut calibrator, current_byte?)?;
+	}
+	primary.end(&mut &mut calibrator)?;
+	Ok(calibrator.calibration)
+}
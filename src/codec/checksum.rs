@@ -0,0 +1,192 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "sha256")]
+use crate::basic::Sha256;
+use crate::basic::{log, AnyError, AnyResult, Crc32, XxHash64};
+use std::io;
+use std::io::Write;
+
+// -----------------------------------------------
+
+// The algorithms a checksum of the original data can be taken with, by their id in the
+// header. Every build reads all of them, but SHA-256 needs the sha256 feature to compute.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ChecksumAlgo {
+	// cheap, for bit rot
+	Crc32,
+	// fast and strong against accidents
+	XxHash64,
+	// for tamper evidence
+	Sha256,
+}
+
+impl ChecksumAlgo {
+	pub const ALL: [ChecksumAlgo; 3] = [Self::Crc32, Self::XxHash64, Self::Sha256];
+
+	pub fn id(&self) -> u8 {
+		match self {
+			Self::Crc32 => 1,
+			Self::XxHash64 => 2,
+			Self::Sha256 => 3,
+		}
+	}
+
+	pub fn from_id(id: u8) -> Option<Self> {
+		Self::ALL.into_iter().find(|algo| algo.id() == id)
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Crc32 => "crc32",
+			Self::XxHash64 => "xxhash",
+			Self::Sha256 => "sha256",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|algo| algo.name() == name)
+	}
+
+	pub fn digest_size(&self) -> usize {
+		match self {
+			Self::Crc32 => 4,
+			Self::XxHash64 => 8,
+			Self::Sha256 => 32,
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// the digest of the original data, checked by the decoder when the header has one
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Checksum {
+	pub algo: ChecksumAlgo,
+	pub digest: Vec<u8>,
+}
+
+impl Checksum {
+	pub fn of(algo: ChecksumAlgo, data: &[u8]) -> AnyResult<Self> {
+		let mut hasher: ChecksumHasher = ChecksumHasher::new(algo)?;
+		hasher.update(data);
+		Ok(hasher.finish())
+	}
+
+	pub fn check(&self, actual: &Checksum) -> AnyResult<()> {
+		if self == actual {
+			return Ok(());
+		}
+		let error: AnyError = AnyError::checksum_mismatch(format!(
+			"The {} checksum of the output does not match the one recorded!",
+			self.algo.name()
+		));
+		log!(Warn, "{}", error);
+		Err(error)
+	}
+}
+
+// -----------------------------------------------
+
+#[derive(Clone, Debug)]
+pub enum ChecksumHasher {
+	Crc32(Crc32),
+	XxHash64(XxHash64),
+	#[cfg(feature = "sha256")]
+	Sha256(Sha256),
+}
+
+impl ChecksumHasher {
+	pub fn new(algo: ChecksumAlgo) -> AnyResult<Self> {
+		match algo {
+			ChecksumAlgo::Crc32 => Ok(Self::Crc32(Crc32::new())),
+			ChecksumAlgo::XxHash64 => Ok(Self::XxHash64(XxHash64::new())),
+			#[cfg(feature = "sha256")]
+			ChecksumAlgo::Sha256 => Ok(Self::Sha256(Sha256::new())),
+			#[cfg(not(feature = "sha256"))]
+			ChecksumAlgo::Sha256 => Err(AnyError::from_string(
+				"SHA-256 is not supported by this build!",
+			)),
+		}
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		match self {
+			Self::Crc32(hasher) => hasher.update(data),
+			Self::XxHash64(hasher) => hasher.update(data),
+			#[cfg(feature = "sha256")]
+			Self::Sha256(hasher) => hasher.update(data),
+		}
+	}
+
+	// the digests are big-endian, so that they print like the usual strings of each algorithm
+	pub fn finish(&self) -> Checksum {
+		match self {
+			Self::Crc32(hasher) => Checksum {
+				algo: ChecksumAlgo::Crc32,
+				digest: hasher.value().to_be_bytes().to_vec(),
+			},
+			Self::XxHash64(hasher) => Checksum {
+				algo: ChecksumAlgo::XxHash64,
+				digest: hasher.value().to_be_bytes().to_vec(),
+			},
+			#[cfg(feature = "sha256")]
+			Self::Sha256(hasher) => Checksum {
+				algo: ChecksumAlgo::Sha256,
+				digest: hasher.value().to_vec(),
+			},
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// Hashes everything written through it with the given algorithm if any, for checking the
+// output of the decoder as it goes.
+pub struct ChecksumWriter<W: Write> {
+	writer: W,
+	hasher: Option<ChecksumHasher>,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+	pub fn new(writer: W, algo: Option<ChecksumAlgo>) -> AnyResult<Self> {
+		Ok(Self {
+			writer,
+			hasher: algo.map(ChecksumHasher::new).transpose()?,
+		})
+	}
+
+	pub fn into_inner(self) -> (W, Option<Checksum>) {
+		let checksum: Option<Checksum> = self.hasher.as_ref().map(ChecksumHasher::finish);
+		(self.writer, checksum)
+	}
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let length: usize = self.writer.write(buffer)?;
+		if let Some(hasher) = &mut self.hasher {
+			hasher.update(&buffer[..length]);
+		}
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
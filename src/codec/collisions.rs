@@ -0,0 +1,98 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use crate::basic::{AnyResult, Byte};
+use crate::bridged_context::BridgedPrimaryContext;
+use crate::primary_context::{HistoryState, PrimaryTable};
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+
+// -----------------------------------------------
+
+// one entry of the primary table in 2^COLLISION_SAMPLE_BITS is watched, which bounds the shadow
+// map to 256 Ki entries for the default table
+pub const COLLISION_SAMPLE_BITS: u32 = 6;
+
+// -----------------------------------------------
+
+// How often an entry of the primary table was last used by another context than the one
+// looking it up, and whose history it then predicts from. Only the sampled entries count.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Collisions {
+	// the lookups of a sampled entry that was used before
+	pub lookups: u64,
+	// the ones of them from another context than the previous one
+	pub collisions: u64,
+}
+
+impl Collisions {
+	pub fn rate(&self) -> f64 {
+		if self.lookups == 0 {
+			0.0
+		} else {
+			self.collisions as f64 / self.lookups as f64
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// The hash of the primary table only depends on the last ceil(bits / 5) bytes of its lane,
+// those bytes are the context the entry stands for. As with the context map, the primary
+// contexts are replayed alone in a pass of their own, so the bitstream is left alone.
+pub fn count_collisions<R: Read>(reader: R, config: &Config) -> AnyResult<Collisions> {
+	config.check()?;
+	let lanes: usize = config.lanes as usize;
+	let mut contexts: Vec<BridgedPrimaryContext> =
+		(0..lanes).map(|_| config.primary_context()).collect();
+	// per lane, the bytes the hash depends on, and per entry the last of them seen there
+	let mut recent_bytes: Vec<u64> = vec![0; lanes];
+	let mut shadows: Vec<HashMap<usize, u64>> = vec![HashMap::new(); lanes];
+	let mut sizes: Vec<usize> = contexts.iter().map(BridgedPrimaryContext::size).collect();
+	let mut collisions: Collisions = Collisions::default();
+	for (position, current_byte) in BufReader::new(reader).bytes().enumerate() {
+		let current_byte: u8 = current_byte?;
+		let lane: usize = position % lanes;
+		let context: &mut BridgedPrimaryContext = &mut contexts[lane];
+		// the entries of a grown table are other entries
+		if context.size() != sizes[lane] {
+			sizes[lane] = context.size();
+			shadows[lane].clear();
+		}
+		let hash_value: usize = context.hash_value();
+		if is_sampled(hash_value) {
+			let bytes: u32 = sizes[lane].trailing_zeros().div_ceil(5);
+			let recent: u64 =
+				recent_bytes[lane] & u64::MAX.checked_shr(64 - bytes * 8).unwrap_or(0);
+			if let Some(previous) = shadows[lane].insert(hash_value, recent) {
+				collisions.lookups += 1;
+				collisions.collisions += (previous != recent) as u64;
+			}
+		}
+		let state: HistoryState = context.get_history().get_state();
+		context.matching(state, Byte::from(current_byte));
+		recent_bytes[lane] = recent_bytes[lane] << 8 | u64::from(current_byte);
+	}
+	Ok(collisions)
+}
+
+// the low bits of the hash follow the last byte, so the entries are picked by a mix of all bits
+fn is_sampled(hash_value: usize) -> bool {
+	(hash_value as u64).wrapping_mul(0x9E3779B97F4A7C15) >> (64 - COLLISION_SAMPLE_BITS) == 0
+}
@@ -0,0 +1,325 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::profile::Profile;
+use super::shared::{IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE};
+use crate::basic::{AnyError, AnyResult};
+use crate::bridged_context::{
+	BridgedPrimaryContext, BridgedSecondaryContext, FixedBridgedPrimaryContext, Model,
+	PRIMARY_CONTEXT_SIZE,
+};
+use crate::primary_context::PrimaryTable;
+
+// -----------------------------------------------
+
+// every lane costs a primary and a secondary context, about 74 MiB, 106 MiB with the text model
+pub const MAX_LANES: usize = 16;
+
+// the standard literal context takes the low 14 bits of the primary hash, a growing table
+// must never be smaller than that, and is kept under 1 GiB per lane
+const MIN_GROWTH_BITS: u8 = 14;
+const MAX_GROWTH_BITS: u8 = 28;
+
+// -----------------------------------------------
+
+// The primary table starts with 1 << initial_bits entries for every lane and grows 4 times
+// larger (at most up to 1 << max_bits entries) each time the lane has seen as many bytes as
+// the table has entries. Small inputs use less memory, huge inputs get a larger table.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Growth {
+	pub initial_bits: u8,
+	pub max_bits: u8,
+}
+
+impl Default for Growth {
+	// from 256 KiB to 256 MiB per lane, reaching the size of the fixed table after 4 MiB
+	fn default() -> Self {
+		Self {
+			initial_bits: 16,
+			max_bits: 26,
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// The sizes of the buffers between the stages, from a menu since they are const generics of
+// the pipeline, see encode_with_config. They are not recorded in the header and do not
+// change the output, only the speed and the memory of the pipes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Buffers {
+	// for many small inputs at once, the smallest message buffer still within a few percent
+	// of the default speed
+	Small,
+	#[default]
+	Default,
+	// for huge inputs on fast storage
+	Large,
+}
+
+impl Buffers {
+	pub const ALL: &'static [Buffers] = &[Buffers::Small, Buffers::Default, Buffers::Large];
+
+	pub const fn io_buffer_size(&self) -> usize {
+		match self {
+			Buffers::Small => 0x10000,
+			Buffers::Default => IO_BUFFER_SIZE,
+			Buffers::Large => 0x1000000,
+		}
+	}
+
+	pub const fn message_buffer_size(&self) -> usize {
+		match self {
+			Buffers::Small => 0x4000,
+			Buffers::Default => MESSAGE_BUFFER_SIZE,
+			Buffers::Large => 0x100000,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Buffers::Small => "small",
+			Buffers::Default => "default",
+			Buffers::Large => "large",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL
+			.iter()
+			.copied()
+			.find(|buffers| buffers.name() == name)
+	}
+}
+
+// Runs the body with consts named by the given identifiers set to the sizes of the buffers,
+// for the const generics of the pipeline.
+macro_rules! with_buffers {
+	($buffers:expr, $io:ident, $body:expr) => {
+		with_buffers!($buffers, $io, _MESSAGE_BUFFER_SIZE, $body)
+	};
+	($buffers:expr, $io:ident, $message:ident, $body:expr) => {
+		match $buffers {
+			$crate::codec::Buffers::Small => {
+				const $io: usize = $crate::codec::Buffers::Small.io_buffer_size();
+				const $message: usize = $crate::codec::Buffers::Small.message_buffer_size();
+				$body
+			}
+			$crate::codec::Buffers::Default => {
+				const $io: usize = $crate::codec::Buffers::Default.io_buffer_size();
+				const $message: usize = $crate::codec::Buffers::Default.message_buffer_size();
+				$body
+			}
+			$crate::codec::Buffers::Large => {
+				const $io: usize = $crate::codec::Buffers::Large.io_buffer_size();
+				const $message: usize = $crate::codec::Buffers::Large.message_buffer_size();
+				$body
+			}
+		}
+	};
+}
+
+pub(crate) use with_buffers;
+
+// -----------------------------------------------
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Config {
+	pub model: Model,
+	// the input is split into this many interleaved streams (byte n goes to stream n % lanes)
+	// with their own contexts, which helps records of fixed width
+	pub lanes: u8,
+	// the primary table grows with the input instead of having a fixed size
+	pub growth: Option<Growth>,
+	// not part of the stream, a decoder may use any of them
+	pub buffers: Buffers,
+	// not part of the stream either, the decoder fails on any byte after the end of it
+	pub strict_end: bool,
+	// not part of the stream either, the stages run on the calling thread one after the other
+	// instead of on threads of their own, which is faster for an input too small to keep them
+	// busy and gives the same output; always so without the threads feature
+	pub threadless: bool,
+}
+
+impl Config {
+	pub fn new(model: Model) -> Self {
+		Self {
+			model,
+			lanes: 1,
+			growth: None,
+			buffers: Buffers::Default,
+			strict_end: false,
+			threadless: false,
+		}
+	}
+
+	pub fn with_lanes(self, lanes: u8) -> Self {
+		Self { lanes, ..self }
+	}
+
+	pub fn with_growth(self, growth: Option<Growth>) -> Self {
+		Self { growth, ..self }
+	}
+
+	pub fn with_buffers(self, buffers: Buffers) -> Self {
+		Self { buffers, ..self }
+	}
+
+	pub fn with_strict_end(self, strict_end: bool) -> Self {
+		Self { strict_end, ..self }
+	}
+
+	pub fn with_threadless(self, threadless: bool) -> Self {
+		Self { threadless, ..self }
+	}
+
+	// every configuration a stream can be encoded with, useful for comparing them
+	pub fn all() -> impl Iterator<Item = Config> {
+		Model::ALL.iter().map(|&model| Config::new(model))
+	}
+
+	pub fn name(&self) -> String {
+		let mut name: String = self.model.name().to_string();
+		if self.lanes != 1 {
+			name += &format!(" x{}", self.lanes);
+		}
+		if self.growth.is_some() {
+			name += " growing";
+		}
+		name
+	}
+
+	pub fn check(&self) -> AnyResult<()> {
+		if self.lanes == 0 || self.lanes as usize > MAX_LANES {
+			return Err(AnyError::from_string(format!(
+				"The number of lanes must be between 1 and {}!",
+				MAX_LANES
+			)));
+		}
+		if self.model.ways().is_some() && self.growth.is_some() {
+			return Err(AnyError::from_string(format!(
+				"The primary table of the {} model can not grow!",
+				self.model.name()
+			)));
+		}
+		if let Some(growth) = self.growth {
+			if growth.initial_bits < MIN_GROWTH_BITS
+				|| growth.initial_bits > growth.max_bits
+				|| growth.max_bits > MAX_GROWTH_BITS
+			{
+				return Err(AnyError::from_string(format!(
+					"The primary table must grow within 2^{} to 2^{} entries!",
+					MIN_GROWTH_BITS, MAX_GROWTH_BITS
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	// memory taken by the contexts of all the lanes, the same when encoding and decoding. A
+	// growing table holds both its last two sizes while it grows for the last time.
+	pub fn estimated_memory(&self) -> u64 {
+		let primary: usize = match self.growth {
+			None => BridgedPrimaryContext::memory_size(
+				PRIMARY_CONTEXT_SIZE,
+				self.model.ways().is_some(),
+			),
+			Some(growth) => {
+				let mut previous: usize = 0;
+				let mut size: usize = 1 << growth.initial_bits;
+				while size < 1 << growth.max_bits {
+					(previous, size) = (size, (size * 4).min(1 << growth.max_bits));
+				}
+				BridgedPrimaryContext::memory_size(size + previous, false)
+			}
+		};
+		let secondary: usize =
+			BridgedSecondaryContext::memory_size(self.model.secondary_context_size());
+		(primary + secondary) as u64 * self.lanes as u64
+	}
+
+	// the primary context of a lane, the encoder and the decoder both start every lane from here
+	pub(crate) fn primary_context(&self) -> BridgedPrimaryContext {
+		match self.growth {
+			None => match self.model.ways() {
+				Some(ways) => BridgedPrimaryContext::associative(PRIMARY_CONTEXT_SIZE, ways),
+				None => BridgedPrimaryContext::new(PRIMARY_CONTEXT_SIZE),
+			},
+			Some(growth) => {
+				BridgedPrimaryContext::growing(1 << growth.initial_bits, 1 << growth.max_bits)
+			}
+		}
+	}
+
+	// the secondary context of a lane, started from the profile if any, for both sides as well
+	pub(crate) fn secondary_context(
+		&self,
+		warm_start: Option<&Profile>,
+	) -> BridgedSecondaryContext {
+		let mut context: BridgedSecondaryContext =
+			BridgedSecondaryContext::new(self.model.secondary_context_size());
+		if let Some(profile) = warm_start {
+			profile.apply(&mut context);
+		}
+		context
+	}
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self::new(Model::default())
+	}
+}
+
+// -----------------------------------------------
+
+// The primary contexts the loops over the bytes are built for, see with_primary, each started
+// from the config the same way by the encoder and the decoder.
+pub(crate) trait ConfigPrimaryContext: PrimaryTable {
+	fn from_config(config: &Config) -> Self;
+}
+
+impl ConfigPrimaryContext for BridgedPrimaryContext {
+	fn from_config(config: &Config) -> Self {
+		config.primary_context()
+	}
+}
+
+impl ConfigPrimaryContext for FixedBridgedPrimaryContext {
+	fn from_config(config: &Config) -> Self {
+		debug_assert!(config.growth.is_none() && config.model.ways().is_none());
+		Self::new()
+	}
+}
+
+// Runs the body with the type named by the given identifier set to the primary context of the
+// config: the fixed table of the default config when the table neither grows nor has tags,
+// PrimaryContext otherwise.
+macro_rules! with_primary {
+	($config:expr, $primary:ident, $body:expr) => {
+		if $config.growth.is_none() && $config.model.ways().is_none() {
+			type $primary = $crate::bridged_context::FixedBridgedPrimaryContext;
+			$body
+		} else {
+			type $primary = $crate::bridged_context::BridgedPrimaryContext;
+			$body
+		}
+	};
+}
+
+pub(crate) use with_primary;
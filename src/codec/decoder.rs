@@ -16,30 +16,65 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use super::shared::{run_file_reader, run_file_writer, thread_join};
-use crate::basic::{pipe, AnyResult, Byte, Closable, PipedReader, PipedWriter, Writer};
-use crate::bridged_context::{BridgedContextInfo, BridgedPrimaryContext, BridgedSecondaryContext};
-use crate::primary_context::ByteMatched;
+use super::config::{with_buffers, with_primary, Config, ConfigPrimaryContext};
+use super::lanes::{with_lanes, Lanes};
+use super::profile::Profile;
+use super::shared::WrappedWriter;
+#[cfg(feature = "threads")]
+use super::shared::{root_cause, run_consumer, run_file_reader, thread_join};
+#[cfg(feature = "threads")]
+use super::stages::{run_stage, stage_clock, Stage, StageClock};
+use super::threadless::{decode_threadless, decode_until_threadless};
+use crate::basic::{
+	log, write_fully, AnyError, AnyResult, Byte, Closable, Consumer, Reader, Writer,
+};
+#[cfg(feature = "threads")]
+use crate::basic::{pipe, PipedReader, PipedWriter, ToConsumer};
+use crate::bridged_context::{BridgedContextInfo, BridgedSecondaryContext, Model};
+use crate::primary_context::{ByteMatched, PrimaryTable};
 use crate::secondary_context::{Bit, BitDecoder, StateInfo};
 use std::io::{Read, Write};
+#[cfg(feature = "threads")]
 use std::thread::{scope, ScopedJoinHandle};
 
 // -----------------------------------------------
 
-struct CombinedContextDecoder<const IO_BUFFER_SIZE: usize> {
-	primary_context: BridgedPrimaryContext,
-	secondary_context: BridgedSecondaryContext,
-	decoder: BitDecoder<IO_BUFFER_SIZE>,
-	writer: PipedWriter<u8, IO_BUFFER_SIZE>,
+// The primary and secondary contexts of the decoder, one byte at a time, for the pipelines and
+// for SrxDecoder alike.
+pub(super) struct CombinedContextDecoder<
+	R: Reader<u8>,
+	P: Lanes<Context: ConfigPrimaryContext>,
+	S: Lanes<Context = BridgedSecondaryContext>,
+> {
+	model: Model,
+	primary_contexts: P,
+	secondary_contexts: S,
+	decoder: BitDecoder<R>,
+	strict_end: bool,
 }
 
-impl<const IO_BUFFER_SIZE: usize> CombinedContextDecoder<IO_BUFFER_SIZE> {
+impl<
+		R: Reader<u8>,
+		P: Lanes<Context: ConfigPrimaryContext>,
+		S: Lanes<Context = BridgedSecondaryContext>,
+	> CombinedContextDecoder<R, P, S>
+{
+	pub(super) fn new(reader: R, config: Config, warm_start: Option<&Profile>) -> Self {
+		Self {
+			model: config.model,
+			primary_contexts: P::new(config.lanes, || P::Context::from_config(&config)),
+			secondary_contexts: S::new(config.lanes, || config.secondary_context(warm_start)),
+			decoder: BitDecoder::new(reader),
+			strict_end: config.strict_end,
+		}
+	}
+
 	#[inline(always)]
 	fn bit(&mut self, context_index: usize) -> AnyResult<Bit> {
-		let current_state: StateInfo = self.secondary_context.get_info(context_index);
+		let secondary_context: &mut BridgedSecondaryContext = self.secondary_contexts.current_mut();
+		let current_state: StateInfo = secondary_context.get_info(context_index);
 		let bit: Bit = self.decoder.bit(current_state.prediction())?;
-		self.secondary_context
-			.update(current_state, context_index, bit);
+		secondary_context.update(current_state, context_index, bit);
 		Ok(bit)
 	}
 
@@ -58,58 +93,93 @@ impl<const IO_BUFFER_SIZE: usize> CombinedContextDecoder<IO_BUFFER_SIZE> {
 		return Ok(Byte::from(((high - 16) << 4) | (low - 16)));
 	}
 
-	fn decode(mut self) -> AnyResult<()> {
-		loop {
-			let info: BridgedContextInfo = BridgedContextInfo::new(
-				self.primary_context.get_history(),
-				self.primary_context.previous_byte(),
-				self.primary_context.hash_value(),
-			);
-			let (next_byte, matched): (Byte, ByteMatched) = match self.bit(info.first_context())? {
-				// match first
-				Bit::Zero => (info.first_byte(), ByteMatched::FIRST),
-				// match next
-				Bit::One => match self.bit(info.second_context())? {
-					// literal
-					Bit::Zero => {
-						let next_byte: Byte = self.byte(info.literal_context())?;
-						if next_byte == info.first_byte() {
-							// eof, gave the reader/writer back
-							self.decoder.close()?;
-							self.writer.close()?;
-							return Ok(());
+	// the next byte of the output, none at the end of the stream, after which there is nothing
+	// more to decode
+	#[inline(always)]
+	pub(super) fn next_byte(&mut self) -> AnyResult<Option<Byte>> {
+		let primary_context: &P::Context = self.primary_contexts.current();
+		let info: BridgedContextInfo = BridgedContextInfo::new(
+			self.model,
+			primary_context.get_history(),
+			primary_context.previous_byte(),
+			primary_context.hash_value(),
+			primary_context.word_hash(),
+		);
+		let (next_byte, matched): (Byte, ByteMatched) = match self.bit(info.first_context())? {
+			// match first
+			Bit::Zero => (info.first_byte(), ByteMatched::FIRST),
+			// match next
+			Bit::One => match self.bit(info.second_context())? {
+				// literal
+				Bit::Zero => {
+					let next_byte: Byte = self.byte(info.literal_context())?;
+					if next_byte == info.first_byte() {
+						// eof
+						if self.strict_end {
+							self.decoder.check_end()?;
 						}
-						(next_byte, ByteMatched::NONE)
+						return Ok(None);
 					}
-					// match next
-					Bit::One => match self.bit(info.third_context())? {
-						// match second
-						Bit::Zero => (info.second_byte(), ByteMatched::SECOND),
-						// match third
-						Bit::One => (info.third_byte(), ByteMatched::THIRD),
-					},
+					(next_byte, ByteMatched::NONE)
+				}
+				// match next
+				Bit::One => match self.bit(info.third_context())? {
+					// match second
+					Bit::Zero => (info.second_byte(), ByteMatched::SECOND),
+					// match third
+					Bit::One => (info.third_byte(), ByteMatched::THIRD),
 				},
-			};
-			self.writer.write(next_byte.into())?;
-			self.primary_context
-				.matched(info.current_state(), next_byte, matched);
+			},
+		};
+		self.primary_contexts
+			.current_mut()
+			.matched(info.current_state(), next_byte, matched);
+		// byte n of the output comes from lane n % lanes, each lane has its own context
+		self.primary_contexts.next();
+		self.secondary_contexts.next();
+		Ok(Some(next_byte))
+	}
+
+	// returns what closing the reader and the writer gave back
+	fn decode<A, B, W: Writer<u8> + Closable<B>>(mut self, mut writer: W) -> AnyResult<(A, B)>
+	where
+		R: Closable<A>,
+	{
+		while let Some(next_byte) = self.next_byte()? {
+			writer.write(next_byte.into())?;
 		}
+		// eof, gave the reader/writer back
+		let returned_reader: A = self.decoder.close()?;
+		let returned_writer: B = writer.close()?;
+		Ok((returned_reader, returned_writer))
 	}
 }
 
 // -----------------------------------------------
 
-fn run_combined_context_decoder<const IO_BUFFER_SIZE: usize>(
-	reader: PipedReader<u8, IO_BUFFER_SIZE>,
-	writer: PipedWriter<u8, IO_BUFFER_SIZE>,
-) -> AnyResult<()> {
-	let decoder: CombinedContextDecoder<IO_BUFFER_SIZE> = CombinedContextDecoder {
-		primary_context: BridgedPrimaryContext::new(),
-		secondary_context: BridgedSecondaryContext::new(),
-		decoder: BitDecoder::new(reader),
-		writer,
-	};
-	decoder.decode()
+pub(super) fn run_combined_context_decoder<
+	A,
+	B,
+	R: Reader<u8> + Closable<A>,
+	W: Writer<u8> + Closable<B>,
+>(
+	reader: R,
+	writer: W,
+	config: Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(A, B)> {
+	with_lanes!(
+		config,
+		PerLane,
+		with_primary!(
+			config,
+			Primary,
+			CombinedContextDecoder::<R, PerLane<Primary>, PerLane<BridgedSecondaryContext>>::new(
+				reader, config, warm_start
+			)
+			.decode(writer)
+		)
+	)
 }
 
 // -----------------------------------------------
@@ -118,7 +188,205 @@ pub fn decode<R: Read + Send, W: Write + Send, const IO_BUFFER_SIZE: usize>(
 	reader: R,
 	writer: W,
 ) -> AnyResult<(R, W)> {
-	scope(|scope| {
+	decode_with::<R, W, IO_BUFFER_SIZE>(reader, writer, &Config::default())
+}
+
+pub fn decode_with<R: Read + Send, W: Write + Send, const IO_BUFFER_SIZE: usize>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	decode_with_profile::<R, W, IO_BUFFER_SIZE>(reader, writer, config, None)
+}
+
+// picks the buffer sizes of the pipeline from the config at runtime instead of the caller
+pub fn decode_with_config<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	decode_with_config_profile(reader, writer, config, None)
+}
+
+// decode_with_profile with the buffer sizes of the config
+pub fn decode_with_config_profile<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, W)> {
+	with_buffers!(
+		config.buffers,
+		IO,
+		decode_with_profile::<R, W, IO>(reader, writer, config, warm_start)
+	)
+}
+
+// the warm start must be the profile the data was encoded with
+pub fn decode_with_profile<R: Read + Send, W: Write + Send, const IO_BUFFER_SIZE: usize>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, W)> {
+	let (returned_reader, returned_writer): (R, WrappedWriter<W>) =
+		decode_to_consumer::<R, WrappedWriter<W>, IO_BUFFER_SIZE>(
+			reader,
+			WrappedWriter(writer),
+			config,
+			warm_start,
+		)?;
+	Ok((returned_reader, returned_writer.0))
+}
+
+// decode_to_consumer with the buffer sizes of the config
+pub fn decode_to_consumer_with_config<R: Read + Send, C: Consumer<u8> + Send>(
+	reader: R,
+	consumer: C,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, C)> {
+	with_buffers!(
+		config.buffers,
+		IO,
+		decode_to_consumer::<R, C, IO>(reader, consumer, config, warm_start)
+	)
+}
+
+// Hands the output to the consumer chunk by chunk instead of writing it, so that the consumer
+// decides where it goes, such as splitting it into files at record boundaries. An error of the
+// consumer stops the decoding and is returned as is.
+pub fn decode_to_consumer<R: Read + Send, C: Consumer<u8> + Send, const IO_BUFFER_SIZE: usize>(
+	reader: R,
+	consumer: C,
+	config: &Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, C)> {
+	config.check()?;
+	let config: Config = *config;
+	log!(Debug, "Decoding with the {} config", config.name());
+	#[cfg(not(feature = "threads"))]
+	return decode_threadless::<R, C, IO_BUFFER_SIZE>(reader, consumer, config, warm_start);
+	#[cfg(feature = "threads")]
+	if config.threadless {
+		return decode_threadless::<R, C, IO_BUFFER_SIZE>(reader, consumer, config, warm_start);
+	}
+	#[cfg(feature = "threads")]
+	let clock: Option<StageClock> = stage_clock();
+	#[cfg(feature = "threads")]
+	return scope(|scope| {
+		let (input_writer, input_reader): (
+			PipedWriter<u8, IO_BUFFER_SIZE>,
+			PipedReader<u8, IO_BUFFER_SIZE>,
+		) = pipe::<u8, IO_BUFFER_SIZE>();
+		let (output_writer, output_reader): (
+			PipedWriter<u8, IO_BUFFER_SIZE>,
+			PipedReader<u8, IO_BUFFER_SIZE>,
+		) = pipe::<u8, IO_BUFFER_SIZE>();
+		let file_reader: ScopedJoinHandle<AnyResult<R>> = scope.spawn(|| {
+			run_stage(&clock, Stage::Reader, || {
+				run_file_reader(reader, input_writer)
+			})
+		});
+		let combined_context_decoder: ScopedJoinHandle<AnyResult<((), ())>> = scope.spawn(|| {
+			run_stage(&clock, Stage::Decoder, || {
+				run_combined_context_decoder(input_reader, output_writer, config, warm_start)
+			})
+		});
+		let consumer: ScopedJoinHandle<AnyResult<C>> = scope.spawn(|| {
+			run_stage(&clock, Stage::Writer, || {
+				run_consumer(output_reader, consumer)
+			})
+		});
+		match (
+			thread_join(file_reader),
+			thread_join(combined_context_decoder),
+			thread_join(consumer),
+		) {
+			(Ok(returned_reader), Ok(((), ())), Ok(returned_consumer)) => {
+				log!(Debug, "Decoded to the end of the stream");
+				Ok((returned_reader, returned_consumer))
+			}
+			(reader, decoder, writer) => {
+				Err(root_cause([reader.err(), decoder.err(), writer.err()]).unwrap())
+			}
+		}
+	});
+}
+
+// -----------------------------------------------
+
+// the file writer of decode_until, asking the predicate about each chunk once it is written,
+// and refusing any chunk after the one it held for, which breaks the decoding without a pipe
+pub(super) struct UntilWriter<W: Write, F: FnMut(&[u8]) -> bool> {
+	pub(super) writer: W,
+	predicate: F,
+	pub(super) found: bool,
+}
+
+impl<W: Write, F: FnMut(&[u8]) -> bool> UntilWriter<W, F> {
+	pub(super) fn new(writer: W, predicate: F) -> Self {
+		Self {
+			writer,
+			predicate,
+			found: false,
+		}
+	}
+}
+
+impl<W: Write, F: FnMut(&[u8]) -> bool> Consumer<u8> for UntilWriter<W, F> {
+	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
+		if self.found {
+			return Err(AnyError::channel_closed());
+		}
+		write_fully(&mut self.writer, buffer)?;
+		self.found = (self.predicate)(buffer);
+		Ok(buffer.len())
+	}
+}
+
+#[cfg(feature = "threads")]
+fn run_until_writer<W: Write, F: FnMut(&[u8]) -> bool, const IO_BUFFER_SIZE: usize>(
+	mut reader: PipedReader<u8, IO_BUFFER_SIZE>,
+	mut writer: UntilWriter<W, F>,
+) -> AnyResult<(W, bool)> {
+	while !writer.found && reader.consume(&mut writer)? > 0 {}
+	// stopping early drops the output pipe, which breaks the pipes of the stages before it
+	reader.close()?;
+	Ok((writer.writer, writer.found))
+}
+
+// Decodes until the predicate returns true for a chunk of the output, then stops the pipeline
+// and returns the writer holding the output up to the end of that chunk, and whether that
+// happened before the end of the stream. The reader is dropped partway through when stopping
+// early, pass a &mut to keep it.
+pub fn decode_until<
+	R: Read + Send,
+	W: Write + Send,
+	F: FnMut(&[u8]) -> bool + Send,
+	const IO_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	predicate: F,
+) -> AnyResult<(W, bool)> {
+	config.check()?;
+	let config: Config = *config;
+	log!(
+		Debug,
+		"Decoding with the {} config until told to stop",
+		config.name()
+	);
+	let until_writer: UntilWriter<W, F> = UntilWriter::new(writer, predicate);
+	#[cfg(not(feature = "threads"))]
+	return decode_until_threadless::<R, W, F, IO_BUFFER_SIZE>(reader, until_writer, config);
+	#[cfg(feature = "threads")]
+	if config.threadless {
+		return decode_until_threadless::<R, W, F, IO_BUFFER_SIZE>(reader, until_writer, config);
+	}
+	#[cfg(feature = "threads")]
+	return scope(|scope| {
 		let (input_writer, input_reader): (
 			PipedWriter<u8, IO_BUFFER_SIZE>,
 			PipedReader<u8, IO_BUFFER_SIZE>,
@@ -129,13 +397,27 @@ pub fn decode<R: Read + Send, W: Write + Send, const IO_BUFFER_SIZE: usize>(
 		) = pipe::<u8, IO_BUFFER_SIZE>();
 		let file_reader: ScopedJoinHandle<AnyResult<R>> =
 			scope.spawn(|| run_file_reader(reader, input_writer));
-		let combined_context_decoder: ScopedJoinHandle<AnyResult<()>> =
-			scope.spawn(|| run_combined_context_decoder(input_reader, output_writer));
-		let file_writer: ScopedJoinHandle<AnyResult<W>> =
-			scope.spawn(|| run_file_writer(output_reader, writer));
-		let returned_reader: R = thread_join(file_reader)?;
-		thread_join(combined_context_decoder)?;
-		let returned_writer: W = thread_join(file_writer)?;
-		Ok((returned_reader, returned_writer))
-	})
+		let combined_context_decoder: ScopedJoinHandle<AnyResult<((), ())>> =
+			scope.spawn(|| run_combined_context_decoder(input_reader, output_writer, config, None));
+		let file_writer: ScopedJoinHandle<AnyResult<(W, bool)>> =
+			scope.spawn(|| run_until_writer(output_reader, until_writer));
+		match (
+			thread_join(file_reader),
+			thread_join(combined_context_decoder),
+			thread_join(file_writer),
+		) {
+			// the stages before the writer only saw their pipes breaking
+			(_, _, Ok((returned_writer, true))) => {
+				log!(Debug, "Decoding stopped before the end of the stream");
+				Ok((returned_writer, true))
+			}
+			(Ok(_), Ok(_), Ok((returned_writer, false))) => {
+				log!(Debug, "Decoded to the end of the stream");
+				Ok((returned_writer, false))
+			}
+			(reader, decoder, writer) => {
+				Err(root_cause([reader.err(), decoder.err(), writer.err()]).unwrap())
+			}
+		}
+	});
 }
@@ -16,24 +16,38 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use super::shared::{run_file_reader, run_file_writer, thread_join};
-use crate::basic::{pipe, AnyResult, Byte, Closable, PipedReader, PipedWriter, Reader, Writer};
-use crate::bridged_context::{BridgedContextInfo, BridgedPrimaryContext, BridgedSecondaryContext};
-use crate::primary_context::ByteMatched;
+use super::config::{with_buffers, with_primary, Config, ConfigPrimaryContext, MAX_LANES};
+use super::lanes::{with_lanes, Lanes};
+use super::literal::literal_batch;
+use super::profile::Profile;
+#[cfg(feature = "threads")]
+use super::shared::{root_cause, run_file_writer, run_producer, thread_join};
+use super::shared::{WrappedReader, MIN_MESSAGE_BUFFER_SIZE};
+#[cfg(feature = "threads")]
+use super::stages::{run_stage, stage_clock, Stage, StageClock};
+use super::threadless::encode_threadless;
+use crate::basic::{log, AnyResult, Byte, Closable, Producer, Reader, Writer};
+#[cfg(feature = "threads")]
+use crate::basic::{pipe, PipedReader, PipedWriter};
+use crate::bridged_context::{
+	BridgedContextInfo, BridgedSecondaryContext, Model, SECONDARY_CONTEXT_SIZE,
+};
+use crate::primary_context::{ByteMatched, PrimaryTable};
 use crate::secondary_context::{Bit, BitEncoder, StateInfo};
 use std::io::{Read, Write};
+#[cfg(feature = "threads")]
 use std::thread::{scope, ScopedJoinHandle};
 
 // -----------------------------------------------
 
 #[derive(Copy, Clone)]
-enum Message {
+pub(super) enum Message {
 	Bit(usize, Bit),
 	Byte(usize, Byte),
 }
 
 #[derive(Copy, Clone)]
-struct PackedMessage(u32);
+pub(super) struct PackedMessage(u32);
 
 impl Default for PackedMessage {
 	fn default() -> Self {
@@ -41,144 +55,293 @@ impl Default for PackedMessage {
 	}
 }
 
+// contexts take the low 26 bits, the lane is kept in the 4 bits above them
+const _CONTEXT_SIZE_CHECK: () = assert!(SECONDARY_CONTEXT_SIZE <= 1 << 26 && MAX_LANES <= 1 << 4);
+
 impl PackedMessage {
-	fn bit(context: usize, bit: Bit) -> Self {
-		Self(u32::from(bit) << 30 | context as u32)
+	fn bit(lane: usize, context: usize, bit: Bit) -> Self {
+		debug_assert!(context < SECONDARY_CONTEXT_SIZE && lane < MAX_LANES);
+		Self(u32::from(bit) << 30 | (lane as u32) << 26 | context as u32)
+	}
+
+	fn byte(lane: usize, context: usize, byte: Byte) -> Self {
+		debug_assert!(context < SECONDARY_CONTEXT_SIZE && lane < MAX_LANES);
+		Self(0x80000000 | (lane as u32) << 26 | context as u32 | u32::from(byte))
 	}
 
-	fn byte(context: usize, byte: Byte) -> Self {
-		Self(0x80000000 | context as u32 | u32::from(byte))
+	pub(super) fn lane(&self) -> usize {
+		(self.0 >> 26 & 0xF) as usize
 	}
 
-	fn get(&self) -> Message {
+	pub(super) fn get(&self) -> Message {
 		if self.0 < 0x80000000 {
-			Message::Bit((self.0 & 0x3FFFFFFF) as usize, Bit::from(self.0 >> 30))
+			// bit 31 is never set, so only 0 or 1 is left of the bit
+			Message::Bit((self.0 & 0x3FFFFFF) as usize, Bit::from(self.0 >> 30))
 		} else {
-			Message::Byte((self.0 & 0x7FFFFF00) as usize, Byte::from(self.0 & 0xFF))
+			Message::Byte((self.0 & 0x3FFFF00) as usize, Byte::from(self.0 & 0xFF))
 		}
 	}
 }
 
 // -----------------------------------------------
 
-fn run_primary_context_encoder<const IO_BUFFER_SIZE: usize, const MESSAGE_BUFFER_SIZE: usize>(
-	mut reader: PipedReader<u8, IO_BUFFER_SIZE>,
-	mut writer: PipedWriter<PackedMessage, MESSAGE_BUFFER_SIZE>,
-) -> AnyResult<()> {
-	let mut context: BridgedPrimaryContext = BridgedPrimaryContext::new();
-	loop {
-		let info: BridgedContextInfo = BridgedContextInfo::new(
+// The primary stage, one byte at a time, for the pipelines and for SrxWriter alike: byte n of
+// the input goes to lane n % lanes, each lane having its own context.
+pub(super) struct PrimaryContextEncoder<L: Lanes<Context: ConfigPrimaryContext>> {
+	model: Model,
+	contexts: L,
+}
+
+impl<L: Lanes<Context: ConfigPrimaryContext>> PrimaryContextEncoder<L> {
+	pub(super) fn new(config: Config) -> Self {
+		Self {
+			model: config.model,
+			contexts: L::new(config.lanes, || L::Context::from_config(&config)),
+		}
+	}
+
+	#[inline(always)]
+	fn info(&self) -> BridgedContextInfo {
+		let context: &L::Context = self.contexts.current();
+		BridgedContextInfo::new(
+			self.model,
 			context.get_history(),
 			context.previous_byte(),
 			context.hash_value(),
-		);
-		match reader.read()? {
-			None => {
-				writer.write(PackedMessage::bit(info.first_context(), Bit::One))?;
-				writer.write(PackedMessage::bit(info.second_context(), Bit::Zero))?;
+			context.word_hash(),
+		)
+	}
+
+	#[inline(always)]
+	pub(super) fn byte<W: Writer<PackedMessage>>(
+		&mut self,
+		writer: &mut W,
+		current_byte: u8,
+	) -> AnyResult<()> {
+		let lane: usize = self.contexts.lane();
+		let info: BridgedContextInfo = self.info();
+		match self
+			.contexts
+			.current_mut()
+			.matching(info.current_state(), Byte::from(current_byte))
+		{
+			ByteMatched::FIRST => {
+				writer.write(PackedMessage::bit(lane, info.first_context(), Bit::Zero))?;
+			}
+			ByteMatched::NONE => {
+				writer.write(PackedMessage::bit(lane, info.first_context(), Bit::One))?;
+				writer.write(PackedMessage::bit(lane, info.second_context(), Bit::Zero))?;
 				writer.write(PackedMessage::byte(
+					lane,
 					info.literal_context(),
-					info.first_byte(),
+					Byte::from(current_byte),
 				))?;
-				reader.close()?;
-				writer.close()?;
-				return Ok(());
 			}
-			Some(current_byte) => {
-				match context.matching(info.current_state(), Byte::from(current_byte)) {
-					ByteMatched::FIRST => {
-						writer.write(PackedMessage::bit(info.first_context(), Bit::Zero))?;
-					}
-					ByteMatched::NONE => {
-						writer.write(PackedMessage::bit(info.first_context(), Bit::One))?;
-						writer.write(PackedMessage::bit(info.second_context(), Bit::Zero))?;
-						writer.write(PackedMessage::byte(
-							info.literal_context(),
-							Byte::from(current_byte),
-						))?;
-					}
-					ByteMatched::SECOND => {
-						writer.write(PackedMessage::bit(info.first_context(), Bit::One))?;
-						writer.write(PackedMessage::bit(info.second_context(), Bit::One))?;
-						writer.write(PackedMessage::bit(info.third_context(), Bit::Zero))?;
-					}
-					ByteMatched::THIRD => {
-						writer.write(PackedMessage::bit(info.first_context(), Bit::One))?;
-						writer.write(PackedMessage::bit(info.second_context(), Bit::One))?;
-						writer.write(PackedMessage::bit(info.third_context(), Bit::One))?;
-					}
-				}
+			ByteMatched::SECOND => {
+				writer.write(PackedMessage::bit(lane, info.first_context(), Bit::One))?;
+				writer.write(PackedMessage::bit(lane, info.second_context(), Bit::One))?;
+				writer.write(PackedMessage::bit(lane, info.third_context(), Bit::Zero))?;
+			}
+			ByteMatched::THIRD => {
+				writer.write(PackedMessage::bit(lane, info.first_context(), Bit::One))?;
+				writer.write(PackedMessage::bit(lane, info.second_context(), Bit::One))?;
+				writer.write(PackedMessage::bit(lane, info.third_context(), Bit::One))?;
 			}
 		}
+		self.contexts.next();
+		Ok(())
+	}
+
+	// the end of the stream: a literal that is the first ranked byte, which no byte codes as
+	pub(super) fn end<W: Writer<PackedMessage>>(&mut self, writer: &mut W) -> AnyResult<()> {
+		let lane: usize = self.contexts.lane();
+		let info: BridgedContextInfo = self.info();
+		writer.write(PackedMessage::bit(lane, info.first_context(), Bit::One))?;
+		writer.write(PackedMessage::bit(lane, info.second_context(), Bit::Zero))?;
+		writer.write(PackedMessage::byte(
+			lane,
+			info.literal_context(),
+			info.first_byte(),
+		))
 	}
 }
 
+// returns what closing the reader gave back, the producer when there is no pipe in between
+pub(super) fn run_primary_context_encoder<
+	T,
+	R: Reader<u8> + Closable<T>,
+	W: Writer<PackedMessage> + Closable<()>,
+>(
+	reader: R,
+	writer: W,
+	config: Config,
+) -> AnyResult<T> {
+	with_lanes!(
+		config,
+		PerLane,
+		with_primary!(
+			config,
+			Primary,
+			encode_primary::<T, R, W, PerLane<Primary>>(reader, writer, config)
+		)
+	)
+}
+
+fn encode_primary<
+	T,
+	R: Reader<u8> + Closable<T>,
+	W: Writer<PackedMessage> + Closable<()>,
+	L: Lanes<Context: ConfigPrimaryContext>,
+>(
+	mut reader: R,
+	mut writer: W,
+	config: Config,
+) -> AnyResult<T> {
+	let mut encoder: PrimaryContextEncoder<L> = PrimaryContextEncoder::new(config);
+	while let Some(current_byte) = reader.read()? {
+		encoder.byte(&mut writer, current_byte)?;
+	}
+	encoder.end(&mut writer)?;
+	let returned: T = reader.close()?;
+	writer.close()?;
+	Ok(returned)
+}
+
 // -----------------------------------------------
 
-struct SecondaryContextEncoder<const IO_BUFFER_SIZE: usize, const MESSAGE_BUFFER_SIZE: usize> {
-	context: BridgedSecondaryContext,
-	reader: PipedReader<PackedMessage, MESSAGE_BUFFER_SIZE>,
-	encoder: BitEncoder<IO_BUFFER_SIZE>,
+pub(super) struct SecondaryContextEncoder<
+	W: Writer<u8>,
+	L: Lanes<Context = BridgedSecondaryContext>,
+> {
+	contexts: L,
+	encoder: BitEncoder<W>,
 }
 
-impl<const IO_BUFFER_SIZE: usize, const MESSAGE_BUFFER_SIZE: usize>
-	SecondaryContextEncoder<IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>
-{
+impl<W: Writer<u8>, L: Lanes<Context = BridgedSecondaryContext>> SecondaryContextEncoder<W, L> {
+	pub(super) fn new(writer: W, config: Config, warm_start: Option<&Profile>) -> Self {
+		Self {
+			contexts: L::new(config.lanes, || config.secondary_context(warm_start)),
+			encoder: BitEncoder::new(writer),
+		}
+	}
+
 	#[inline(always)]
 	fn bit(&mut self, context_index: usize, bit: Bit) -> AnyResult<()> {
-		let current_state: StateInfo = self.context.get_info(context_index);
-		self.context.update(current_state, context_index, bit);
+		let context: &mut BridgedSecondaryContext = self.contexts.current_mut();
+		let current_state: StateInfo = context.get_info(context_index);
+		context.update(current_state, context_index, bit);
 		self.encoder.bit(current_state.prediction(), bit)
 	}
 
 	fn byte(&mut self, context_index: usize, byte: Byte) -> AnyResult<()> {
-		// code high 4 bits in first 15 contexts
-		let high: usize = (usize::from(byte) >> 4) | 16;
-		self.bit(context_index + 1, Bit::from(high >> 3 & 1))?;
-		self.bit(context_index + (high >> 3), Bit::from(high >> 2 & 1))?;
-		self.bit(context_index + (high >> 2), Bit::from(high >> 1 & 1))?;
-		self.bit(context_index + (high >> 1), Bit::from(high & 1))?;
-		// code low 4 bits in one of 16 blocks of 15 contexts (to reduce cache misses)
-		let low_context: usize = context_index + 15 * (high - 15);
-		let low: usize = (usize::from(byte) & 15) | 16;
-		self.bit(low_context + 1, Bit::from(low >> 3 & 1))?;
-		self.bit(low_context + (low >> 3), Bit::from(low >> 2 & 1))?;
-		self.bit(low_context + (low >> 2), Bit::from(low >> 1 & 1))?;
-		self.bit(low_context + (low >> 1), Bit::from(low & 1))?;
+		// the context offsets and bits are precomputed for each byte, the 8 entries are all
+		// different so they are fetched and updated at once before coding the bits
+		let (offsets, bits): ([usize; 8], [Bit; 8]) = literal_batch(byte);
+		let context: &mut BridgedSecondaryContext = self.contexts.current_mut();
+		let current_states: [StateInfo; 8] = context.get_info_batch(context_index, &offsets);
+		context.update_batch(context_index, &offsets, &current_states, &bits);
+		for (current_state, bit) in current_states.into_iter().zip(bits) {
+			self.encoder.bit(current_state.prediction(), bit)?;
+		}
 		// oke
 		return Ok(());
 	}
 
-	fn encode(mut self) -> AnyResult<()> {
-		loop {
-			match self.reader.read()? {
-				None => {
-					self.reader.close()?;
-					self.encoder.close()?;
-					return Ok(());
-				}
-				Some(message) => match message.get() {
-					Message::Bit(context_index, bit) => self.bit(context_index, bit)?,
-					Message::Byte(context_index, value) => self.byte(context_index, value)?,
-				},
-			}
+	#[inline(always)]
+	fn message(&mut self, message: PackedMessage) -> AnyResult<()> {
+		// a single lane has nothing to pick, the lane of the message is not even looked at
+		self.contexts.select(message.lane());
+		match message.get() {
+			Message::Bit(context_index, bit) => self.bit(context_index, bit),
+			Message::Byte(context_index, value) => self.byte(context_index, value),
 		}
 	}
+
+	pub(super) fn encoder(&self) -> &BitEncoder<W> {
+		&self.encoder
+	}
+
+	pub(super) fn encoder_mut(&mut self) -> &mut BitEncoder<W> {
+		&mut self.encoder
+	}
+
+	// returns what closing the writer gave back, the length of the payload, and the profile of
+	// the first lane if asked for
+	pub(super) fn finish<T>(
+		self,
+		profile_entries: Option<usize>,
+	) -> AnyResult<(T, u64, Option<Profile>)>
+	where
+		W: Closable<T>,
+	{
+		let length: u64 = self.encoder.bytes_written() + self.encoder.tail_size();
+		let profile: Option<Profile> = profile_entries
+			.map(|max_entries| Profile::from_context(self.contexts.first(), max_entries));
+		let returned: T = self.encoder.close()?;
+		Ok((returned, length, profile))
+	}
+}
+
+// without a pipe, the primary stage writes its messages straight into the secondary one
+impl<W: Writer<u8>, L: Lanes<Context = BridgedSecondaryContext>> Writer<PackedMessage>
+	for &mut SecondaryContextEncoder<W, L>
+{
+	fn write(&mut self, message: PackedMessage) -> AnyResult<()> {
+		self.message(message)
+	}
+}
+
+impl<W: Writer<u8>, L: Lanes<Context = BridgedSecondaryContext>> Closable<()>
+	for &mut SecondaryContextEncoder<W, L>
+{
+	fn close(self) -> AnyResult<()> {
+		Ok(())
+	}
 }
 
 // -----------------------------------------------
 
+#[cfg(feature = "threads")]
 fn run_secondary_context_encoder<const IO_BUFFER_SIZE: usize, const MESSAGE_BUFFER_SIZE: usize>(
 	reader: PipedReader<PackedMessage, MESSAGE_BUFFER_SIZE>,
 	writer: PipedWriter<u8, IO_BUFFER_SIZE>,
-) -> AnyResult<()> {
-	let encoder: SecondaryContextEncoder<IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE> =
-		SecondaryContextEncoder {
-			context: BridgedSecondaryContext::new(),
+	config: Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(u64, Option<Profile>)> {
+	with_lanes!(
+		config,
+		PerLane,
+		encode_secondary::<IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE, PerLane<BridgedSecondaryContext>>(
 			reader,
-			encoder: BitEncoder::new(writer),
-		};
-	encoder.encode()
+			writer,
+			config,
+			warm_start,
+			profile_entries
+		)
+	)
+}
+
+#[cfg(feature = "threads")]
+fn encode_secondary<
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+	L: Lanes<Context = BridgedSecondaryContext>,
+>(
+	mut reader: PipedReader<PackedMessage, MESSAGE_BUFFER_SIZE>,
+	writer: PipedWriter<u8, IO_BUFFER_SIZE>,
+	config: Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(u64, Option<Profile>)> {
+	let mut encoder: SecondaryContextEncoder<PipedWriter<u8, IO_BUFFER_SIZE>, L> =
+		SecondaryContextEncoder::new(writer, config, warm_start);
+	while let Some(message) = reader.read()? {
+		encoder.message(message)?;
+	}
+	reader.close()?;
+	let ((), length, profile): ((), u64, Option<Profile>) = encoder.finish(profile_entries)?;
+	Ok((length, profile))
 }
 
 // -----------------------------------------------
@@ -192,7 +355,149 @@ pub fn encode<
 	reader: R,
 	writer: W,
 ) -> AnyResult<(R, W)> {
-	scope(|scope| {
+	encode_with::<R, W, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(reader, writer, &Config::default())
+}
+
+pub fn encode_with<
+	R: Read + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	let (wrapped_reader, returned_writer): (WrappedReader<R>, W) =
+		encode_producer::<WrappedReader<R>, W, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			WrappedReader(reader),
+			writer,
+			config,
+		)?;
+	Ok((wrapped_reader.0, returned_writer))
+}
+
+// starts every lane from the warm start profile if given, and takes a profile of at most
+// profile_entries contexts at the end if asked for; the same warm start is needed to decode
+pub fn encode_with_profile<
+	R: Read + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(R, W, Option<Profile>)> {
+	let (wrapped_reader, returned_writer, profile): (WrappedReader<R>, W, Option<Profile>) =
+		encode_profiled::<WrappedReader<R>, W, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			WrappedReader(reader),
+			writer,
+			config,
+			warm_start,
+			profile_entries,
+		)?;
+	Ok((wrapped_reader.0, returned_writer, profile))
+}
+
+// picks the buffer sizes of the pipeline from the config at runtime instead of the caller
+pub fn encode_with_config<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	with_buffers!(
+		config.buffers,
+		IO,
+		MESSAGE,
+		encode_with::<R, W, IO, MESSAGE>(reader, writer, config)
+	)
+}
+
+// encode_with_profile with the buffer sizes of the config
+pub fn encode_with_config_profile<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(R, W, Option<Profile>)> {
+	with_buffers!(
+		config.buffers,
+		IO,
+		MESSAGE,
+		encode_with_profile::<R, W, IO, MESSAGE>(
+			reader,
+			writer,
+			config,
+			warm_start,
+			profile_entries
+		)
+	)
+}
+
+pub fn encode_producer<
+	P: Producer<u8> + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	producer: P,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(P, W)> {
+	let (returned_producer, returned_writer, _): (P, W, Option<Profile>) =
+		encode_profiled::<P, W, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			producer, writer, config, None, None,
+		)?;
+	Ok((returned_producer, returned_writer))
+}
+
+fn encode_profiled<
+	P: Producer<u8> + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	producer: P,
+	writer: W,
+	config: &Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(P, W, Option<Profile>)> {
+	const {
+		assert!(
+			MESSAGE_BUFFER_SIZE >= MIN_MESSAGE_BUFFER_SIZE,
+			"The message buffer can not hold the messages of a byte!"
+		)
+	};
+	config.check()?;
+	let config: Config = *config;
+	log!(Debug, "Encoding with the {} config", config.name());
+	#[cfg(not(feature = "threads"))]
+	return encode_threadless::<P, W, IO_BUFFER_SIZE>(
+		producer,
+		writer,
+		config,
+		warm_start,
+		profile_entries,
+	);
+	#[cfg(feature = "threads")]
+	if config.threadless {
+		return encode_threadless::<P, W, IO_BUFFER_SIZE>(
+			producer,
+			writer,
+			config,
+			warm_start,
+			profile_entries,
+		);
+	}
+	#[cfg(feature = "threads")]
+	let clock: Option<StageClock> = stage_clock();
+	#[cfg(feature = "threads")]
+	return scope(|scope| {
 		let (input_writer, input_reader): (
 			PipedWriter<u8, IO_BUFFER_SIZE>,
 			PipedReader<u8, IO_BUFFER_SIZE>,
@@ -205,18 +510,49 @@ pub fn encode<
 			PipedWriter<u8, IO_BUFFER_SIZE>,
 			PipedReader<u8, IO_BUFFER_SIZE>,
 		) = pipe::<u8, IO_BUFFER_SIZE>();
-		let file_reader: ScopedJoinHandle<AnyResult<R>> =
-			scope.spawn(|| run_file_reader(reader, input_writer));
-		let primary_context_encoder: ScopedJoinHandle<AnyResult<()>> =
-			scope.spawn(|| run_primary_context_encoder(input_reader, message_writer));
-		let secondary_context_encoder: ScopedJoinHandle<AnyResult<()>> =
-			scope.spawn(|| run_secondary_context_encoder(message_reader, output_writer));
-		let file_writer: ScopedJoinHandle<AnyResult<W>> =
-			scope.spawn(|| run_file_writer(output_reader, writer));
-		let returned_reader: R = thread_join(file_reader)?;
-		thread_join(primary_context_encoder)?;
-		thread_join(secondary_context_encoder)?;
-		let returned_writer: W = thread_join(file_writer)?;
-		Ok((returned_reader, returned_writer))
-	})
+		let producer_thread: ScopedJoinHandle<AnyResult<P>> = scope.spawn(|| {
+			run_stage(&clock, Stage::Reader, || {
+				run_producer(producer, input_writer)
+			})
+		});
+		let primary_context_encoder: ScopedJoinHandle<AnyResult<()>> = scope.spawn(|| {
+			run_stage(&clock, Stage::PrimaryEncoder, || {
+				run_primary_context_encoder(input_reader, message_writer, config)
+			})
+		});
+		let secondary_context_encoder: ScopedJoinHandle<AnyResult<(u64, Option<Profile>)>> = scope
+			.spawn(|| {
+				run_stage(&clock, Stage::SecondaryEncoder, || {
+					run_secondary_context_encoder(
+						message_reader,
+						output_writer,
+						config,
+						warm_start,
+						profile_entries,
+					)
+				})
+			});
+		let file_writer: ScopedJoinHandle<AnyResult<W>> = scope.spawn(|| {
+			run_stage(&clock, Stage::Writer, || {
+				run_file_writer(output_reader, writer)
+			})
+		});
+		match (
+			thread_join(producer_thread),
+			thread_join(primary_context_encoder),
+			thread_join(secondary_context_encoder),
+			thread_join(file_writer),
+		) {
+			(Ok(returned_producer), Ok(()), Ok((payload_length, profile)), Ok(returned_writer)) => {
+				log!(Debug, "Encoded into {} bytes", payload_length);
+				Ok((returned_producer, returned_writer, profile))
+			}
+			(producer, primary, secondary, writer) => {
+				Err(
+					root_cause([producer.err(), primary.err(), secondary.err(), writer.err()])
+						.unwrap(),
+				)
+			}
+		}
+	});
 }
@@ -0,0 +1,128 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::decode_to_consumer_with_config;
+use super::encoder::encode_producer;
+use crate::basic::{read_retrying, write_fully, AnyError, AnyResult, Consumer, Producer};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+// -----------------------------------------------
+
+// Inflates a gzip stream of any number of members as a producer, for feeding it to the encoder
+// without going through a file. flate2 checks every member against its CRC-32 and size.
+pub struct GzipProducer<R: Read> {
+	decoder: MultiGzDecoder<R>,
+	inflated_size: u64,
+}
+
+impl<R: Read> GzipProducer<R> {
+	pub fn new(reader: R) -> Self {
+		Self {
+			decoder: MultiGzDecoder::new(reader),
+			inflated_size: 0,
+		}
+	}
+
+	pub fn inflated_size(&self) -> u64 {
+		self.inflated_size
+	}
+
+	pub fn into_inner(self) -> R {
+		self.decoder.into_inner()
+	}
+}
+
+impl<R: Read> Producer<u8> for GzipProducer<R> {
+	fn produce(&mut self, buffer: &mut [u8]) -> AnyResult<usize> {
+		let length: usize = read_retrying(&mut self.decoder, buffer)?;
+		// an input without a single member is not a gzip stream, not an empty one
+		if length == 0 && self.decoder.header().is_none() {
+			return Err(AnyError::from_string("Not a gzip stream!"));
+		}
+		self.inflated_size += length as u64;
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+// Deflates what it is given into a gzip stream of a single member, for the output of the
+// decoder going back to gzip without going through a file.
+pub struct GzipConsumer<W: Write> {
+	encoder: GzEncoder<W>,
+}
+
+impl<W: Write> GzipConsumer<W> {
+	pub fn new(writer: W) -> Self {
+		Self {
+			encoder: GzEncoder::new(writer, Compression::default()),
+		}
+	}
+
+	// writes the end of the member, returns the writer
+	pub fn finish(self) -> AnyResult<W> {
+		Ok(self.encoder.finish()?)
+	}
+}
+
+impl<W: Write> Consumer<u8> for GzipConsumer<W> {
+	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
+		write_fully(&mut self.encoder, buffer)?;
+		Ok(buffer.len())
+	}
+}
+
+// -----------------------------------------------
+
+// Compresses what a gzip stream inflates to, without a temporary file in between. Returns the
+// inflated size along with the reader and the writer.
+pub fn recompress_gzip<
+	R: Read + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W, u64)> {
+	let (producer, writer): (GzipProducer<R>, W) = encode_producer::<
+		_,
+		_,
+		IO_BUFFER_SIZE,
+		MESSAGE_BUFFER_SIZE,
+	>(GzipProducer::new(reader), writer, config)?;
+	let inflated_size: u64 = producer.inflated_size();
+	Ok((producer.into_inner(), writer, inflated_size))
+}
+
+// Decodes a payload of srx straight into a gzip stream, the header being already read by the
+// caller. Returns the reader and the writer.
+pub fn decompress_to_gzip<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	let (reader, consumer): (R, GzipConsumer<W>) =
+		decode_to_consumer_with_config(reader, GzipConsumer::new(writer), config, None)?;
+	Ok((reader, consumer.finish()?))
+}
@@ -0,0 +1,504 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::blocks::{Block, MAX_BLOCKS};
+use super::checksum::{Checksum, ChecksumAlgo};
+use super::config::{Config, Growth};
+use super::shared::SRX_HEADER;
+use crate::basic::{log, write_fully, AnyError, AnyResult};
+use crate::bridged_context::Model;
+use std::io;
+use std::io::{Read, Write};
+
+// -----------------------------------------------
+
+// The third byte of the magic is the version of the format, the first one being b'x'. Every bit
+// of the flags is taken, so a change that no flag describes gets the next version, which
+// decoders that do not know it refuse as such instead of as a foreign file.
+const FORMAT_VERSION: u8 = SRX_HEADER[2];
+
+// Version b'z' is a sequence of records after the magic, each of them a type, a u32
+// little-endian length and that many bytes of value, up to a record of type END_RECORD that has
+// neither. A decoder skips the records of a type it does not know, unless the type has the
+// REQUIRED_RECORD bit: then it can not decode the file without it and refuses it. A checksum
+// record of an algorithm it does not know is skipped as well, the output going unchecked. It is only
+// written when the header has a section that version b'x' can not hold, so the other files
+// stay readable by the decoders that only know version b'x'.
+//
+// The index of a file compressed in blocks is such a section, the original and the compressed
+// size of every block as two u64 little-endian, in the order of their payloads.
+const RECORD_VERSION: u8 = b'z';
+const END_RECORD: u8 = 0x00;
+const REQUIRED_RECORD: u8 = 0x80;
+const BLOCK_ENTRY_SIZE: usize = 16;
+
+// The last byte of the header holds flags, each of them adding a section after the header,
+// in this order:
+// - the config, as the model id and the number of lanes, when it is not the default one;
+// - the growth of the primary table, as its initial and maximum number of bits, when it grows;
+// - the size of the original data as a u64 little-endian, when it was known;
+// - a u8 length and that many bytes of a hash of the original data, opaque to the codec;
+// - the id of the profile the contexts started from as a u32 little-endian, when there was one;
+// - the id of a checksum algorithm and the digest of the original data, checked by decoders;
+// - the size of the volumes the file was split into as a u64 little-endian, when it was;
+// - a u32 little-endian length and that many bytes of metadata, opaque to the codec.
+const CONFIG_FLAG: u8 = 0x02;
+const GROWTH_FLAG: u8 = 0x04;
+const ORIGINAL_SIZE_FLAG: u8 = 0x08;
+const CONTENT_HASH_FLAG: u8 = 0x10;
+const PROFILE_FLAG: u8 = 0x20;
+const CHECKSUM_FLAG: u8 = 0x40;
+const VOLUME_FLAG: u8 = 0x80;
+const METADATA_FLAG: u8 = 0x01;
+const ALL_FLAGS: u8 = CONFIG_FLAG
+	| GROWTH_FLAG
+	| ORIGINAL_SIZE_FLAG
+	| CONTENT_HASH_FLAG
+	| PROFILE_FLAG
+	| CHECKSUM_FLAG
+	| VOLUME_FLAG
+	| METADATA_FLAG;
+
+// every bit of the flags is a section, so no flags can be refused as unknown
+const _: () = assert!(ALL_FLAGS == u8::MAX);
+
+// -----------------------------------------------
+
+// The sections of a header, in the order of their flags in version b'x'. A record of version
+// b'z' holds the same bytes as the section of its type, the stored tail being an empty one.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Section {
+	Config,
+	Growth,
+	OriginalSize,
+	ContentHash,
+	Profile,
+	Checksum,
+	Volume,
+	Metadata,
+	StoredTail,
+	Blocks,
+}
+
+impl Section {
+	const ALL: [Section; 10] = [
+		Section::Config,
+		Section::Growth,
+		Section::OriginalSize,
+		Section::ContentHash,
+		Section::Profile,
+		Section::Checksum,
+		Section::Volume,
+		Section::Metadata,
+		Section::StoredTail,
+		Section::Blocks,
+	];
+
+	// None for a section that version b'x' can not hold
+	fn flag(&self) -> Option<u8> {
+		match self {
+			Section::Config => Some(CONFIG_FLAG),
+			Section::Growth => Some(GROWTH_FLAG),
+			Section::OriginalSize => Some(ORIGINAL_SIZE_FLAG),
+			Section::ContentHash => Some(CONTENT_HASH_FLAG),
+			Section::Profile => Some(PROFILE_FLAG),
+			Section::Checksum => Some(CHECKSUM_FLAG),
+			Section::Volume => Some(VOLUME_FLAG),
+			Section::Metadata => Some(METADATA_FLAG),
+			Section::StoredTail | Section::Blocks => None,
+		}
+	}
+
+	// the sections a decoder can not do without are required, the others only inform
+	fn record_type(&self) -> u8 {
+		match self {
+			Section::Config => REQUIRED_RECORD | 0x01,
+			Section::Growth => REQUIRED_RECORD | 0x02,
+			Section::OriginalSize => 0x03,
+			Section::ContentHash => 0x04,
+			Section::Profile => REQUIRED_RECORD | 0x05,
+			Section::Checksum => 0x06,
+			Section::Volume => REQUIRED_RECORD | 0x07,
+			Section::Metadata => 0x08,
+			Section::StoredTail => REQUIRED_RECORD | 0x09,
+			Section::Blocks => REQUIRED_RECORD | 0x0A,
+		}
+	}
+
+	fn from_record_type(record_type: u8) -> Option<Self> {
+		Self::ALL
+			.into_iter()
+			.find(|section| section.record_type() == record_type)
+	}
+
+	// the bytes of the section, None when the header has no such section
+	fn write(&self, header: &Header) -> AnyResult<Option<Vec<u8>>> {
+		let config: &Config = &header.config;
+		Ok(match self {
+			Section::Config => {
+				// the fields of the stream alone, the buffers and the like are up to the decoder
+				let default: Config = Config::default();
+				((config.model, config.lanes) != (default.model, default.lanes))
+					.then(|| vec![config.model.id(), config.lanes])
+			}
+			Section::Growth => config
+				.growth
+				.map(|growth| vec![growth.initial_bits, growth.max_bits]),
+			Section::OriginalSize => header.original_size.map(|size| size.to_le_bytes().to_vec()),
+			Section::ContentHash => match &header.content_hash {
+				Some(content_hash) => {
+					if content_hash.is_empty() || content_hash.len() > u8::MAX as usize {
+						return Err(AnyError::from_string(
+							"Content hash must be 1 to 255 bytes!",
+						));
+					}
+					Some([&[content_hash.len() as u8], content_hash.as_slice()].concat())
+				}
+				None => None,
+			},
+			Section::Profile => header.profile_id.map(|id| id.to_le_bytes().to_vec()),
+			Section::Checksum => match &header.checksum {
+				Some(checksum) => {
+					if checksum.digest.len() != checksum.algo.digest_size() {
+						return Err(AnyError::from_string(format!(
+							"A {} checksum must be {} bytes!",
+							checksum.algo.name(),
+							checksum.algo.digest_size()
+						)));
+					}
+					Some([&[checksum.algo.id()], checksum.digest.as_slice()].concat())
+				}
+				None => None,
+			},
+			Section::Volume => match header.volume_size {
+				Some(0) => return Err(AnyError::from_string("The volume size must not be zero!")),
+				volume_size => volume_size.map(|size| size.to_le_bytes().to_vec()),
+			},
+			Section::Metadata => match &header.metadata {
+				Some(metadata) => {
+					let length: u32 = u32::try_from(metadata.len())
+						.map_err(|_| AnyError::from_string("Metadata is too large!"))?;
+					Some([&length.to_le_bytes(), metadata.as_slice()].concat())
+				}
+				None => None,
+			},
+			Section::StoredTail => header.stored_tail.then(Vec::new),
+			Section::Blocks => match &header.blocks {
+				Some(blocks) => {
+					if blocks.is_empty() || blocks.len() > MAX_BLOCKS {
+						return Err(AnyError::from_string(format!(
+							"The number of blocks must be from 1 to {}!",
+							MAX_BLOCKS
+						)));
+					}
+					Some(
+						blocks
+							.iter()
+							.flat_map(|block| {
+								[block.original_size, block.compressed_size].map(u64::to_le_bytes)
+							})
+							.flatten()
+							.collect(),
+					)
+				}
+				None => None,
+			},
+		})
+	}
+
+	// reads the section into the header, the config being checked once they are all read
+	fn read<R: Read>(&self, reader: &mut R, header: &mut Header) -> AnyResult<()> {
+		match self {
+			Section::Config => {
+				let mut config: [u8; 2] = [0; 2];
+				reader.read_exact(&mut config)?;
+				let model: Model = Model::from_id(config[0])
+					.ok_or_else(|| AnyError::bad_header("Unknown SRX model!"))?;
+				header.config = Config::new(model)
+					.with_lanes(config[1])
+					.with_growth(header.config.growth);
+			}
+			Section::Growth => {
+				let mut growth: [u8; 2] = [0; 2];
+				reader.read_exact(&mut growth)?;
+				header.config = header.config.with_growth(Some(Growth {
+					initial_bits: growth[0],
+					max_bits: growth[1],
+				}));
+			}
+			Section::OriginalSize => {
+				let mut original_size: [u8; 8] = [0; 8];
+				reader.read_exact(&mut original_size)?;
+				header.original_size = Some(u64::from_le_bytes(original_size));
+			}
+			Section::ContentHash => {
+				let mut length: [u8; 1] = [0; 1];
+				reader.read_exact(&mut length)?;
+				let mut content_hash: Vec<u8> = vec![0; length[0] as usize];
+				reader.read_exact(&mut content_hash)?;
+				header.content_hash = Some(content_hash);
+			}
+			Section::Profile => {
+				let mut profile_id: [u8; 4] = [0; 4];
+				reader.read_exact(&mut profile_id)?;
+				header.profile_id = Some(u32::from_le_bytes(profile_id));
+			}
+			Section::Checksum => {
+				// without its algorithm, not even the size of the digest is known
+				if !read_checksum(reader, header)? {
+					return Err(AnyError::bad_header("Unknown SRX checksum algorithm!"));
+				}
+			}
+			Section::Volume => {
+				let mut volume_size: [u8; 8] = [0; 8];
+				reader.read_exact(&mut volume_size)?;
+				header.volume_size = match u64::from_le_bytes(volume_size) {
+					0 => return Err(AnyError::bad_header("Invalid SRX volume size!")),
+					volume_size => Some(volume_size),
+				};
+			}
+			Section::Metadata => {
+				let mut length: [u8; 4] = [0; 4];
+				reader.read_exact(&mut length)?;
+				let mut metadata: Vec<u8> = Vec::new();
+				reader
+					.take(u32::from_le_bytes(length) as u64)
+					.read_to_end(&mut metadata)?;
+				if metadata.len() != u32::from_le_bytes(length) as usize {
+					return Err(AnyError::truncated_input("Truncated SRX metadata!"));
+				}
+				header.metadata = Some(metadata);
+			}
+			Section::StoredTail => header.stored_tail = true,
+			Section::Blocks => {
+				// one more byte than the largest index, which is then refused as well
+				let mut index: Vec<u8> = Vec::new();
+				reader
+					.take((MAX_BLOCKS * BLOCK_ENTRY_SIZE + 1) as u64)
+					.read_to_end(&mut index)?;
+				if index.is_empty()
+					|| index.len() > MAX_BLOCKS * BLOCK_ENTRY_SIZE
+					|| !index.len().is_multiple_of(BLOCK_ENTRY_SIZE)
+				{
+					return Err(AnyError::bad_header("Invalid SRX block index!"));
+				}
+				let mut blocks: Vec<Block> = Vec::with_capacity(index.len() / BLOCK_ENTRY_SIZE);
+				for entry in index.chunks(BLOCK_ENTRY_SIZE) {
+					blocks.push(Block {
+						original_size: u64::from_le_bytes(entry[..8].try_into()?),
+						compressed_size: u64::from_le_bytes(entry[8..].try_into()?),
+					});
+				}
+				header.blocks = Some(blocks);
+			}
+		}
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Header {
+	pub config: Config,
+	// the decoder does not rely on it, its output is checked against it, see check_size
+	pub original_size: Option<u64>,
+	// computed by the caller, for comparing files without decompressing them, the decoder
+	// does not check it
+	pub content_hash: Option<Vec<u8>>,
+	// the decoder needs the same profile, see Profile::id
+	pub profile_id: Option<u32>,
+	pub checksum: Option<Checksum>,
+	// the file continues in volumes of this size, see VolumeReader
+	pub volume_size: Option<u64>,
+	pub metadata: Option<Vec<u8>>,
+	// the compression stopped early, the rest of the data follows the payload as it is
+	pub stored_tail: bool,
+	// the payload is in blocks compressed apart, to be decoded with decode_blocks
+	pub blocks: Option<Vec<Block>>,
+}
+
+impl Header {
+	pub fn write<W: Write>(&self, writer: &mut W) -> AnyResult<()> {
+		self.config.check()?;
+		let mut sections: Vec<(Section, Vec<u8>)> = Vec::new();
+		for section in Section::ALL {
+			if let Some(bytes) = section.write(self)? {
+				sections.push((section, bytes));
+			}
+		}
+		if sections.iter().all(|(section, _)| section.flag().is_some()) {
+			let flags: u8 = sections
+				.iter()
+				.filter_map(|(section, _)| section.flag())
+				.fold(SRX_HEADER[3], |flags, flag| flags | flag);
+			write_fully(writer, &SRX_HEADER[..3])?;
+			write_fully(writer, &[flags])?;
+			for (_, bytes) in &sections {
+				write_fully(writer, bytes)?;
+			}
+		} else {
+			write_fully(writer, &SRX_HEADER[..2])?;
+			write_fully(writer, &[RECORD_VERSION])?;
+			for (section, bytes) in &sections {
+				// the length of the metadata is within a u32 and the rest is small
+				let length: u32 = u32::try_from(bytes.len())
+					.map_err(|_| AnyError::from_string("Metadata is too large!"))?;
+				write_fully(writer, &[section.record_type()])?;
+				write_fully(writer, &length.to_le_bytes())?;
+				write_fully(writer, bytes)?;
+			}
+			write_fully(writer, &[END_RECORD])?;
+		}
+		Ok(())
+	}
+
+	// the input ending within the header is told apart from the other failures of the reader
+	pub fn read<R: Read>(reader: &mut R) -> AnyResult<Header> {
+		Self::read_sections(reader).map_err(|error| match error {
+			AnyError::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+				AnyError::truncated_input("Truncated SRX header!")
+			}
+			error => error,
+		})
+	}
+
+	fn read_sections<R: Read>(reader: &mut R) -> AnyResult<Header> {
+		let mut magic: [u8; 3] = [0; 3];
+		reader.read_exact(&mut magic)?;
+		if magic[..2] != SRX_HEADER[..2] {
+			return Err(AnyError::bad_header("Not a SRX compressed file!"));
+		}
+		let mut header: Header = Header::default();
+		match magic[2] {
+			FORMAT_VERSION => {
+				// every bit of the flags is a section, see ALL_FLAGS
+				let mut flags: [u8; 1] = [0; 1];
+				reader.read_exact(&mut flags)?;
+				for section in Section::ALL {
+					if section.flag().is_some_and(|flag| flags[0] & flag != 0) {
+						section.read(reader, &mut header)?;
+					}
+				}
+			}
+			RECORD_VERSION => loop {
+				let mut record_type: [u8; 1] = [0; 1];
+				reader.read_exact(&mut record_type)?;
+				if record_type[0] == END_RECORD {
+					break;
+				}
+				let mut length: [u8; 4] = [0; 4];
+				reader.read_exact(&mut length)?;
+				let length: u64 = u32::from_le_bytes(length) as u64;
+				let mut value: io::Take<&mut R> = reader.take(length);
+				match Section::from_record_type(record_type[0]) {
+					// the record holds its length, so a checksum can be skipped without its
+					// algorithm, only the output goes unchecked
+					Some(Section::Checksum) => {
+						if !read_checksum(&mut value, &mut header)? {
+							log!(
+								Warn,
+								"Skipping a checksum of an algorithm this srx does not know"
+							);
+							skip_record(&mut value)?;
+						} else if value.limit() != 0 {
+							return Err(AnyError::bad_header("Invalid SRX header record!"));
+						}
+					}
+					Some(section) => {
+						section.read(&mut value, &mut header)?;
+						if value.limit() != 0 {
+							return Err(AnyError::bad_header("Invalid SRX header record!"));
+						}
+					}
+					None if record_type[0] & REQUIRED_RECORD != 0 => {
+						return Err(AnyError::bad_header(
+							"Unsupported SRX header record, made by a newer srx?",
+						));
+					}
+					None => skip_record(&mut value)?,
+				}
+			},
+			_ => return Err(unsupported_version()),
+		}
+		header.config.check()?;
+		Ok(header)
+	}
+
+	// the size of the output, the stored tail included, against the size recorded if any: bytes
+	// appended to a file of an older srx may decode to an end all the same, only later
+	pub fn check_size(&self, size: u64) -> AnyResult<()> {
+		match self.original_size {
+			Some(original_size) if original_size != size => Err(AnyError::from_string(format!(
+				"The output is {} bytes, not the {} bytes the header recorded!",
+				size, original_size
+			))),
+			_ => Ok(()),
+		}
+	}
+}
+
+fn unsupported_version() -> AnyError {
+	AnyError::bad_header("Unsupported SRX format version, made by a newer srx?")
+}
+
+// reads a checksum section into the header, false when its algorithm is unknown
+fn read_checksum<R: Read>(reader: &mut R, header: &mut Header) -> AnyResult<bool> {
+	let mut id: [u8; 1] = [0; 1];
+	reader.read_exact(&mut id)?;
+	match ChecksumAlgo::from_id(id[0]) {
+		Some(algo) => {
+			let mut digest: Vec<u8> = vec![0; algo.digest_size()];
+			reader.read_exact(&mut digest)?;
+			header.checksum = Some(Checksum { algo, digest });
+			Ok(true)
+		}
+		None => Ok(false),
+	}
+}
+
+// the rest of a record that is not read, which must be there all the same
+fn skip_record<R: Read>(value: &mut io::Take<R>) -> AnyResult<()> {
+	let remaining: u64 = value.limit();
+	if io::copy(value, &mut io::sink())? != remaining {
+		return Err(AnyError::truncated_input("Truncated SRX header record!"));
+	}
+	Ok(())
+}
+
+// the payload and the stored tail in what follows the header of a file with a stored tail
+pub fn split_stored_tail(data: &[u8]) -> AnyResult<(&[u8], &[u8])> {
+	let truncated = || AnyError::truncated_input("Truncated SRX stored tail!");
+	let trailer_start: usize = data.len().checked_sub(8).ok_or_else(truncated)?;
+	let (data, trailer): (&[u8], &[u8]) = data.split_at(trailer_start);
+	let tail_size: u64 = u64::from_le_bytes(trailer.try_into()?);
+	let tail_start: usize = usize::try_from(tail_size)
+		.ok()
+		.and_then(|tail_size| data.len().checked_sub(tail_size))
+		.ok_or_else(truncated)?;
+	Ok(data.split_at(tail_start))
+}
+
+// the same as Header::write and Header::read
+pub fn write_header<W: Write>(writer: &mut W, header: &Header) -> AnyResult<()> {
+	header.write(writer)
+}
+
+pub fn read_header<R: Read>(reader: &mut R) -> AnyResult<Header> {
+	Header::read(reader)
+}
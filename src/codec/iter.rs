@@ -0,0 +1,90 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::encoder::encode_producer;
+use super::shared::{IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE, SRX_HEADER};
+use crate::basic::{AnyResult, Producer};
+
+// -----------------------------------------------
+
+struct IteratorProducer<I: Iterator<Item = u8>>(I);
+
+impl<I: Iterator<Item = u8>> Producer<u8> for IteratorProducer<I> {
+	fn produce(&mut self, buffer: &mut [u8]) -> AnyResult<usize> {
+		// the buffer goes first so that no byte is pulled out of the iterator and dropped
+		let mut length: usize = 0;
+		for (slot, value) in buffer.iter_mut().zip(&mut self.0) {
+			*slot = value;
+			length += 1;
+		}
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+struct ChunksProducer<'a, I: Iterator<Item = &'a [u8]>> {
+	chunks: I,
+	current: &'a [u8],
+}
+
+impl<'a, I: Iterator<Item = &'a [u8]>> Producer<u8> for ChunksProducer<'a, I> {
+	fn produce(&mut self, buffer: &mut [u8]) -> AnyResult<usize> {
+		// skip empty chunks, returning zero here would be taken as the end of the stream
+		while self.current.is_empty() {
+			match self.chunks.next() {
+				None => return Ok(0),
+				Some(chunk) => self.current = chunk,
+			}
+		}
+		let length: usize = buffer.len().min(self.current.len());
+		let (head, tail): (&[u8], &[u8]) = self.current.split_at(length);
+		buffer[..length].copy_from_slice(head);
+		self.current = tail;
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+pub fn compress_iter<I: Iterator<Item = u8> + Send>(iter: I) -> AnyResult<Vec<u8>> {
+	let output: Vec<u8> = SRX_HEADER.to_vec();
+	let (_, output): (IteratorProducer<I>, Vec<u8>) =
+		encode_producer::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			IteratorProducer(iter),
+			output,
+			&Config::default(),
+		)?;
+	Ok(output)
+}
+
+pub fn compress_chunks<'a, I: Iterator<Item = &'a [u8]> + Send>(chunks: I) -> AnyResult<Vec<u8>> {
+	let output: Vec<u8> = SRX_HEADER.to_vec();
+	let producer: ChunksProducer<'a, I> = ChunksProducer {
+		chunks,
+		current: &[],
+	};
+	let (_, output): (ChunksProducer<'a, I>, Vec<u8>) = encode_producer::<
+		_,
+		_,
+		IO_BUFFER_SIZE,
+		MESSAGE_BUFFER_SIZE,
+	>(producer, output, &Config::default())?;
+	Ok(output)
+}
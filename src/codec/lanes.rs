@@ -0,0 +1,152 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+// -----------------------------------------------
+
+// The contexts of one kind for every lane of a stream, see Config::lanes. A single lane, as in
+// the default config, is a type of its own, so that the loops over the bytes built for it
+// neither pick a lane for each byte nor index a vector: see with_lanes.
+pub(super) trait Lanes {
+	type Context;
+
+	fn new(lanes: u8, context: impl FnMut() -> Self::Context) -> Self;
+
+	// the lane of the current byte
+	fn lane(&self) -> usize;
+
+	fn current(&self) -> &Self::Context;
+
+	fn current_mut(&mut self) -> &mut Self::Context;
+
+	// byte n of the stream goes to lane n % lanes, this moves on to the lane of the next byte
+	fn next(&mut self);
+
+	// moves to the given lane, for the secondary encoder told the lane of each message
+	fn select(&mut self, lane: usize);
+
+	fn first(&self) -> &Self::Context;
+}
+
+// -----------------------------------------------
+
+pub(super) struct OneLane<T>(T);
+
+impl<T> Lanes for OneLane<T> {
+	type Context = T;
+
+	fn new(lanes: u8, mut context: impl FnMut() -> T) -> Self {
+		debug_assert_eq!(lanes, 1);
+		Self(context())
+	}
+
+	#[inline(always)]
+	fn lane(&self) -> usize {
+		0
+	}
+
+	#[inline(always)]
+	fn current(&self) -> &T {
+		&self.0
+	}
+
+	#[inline(always)]
+	fn current_mut(&mut self) -> &mut T {
+		&mut self.0
+	}
+
+	#[inline(always)]
+	fn next(&mut self) {}
+
+	#[inline(always)]
+	fn select(&mut self, lane: usize) {
+		debug_assert_eq!(lane, 0);
+	}
+
+	fn first(&self) -> &T {
+		&self.0
+	}
+}
+
+// -----------------------------------------------
+
+pub(super) struct ManyLanes<T> {
+	contexts: Vec<T>,
+	lane: usize,
+}
+
+impl<T> Lanes for ManyLanes<T> {
+	type Context = T;
+
+	fn new(lanes: u8, mut context: impl FnMut() -> T) -> Self {
+		Self {
+			contexts: (0..lanes).map(|_| context()).collect(),
+			lane: 0,
+		}
+	}
+
+	#[inline(always)]
+	fn lane(&self) -> usize {
+		self.lane
+	}
+
+	#[inline(always)]
+	fn current(&self) -> &T {
+		&self.contexts[self.lane]
+	}
+
+	#[inline(always)]
+	fn current_mut(&mut self) -> &mut T {
+		&mut self.contexts[self.lane]
+	}
+
+	#[inline(always)]
+	fn next(&mut self) {
+		self.lane = if self.lane + 1 == self.contexts.len() {
+			0
+		} else {
+			self.lane + 1
+		};
+	}
+
+	#[inline(always)]
+	fn select(&mut self, lane: usize) {
+		self.lane = lane;
+	}
+
+	fn first(&self) -> &T {
+		&self.contexts[0]
+	}
+}
+
+// -----------------------------------------------
+
+// Runs the body with the generic type named by the given identifier set to the lanes of the
+// config, OneLane or ManyLanes, for the loops over the bytes to be built for each of them.
+macro_rules! with_lanes {
+	($config:expr, $lanes:ident, $body:expr) => {
+		if $config.lanes == 1 {
+			type $lanes<T> = $crate::codec::lanes::OneLane<T>;
+			$body
+		} else {
+			type $lanes<T> = $crate::codec::lanes::ManyLanes<T>;
+			$body
+		}
+	};
+}
+
+pub(super) use with_lanes;
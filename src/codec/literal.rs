@@ -0,0 +1,83 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::basic::Byte;
+use crate::secondary_context::Bit;
+
+// -----------------------------------------------
+
+// A literal is coded as two binary trees of 4 bits each: the high nibble in the first 15
+// contexts, then the low nibble in one of 16 blocks of 15 contexts (to reduce cache
+// misses). Each step is packed as (context offset << 1) | bit, the offset is at most 255.
+const fn build_steps(byte: usize) -> [u16; 8] {
+	let mut steps: [u16; 8] = [0; 8];
+	let high: usize = (byte >> 4) | 16;
+	let low: usize = (byte & 15) | 16;
+	let low_offset: usize = 15 * (high - 15);
+	let mut index: usize = 0;
+	while index < 4 {
+		let shift: usize = 4 - index;
+		steps[index] = (((high >> shift) << 1) | (high >> (shift - 1) & 1)) as u16;
+		steps[index + 4] = (((low_offset + (low >> shift)) << 1) | (low >> (shift - 1) & 1)) as u16;
+		index += 1;
+	}
+	steps
+}
+
+const fn literal_table() -> [[u16; 8]; 256] {
+	let mut table: [[u16; 8]; 256] = [[0; 8]; 256];
+	let mut byte: usize = 0;
+	while byte < 256 {
+		table[byte] = build_steps(byte);
+		byte += 1;
+	}
+	table
+}
+
+static LITERAL_TABLE: [[u16; 8]; 256] = literal_table();
+
+// -----------------------------------------------
+
+#[derive(Copy, Clone)]
+pub struct LiteralStep(u16);
+
+impl LiteralStep {
+	#[inline(always)]
+	pub fn context_offset(self) -> usize {
+		(self.0 >> 1) as usize
+	}
+
+	#[inline(always)]
+	pub fn bit(self) -> Bit {
+		match self.0 & 1 {
+			0 => Bit::Zero,
+			_ => Bit::One,
+		}
+	}
+}
+
+// the context offsets and bits coding a literal, in coding order, as two arrays for fetching and
+// updating the entries of the whole literal at once, see SecondaryContext::get_info_batch
+#[inline(always)]
+pub fn literal_batch(byte: Byte) -> ([usize; 8], [Bit; 8]) {
+	let steps: [LiteralStep; 8] = LITERAL_TABLE[usize::from(byte)].map(LiteralStep);
+	(
+		steps.map(LiteralStep::context_offset),
+		steps.map(LiteralStep::bit),
+	)
+}
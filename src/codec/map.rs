@@ -0,0 +1,95 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use crate::basic::{AnyResult, Byte};
+use crate::bridged_context::{BridgedContextInfo, BridgedPrimaryContext};
+use crate::primary_context::{ByteMatched, PrimaryTable};
+use std::io::{BufReader, BufWriter, Read, Write};
+
+// -----------------------------------------------
+
+pub const CONTEXT_MAP_COLUMNS: &str = "position,lane,byte,first_byte,second_byte,third_byte,\
+	match_count,matched,first_context,second_context,third_context,literal_context";
+
+// -----------------------------------------------
+
+// Writes one CSV row for each of the first limit bytes of the input: the primary history it
+// was predicted from and the secondary contexts it was coded with, empty for the contexts it
+// did not need. The primary contexts do not depend on the secondary ones, so replaying them
+// alone gives the same contexts as the encoder without touching it nor the bitstream.
+pub fn map_contexts<R: Read, W: Write>(
+	reader: R,
+	writer: W,
+	config: &Config,
+	limit: u64,
+) -> AnyResult<u64> {
+	config.check()?;
+	let lanes: usize = config.lanes as usize;
+	let mut contexts: Vec<BridgedPrimaryContext> =
+		(0..lanes).map(|_| config.primary_context()).collect();
+	let mut writer: BufWriter<W> = BufWriter::new(writer);
+	writeln!(writer, "{}", CONTEXT_MAP_COLUMNS)?;
+	let mut position: u64 = 0;
+	for current_byte in BufReader::new(reader.take(limit)).bytes() {
+		let current_byte: u8 = current_byte?;
+		let lane: usize = (position % lanes as u64) as usize;
+		let context: &mut BridgedPrimaryContext = &mut contexts[lane];
+		let info: BridgedContextInfo = BridgedContextInfo::new(
+			config.model,
+			context.get_history(),
+			context.previous_byte(),
+			context.hash_value(),
+			context.word_hash(),
+		);
+		let matched: ByteMatched = context.matching(info.current_state(), Byte::from(current_byte));
+		let (name, second, third, literal): (&str, bool, bool, bool) = match matched {
+			ByteMatched::FIRST => ("first", false, false, false),
+			ByteMatched::SECOND => ("second", true, true, false),
+			ByteMatched::THIRD => ("third", true, true, false),
+			ByteMatched::NONE => ("none", true, false, true),
+		};
+		writeln!(
+			writer,
+			"{},{},{},{},{},{},{},{},{},{},{},{}",
+			position,
+			lane,
+			current_byte,
+			u8::from(info.first_byte()),
+			u8::from(info.second_byte()),
+			u8::from(info.third_byte()),
+			info.current_state().match_count(),
+			name,
+			info.first_context(),
+			optional(second, info.second_context()),
+			optional(third, info.third_context()),
+			optional(literal, info.literal_context()),
+		)?;
+		position += 1;
+	}
+	writer.flush()?;
+	Ok(position)
+}
+
+fn optional(used: bool, context: usize) -> String {
+	if used {
+		context.to_string()
+	} else {
+		String::new()
+	}
+}
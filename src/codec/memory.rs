@@ -0,0 +1,99 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::blocks::decode_blocks;
+use super::checksum::Checksum;
+use super::config::Config;
+use super::decoder::decode_with;
+use super::encoder::encode_producer;
+use super::header::{read_header, split_stored_tail, write_header, Header};
+use super::profile::check_profile;
+use super::shared::{WrappedReader, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE, SRX_HEADER};
+use crate::basic::AnyResult;
+
+// -----------------------------------------------
+
+// the output is allocated up front up to this size from the header, and grows as usual beyond it,
+// so that a header saying anything does not get that much memory at once
+pub const PREALLOCATED_SIZE_LIMIT: u64 = 1 << 26;
+
+// -----------------------------------------------
+
+// the whole of a slice, behind a header like the one of a file, so decompress takes it back
+pub fn compress(data: &[u8]) -> AnyResult<Vec<u8>> {
+	compress_with(data, &Config::default())
+}
+
+pub fn compress_with(data: &[u8], config: &Config) -> AnyResult<Vec<u8>> {
+	let mut output: Vec<u8> = Vec::with_capacity(SRX_HEADER.len() + data.len() / 2);
+	let header: Header = Header {
+		config: *config,
+		original_size: Some(data.len() as u64),
+		content_hash: None,
+		profile_id: None,
+		checksum: None,
+		volume_size: None,
+		metadata: None,
+		stored_tail: false,
+		blocks: None,
+	};
+	write_header(&mut output, &header)?;
+	let (_, output): (WrappedReader<&[u8]>, Vec<u8>) = encode_producer::<
+		_,
+		_,
+		IO_BUFFER_SIZE,
+		MESSAGE_BUFFER_SIZE,
+	>(WrappedReader(data), output, config)?;
+	Ok(output)
+}
+
+pub fn decompress(data: &[u8]) -> AnyResult<Vec<u8>> {
+	// the metadata, if any, belongs to the caller and is skipped here
+	let mut payload: &[u8] = data;
+	let header: Header = read_header(&mut payload)?;
+	check_profile(header.profile_id, None)?;
+	let (payload, tail): (&[u8], &[u8]) = if header.stored_tail {
+		split_stored_tail(payload)?
+	} else {
+		(payload, &[])
+	};
+	let capacity: u64 = header
+		.original_size
+		.unwrap_or(0)
+		.min(PREALLOCATED_SIZE_LIMIT);
+	let (_, mut output): (&[u8], Vec<u8>) = match &header.blocks {
+		Some(blocks) => decode_blocks(
+			payload,
+			Vec::with_capacity(capacity as usize),
+			blocks,
+			&header.config,
+			None,
+		)?,
+		None => decode_with::<_, _, IO_BUFFER_SIZE>(
+			payload,
+			Vec::with_capacity(capacity as usize),
+			&header.config,
+		)?,
+	};
+	output.extend_from_slice(tail);
+	header.check_size(output.len() as u64)?;
+	if let Some(expected) = &header.checksum {
+		expected.check(&Checksum::of(expected.algo, &output)?)?;
+	}
+	Ok(output)
+}
@@ -16,9 +16,60 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+mod blocks;
+mod calibration;
+mod checksum;
+mod collisions;
+mod config;
 mod decoder;
 mod encoder;
+#[cfg(feature = "gzip")]
+mod gzip;
+mod header;
+mod iter;
+mod lanes;
+mod literal;
+#[cfg(feature = "map-context")]
+mod map;
+mod memory;
+mod outcomes;
+mod profile;
+mod reader;
+mod resync;
 mod shared;
+mod stages;
+#[cfg(test)]
+mod test;
+mod threadless;
+mod writer;
+#[cfg(feature = "zstd")]
+mod zstd;
 
-pub use self::decoder::decode;
-pub use self::encoder::encode;
+pub use self::blocks::{decode_blocks, encode_blocks, Block, MAX_BLOCKS};
+pub use self::calibration::{calibrate, Calibration, CalibrationBin, CALIBRATION_BINS};
+pub use self::checksum::{Checksum, ChecksumAlgo, ChecksumHasher, ChecksumWriter};
+pub use self::collisions::{count_collisions, Collisions, COLLISION_SAMPLE_BITS};
+pub use self::config::{Buffers, Config, Growth, MAX_LANES};
+pub use self::decoder::{
+	decode, decode_to_consumer, decode_to_consumer_with_config, decode_until, decode_with,
+	decode_with_config, decode_with_config_profile, decode_with_profile,
+};
+pub use self::encoder::{
+	encode, encode_with, encode_with_config, encode_with_config_profile, encode_with_profile,
+};
+#[cfg(feature = "gzip")]
+pub use self::gzip::{decompress_to_gzip, recompress_gzip, GzipConsumer, GzipProducer};
+pub use self::header::{read_header, split_stored_tail, write_header, Header};
+pub use self::iter::{compress_chunks, compress_iter};
+#[cfg(feature = "map-context")]
+pub use self::map::{map_contexts, CONTEXT_MAP_COLUMNS};
+pub use self::memory::{compress, compress_with, decompress};
+pub use self::outcomes::{count_outcomes, Outcomes};
+pub use self::profile::{check_profile, Profile, DEFAULT_PROFILE_ENTRIES};
+pub use self::reader::SrxDecoder;
+pub use self::resync::{decode_resync, encode_resync, Resynced, MAX_SEGMENT_SIZE, RESYNC_MARKER};
+pub use self::shared::{IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE, MIN_MESSAGE_BUFFER_SIZE, SRX_HEADER};
+pub use self::stages::{time_stages, Stage, StageTimes};
+pub use self::writer::{SrxEncoder, SrxWriter};
+#[cfg(feature = "zstd")]
+pub use self::zstd::{decompress_to_zstd, recompress_zstd, ZstdConsumer, ZstdProducer};
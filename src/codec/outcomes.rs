@@ -0,0 +1,102 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::config::Config;
+use crate::basic::{AnyResult, Byte};
+use crate::bridged_context::BridgedPrimaryContext;
+use crate::primary_context::{ByteMatched, HistoryState, PrimaryTable};
+use std::io::{BufReader, Read};
+
+// -----------------------------------------------
+
+// How the bytes of the input matched the bytes ranked by their primary context, which decides
+// the bits the encoder gives the coder for them: a bit for the first rank for every byte, one for
+// the second for the bytes that missed the first, one for the third for the ones that missed
+// both but the literals, and the 8 bits of each literal. The end of the stream is left out.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Outcomes {
+	pub first: u64,
+	pub second: u64,
+	pub third: u64,
+	pub literal: u64,
+	// the ones among the bits of the literals
+	pub literal_ones: u64,
+}
+
+impl Outcomes {
+	pub fn bytes(&self) -> u64 {
+		self.first + self.second + self.third + self.literal
+	}
+
+	// the zeros and the ones coded for the first rank, then for the second and the third
+	pub fn first_bits(&self) -> (u64, u64) {
+		(self.first, self.second + self.third + self.literal)
+	}
+
+	pub fn second_bits(&self) -> (u64, u64) {
+		(self.literal, self.second + self.third)
+	}
+
+	pub fn third_bits(&self) -> (u64, u64) {
+		(self.second, self.third)
+	}
+
+	pub fn literal_bits(&self) -> (u64, u64) {
+		(self.literal * 8 - self.literal_ones, self.literal_ones)
+	}
+
+	// all the zeros and the ones given to the coder
+	pub fn bits(&self) -> (u64, u64) {
+		[
+			self.first_bits(),
+			self.second_bits(),
+			self.third_bits(),
+			self.literal_bits(),
+		]
+		.iter()
+		.fold((0, 0), |(zeros, ones), bits| {
+			(zeros + bits.0, ones + bits.1)
+		})
+	}
+}
+
+// -----------------------------------------------
+
+// As with the collisions, the primary contexts are replayed alone in a pass of their own, so
+// the encoder does not count anything.
+pub fn count_outcomes<R: Read>(reader: R, config: &Config) -> AnyResult<Outcomes> {
+	config.check()?;
+	let lanes: usize = config.lanes as usize;
+	let mut contexts: Vec<BridgedPrimaryContext> =
+		(0..lanes).map(|_| config.primary_context()).collect();
+	let mut outcomes: Outcomes = Outcomes::default();
+	for (position, current_byte) in BufReader::new(reader).bytes().enumerate() {
+		let current_byte: u8 = current_byte?;
+		let context: &mut BridgedPrimaryContext = &mut contexts[position % lanes];
+		let state: HistoryState = context.get_history().get_state();
+		match context.matching(state, Byte::from(current_byte)) {
+			ByteMatched::FIRST => outcomes.first += 1,
+			ByteMatched::SECOND => outcomes.second += 1,
+			ByteMatched::THIRD => outcomes.third += 1,
+			ByteMatched::NONE => {
+				outcomes.literal += 1;
+				outcomes.literal_ones += current_byte.count_ones() as u64;
+			}
+		}
+	}
+	Ok(outcomes)
+}
@@ -0,0 +1,164 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::basic::{AnyError, AnyResult, Crc32};
+use crate::bridged_context::{BridgedSecondaryContext, SECONDARY_CONTEXT_SIZE};
+use crate::secondary_context::state_depths;
+use std::cmp::Reverse;
+
+// -----------------------------------------------
+
+// A profile is the magic, a u32 little-endian number of entries, then for each entry by
+// increasing context index: the distance to the previous index (to 0 for the first one) as a
+// LEB128 varint, and the state of that context as a u16 little-endian.
+const PROFILE_MAGIC: &[u8; 4] = b"sRxP";
+
+// about 1 MiB of profile, enough for the contexts that matter on most inputs
+pub const DEFAULT_PROFILE_ENTRIES: usize = 1 << 18;
+
+// -----------------------------------------------
+
+// The states of the secondary contexts that saw the most bits at the end of a compression,
+// for starting the next ones on similar data from there instead of from nothing. Every lane
+// starts from the same profile, which is taken from the first lane.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Profile {
+	// (context index, state), sorted by context index
+	entries: Vec<(u32, u16)>,
+}
+
+impl Profile {
+	pub fn from_context(context: &BridgedSecondaryContext, max_entries: usize) -> Self {
+		let mut entries: Vec<(u32, u16)> = (0..context.size())
+			.filter_map(|index| match context.state(index) {
+				0 => None,
+				state => Some((index as u32, state)),
+			})
+			.collect();
+		if entries.len() > max_entries {
+			// the deeper the state, the more bits the context saw
+			let depths: Vec<u8> = state_depths();
+			entries
+				.sort_unstable_by_key(|&(index, state)| (Reverse(depths[state as usize]), index));
+			entries.truncate(max_entries);
+			entries.sort_unstable();
+		}
+		Self { entries }
+	}
+
+	// the entries past the table, from the text model for one without its literals, are left out
+	pub fn apply(&self, context: &mut BridgedSecondaryContext) {
+		for &(index, state) in &self.entries {
+			if (index as usize) < context.size() {
+				context.set_state(index as usize, state);
+			}
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	// recorded in the header, so that decompressing with another profile is refused
+	pub fn id(&self) -> u32 {
+		Crc32::of(&self.to_bytes())
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes: Vec<u8> = Vec::with_capacity(8 + self.entries.len() * 4);
+		bytes.extend_from_slice(PROFILE_MAGIC);
+		bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+		let mut previous: u32 = 0;
+		for &(index, state) in &self.entries {
+			let mut delta: u32 = index - previous;
+			while delta >= 0x80 {
+				bytes.push((delta as u8) | 0x80);
+				delta >>= 7;
+			}
+			bytes.push(delta as u8);
+			bytes.extend_from_slice(&state.to_le_bytes());
+			previous = index;
+		}
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> AnyResult<Self> {
+		let corrupted = || AnyError::from_string("Corrupted SRX profile!");
+		let rest: &[u8] = bytes
+			.strip_prefix(PROFILE_MAGIC)
+			.ok_or_else(|| AnyError::from_string("Not a SRX profile!"))?;
+		let (count, mut rest): (&[u8], &[u8]) = rest.split_at_checked(4).ok_or_else(corrupted)?;
+		let count: u32 = u32::from_le_bytes(count.try_into()?);
+		let mut entries: Vec<(u32, u16)> = Vec::with_capacity(count.min(1 << 24) as usize);
+		let mut previous: Option<u32> = None;
+		for _ in 0..count {
+			let mut delta: u32 = 0;
+			let mut shift: u32 = 0;
+			loop {
+				let (&byte, tail): (&u8, &[u8]) = rest.split_first().ok_or_else(corrupted)?;
+				rest = tail;
+				if shift > 28 {
+					return Err(corrupted());
+				}
+				delta |= ((byte & 0x7F) as u32) << shift;
+				shift += 7;
+				if byte < 0x80 {
+					break;
+				}
+			}
+			// indices go strictly up and stay inside the table
+			let index: u32 = match previous {
+				None => delta,
+				Some(_) if delta == 0 => return Err(corrupted()),
+				Some(previous) => previous.checked_add(delta).ok_or_else(corrupted)?,
+			};
+			if index as usize >= SECONDARY_CONTEXT_SIZE {
+				return Err(corrupted());
+			}
+			let (state, tail): (&[u8], &[u8]) = rest.split_at_checked(2).ok_or_else(corrupted)?;
+			rest = tail;
+			entries.push((index, u16::from_le_bytes(state.try_into()?)));
+			previous = Some(index);
+		}
+		if !rest.is_empty() {
+			return Err(corrupted());
+		}
+		Ok(Self { entries })
+	}
+}
+
+// -----------------------------------------------
+
+// the profile given for decompressing must be the one the data was compressed with
+pub fn check_profile(profile_id: Option<u32>, profile: Option<&Profile>) -> AnyResult<()> {
+	match (profile_id, profile) {
+		(None, None) => Ok(()),
+		(Some(id), Some(profile)) if profile.id() == id => Ok(()),
+		(Some(_), None) => Err(AnyError::from_string(
+			"Compressed with a profile, which is needed to decompress!",
+		)),
+		(Some(_), Some(_)) => Err(AnyError::from_string(
+			"Compressed with another profile than the given one!",
+		)),
+		(None, Some(_)) => Err(AnyError::from_string("Compressed without a profile!")),
+	}
+}
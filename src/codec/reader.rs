@@ -0,0 +1,86 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::CombinedContextDecoder;
+use super::lanes::ManyLanes;
+use super::shared::{WrappedReader, IO_BUFFER_SIZE};
+use crate::basic::{log, AnyResult, DirectReader};
+use crate::bridged_context::{BridgedPrimaryContext, BridgedSecondaryContext};
+use std::io;
+use std::io::Read;
+
+// -----------------------------------------------
+
+// Decompresses the payload read from the reader as it is read from, the counterpart of
+// SrxEncoder: each read decodes as many bytes as asked for, pulling from the reader only what
+// the coder needs for them, on the calling thread. The header of a file is not part of the
+// payload, it is read first with read_header and its config given to with_config.
+//
+// The end of the stream is found as the pipelines find it, after which reads return nothing.
+// The reader is read ahead by up to IO_BUFFER_SIZE bytes, so what follows the payload in it is
+// gone.
+//
+// The contexts are kept as for any number of lanes, only the pipelines have a path of their own
+// for a single lane.
+pub struct SrxDecoder<R: Read> {
+	decoder: CombinedContextDecoder<
+		DirectReader<u8, WrappedReader<R>, IO_BUFFER_SIZE>,
+		ManyLanes<BridgedPrimaryContext>,
+		ManyLanes<BridgedSecondaryContext>,
+	>,
+	ended: bool,
+}
+
+impl<R: Read> SrxDecoder<R> {
+	pub fn new(reader: R) -> AnyResult<Self> {
+		Self::with_config(reader, &Config::default())
+	}
+
+	pub fn with_config(reader: R, config: &Config) -> AnyResult<Self> {
+		config.check()?;
+		log!(Debug, "Decoding with the {} config", config.name());
+		Ok(Self {
+			decoder: CombinedContextDecoder::new(
+				DirectReader::new(WrappedReader(reader)),
+				*config,
+				None,
+			),
+			ended: false,
+		})
+	}
+}
+
+impl<R: Read> Read for SrxDecoder<R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let mut length: usize = 0;
+		while !self.ended && length < buffer.len() {
+			match self.decoder.next_byte()? {
+				Some(next_byte) => {
+					buffer[length] = next_byte.into();
+					length += 1;
+				}
+				None => {
+					log!(Debug, "Decoded to the end of the stream");
+					self.ended = true;
+				}
+			}
+		}
+		Ok(length)
+	}
+}
@@ -0,0 +1,259 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::decode_with;
+use super::header::{read_header, Header};
+use super::memory::compress_with;
+use super::profile::check_profile;
+use super::shared::{BoundedWriter, IO_BUFFER_SIZE};
+use crate::basic::{log, read_retrying, write_fully, AnyError, AnyResult, Crc32};
+use std::io;
+use std::io::{BufReader, Bytes, Read, Write};
+use std::ops::Range;
+
+// -----------------------------------------------
+
+// A resync stream is a sequence of segments, each one starting with the marker and followed by
+// the escaped segment body: the offset of the segment in the original data (u64 LE), the CRC-32
+// of its data (u32 LE) and a complete srx stream of its data. Every segment starts from the
+// initial model, the only snapshot both ends share without sending it, so a segment decodes on
+// its own and a receiver that missed some bytes only loses the segments they belong to.
+//
+// The body is escaped by following every 0xFF with a 0x00, so 0xFF followed by anything else
+// never occurs inside a body and the marker can be found again after a damaged region.
+pub const RESYNC_MARKER: [u8; 2] = [0xFF, b'R'];
+
+const ESCAPE: u8 = 0xFF;
+const ESCAPED: u8 = 0x00;
+
+// a segment is held in memory on both ends, and a damaged header must not claim gigabytes
+pub const MAX_SEGMENT_SIZE: usize = 1 << 30;
+
+// offset + checksum
+const SEGMENT_HEADER_SIZE: usize = 12;
+
+// -----------------------------------------------
+
+// what decoding a resync stream recovered
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Resynced {
+	// bytes written, including the zero-filled gaps
+	pub size: u64,
+	// gaps between the segments that were decoded, written as zeros
+	pub lost: Vec<Range<u64>>,
+	// segments found but not decoded, a damaged segment after the last good one is only
+	// counted here as nothing tells how long it was
+	pub damaged: usize,
+}
+
+// -----------------------------------------------
+
+fn read_segment<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+	let mut length: usize = 0;
+	while length < buffer.len() {
+		match read_retrying(reader, &mut buffer[length..])? {
+			0 => break,
+			read => length += read,
+		}
+	}
+	Ok(length)
+}
+
+fn escape(body: &[u8], output: &mut Vec<u8>) {
+	for &value in body {
+		output.push(value);
+		if value == ESCAPE {
+			output.push(ESCAPED);
+		}
+	}
+}
+
+pub fn encode_resync<R: Read, W: Write>(
+	mut reader: R,
+	mut writer: W,
+	config: &Config,
+	segment_size: usize,
+) -> AnyResult<(R, W)> {
+	config.check()?;
+	if segment_size == 0 || segment_size > MAX_SEGMENT_SIZE {
+		return Err(AnyError::from_string(format!(
+			"Resync segment size must be from 1 to {} bytes!",
+			MAX_SEGMENT_SIZE
+		)));
+	}
+	let mut segment: Vec<u8> = vec![0; segment_size];
+	let mut output: Vec<u8> = Vec::new();
+	let mut offset: u64 = 0;
+	loop {
+		// an empty input still gets one segment, the stream is recognizable by its marker
+		let length: usize = read_segment(&mut reader, &mut segment)?;
+		if length == 0 && offset > 0 {
+			break;
+		}
+		let data: &[u8] = &segment[..length];
+		let mut body: Vec<u8> = Vec::with_capacity(SEGMENT_HEADER_SIZE + length / 2);
+		body.extend_from_slice(&offset.to_le_bytes());
+		body.extend_from_slice(&Crc32::of(data).to_le_bytes());
+		body.extend_from_slice(&compress_with(data, config)?);
+		output.clear();
+		output.extend_from_slice(&RESYNC_MARKER);
+		escape(&body, &mut output);
+		write_fully(&mut writer, &output)?;
+		offset += length as u64;
+		if length < segment_size {
+			break;
+		}
+	}
+	Ok((reader, writer))
+}
+
+// -----------------------------------------------
+
+fn decode_segment(body: &[u8]) -> AnyResult<(u64, Vec<u8>)> {
+	if body.len() < SEGMENT_HEADER_SIZE {
+		return Err(AnyError::from_string("Segment is too short!"));
+	}
+	let offset: u64 = u64::from_le_bytes(body[0..8].try_into()?);
+	let checksum: u32 = u32::from_le_bytes(body[8..12].try_into()?);
+	let mut stream: &[u8] = &body[SEGMENT_HEADER_SIZE..];
+	let header: Header = read_header(&mut stream)?;
+	check_profile(header.profile_id, None)?;
+	let size: usize = match header.original_size {
+		Some(size) if size <= MAX_SEGMENT_SIZE as u64 => size as usize,
+		_ => {
+			return Err(AnyError::from_string(
+				"Segment size is missing or too large!",
+			))
+		}
+	};
+	let writer: BoundedWriter = BoundedWriter::new(size, size);
+	let (_, writer): (&[u8], BoundedWriter) =
+		decode_with::<_, _, IO_BUFFER_SIZE>(stream, writer, &header.config)?;
+	if writer.output.len() != size || Crc32::of(&writer.output) != checksum {
+		return Err(AnyError::checksum_mismatch("Segment checksum mismatch!"));
+	}
+	Ok((offset, writer.output))
+}
+
+// -----------------------------------------------
+
+// how a segment body ended
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Ending {
+	// the marker of the next segment
+	Marker,
+	// the end of the stream
+	End,
+	// 0xFF followed by neither the marker nor the escape, the body is damaged
+	Damaged(u8),
+}
+
+struct Scanner<R: Read>(Bytes<BufReader<R>>);
+
+impl<R: Read> Scanner<R> {
+	fn next(&mut self) -> AnyResult<Option<u8>> {
+		Ok(self.0.next().transpose()?)
+	}
+
+	// skip everything up to and including the next marker, false if the stream ended first,
+	// escape tells whether the last byte already taken out of the stream was 0xFF
+	fn skip_to_marker(&mut self, mut escape: bool) -> AnyResult<bool> {
+		while let Some(value) = self.next()? {
+			if escape && value == RESYNC_MARKER[1] {
+				return Ok(true);
+			}
+			escape = value == ESCAPE;
+		}
+		Ok(false)
+	}
+
+	// unescape the body of a segment up to the next marker or the end of the stream
+	fn read_body(&mut self, body: &mut Vec<u8>) -> AnyResult<Ending> {
+		body.clear();
+		while let Some(value) = self.next()? {
+			if value != ESCAPE {
+				body.push(value);
+				continue;
+			}
+			match self.next()? {
+				Some(ESCAPED) => body.push(ESCAPE),
+				Some(value) if value == RESYNC_MARKER[1] => return Ok(Ending::Marker),
+				Some(value) => return Ok(Ending::Damaged(value)),
+				None => return Ok(Ending::End),
+			}
+		}
+		Ok(Ending::End)
+	}
+}
+
+fn write_zeros<W: Write>(writer: &mut W, mut length: u64) -> AnyResult<()> {
+	let zeros: [u8; 4096] = [0; 4096];
+	while length > 0 {
+		let chunk: usize = length.min(zeros.len() as u64) as usize;
+		write_fully(writer, &zeros[..chunk])?;
+		length -= chunk as u64;
+	}
+	Ok(())
+}
+
+// Decode every segment that can be decoded, skipping to the next marker after a damaged one.
+// The reader is only ever read forward, so it can be a live stream.
+pub fn decode_resync<R: Read, W: Write>(reader: R, mut writer: W) -> AnyResult<(Resynced, W)> {
+	let mut scanner: Scanner<R> = Scanner(BufReader::new(reader).bytes());
+	let mut resynced: Resynced = Resynced::default();
+	let mut body: Vec<u8> = Vec::new();
+	if !scanner.skip_to_marker(false)? {
+		return Err(AnyError::bad_header("Not a resync stream!"));
+	}
+	let mut found: bool = true;
+	while found {
+		let ending: Ending = scanner.read_body(&mut body)?;
+		let decoded: Option<(u64, Vec<u8>)> = match ending {
+			Ending::Damaged(byte) => {
+				log!(Warn, "Skipping a segment escaping a byte {:#04x}", byte);
+				None
+			}
+			Ending::Marker | Ending::End => match decode_segment(&body) {
+				Ok(decoded) => Some(decoded),
+				Err(error) => {
+					log!(Warn, "Skipping a segment: {}", error);
+					None
+				}
+			},
+		};
+		match decoded {
+			// segments only go forward, anything else comes from a damaged or false marker
+			Some((offset, data)) if offset >= resynced.size => {
+				if offset > resynced.size {
+					resynced.lost.push(resynced.size..offset);
+					write_zeros(&mut writer, offset - resynced.size)?;
+				}
+				write_fully(&mut writer, &data)?;
+				resynced.size = offset + data.len() as u64;
+			}
+			_ => resynced.damaged += 1,
+		}
+		found = match ending {
+			Ending::Marker => true,
+			Ending::End => false,
+			Ending::Damaged(value) => scanner.skip_to_marker(value == ESCAPE)?,
+		};
+	}
+	Ok((resynced, writer))
+}
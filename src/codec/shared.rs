@@ -16,65 +16,143 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::{
-	AnyError, AnyResult, Closable, Consumer, FromProducer, PipedReader, PipedWriter, Producer,
-	ToConsumer,
-};
+#[cfg(feature = "threads")]
+use crate::basic::{log, AnyError, Closable, FromProducer, PipedReader, PipedWriter, ToConsumer};
+use crate::basic::{read_retrying, write_fully, AnyResult, Consumer, Producer};
+use std::io;
 use std::io::{Read, Write};
+#[cfg(feature = "threads")]
 use std::thread::ScopedJoinHandle;
 
 // -----------------------------------------------
 
-struct WrappedReader<R: Read>(R);
+pub const SRX_HEADER: &[u8; 4] = b"sRx\x00";
+
+pub const IO_BUFFER_SIZE: usize = 0x400000;
+pub const MESSAGE_BUFFER_SIZE: usize = 0x40000;
+
+// a byte sends up to 3 messages, which then cross at most one sync of the pipe
+pub const MIN_MESSAGE_BUFFER_SIZE: usize = 4;
+
+// -----------------------------------------------
+
+#[cfg(feature = "threads")]
+pub fn run_producer<P: Producer<u8>, const IO_BUFFER_SIZE: usize>(
+	mut producer: P,
+	mut writer: PipedWriter<u8, IO_BUFFER_SIZE>,
+) -> AnyResult<P> {
+	while writer.produce(&mut producer)? > 0 {}
+	writer.close()?;
+	Ok(producer)
+}
+
+// -----------------------------------------------
+
+pub struct WrappedReader<R: Read>(pub R);
 
 impl<R: Read> Producer<u8> for WrappedReader<R> {
 	fn produce(&mut self, buffer: &mut [u8]) -> AnyResult<usize> {
-		Ok(self.0.read(buffer)?)
+		Ok(read_retrying(&mut self.0, buffer)?)
 	}
 }
 
+#[cfg(feature = "threads")]
 pub fn run_file_reader<R: Read, const IO_BUFFER_SIZE: usize>(
 	std_reader: R,
-	mut writer: PipedWriter<u8, IO_BUFFER_SIZE>,
+	writer: PipedWriter<u8, IO_BUFFER_SIZE>,
 ) -> AnyResult<R> {
-	let mut reader: WrappedReader<R> = WrappedReader(std_reader);
-	while writer.produce(&mut reader)? > 0 {}
-	writer.close()?;
-	Ok(reader.0)
+	Ok(run_producer(WrappedReader(std_reader), writer)?.0)
 }
 
 // -----------------------------------------------
 
-struct WrappedWriter<W: Write>(W);
+pub struct WrappedWriter<W: Write>(pub W);
 
 impl<W: Write> Consumer<u8> for WrappedWriter<W> {
 	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
-		self.0.write_all(buffer)?;
+		write_fully(&mut self.0, buffer)?;
 		Ok(buffer.len())
 	}
 }
 
-pub fn run_file_writer<W: Write, const IO_BUFFER_SIZE: usize>(
+#[cfg(feature = "threads")]
+pub fn run_consumer<C: Consumer<u8>, const IO_BUFFER_SIZE: usize>(
 	mut reader: PipedReader<u8, IO_BUFFER_SIZE>,
+	mut consumer: C,
+) -> AnyResult<C> {
+	while reader.consume(&mut consumer)? > 0 {}
+	reader.close()?;
+	Ok(consumer)
+}
+
+#[cfg(feature = "threads")]
+pub fn run_file_writer<W: Write, const IO_BUFFER_SIZE: usize>(
+	reader: PipedReader<u8, IO_BUFFER_SIZE>,
 	std_writer: W,
 ) -> AnyResult<W> {
-	let mut writer: WrappedWriter<W> = WrappedWriter(std_writer);
-	while reader.consume(&mut writer)? > 0 {}
-	reader.close()?;
-	Ok(writer.0)
+	Ok(run_consumer(reader, WrappedWriter(std_writer))?.0)
 }
 
 // -----------------------------------------------
 
+// The decoded data of a segment or a block must be exactly as long as declared, and a damaged
+// payload that decodes to more stops as soon as it is longer instead of going on.
+pub struct BoundedWriter {
+	pub output: Vec<u8>,
+	limit: usize,
+}
+
+impl BoundedWriter {
+	pub fn new(capacity: usize, limit: usize) -> Self {
+		Self {
+			output: Vec::with_capacity(capacity),
+			limit,
+		}
+	}
+}
+
+impl Write for BoundedWriter {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		if self.output.len() + buffer.len() > self.limit {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"The output is longer than declared!",
+			));
+		}
+		self.output.extend_from_slice(buffer);
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+#[cfg(feature = "threads")]
 pub fn thread_join<T>(thread_handle: ScopedJoinHandle<AnyResult<T>>) -> AnyResult<T> {
 	match thread_handle.join() {
 		Ok(value) => Ok(value?),
-		Err(error) => Err(match error.downcast_ref::<String>() {
-			Some(string) => AnyError::from_string(string),
-			None => match error.downcast_ref::<&'static str>() {
-				Some(&string) => AnyError::from_string(string),
-				None => AnyError::from_box(error),
-			},
-		}),
+		Err(error) => {
+			let error: AnyError = AnyError::from_box(error);
+			log!(Error, "A stage panicked: {}", error);
+			Err(error)
+		}
+	}
+}
+
+// Every stage must be joined before reporting anything, or scope would rethrow the panic of
+// a stage that was not. The stages around a failing one only see their pipes breaking, so
+// the error to report is the first one that is not a closed pipe.
+#[cfg(feature = "threads")]
+pub fn root_cause<const N: usize>(errors: [Option<AnyError>; N]) -> Option<AnyError> {
+	let mut errors: Vec<AnyError> = errors.into_iter().flatten().collect();
+	match errors
+		.iter()
+		.position(|error: &AnyError| !error.is_channel_closed())
+	{
+		Some(index) => Some(errors.swap_remove(index)),
+		None => errors.into_iter().next(),
 	}
 }
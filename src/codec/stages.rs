@@ -0,0 +1,180 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(feature = "pipe-stats")]
+use crate::basic::{pipe_stats, PipeStats};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(all(feature = "threads", not(unix)))]
+use std::time::Instant;
+
+// -----------------------------------------------
+
+// the stages of the pipelines, each on a thread of its own
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Stage {
+	// reading the input, to compress or to decompress
+	Reader,
+	PrimaryEncoder,
+	SecondaryEncoder,
+	// the primary and secondary contexts of the decoder, in a single stage
+	Decoder,
+	// writing the output, or handing it to a consumer
+	Writer,
+}
+
+impl Stage {
+	pub const ALL: &'static [Stage] = &[
+		Stage::Reader,
+		Stage::PrimaryEncoder,
+		Stage::SecondaryEncoder,
+		Stage::Decoder,
+		Stage::Writer,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Stage::Reader => "reader",
+			Stage::PrimaryEncoder => "primary",
+			Stage::SecondaryEncoder => "secondary",
+			Stage::Decoder => "decoder",
+			Stage::Writer => "writer",
+		}
+	}
+}
+
+// -----------------------------------------------
+
+// The time each stage spent working, summed over all the pipelines run by the body given to
+// time_stages. It is the CPU time of the threads of the stages (the wall time of them where
+// there is no such clock, the waits on the other stages included), so that the stage keeping
+// the others waiting is the one with the most of it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct StageTimes {
+	times: [Duration; Stage::ALL.len()],
+	#[cfg(feature = "pipe-stats")]
+	pipes: [PipeStats; Stage::ALL.len()],
+}
+
+impl StageTimes {
+	pub fn get(&self, stage: Stage) -> Duration {
+		self.times[stage as usize]
+	}
+
+	// the syncs of the pipes the stage read from and wrote to, the stalls of a stage with
+	// little time of its own telling that it waited on the others
+	#[cfg(feature = "pipe-stats")]
+	pub fn pipe_stats(&self, stage: Stage) -> PipeStats {
+		self.pipes[stage as usize]
+	}
+
+	pub fn total(&self) -> Duration {
+		self.times.iter().sum()
+	}
+}
+
+// -----------------------------------------------
+
+// what the threads of a stage add up, see run_stage
+#[derive(Default, Debug)]
+pub struct StageCounters {
+	nanos: AtomicU64,
+	#[cfg(feature = "pipe-stats")]
+	syncs: AtomicU64,
+	#[cfg(feature = "pipe-stats")]
+	stalls: AtomicU64,
+}
+
+pub type StageClock = Arc<[StageCounters; Stage::ALL.len()]>;
+
+thread_local! {
+	// the clock of the stages of the pipelines started on this thread, see time_stages
+	static STAGE_CLOCK: RefCell<Option<StageClock>> = const { RefCell::new(None) };
+}
+
+// Runs the body and times the stages of the pipelines it runs on this thread, nothing being
+// timed otherwise. The pipelines without threads have no stages to tell apart and are not
+// timed at all.
+pub fn time_stages<T>(body: impl FnOnce() -> T) -> (T, StageTimes) {
+	let clock: StageClock = Arc::default();
+	let previous: Option<StageClock> = STAGE_CLOCK.replace(Some(clock.clone()));
+	let result: T = body();
+	STAGE_CLOCK.set(previous);
+	let mut times: StageTimes = StageTimes::default();
+	for (index, counters) in clock.iter().enumerate() {
+		times.times[index] = Duration::from_nanos(counters.nanos.load(Ordering::Relaxed));
+		#[cfg(feature = "pipe-stats")]
+		{
+			times.pipes[index] = PipeStats {
+				syncs: counters.syncs.load(Ordering::Relaxed),
+				stalls: counters.stalls.load(Ordering::Relaxed),
+			};
+		}
+	}
+	(result, times)
+}
+
+// taken by the pipeline on the thread that starts it, before the stages get threads of their own
+#[cfg(feature = "threads")]
+pub fn stage_clock() -> Option<StageClock> {
+	STAGE_CLOCK.with_borrow(Clone::clone)
+}
+
+// runs a stage on the thread it has to itself, adding its time to the clock if there is one
+#[cfg(feature = "threads")]
+pub fn run_stage<T>(clock: &Option<StageClock>, stage: Stage, body: impl FnOnce() -> T) -> T {
+	let Some(clock) = clock else {
+		return body();
+	};
+	#[cfg(unix)]
+	let start: Duration = thread_time();
+	#[cfg(not(unix))]
+	let start: Instant = Instant::now();
+	#[cfg(feature = "pipe-stats")]
+	let pipes: PipeStats = pipe_stats();
+	let result: T = body();
+	#[cfg(feature = "pipe-stats")]
+	{
+		let pipes: PipeStats = pipe_stats().since(pipes);
+		let counters: &StageCounters = &clock[stage as usize];
+		counters.syncs.fetch_add(pipes.syncs, Ordering::Relaxed);
+		counters.stalls.fetch_add(pipes.stalls, Ordering::Relaxed);
+	}
+	#[cfg(unix)]
+	let elapsed: Duration = thread_time().saturating_sub(start);
+	#[cfg(not(unix))]
+	let elapsed: Duration = start.elapsed();
+	clock[stage as usize]
+		.nanos
+		.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+	result
+}
+
+// the CPU time of the calling thread, the time spent in the kernel for it included
+#[cfg(all(feature = "threads", unix))]
+fn thread_time() -> Duration {
+	let mut time: libc::timespec = libc::timespec {
+		tv_sec: 0,
+		tv_nsec: 0,
+	};
+	// cannot fail with a valid clock and pointer
+	unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+	Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
@@ -0,0 +1,2092 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::blocks::{decode_blocks, encode_blocks, Block, MAX_BLOCKS};
+use super::calibration::{calibrate, Calibration, CALIBRATION_BINS};
+use super::checksum::{Checksum, ChecksumAlgo, ChecksumHasher, ChecksumWriter};
+use super::collisions::{count_collisions, Collisions};
+use super::config::{Buffers, Config, Growth, MAX_LANES};
+use super::decoder::{
+	decode, decode_to_consumer, decode_until, decode_with, decode_with_config, decode_with_profile,
+	UntilWriter,
+};
+use super::encoder::{encode_with, encode_with_config, encode_with_profile};
+use super::header::{read_header, split_stored_tail, write_header, Header};
+use super::iter::{compress_chunks, compress_iter};
+use super::literal::literal_batch;
+use super::memory::{compress, compress_with, decompress as decompress_with_header};
+use super::outcomes::{count_outcomes, Outcomes};
+use super::profile::{check_profile, Profile, DEFAULT_PROFILE_ENTRIES};
+use super::reader::SrxDecoder;
+use super::resync::{decode_resync, encode_resync, Resynced, RESYNC_MARKER};
+use super::shared::{
+	WrappedReader, WrappedWriter, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE, MIN_MESSAGE_BUFFER_SIZE,
+	SRX_HEADER,
+};
+use super::threadless::{decode_threadless, decode_until_threadless, encode_threadless};
+use super::writer::{SrxEncoder, SrxWriter};
+#[cfg(feature = "threads")]
+use crate::basic::with_jitter;
+use crate::basic::{AnyError, AnyResult, Byte, Consumer};
+use crate::bridged_context::{
+	BridgedPrimaryContext, BridgedSecondaryContext, Model, SECONDARY_CONTEXT_SIZE,
+};
+use crate::primary_context::{PrimaryTable, INITIAL_BYTE, INITIAL_HASH};
+use crate::secondary_context::Bit;
+use std::error::Error;
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// -----------------------------------------------
+
+fn decompress(compressed: &[u8]) -> AnyResult<Vec<u8>> {
+	assert_eq!(&compressed[..SRX_HEADER.len()], SRX_HEADER);
+	let reader: Cursor<&[u8]> = Cursor::new(&compressed[SRX_HEADER.len()..]);
+	let (_, output): (Cursor<&[u8]>, Vec<u8>) = decode::<_, _, IO_BUFFER_SIZE>(reader, Vec::new())?;
+	Ok(output)
+}
+
+fn generated() -> impl Iterator<Item = u8> {
+	(0u32..).map(|index| (index.wrapping_mul(index) >> 3) as u8 ^ (index % 7) as u8)
+}
+
+// an output taking at most 3 bytes per call, like a pipe or a socket under pressure may do
+struct ShortWriter(Vec<u8>);
+
+impl Write for ShortWriter {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let length: usize = buffer.len().min(3);
+		self.0.extend_from_slice(&buffer[..length]);
+		Ok(length)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// a nonblocking handle with nothing ready on two calls out of three
+struct WouldBlock<T> {
+	inner: T,
+	calls: usize,
+}
+
+impl<T> WouldBlock<T> {
+	fn new(inner: T) -> Self {
+		Self { inner, calls: 0 }
+	}
+
+	fn ready(&mut self) -> io::Result<()> {
+		self.calls += 1;
+		match self.calls % 3 {
+			0 => Ok(()),
+			_ => Err(io::ErrorKind::WouldBlock.into()),
+		}
+	}
+}
+
+impl<R: Read> Read for WouldBlock<R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		self.ready()?;
+		// a few bytes at a time, so that it would block many times
+		let length: usize = buffer.len().min(1000);
+		self.inner.read(&mut buffer[..length])
+	}
+}
+
+impl<W: Write> Write for WouldBlock<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		self.ready()?;
+		self.inner.write(&buffer[..buffer.len().min(1000)])
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+// an input panicking after the given number of reads, taking the reader stage down with it
+#[cfg(feature = "threads")]
+#[derive(Debug)]
+struct PanickingReader(usize);
+
+#[cfg(feature = "threads")]
+impl Read for PanickingReader {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		if self.0 == 0 {
+			panic!("Reader stage panicked!");
+		}
+		self.0 -= 1;
+		buffer.fill(b'x');
+		Ok(buffer.len())
+	}
+}
+
+// an output panicking on the first write, taking the writer stage down with it
+#[cfg(feature = "threads")]
+#[derive(Debug)]
+struct PanickingWriter;
+
+#[cfg(feature = "threads")]
+impl Write for PanickingWriter {
+	fn write(&mut self, _buffer: &[u8]) -> io::Result<usize> {
+		panic!("Writer stage panicked!");
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_compress_iter_take() -> AnyResult<()> {
+	let expected: Vec<u8> = generated().take(100_000).collect();
+	let compressed: Vec<u8> = compress_iter(generated().take(100_000))?;
+	assert_eq!(decompress(&compressed)?, expected);
+	Ok(())
+}
+
+#[test]
+fn test_compress_iter_empty() -> AnyResult<()> {
+	let compressed: Vec<u8> = compress_iter(std::iter::empty())?;
+	assert!(decompress(&compressed)?.is_empty());
+	Ok(())
+}
+
+#[test]
+fn test_compress_chunks_matches_iter() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	// uneven chunks, including empty ones in the middle of the stream
+	let chunks: Vec<&[u8]> = data
+		.chunks(777)
+		.flat_map(|chunk| [chunk, &[][..]])
+		.collect();
+	let compressed: Vec<u8> = compress_chunks(chunks.into_iter())?;
+	assert_eq!(compressed, compress_iter(data.iter().copied())?);
+	assert_eq!(decompress(&compressed)?, data);
+	Ok(())
+}
+
+#[test]
+fn test_compress_chunks_empty() -> AnyResult<()> {
+	let compressed: Vec<u8> = compress_chunks(std::iter::empty())?;
+	assert!(decompress(&compressed)?.is_empty());
+	Ok(())
+}
+
+// the body of a response written in chunks, each flushed to the client as it goes
+#[test]
+fn test_srx_writer_flushed() -> AnyResult<()> {
+	let body: Vec<u8> = generated().take(300_000).collect();
+	for config in [Config::default(), Config::new(Model::Text).with_lanes(2)] {
+		let mut expected: Vec<u8> = Vec::new();
+		write_header(
+			&mut expected,
+			&Header {
+				config,
+				..Header::default()
+			},
+		)?;
+		let (_, expected): (&[u8], Vec<u8>) =
+			encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(&body[..], expected, &config)?;
+
+		let mut unflushed: SrxWriter<Vec<u8>> = SrxWriter::with_config(Vec::new(), &config)?;
+		unflushed.write_all(&body)?;
+		assert_eq!(unflushed.finish()?, expected);
+
+		let mut flushed: SrxWriter<ShortWriter> =
+			SrxWriter::with_config(ShortWriter(Vec::new()), &config)?;
+		// far less than a chunk of the writer, only given to the client when flushed
+		let header_size: usize = flushed.get_ref().0.len();
+		flushed.write_all(&body[..5000])?;
+		assert_eq!(flushed.get_ref().0.len(), header_size);
+		flushed.flush()?;
+		assert!(flushed.get_ref().0.len() > header_size);
+		let mut rest: &[u8] = &body[5000..];
+		let mut size: usize = 1;
+		while !rest.is_empty() {
+			let (chunk, tail): (&[u8], &[u8]) = rest.split_at(size.min(rest.len()));
+			flushed.write_all(chunk)?;
+			flushed.flush()?;
+			rest = tail;
+			size = size * 3 % 5003 + 1;
+		}
+		let flushed: Vec<u8> = flushed.finish()?.0;
+		assert_eq!(flushed, expected);
+		assert_eq!(decompress_with_header(&flushed)?, body);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_srx_decoder() -> AnyResult<()> {
+	let body: Vec<u8> = generated().take(200_000).collect();
+	for config in [Config::default(), Config::new(Model::Text).with_lanes(3)] {
+		let (_, encoded): (&[u8], Vec<u8>) = encode_with::<
+			_,
+			_,
+			IO_BUFFER_SIZE,
+			MESSAGE_BUFFER_SIZE,
+		>(&body[..], Vec::new(), &config)?;
+		// the output is taken in pieces of all sizes, then nothing once it ended
+		let mut decoder: SrxDecoder<&[u8]> = SrxDecoder::with_config(&encoded[..], &config)?;
+		let mut decoded: Vec<u8> = Vec::new();
+		let mut size: usize = 1;
+		loop {
+			let mut piece: Vec<u8> = vec![0; size];
+			let length: usize = decoder.read(&mut piece)?;
+			if length == 0 {
+				break;
+			}
+			decoded.extend_from_slice(&piece[..length]);
+			size = size * 7 % 4099 + 1;
+		}
+		assert!(decoded == body);
+		assert_eq!(decoder.read(&mut [0; 16])?, 0);
+	}
+	// a file, its header read first
+	let compressed: Vec<u8> = compress_with(&body, &Config::default())?;
+	let mut reader: &[u8] = &compressed;
+	let header: Header = read_header(&mut reader)?;
+	let mut output: Vec<u8> = Vec::new();
+	io::copy(
+		&mut SrxDecoder::with_config(reader, &header.config)?,
+		&mut output,
+	)?;
+	assert!(output == body);
+	Ok(())
+}
+
+#[test]
+fn test_srx_encoder() -> AnyResult<()> {
+	let body: Vec<u8> = generated().take(200_000).collect();
+	for config in [Config::default(), Config::new(Model::Text).with_lanes(3)] {
+		let (_, expected): (&[u8], Vec<u8>) = encode_with::<
+			_,
+			_,
+			IO_BUFFER_SIZE,
+			MESSAGE_BUFFER_SIZE,
+		>(&body[..], Vec::new(), &config)?;
+		// the input comes in pieces of all sizes, as from a socket, the end included
+		let mut encoder: SrxEncoder<Vec<u8>> = SrxEncoder::with_config(Vec::new(), &config)?;
+		let mut rest: &[u8] = &body;
+		let mut size: usize = 0;
+		while !rest.is_empty() {
+			let (chunk, tail): (&[u8], &[u8]) = rest.split_at(size.min(rest.len()));
+			encoder.write_all(chunk)?;
+			rest = tail;
+			size = size * 7 % 4099 + 1;
+		}
+		let encoded: Vec<u8> = encoder.finish()?;
+		assert_eq!(encoded, expected);
+		let (_, decoded): (&[u8], Vec<u8>) =
+			decode_with::<_, _, IO_BUFFER_SIZE>(&encoded[..], Vec::new(), &config)?;
+		assert!(decoded == body);
+	}
+	// nothing written still has an end
+	let empty: Vec<u8> = SrxEncoder::new(Vec::new())?.finish()?;
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		&[][..],
+		Vec::new(),
+		&Config::default(),
+	)?;
+	assert_eq!(empty, expected);
+	Ok(())
+}
+
+#[test]
+fn test_literal_steps_all_bytes() -> AnyResult<()> {
+	for value in 0..=255usize {
+		// the bit by bit decomposition the table replaced
+		let high: usize = (value >> 4) | 16;
+		let low_context: usize = 15 * (high - 15);
+		let low: usize = (value & 15) | 16;
+		let expected: [(usize, Bit); 8] = [
+			(1, Bit::from(high >> 3 & 1)),
+			(high >> 3, Bit::from(high >> 2 & 1)),
+			(high >> 2, Bit::from(high >> 1 & 1)),
+			(high >> 1, Bit::from(high & 1)),
+			(low_context + 1, Bit::from(low >> 3 & 1)),
+			(low_context + (low >> 3), Bit::from(low >> 2 & 1)),
+			(low_context + (low >> 2), Bit::from(low >> 1 & 1)),
+			(low_context + (low >> 1), Bit::from(low & 1)),
+		];
+		let (offsets, bits): ([usize; 8], [Bit; 8]) = literal_batch(Byte::from(value));
+		let actual: Vec<(usize, Bit)> = offsets.into_iter().zip(bits).collect();
+		assert_eq!(actual, expected, "byte {}", value);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_header_round_trip() -> AnyResult<()> {
+	// the default header is the bare magic
+	let mut bytes: Vec<u8> = Vec::new();
+	Header::default().write(&mut bytes)?;
+	assert_eq!(bytes, SRX_HEADER);
+	assert_eq!(Header::read(&mut bytes.as_slice())?, Header::default());
+	// every section at once, the reader left right after the header
+	let header: Header = Header {
+		config: Config::new(Model::Text)
+			.with_lanes(3)
+			.with_growth(Some(Growth::default())),
+		original_size: Some(1 << 40),
+		content_hash: Some(vec![0x5A; 20]),
+		profile_id: Some(0xDEADBEEF),
+		checksum: Some(Checksum {
+			algo: ChecksumAlgo::XxHash64,
+			digest: vec![7; 8],
+		}),
+		volume_size: Some(1 << 20),
+		metadata: Some(b"metadata".to_vec()),
+		stored_tail: false,
+		blocks: None,
+	};
+	let mut bytes: Vec<u8> = Vec::new();
+	header.write(&mut bytes)?;
+	assert_eq!(bytes[3], 0xFF);
+	bytes.extend_from_slice(b"payload");
+	let mut reader: &[u8] = &bytes;
+	assert_eq!(Header::read(&mut reader)?, header);
+	assert_eq!(reader, b"payload");
+	// a stored tail is the only change that takes the records
+	let header: Header = Header {
+		stored_tail: true,
+		..Header::default()
+	};
+	let mut bytes: Vec<u8> = Vec::new();
+	header.write(&mut bytes)?;
+	assert_eq!(bytes, b"sRz\x89\0\0\0\0\x00");
+	assert_eq!(Header::read(&mut bytes.as_slice())?, header);
+	Ok(())
+}
+
+#[test]
+fn test_header_records() -> AnyResult<()> {
+	// every section as a record, the reader left right after the end record
+	let header: Header = Header {
+		config: Config::new(Model::TwoWay).with_lanes(2),
+		original_size: Some(12345),
+		content_hash: Some(vec![0x5A; 20]),
+		profile_id: Some(0xDEADBEEF),
+		checksum: Some(Checksum {
+			algo: ChecksumAlgo::Crc32,
+			digest: vec![7; 4],
+		}),
+		volume_size: Some(1 << 20),
+		metadata: Some(b"metadata".to_vec()),
+		stored_tail: true,
+		blocks: Some(vec![
+			Block {
+				original_size: 12345,
+				compressed_size: 678,
+			},
+			Block::default(),
+		]),
+	};
+	let mut bytes: Vec<u8> = Vec::new();
+	header.write(&mut bytes)?;
+	assert_eq!(&bytes[..4], b"sRz\x81");
+	bytes.extend_from_slice(b"payload");
+	let mut reader: &[u8] = &bytes;
+	assert_eq!(Header::read(&mut reader)?, header);
+	assert_eq!(reader, b"payload");
+
+	// a record added by a newer srx, before the end record
+	let with_record = |record: &[u8]| -> Vec<u8> {
+		let end: usize = bytes.len() - b"payload".len() - 1;
+		[&bytes[..end], record, &bytes[end..]].concat()
+	};
+	// one that only informs is skipped
+	let optional: Vec<u8> = with_record(b"\x7F\x03\0\0\0new");
+	let mut reader: &[u8] = &optional;
+	assert_eq!(Header::read(&mut reader)?, header);
+	assert_eq!(reader, b"payload");
+	// so is a checksum of an algorithm this srx does not know, which goes unchecked
+	let unknown_checksum: Vec<u8> = with_record(b"\x06\x05\0\0\0\x63abcd");
+	let mut reader: &[u8] = &unknown_checksum;
+	assert_eq!(Header::read(&mut reader)?.checksum, header.checksum);
+	assert_eq!(reader, b"payload");
+	let without_checksum: Header = Header {
+		checksum: None,
+		..header.clone()
+	};
+	let mut bytes_without: Vec<u8> = Vec::new();
+	without_checksum.write(&mut bytes_without)?;
+	bytes_without.pop();
+	bytes_without.extend_from_slice(b"\x06\x05\0\0\0\x63abcd\x00");
+	assert_eq!(
+		Header::read(&mut bytes_without.as_slice())?,
+		without_checksum
+	);
+	// one that is needed to decode is refused
+	let required: Vec<u8> = with_record(b"\xFF\x03\0\0\0new");
+	assert_eq!(
+		Header::read(&mut required.as_slice())
+			.unwrap_err()
+			.to_string(),
+		"Unsupported SRX header record, made by a newer srx?"
+	);
+	// and so are a known record of the wrong length, or records cut short
+	assert_eq!(
+		Header::read(&mut with_record(b"\x03\x09\0\0\0\0\0\0\0\0\0\0\0\0").as_slice())
+			.unwrap_err()
+			.to_string(),
+		"Invalid SRX header record!"
+	);
+	assert!(Header::read(&mut &b"sRz\x7F\x03\0\0\0ne"[..]).is_err());
+	assert!(Header::read(&mut &b"sRz\x03\x08\0\0\0\0\0"[..]).is_err());
+	assert!(Header::read(&mut &b"sRz"[..]).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_header_rejected() {
+	let error = |bytes: &[u8]| Header::read(&mut &bytes[..]).unwrap_err().to_string();
+	// another magic, or the magic of a version this srx does not know
+	assert_eq!(error(b"PK\x03\x04"), "Not a SRX compressed file!");
+	assert_eq!(error(b"sQx\x00"), "Not a SRX compressed file!");
+	assert_eq!(
+		error(b"sRq\x00"),
+		"Unsupported SRX format version, made by a newer srx?"
+	);
+	assert_eq!(
+		error(b"sRy\x00\x01"),
+		"Unsupported SRX format version, made by a newer srx?"
+	);
+	// flags announcing sections that are not valid
+	assert_eq!(error(b"sRx\x02\xC8\x01"), "Unknown SRX model!");
+	assert!(Header::read(&mut &b"sRx\x02\x00\x00"[..]).is_err());
+	assert_eq!(error(b"sRx\x40\x63"), "Unknown SRX checksum algorithm!");
+	assert_eq!(
+		error(b"sRx\x80\0\0\0\0\0\0\0\0"),
+		"Invalid SRX volume size!"
+	);
+	// or missing
+	assert!(Header::read(&mut &b"sR"[..]).is_err());
+	assert!(Header::read(&mut &b"sRx\x08\x01\x02"[..]).is_err());
+	// and nothing invalid is written either
+	let header: Header = Header {
+		checksum: Some(Checksum {
+			algo: ChecksumAlgo::Crc32,
+			digest: vec![0; 3],
+		}),
+		..Header::default()
+	};
+	assert!(header.write(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_error_kinds() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(10_000).collect();
+	let compressed: Vec<u8> = compress(&data)?;
+	// the kinds a caller matches on instead of the messages
+	assert!(matches!(
+		decompress_with_header(b"PK\x03\x04"),
+		Err(AnyError::BadHeader(_))
+	));
+	assert!(matches!(
+		decompress_with_header(&compressed[..2]),
+		Err(AnyError::TruncatedInput(_))
+	));
+	assert!(matches!(
+		split_stored_tail(&[0; 4]),
+		Err(AnyError::TruncatedInput(_))
+	));
+	let checksum: Checksum = Checksum::of(ChecksumAlgo::Crc32, &data)?;
+	assert!(matches!(
+		checksum.check(&Checksum::of(ChecksumAlgo::Crc32, &compressed)?),
+		Err(AnyError::ChecksumMismatch(_))
+	));
+	// the reader failing is given back as an io::Error, also as the source of the error
+	let error: AnyError =
+		decode_with::<_, _, 0x1000>(FailingReader, Vec::new(), &Config::default()).unwrap_err();
+	assert_eq!(error.to_string(), "The disk is gone!");
+	let source: &io::Error = error.source().unwrap().downcast_ref().unwrap();
+	assert_eq!(source.kind(), io::ErrorKind::Other);
+	assert!(matches!(error, AnyError::Io(_)));
+	// and it goes wherever an error of std goes, across threads too
+	let boxed: Box<dyn Error + Send + Sync> =
+		Box::new(AnyError::bad_header("Not a SRX compressed file!"));
+	assert_eq!(boxed.to_string(), "Not a SRX compressed file!");
+	// a closed channel is a broken pipe to the callers of Read and Write
+	let (sender, receiver): (Sender<u8>, Receiver<u8>) = channel();
+	drop(receiver);
+	let error: AnyError = sender.send(0).unwrap_err().into();
+	assert!(error.is_channel_closed());
+	assert_eq!(io::Error::from(error).kind(), io::ErrorKind::BrokenPipe);
+	// a panic is kept as its message, whatever it was raised with
+	assert_eq!(
+		AnyError::from_box(Box::new(String::from("Panicked!"))).to_string(),
+		"Panicked!"
+	);
+	assert_eq!(
+		AnyError::from_box(Box::new("Panicked!")).to_string(),
+		"Panicked!"
+	);
+	assert_eq!(
+		AnyError::from_box(Box::new(42)).to_string(),
+		"Panicked with a payload that is not a message!"
+	);
+	Ok(())
+}
+
+#[derive(Debug)]
+struct FailingReader;
+
+impl Read for FailingReader {
+	fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+		Err(io::Error::other("The disk is gone!"))
+	}
+}
+
+#[test]
+fn test_compress_slices() -> AnyResult<()> {
+	let mut state: u32 = 7;
+	let random: Vec<u8> = (0..4096)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			(state >> 24) as u8
+		})
+		.collect();
+	let structured: Vec<u8> = generated().take(6000).collect();
+	for data in [&[][..], &random[..], &structured[..]] {
+		let compressed: Vec<u8> = compress(data)?;
+		assert!(compressed.starts_with(&SRX_HEADER[..2]));
+		assert_eq!(compressed, compress_with(data, &Config::default())?);
+		assert_eq!(decompress_with_header(&compressed)?, data);
+	}
+	// nothing but an SRX header is taken
+	assert!(decompress_with_header(&random).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_original_size_checked() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(10_000).collect();
+	let compressed: Vec<u8> = compress_with(&data, &Config::default())?;
+	let mut payload: &[u8] = &compressed;
+	let header: Header = read_header(&mut payload)?;
+	assert_eq!(header.original_size, Some(data.len() as u64));
+	assert!(header.check_size(data.len() as u64).is_ok());
+	assert!(header.check_size(data.len() as u64 + 1).is_err());
+	// the same payload behind a header recording another size, or none
+	for (original_size, valid) in [
+		(Some(data.len() as u64 - 1), false),
+		(Some(data.len() as u64 + 1), false),
+		(None, true),
+	] {
+		let mut rewritten: Vec<u8> = Vec::new();
+		write_header(
+			&mut rewritten,
+			&Header {
+				original_size,
+				..Header::default()
+			},
+		)?;
+		rewritten.extend_from_slice(payload);
+		match valid {
+			true => assert_eq!(decompress_with_header(&rewritten)?, data),
+			false => assert!(decompress_with_header(&rewritten).is_err()),
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_header_metadata() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(10_000).collect();
+	let compressed: Vec<u8> = compress_with(&data, &Config::default())?;
+	let mut payload: &[u8] = &compressed;
+	assert_eq!(
+		read_header(&mut payload)?,
+		Header {
+			original_size: Some(data.len() as u64),
+			..Header::default()
+		}
+	);
+	// the same payload behind a header carrying metadata
+	let mut with_metadata: Vec<u8> = Vec::new();
+	write_header(
+		&mut with_metadata,
+		&Header {
+			metadata: Some(b"some metadata".to_vec()),
+			..Header::default()
+		},
+	)?;
+	with_metadata.extend_from_slice(payload);
+	assert_eq!(
+		read_header(&mut with_metadata.as_slice())?
+			.metadata
+			.as_deref(),
+		Some(b"some metadata".as_slice())
+	);
+	assert_eq!(decompress_with_header(&with_metadata)?, data);
+	// a content hash goes between the original size and the metadata
+	let header: Header = Header {
+		original_size: Some(data.len() as u64),
+		content_hash: Some(vec![0xAB; 32]),
+		metadata: Some(b"some metadata".to_vec()),
+		..Header::default()
+	};
+	let mut with_hash: Vec<u8> = Vec::new();
+	write_header(&mut with_hash, &header)?;
+	with_hash.extend_from_slice(payload);
+	assert_eq!(read_header(&mut with_hash.as_slice())?, header);
+	assert_eq!(decompress_with_header(&with_hash)?, data);
+	for content_hash in [Vec::new(), vec![0; 256]] {
+		let header: Header = Header {
+			content_hash: Some(content_hash),
+			..Header::default()
+		};
+		assert!(write_header(&mut Vec::new(), &header).is_err());
+	}
+	// truncated metadata and an empty volume size are rejected
+	assert!(read_header(&mut &with_metadata[..10]).is_err());
+	assert!(read_header(&mut b"sRx\x80\0\0\0\0\0\0\0\0".as_slice()).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_stored_tail() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(20_000).collect();
+	let compressed: Vec<u8> = compress_with(&data[..12_000], &Config::default())?;
+	let mut payload: &[u8] = &compressed;
+	read_header(&mut payload)?;
+	// the rest of the data and its size follow the payload
+	let header: Header = Header {
+		original_size: Some(data.len() as u64),
+		checksum: Some(Checksum::of(ChecksumAlgo::Crc32, &data)?),
+		stored_tail: true,
+		..Header::default()
+	};
+	let mut with_tail: Vec<u8> = Vec::new();
+	write_header(&mut with_tail, &header)?;
+	with_tail.extend_from_slice(payload);
+	with_tail.extend_from_slice(&data[12_000..]);
+	with_tail.extend_from_slice(&8_000u64.to_le_bytes());
+	assert_eq!(decompress_with_header(&with_tail)?, data);
+	let mut rest: &[u8] = &with_tail;
+	assert_eq!(read_header(&mut rest)?, header);
+	assert_eq!(split_stored_tail(rest)?, (payload, &data[12_000..]));
+	// a tail longer than the file, or no room for its size
+	assert!(split_stored_tail(&with_tail[with_tail.len() - 100..]).is_err());
+	assert!(split_stored_tail(&[0; 7]).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_trailing_bytes() -> AnyResult<()> {
+	let strict: Config = Config::default().with_strict_end(true);
+	for size in [0, 1, 100, 20_000] {
+		let data: Vec<u8> = generated().take(size).collect();
+		let compressed: Vec<u8> = compress_with(&data, &Config::default())?;
+		let mut payload: &[u8] = &compressed;
+		read_header(&mut payload)?;
+		let (_, output): (&[u8], Vec<u8>) = decode_with_config(payload, Vec::new(), &strict)?;
+		assert!(output == data, "{} bytes", size);
+		// appended bytes are ignored, unless the end is checked
+		for trailing in [&[0x00][..], &[0x41], &[0xFF], &[0x00; 5]] {
+			let appended: Vec<u8> = [payload, trailing].concat();
+			let (_, output): (&[u8], Vec<u8>) =
+				decode_with_config(appended.as_slice(), Vec::new(), &Config::default())?;
+			assert!(output == data, "{} bytes, {:?} appended", size, trailing);
+			let error: AnyError =
+				decode_with_config(appended.as_slice(), Vec::new(), &strict).unwrap_err();
+			assert_eq!(
+				error.to_string(),
+				"Unexpected bytes after the end of the SRX stream!"
+			);
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_checksum_round_trip() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(20_000).collect();
+	let compressed: Vec<u8> = compress_with(&data, &Config::default())?;
+	let mut payload: &[u8] = &compressed;
+	read_header(&mut payload)?;
+	for algo in ChecksumAlgo::ALL {
+		if algo == ChecksumAlgo::Sha256 && cfg!(not(feature = "sha256")) {
+			assert!(ChecksumHasher::new(algo).is_err());
+			continue;
+		}
+		let checksum: Checksum = Checksum::of(algo, &data)?;
+		assert_eq!(checksum.digest.len(), algo.digest_size());
+		// the decoder picks the algorithm by the id in the header
+		let mut compressed: Vec<u8> = Vec::new();
+		let header: Header = Header {
+			checksum: Some(checksum.clone()),
+			..Header::default()
+		};
+		write_header(&mut compressed, &header)?;
+		compressed.extend_from_slice(payload);
+		assert_eq!(read_header(&mut compressed.as_slice())?, header);
+		assert_eq!(decompress_with_header(&compressed)?, data);
+		// hashing as the output is written gives the same checksum
+		let writer: ChecksumWriter<Vec<u8>> = ChecksumWriter::new(Vec::new(), Some(algo))?;
+		let (_, writer): (&[u8], ChecksumWriter<Vec<u8>>) =
+			decode_with::<_, _, IO_BUFFER_SIZE>(payload, writer, &Config::default())?;
+		let (output, actual): (Vec<u8>, Option<Checksum>) = writer.into_inner();
+		assert_eq!(output, data);
+		assert_eq!(actual.as_ref(), Some(&checksum));
+		// an output not matching the checksum is an error
+		let mut compressed: Vec<u8> = Vec::new();
+		let header: Header = Header {
+			checksum: Some(Checksum::of(algo, &data[1..])?),
+			..Header::default()
+		};
+		write_header(&mut compressed, &header)?;
+		compressed.extend_from_slice(payload);
+		assert!(decompress_with_header(&compressed).is_err());
+	}
+	// an unknown algorithm id
+	assert!(read_header(&mut b"sRx\x40\x00".as_slice()).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_short_writes() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let config: Config = Config::new(Model::Text).with_lanes(2);
+
+	// the header and the payload come out the same as when written in one go
+	let header: Header = Header {
+		config,
+		original_size: Some(data.len() as u64),
+		content_hash: Some(b"some hash".to_vec()),
+		profile_id: Some(0x12345678),
+		checksum: Some(Checksum::of(ChecksumAlgo::XxHash64, &data)?),
+		volume_size: Some(1 << 30),
+		metadata: Some(b"some metadata".to_vec()),
+		stored_tail: false,
+		blocks: None,
+	};
+	let mut expected: Vec<u8> = Vec::new();
+	write_header(&mut expected, &header)?;
+	let mut writer: ShortWriter = ShortWriter(Vec::new());
+	write_header(&mut writer, &header)?;
+	assert_eq!(writer.0, expected);
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		expected,
+		&config,
+	)?;
+	let (_, writer): (&[u8], ShortWriter) =
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(data.as_slice(), writer, &config)?;
+	assert!(writer.0 == expected);
+
+	// and so does the decoded output
+	let mut reader: &[u8] = &expected;
+	assert_eq!(read_header(&mut reader)?, header);
+	let (_, writer): (&[u8], ShortWriter) =
+		decode_with::<_, _, IO_BUFFER_SIZE>(reader, ShortWriter(Vec::new()), &config)?;
+	assert!(writer.0 == data);
+	Ok(())
+}
+
+#[test]
+fn test_would_block() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let config: Config = Config::default();
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&config,
+	)?;
+	let (reader, writer): (WouldBlock<&[u8]>, WouldBlock<Vec<u8>>) =
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			WouldBlock::new(data.as_slice()),
+			WouldBlock::new(Vec::new()),
+			&config,
+		)?;
+	// the calls that would block are retried, until the end of the input
+	assert!(reader.calls > 3 * (data.len() / 1000));
+	assert!(writer.inner == expected);
+	let (_, writer): (WouldBlock<&[u8]>, WouldBlock<Vec<u8>>) = decode_with::<_, _, IO_BUFFER_SIZE>(
+		WouldBlock::new(expected.as_slice()),
+		WouldBlock::new(Vec::new()),
+		&config,
+	)?;
+	assert!(writer.inner == data);
+	// and so are they for the segments of a resync stream
+	let (_, resynced): (WouldBlock<&[u8]>, Vec<u8>) = encode_resync(
+		WouldBlock::new(data.as_slice()),
+		Vec::new(),
+		&config,
+		10_000,
+	)?;
+	let (_, reference): (&[u8], Vec<u8>) =
+		encode_resync(data.as_slice(), Vec::new(), &config, 10_000)?;
+	assert!(resynced == reference);
+	Ok(())
+}
+
+#[test]
+fn test_decode_until() -> AnyResult<()> {
+	// letters with a single sentinel, far from the end
+	let mut state: u32 = 1;
+	let mut data: Vec<u8> = (0..1 << 20)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			b'a' + (state >> 24) as u8 % 26
+		})
+		.collect();
+	data[100_000] = b'#';
+	let config: Config = Config::default();
+	let (_, compressed): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&config,
+	)?;
+
+	// stops right after the chunk with the sentinel, long before the end of the input
+	let mut reader: LargestReadReader = LargestReadReader {
+		data: &compressed,
+		largest: 0,
+	};
+	let (output, found): (Vec<u8>, bool) =
+		decode_until::<_, _, _, 0x1000>(&mut reader, Vec::new(), &config, |chunk: &[u8]| {
+			chunk.contains(&b'#')
+		})?;
+	assert!(found);
+	assert_eq!(output.len(), (100_000 / 0x1000 + 1) * 0x1000);
+	assert!(output == data[..output.len()]);
+	assert!(reader.data.len() > compressed.len() / 2);
+
+	// or goes to the end without it
+	let (output, found): (Vec<u8>, bool) = decode_until::<_, _, _, 0x1000>(
+		compressed.as_slice(),
+		Vec::new(),
+		&config,
+		|_: &[u8]| false,
+	)?;
+	assert!(!found);
+	assert!(output == data);
+	Ok(())
+}
+
+// starts a new file every K bytes, taking only what fits in the current one from each chunk
+struct RotatingConsumer {
+	size: usize,
+	files: Vec<Vec<u8>>,
+	max_files: usize,
+}
+
+impl Consumer<u8> for RotatingConsumer {
+	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
+		if self
+			.files
+			.last()
+			.is_none_or(|file: &Vec<u8>| file.len() == self.size)
+		{
+			if self.files.len() == self.max_files {
+				return Err(AnyError::from_string("Too many files!"));
+			}
+			self.files.push(Vec::new());
+		}
+		let file: &mut Vec<u8> = self.files.last_mut().unwrap();
+		let length: usize = buffer.len().min(self.size - file.len());
+		file.extend_from_slice(&buffer[..length]);
+		Ok(length)
+	}
+}
+
+#[test]
+fn test_decode_to_consumer() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let config: Config = Config::default().with_lanes(2);
+	let (_, compressed): (&[u8], Vec<u8>) =
+		encode_with::<_, _, 0x1000, 0x1000>(data.as_slice(), Vec::new(), &config)?;
+	let rotating = |max_files: usize| RotatingConsumer {
+		size: 3000,
+		files: Vec::new(),
+		max_files,
+	};
+	// the chunks of the pipes do not line up with the files
+	let (rest, consumer): (&[u8], RotatingConsumer) = decode_to_consumer::<_, _, 0x1000>(
+		compressed.as_slice(),
+		rotating(usize::MAX),
+		&config,
+		None,
+	)?;
+	assert!(rest.is_empty());
+	assert_eq!(consumer.files.len(), data.len().div_ceil(3000));
+	assert!(consumer
+		.files
+		.iter()
+		.rev()
+		.skip(1)
+		.all(|file: &Vec<u8>| file.len() == 3000));
+	assert!(consumer.files.concat() == data);
+	// same on the calling thread
+	let (_, consumer): (&[u8], RotatingConsumer) = decode_threadless::<_, _, 0x1000>(
+		compressed.as_slice(),
+		rotating(usize::MAX),
+		config,
+		None,
+	)?;
+	assert!(consumer.files.concat() == data);
+	// the error of the consumer is the error of the decoding
+	let error: AnyError =
+		decode_to_consumer::<_, _, 0x1000>(compressed.as_slice(), rotating(10), &config, None)
+			.err()
+			.unwrap();
+	assert_eq!(error.to_string(), "Too many files!");
+	Ok(())
+}
+
+// records whose contexts share their last 4 bytes, the byte before them starting with first
+// or second, which differ in the fifth bit only
+fn colliding_records(first: u8, second: u8) -> Vec<u8> {
+	let mut state: u32 = 3;
+	let mut output: Vec<u8> = Vec::new();
+	for _ in 0..4000 {
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		let suffix: [u8; 4] = state.to_le_bytes().map(|byte: u8| b'a' + byte % 26);
+		for _ in 0..4 {
+			for (head, tail) in [(first, b'1'), (second, b'2')] {
+				output.push(head);
+				output.extend_from_slice(&suffix);
+				output.push(tail);
+			}
+		}
+	}
+	output
+}
+
+#[test]
+fn test_count_collisions() -> AnyResult<()> {
+	// the hash of the default table ignores the high bits of the fifth last byte
+	let colliding: Collisions =
+		count_collisions(colliding_records(b'A', b'Q').as_slice(), &Config::default())?;
+	assert!(colliding.lookups > 100, "{:?}", colliding);
+	assert!(colliding.rate() > 0.1, "{:?}", colliding);
+	// the same records keep their entries when that byte differs in its low bits
+	let apart: Collisions =
+		count_collisions(colliding_records(b'A', b'B').as_slice(), &Config::default())?;
+	assert!(apart.lookups > 100, "{:?}", apart);
+	assert!(apart.rate() < 0.05, "{:?}", apart);
+	// a single context is never a collision, whatever the lanes
+	let repeated: Vec<u8> = vec![b'x'; 10_000];
+	for lanes in [1, 3] {
+		let config: Config = Config::default().with_lanes(lanes);
+		assert_eq!(
+			count_collisions(repeated.as_slice(), &config)?.collisions,
+			0
+		);
+	}
+	assert_eq!(
+		count_collisions(&[][..], &Config::default())?,
+		Collisions::default()
+	);
+	Ok(())
+}
+
+fn markers(stream: &[u8]) -> Vec<usize> {
+	stream
+		.windows(RESYNC_MARKER.len())
+		.enumerate()
+		.filter(|(_, window)| *window == RESYNC_MARKER)
+		.map(|(index, _)| index)
+		.collect()
+}
+
+#[test]
+fn test_resync_round_trip() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(40_000).collect();
+	let (_, stream): (&[u8], Vec<u8>) =
+		encode_resync(data.as_slice(), Vec::new(), &Config::default(), 10_000)?;
+	// the escaping keeps the marker out of the segment bodies
+	let found: Vec<usize> = markers(&stream);
+	assert_eq!(found.len(), 4);
+	assert_eq!(found[0], 0);
+	let (resynced, output): (Resynced, Vec<u8>) = decode_resync(stream.as_slice(), Vec::new())?;
+	assert_eq!(
+		resynced,
+		Resynced {
+			size: 40_000,
+			lost: Vec::new(),
+			damaged: 0
+		}
+	);
+	assert_eq!(output, data);
+
+	// an empty input is still a stream with one segment
+	let (_, stream): (&[u8], Vec<u8>) =
+		encode_resync(&[][..], Vec::new(), &Config::default(), 10_000)?;
+	let (resynced, output): (Resynced, Vec<u8>) = decode_resync(stream.as_slice(), Vec::new())?;
+	assert_eq!(resynced, Resynced::default());
+	assert!(output.is_empty());
+	assert!(decode_resync(&b"not a resync stream"[..], Vec::new()).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_resync_damaged_region() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(40_000).collect();
+	let (_, stream): (&[u8], Vec<u8>) =
+		encode_resync(data.as_slice(), Vec::new(), &Config::default(), 10_000)?;
+	let found: Vec<usize> = markers(&stream);
+	assert_eq!(found.len(), 4);
+
+	// damage the bytes between the second and the third marker
+	let mut damaged: Vec<u8> = stream.clone();
+	for value in &mut damaged[found[1] + 20..found[2] - 20] {
+		*value = value.wrapping_mul(7).wrapping_add(13);
+	}
+	let (resynced, output): (Resynced, Vec<u8>) = decode_resync(damaged.as_slice(), Vec::new())?;
+	assert_eq!(resynced.lost, vec![10_000..20_000]);
+	assert_eq!(resynced.damaged, 1);
+	assert_eq!(output.len(), data.len());
+	assert_eq!(output[..10_000], data[..10_000]);
+	assert!(output[10_000..20_000].iter().all(|&value| value == 0));
+	assert_eq!(output[20_000..], data[20_000..]);
+
+	// bytes missing across a marker take both segments around it
+	let mut missing: Vec<u8> = stream[..found[2] - 10].to_vec();
+	missing.extend_from_slice(&stream[found[2] + 10..]);
+	let (resynced, output): (Resynced, Vec<u8>) = decode_resync(missing.as_slice(), Vec::new())?;
+	assert_eq!(resynced.lost, vec![10_000..30_000]);
+	assert_eq!(resynced.damaged, 1);
+	assert_eq!(output[30_000..], data[30_000..]);
+	Ok(())
+}
+
+#[test]
+fn test_blocks_round_trip() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(40_000).collect();
+	for count in [1, 3, 4, MAX_BLOCKS] {
+		let (blocks, payloads): (Vec<Block>, Vec<Vec<u8>>) =
+			encode_blocks(&data, count, &Config::default(), None)?;
+		// blocks of the same size give or take a byte
+		assert_eq!(blocks.len(), count, "{} blocks", count);
+		let size: u64 = blocks[0].original_size;
+		assert!(blocks
+			.iter()
+			.all(|block| block.original_size == size || block.original_size == size - 1));
+		assert_eq!(
+			blocks.iter().map(|block| block.original_size).sum::<u64>(),
+			data.len() as u64
+		);
+		// each payload decodes on its own, the last one to the end of the data
+		for (block, payload) in blocks.iter().zip(&payloads) {
+			assert_eq!(block.compressed_size, payload.len() as u64);
+		}
+		let (_, output): (&[u8], Vec<u8>) = decode_with_config(
+			payloads[payloads.len() - 1].as_slice(),
+			Vec::new(),
+			&Config::default(),
+		)?;
+		assert!(data.ends_with(&output));
+		assert_eq!(output.len() as u64, blocks[blocks.len() - 1].original_size);
+		// and all of them one after the other, the reader left after the last one
+		let stream: Vec<u8> = [payloads.concat().as_slice(), b"after"].concat();
+		let (rest, output): (&[u8], Vec<u8>) = decode_blocks(
+			stream.as_slice(),
+			Vec::new(),
+			&blocks,
+			&Config::default(),
+			None,
+		)?;
+		assert!(output == data, "{} blocks", count);
+		assert_eq!(rest, b"after");
+	}
+	// the longer blocks first, fewer blocks than asked for when there are fewer bytes, and one
+	// for no bytes at all
+	let sizes = |data: &[u8], count: usize| -> AnyResult<Vec<u64>> {
+		let (blocks, _): (Vec<Block>, Vec<Vec<u8>>) =
+			encode_blocks(data, count, &Config::default(), None)?;
+		Ok(blocks.iter().map(|block| block.original_size).collect())
+	};
+	assert_eq!(sizes(&data[..10], 4)?, [3, 3, 2, 2]);
+	assert_eq!(sizes(&data[..10], 40)?, [1; 10]);
+	let (blocks, payloads): (Vec<Block>, Vec<Vec<u8>>) =
+		encode_blocks(&[], 4, &Config::default(), None)?;
+	assert_eq!(blocks.len(), 1);
+	let (_, output): (&[u8], Vec<u8>) = decode_blocks(
+		payloads[0].as_slice(),
+		Vec::new(),
+		&blocks,
+		&Config::default(),
+		None,
+	)?;
+	assert!(output.is_empty());
+	for count in [0, MAX_BLOCKS + 1] {
+		assert!(encode_blocks(&data, count, &Config::default(), None).is_err());
+	}
+	Ok(())
+}
+
+#[test]
+fn test_blocks_damaged() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(40_000).collect();
+	let (blocks, payloads): (Vec<Block>, Vec<Vec<u8>>) =
+		encode_blocks(&data, 4, &Config::default(), None)?;
+	let stream: Vec<u8> = payloads.concat();
+	let decoded = |stream: &[u8], blocks: &[Block]| -> AnyResult<Vec<u8>> {
+		Ok(decode_blocks(stream, Vec::new(), blocks, &Config::default(), None)?.1)
+	};
+	// a payload cut short
+	let error: AnyError = decoded(&stream[..stream.len() - 1], &blocks).unwrap_err();
+	assert_eq!(error.to_string(), "Truncated SRX block!");
+	// an index that does not match the payloads, whichever way
+	let mut longer: Vec<Block> = blocks.clone();
+	longer[2].original_size += 1;
+	let error: AnyError = decoded(&stream, &longer).unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"A block decoded to 10000 bytes, not the 10001 bytes of the index!"
+	);
+	let mut shorter: Vec<Block> = blocks.clone();
+	shorter[2].original_size -= 1;
+	let error: AnyError = decoded(&stream, &shorter).unwrap_err();
+	assert_eq!(error.to_string(), "The output is longer than declared!");
+
+	// the whole file, read back with the index from its header
+	let header: Header = Header {
+		original_size: Some(data.len() as u64),
+		blocks: Some(blocks),
+		..Header::default()
+	};
+	let mut file: Vec<u8> = Vec::new();
+	write_header(&mut file, &header)?;
+	assert_eq!(&file[..3], b"sRz");
+	file.extend_from_slice(&stream);
+	assert_eq!(decompress_with_header(&file)?, data);
+	// an index that older srx can not do without, and none without blocks
+	let mut rest: &[u8] = &file;
+	assert_eq!(read_header(&mut rest)?, header);
+	let empty: Header = Header {
+		blocks: Some(Vec::new()),
+		..Header::default()
+	};
+	assert!(write_header(&mut Vec::new(), &empty).is_err());
+	assert!(read_header(&mut &b"sRz\x8A\0\0\0\0\x00"[..]).is_err());
+	assert!(read_header(&mut &b"sRz\x8A\x08\0\0\0\0\0\0\0\0\0\0\0\x00"[..]).is_err());
+	Ok(())
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_log_checksum_mismatch() -> AnyResult<()> {
+	use log::{Level, Log, Metadata, Record};
+	use std::sync::Mutex;
+
+	struct CapturingLogger(Mutex<Vec<(Level, String)>>);
+
+	impl Log for CapturingLogger {
+		fn enabled(&self, _: &Metadata) -> bool {
+			true
+		}
+
+		fn log(&self, record: &Record) {
+			self.0
+				.lock()
+				.unwrap()
+				.push((record.level(), record.args().to_string()));
+		}
+
+		fn flush(&self) {}
+	}
+
+	static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+	log::set_logger(&LOGGER).map_err(|error| AnyError::from_string(error.to_string()))?;
+	log::set_max_level(log::LevelFilter::Trace);
+
+	let data: Vec<u8> = generated().take(20_000).collect();
+	let (_, mut stream): (&[u8], Vec<u8>) =
+		encode_resync(data.as_slice(), Vec::new(), &Config::default(), 10_000)?;
+	// the CRC-32 of the second segment follows its marker and offset, neither holding a 0xFF
+	let checksum: usize = markers(&stream)[1] + RESYNC_MARKER.len() + 8;
+	let index: usize = (checksum..checksum + 4)
+		.find(|&index| stream[index] < 0xFE)
+		.unwrap();
+	stream[index] ^= 1;
+	let (resynced, _): (Resynced, Vec<u8>) = decode_resync(stream.as_slice(), Vec::new())?;
+	assert_eq!(resynced.damaged, 1);
+	assert!(LOGGER.0.lock().unwrap().contains(&(
+		Level::Warn,
+		"Skipping a segment: Segment checksum mismatch!".to_string()
+	)));
+
+	// a stream as srx c --checksum writes it, its header recording the CRC-32 of other data
+	let mut stream: Vec<u8> = Vec::new();
+	write_header(
+		&mut stream,
+		&Header {
+			original_size: Some(data.len() as u64),
+			checksum: Some(Checksum::of(ChecksumAlgo::Crc32, &data[1..])?),
+			..Header::default()
+		},
+	)?;
+	let (_, stream): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		stream,
+		&Config::default(),
+	)?;
+	assert!(matches!(
+		decompress_with_header(&stream),
+		Err(AnyError::ChecksumMismatch(_))
+	));
+	assert!(LOGGER.0.lock().unwrap().contains(&(
+		Level::Warn,
+		"The crc32 checksum of the output does not match the one recorded!".to_string()
+	)));
+	Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_recompress_gzip() -> AnyResult<()> {
+	use super::gzip::{recompress_gzip, GzipProducer};
+	use crate::basic::Producer;
+
+	// members with stored, fixed and dynamic blocks, the last one empty
+	let original: &[u8] = include_bytes!("../../LICENSE");
+	let gzip: &[u8] = include_bytes!("../../tests/data/LICENSE.gz");
+	let (_, compressed, inflated_size): (&[u8], Vec<u8>, u64) =
+		recompress_gzip::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			gzip,
+			Vec::new(),
+			&Config::default(),
+		)?;
+	assert_eq!(inflated_size, original.len() as u64);
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		original,
+		Vec::new(),
+		&Config::default(),
+	)?;
+	assert_eq!(compressed, expected);
+	let (_, output): (&[u8], Vec<u8>) =
+		decode_with::<_, _, IO_BUFFER_SIZE>(compressed.as_slice(), Vec::new(), &Config::default())?;
+	assert_eq!(output, original);
+
+	// damage and truncation are errors, not a shorter output
+	let inflate = |gzip: &[u8]| -> AnyResult<Vec<u8>> {
+		let mut producer: GzipProducer<&[u8]> = GzipProducer::new(gzip);
+		let mut output: Vec<u8> = Vec::new();
+		let mut buffer: [u8; 1000] = [0; 1000];
+		loop {
+			match producer.produce(&mut buffer)? {
+				0 => return Ok(output),
+				length => output.extend_from_slice(&buffer[..length]),
+			}
+		}
+	};
+	assert_eq!(inflate(gzip)?, original);
+	let mut damaged: Vec<u8> = gzip.to_vec();
+	damaged[gzip.len() / 2] ^= 0x10;
+	assert!(inflate(&damaged).is_err());
+	assert!(inflate(&gzip[..gzip.len() - 30]).is_err());
+	assert!(inflate(b"").is_err());
+	assert!(inflate(original).is_err());
+	Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_decompress_to_gzip() -> AnyResult<()> {
+	use super::gzip::{decompress_to_gzip, GzipProducer};
+	use crate::basic::Producer;
+	use std::io::Read;
+
+	// back to gzip, which both flate2 and the producer inflate to the original
+	let original: Vec<u8> = generated().take(100_000).collect();
+	let compressed: Vec<u8> = compress(&original)?;
+	let mut payload: &[u8] = compressed.as_slice();
+	read_header(&mut payload)?;
+	let (_, gzip): (&[u8], Vec<u8>) = decompress_to_gzip(payload, Vec::new(), &Config::default())?;
+	let mut inflated: Vec<u8> = Vec::new();
+	flate2::read::GzDecoder::new(gzip.as_slice()).read_to_end(&mut inflated)?;
+	assert_eq!(inflated, original);
+	let mut producer: GzipProducer<&[u8]> = GzipProducer::new(gzip.as_slice());
+	let mut buffer: Vec<u8> = vec![0; original.len() + 1];
+	let mut length: usize = 0;
+	while let produced @ 1.. = producer.produce(&mut buffer[length..])? {
+		length += produced;
+	}
+	assert_eq!(&buffer[..length], original.as_slice());
+	assert_eq!(producer.inflated_size(), original.len() as u64);
+	Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_recompress_zstd() -> AnyResult<()> {
+	use super::zstd::{decompress_to_zstd, recompress_zstd, ZstdConsumer};
+	use crate::basic::Consumer;
+
+	// two frames, as two zstd files one after the other are
+	let original: Vec<u8> = generated().take(100_000).collect();
+	let mut zstd: Vec<u8> = Vec::new();
+	for half in original.chunks(original.len() / 2) {
+		let mut consumer: ZstdConsumer<Vec<u8>> = ZstdConsumer::new(Vec::new())?;
+		assert_eq!(consumer.consume(half)?, half.len());
+		zstd.extend_from_slice(&consumer.finish()?);
+	}
+	let (_, compressed, decompressed_size): (&[u8], Vec<u8>, u64) =
+		recompress_zstd::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			zstd.as_slice(),
+			Vec::new(),
+			&Config::default(),
+		)?;
+	assert_eq!(decompressed_size, original.len() as u64);
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		original.as_slice(),
+		Vec::new(),
+		&Config::default(),
+	)?;
+	assert_eq!(compressed, expected);
+
+	// and back to zstd, in a single frame
+	let (_, back): (&[u8], Vec<u8>) =
+		decompress_to_zstd(compressed.as_slice(), Vec::new(), &Config::default())?;
+	assert_eq!(zstd::decode_all(back.as_slice())?, original);
+
+	// damage and truncation are errors, not a shorter output
+	let recompress = |zstd: &[u8]| -> bool {
+		recompress_zstd::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			zstd,
+			Vec::new(),
+			&Config::default(),
+		)
+		.is_err()
+	};
+	assert!(recompress(&zstd[..zstd.len() - 10]));
+	assert!(recompress(&original));
+	Ok(())
+}
+
+#[cfg(feature = "map-context")]
+#[test]
+fn test_map_contexts() -> AnyResult<()> {
+	use super::map::{map_contexts, CONTEXT_MAP_COLUMNS};
+	use crate::bridged_context::SECONDARY_CONTEXT_SIZE;
+
+	let data: &[u8] = b"abcabcabcabcabcabcabcabcabcabc and the rest is not mapped";
+	let mut map: Vec<u8> = Vec::new();
+	assert_eq!(map_contexts(data, &mut map, &Config::default(), 30)?, 30);
+	let map: String = String::from_utf8(map)?;
+	let mut lines = map.lines();
+	assert_eq!(lines.next(), Some(CONTEXT_MAP_COLUMNS));
+	let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+	assert_eq!(rows.len(), 30);
+	for (position, row) in rows.iter().enumerate() {
+		assert_eq!(row.len(), 12);
+		assert_eq!(row[0], position.to_string());
+		assert_eq!(row[2], data[position].to_string());
+		for context in [row[8], row[9], row[10], row[11]] {
+			assert!(context.is_empty() || context.parse::<usize>()? < SECONDARY_CONTEXT_SIZE);
+		}
+	}
+	// nothing is known at first, then the repeating input is predicted by its first byte
+	assert_eq!(rows[0][7], "none");
+	assert!(!rows[0][11].is_empty());
+	let last: &Vec<&str> = &rows[29];
+	assert_eq!(
+		(last[3], last[7], last[9], last[11]),
+		("99", "first", "", "")
+	);
+	assert!(last[6].parse::<usize>()? > 0);
+	Ok(())
+}
+
+#[test]
+fn test_lanes_records() -> AnyResult<()> {
+	// records of 8 bytes: a counter, a slowly drifting reading and a few flags
+	let data: Vec<u8> = (0u32..100_000)
+		.flat_map(|index: u32| {
+			let reading: u16 = (1000.0 + 200.0 * (index as f64 / 500.0).sin()) as u16;
+			let flags: u16 = if index.is_multiple_of(97) { 3 } else { 1 };
+			let mut record: [u8; 8] = [0; 8];
+			record[..4].copy_from_slice(&index.to_le_bytes());
+			record[4..6].copy_from_slice(&reading.to_le_bytes());
+			record[6..].copy_from_slice(&flags.to_le_bytes());
+			record
+		})
+		.collect();
+	let single: Vec<u8> = compress_with(&data, &Config::default())?;
+	let laned: Vec<u8> = compress_with(&data, &Config::default().with_lanes(8))?;
+	assert_eq!(decompress_with_header(&single)?, data);
+	assert_eq!(decompress_with_header(&laned)?, data);
+	assert!(
+		laned.len() < single.len(),
+		"8 lanes: {} bytes, 1 lane: {} bytes",
+		laned.len(),
+		single.len()
+	);
+	// the lanes are recorded in the header
+	assert_eq!(read_header(&mut laned.as_slice())?.config.lanes, 8);
+	Ok(())
+}
+
+#[test]
+fn test_lanes_out_of_range() -> AnyResult<()> {
+	assert!(compress_with(b"data", &Config::default().with_lanes(0)).is_err());
+	assert!(compress_with(b"data", &Config::default().with_lanes(MAX_LANES as u8 + 1)).is_err());
+	// fewer bytes than lanes still round-trip
+	let compressed: Vec<u8> = compress_with(b"abc", &Config::default().with_lanes(5))?;
+	assert_eq!(decompress_with_header(&compressed)?, b"abc");
+	Ok(())
+}
+
+#[test]
+fn test_growth_round_trip() -> AnyResult<()> {
+	// the table grows at 16 KiB, 64 KiB and 256 KiB, the last step only doubles it
+	let growth: Growth = Growth {
+		initial_bits: 14,
+		max_bits: 19,
+	};
+	let data: Vec<u8> = generated().take(400_000).collect();
+	for config in [
+		Config::default().with_growth(Some(growth)),
+		Config::new(Model::Text)
+			.with_lanes(3)
+			.with_growth(Some(growth)),
+	] {
+		let compressed: Vec<u8> = compress_with(&data, &config)?;
+		assert_eq!(decompress_with_header(&compressed)?, data);
+		// the growth is recorded in the header
+		assert_eq!(read_header(&mut compressed.as_slice())?.config, config);
+	}
+	// a table that never grows past its initial size is just a smaller fixed table
+	let fixed: Growth = Growth {
+		initial_bits: 16,
+		max_bits: 16,
+	};
+	let compressed: Vec<u8> = compress_with(&data, &Config::default().with_growth(Some(fixed)))?;
+	assert_eq!(decompress_with_header(&compressed)?, data);
+	Ok(())
+}
+
+#[test]
+fn test_growth_out_of_range() {
+	for (initial_bits, max_bits) in [(13, 20), (20, 16), (16, 29)] {
+		let growth: Growth = Growth {
+			initial_bits,
+			max_bits,
+		};
+		assert!(compress_with(b"data", &Config::default().with_growth(Some(growth))).is_err());
+	}
+}
+
+#[cfg(feature = "threads")]
+#[test]
+fn test_panic_in_stage() -> AnyResult<()> {
+	// large enough for the stages before the panicking one to block on their pipes
+	let data: Vec<u8> = generated().take(3 * IO_BUFFER_SIZE).collect();
+	let error: AnyError = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		PanickingWriter,
+		&Config::default(),
+	)
+	.unwrap_err();
+	assert_eq!(error.to_string(), "Writer stage panicked!");
+	// the stages after the panicking one see their input break off instead of ending
+	let error: AnyError = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		PanickingReader(2),
+		Vec::new(),
+		&Config::default(),
+	)
+	.unwrap_err();
+	assert_eq!(error.to_string(), "Reader stage panicked!");
+	let compressed: Vec<u8> = compress_with(&data, &Config::default())?;
+	let error: AnyError = decode_with::<_, _, IO_BUFFER_SIZE>(
+		&compressed[SRX_HEADER.len()..],
+		PanickingWriter,
+		&Config::default(),
+	)
+	.unwrap_err();
+	assert_eq!(error.to_string(), "Writer stage panicked!");
+	Ok(())
+}
+
+#[cfg(feature = "threads")]
+#[test]
+fn test_jitter_in_pipes() -> AnyResult<()> {
+	// tiny buffers so the pipes sync often, each sync possibly delayed
+	const IO: usize = 256;
+	const MESSAGE: usize = MIN_MESSAGE_BUFFER_SIZE;
+	let data: Vec<u8> = generated().take(20_000).collect();
+	let config: Config = Config::default();
+	let (_, expected): (&[u8], Vec<u8>) =
+		encode_with::<_, _, IO, MESSAGE>(data.as_slice(), Vec::new(), &config)?;
+	for seed in 0..8 {
+		let (_, compressed): (&[u8], Vec<u8>) = with_jitter(seed, || {
+			encode_with::<_, _, IO, MESSAGE>(data.as_slice(), Vec::new(), &config)
+		})?;
+		assert!(compressed == expected, "seed {}", seed);
+		let (_, output): (&[u8], Vec<u8>) = with_jitter(seed, || {
+			decode_with::<_, _, IO>(compressed.as_slice(), Vec::new(), &config)
+		})?;
+		assert!(output == data, "seed {}", seed);
+		// a panicking stage still takes the whole pipeline down with its own error
+		let error: AnyError = with_jitter(seed, || {
+			encode_with::<_, _, IO, MESSAGE>(data.as_slice(), PanickingWriter, &config)
+		})
+		.unwrap_err();
+		assert_eq!(error.to_string(), "Writer stage panicked!");
+	}
+	Ok(())
+}
+
+#[test]
+fn test_threadless_matches_threaded() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let profile: Profile = {
+		let (_, _, profile) = encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			data.as_slice(),
+			Vec::new(),
+			&Config::default(),
+			None,
+			Some(DEFAULT_PROFILE_ENTRIES),
+		)?;
+		profile.unwrap()
+	};
+	let configs: [(Config, Option<&Profile>); 5] = [
+		(Config::default(), None),
+		(Config::new(Model::Text), None),
+		(Config::default().with_lanes(3), None),
+		(Config::default().with_growth(Some(Growth::default())), None),
+		(Config::default(), Some(&profile)),
+	];
+	for (config, warm_start) in configs {
+		let (_, expected, expected_profile) = encode_with_profile::<_, _, 256, 256>(
+			data.as_slice(),
+			Vec::new(),
+			&config,
+			warm_start,
+			Some(16),
+		)?;
+		// small buffers so that both ends refill and flush many times
+		let (_, compressed, profile) = encode_threadless::<_, _, 256>(
+			WrappedReader(data.as_slice()),
+			Vec::new(),
+			config,
+			warm_start,
+			Some(16),
+		)?;
+		assert!(compressed == expected, "{}", config.name());
+		assert_eq!(
+			profile.map(|profile| profile.id()),
+			expected_profile.map(|profile| profile.id())
+		);
+		// the whole input is read, as the reader thread does
+		let (rest, output): (&[u8], WrappedWriter<Vec<u8>>) = decode_threadless::<_, _, 256>(
+			compressed.as_slice(),
+			WrappedWriter(Vec::new()),
+			config,
+			warm_start,
+		)?;
+		assert!(output.0 == data, "{}", config.name());
+		assert!(rest.is_empty());
+	}
+	// an empty input is a stream of its own too
+	let (_, expected): (&[u8], Vec<u8>) =
+		encode_with::<_, _, 256, 256>(&[][..], Vec::new(), &Config::default())?;
+	let (_, compressed, _) = encode_threadless::<_, _, 256>(
+		WrappedReader(&[][..]),
+		Vec::new(),
+		Config::default(),
+		None,
+		None,
+	)?;
+	assert_eq!(compressed, expected);
+	Ok(())
+}
+
+#[test]
+fn test_threadless_config() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	for config in [Config::default(), Config::new(Model::Text).with_lanes(2)] {
+		let threadless: Config = config.with_threadless(true);
+		let (_, expected): (&[u8], Vec<u8>) =
+			encode_with_config(data.as_slice(), Vec::new(), &config)?;
+		let (_, compressed): (&[u8], Vec<u8>) =
+			encode_with_config(data.as_slice(), Vec::new(), &threadless)?;
+		assert!(compressed == expected, "{}", config.name());
+		// not part of the stream, the header does not keep it
+		assert!(compress_with(&data, &threadless)? == compress_with(&data, &config)?);
+		let (_, output): (&[u8], Vec<u8>) =
+			decode_with_config(compressed.as_slice(), Vec::new(), &threadless)?;
+		assert!(output == data, "{}", config.name());
+		let (output, found): (Vec<u8>, bool) = decode_until::<_, _, _, 0x1000>(
+			compressed.as_slice(),
+			Vec::new(),
+			&threadless,
+			|_: &[u8]| false,
+		)?;
+		assert!(!found && output == data);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_threadless_decode_until() -> AnyResult<()> {
+	let mut data: Vec<u8> = generated().take(100_000).collect();
+	data[50_000] = b'#';
+	let config: Config = Config::default();
+	let (_, compressed): (&[u8], Vec<u8>) =
+		encode_with::<_, _, 0x1000, 0x1000>(data.as_slice(), Vec::new(), &config)?;
+	let contains = |chunk: &[u8]| chunk.contains(&b'#');
+	let (expected, _): (Vec<u8>, bool) =
+		decode_until::<_, _, _, 0x1000>(compressed.as_slice(), Vec::new(), &config, contains)?;
+	// the same chunks as the threaded pipeline, stopping after the same one
+	let (output, found): (Vec<u8>, bool) = decode_until_threadless::<_, _, _, 0x1000>(
+		compressed.as_slice(),
+		UntilWriter::new(Vec::new(), contains),
+		config,
+	)?;
+	assert!(found);
+	assert_eq!(output, expected);
+	let (output, found): (Vec<u8>, bool) = decode_until_threadless::<_, _, _, 0x1000>(
+		compressed.as_slice(),
+		UntilWriter::new(Vec::new(), |_: &[u8]| false),
+		config,
+	)?;
+	assert!(!found);
+	assert!(output == data);
+	Ok(())
+}
+
+// remembers the largest buffer it was asked to fill, which is the size of the pipe buffers
+struct LargestReadReader<'a> {
+	data: &'a [u8],
+	largest: usize,
+}
+
+impl Read for LargestReadReader<'_> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		self.largest = self.largest.max(buffer.len());
+		self.data.read(buffer)
+	}
+}
+
+#[test]
+fn test_buffers_round_trip() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let (_, expected): (&[u8], Vec<u8>) = encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&Config::default(),
+	)?;
+	for &buffers in Buffers::ALL {
+		let config: Config = Config::default().with_buffers(buffers);
+		let reader: LargestReadReader = LargestReadReader {
+			data: &data,
+			largest: 0,
+		};
+		let (reader, compressed): (LargestReadReader, Vec<u8>) =
+			encode_with_config(reader, Vec::new(), &config)?;
+		assert_eq!(reader.largest, buffers.io_buffer_size(), "{:?}", buffers);
+		// the buffers change nothing in the stream
+		assert!(compressed == expected, "{:?}", buffers);
+		let reader: LargestReadReader = LargestReadReader {
+			data: &compressed,
+			largest: 0,
+		};
+		let (reader, output): (LargestReadReader, Vec<u8>) =
+			decode_with_config(reader, Vec::new(), &config)?;
+		assert_eq!(reader.largest, buffers.io_buffer_size(), "{:?}", buffers);
+		assert!(output == data, "{:?}", buffers);
+		// and are not recorded in the header
+		let mut header: Vec<u8> = Vec::new();
+		write_header(
+			&mut header,
+			&Header {
+				config,
+				..Header::default()
+			},
+		)?;
+		assert_eq!(header, SRX_HEADER);
+	}
+	// other sizes need not be powers of two, the message buffer only holding a byte at least
+	let (_, compressed): (&[u8], Vec<u8>) = encode_with::<_, _, 1000, MIN_MESSAGE_BUFFER_SIZE>(
+		data.as_slice(),
+		Vec::new(),
+		&Config::default(),
+	)?;
+	assert!(compressed == expected);
+	let (_, output): (&[u8], Vec<u8>) =
+		decode_with::<_, _, 1000>(compressed.as_slice(), Vec::new(), &Config::default())?;
+	assert!(output == data);
+	Ok(())
+}
+
+#[test]
+fn test_text_model_on_english() -> AnyResult<()> {
+	let text: &[u8] = include_bytes!("../../LICENSE");
+	let standard: Vec<u8> = compress_with(text, &Config::new(Model::Standard))?;
+	let word: Vec<u8> = compress_with(text, &Config::new(Model::Text))?;
+	assert_eq!(decompress_with_header(&word)?, text);
+	assert!(
+		word.len() < standard.len(),
+		"text: {} bytes, standard: {} bytes",
+		word.len(),
+		standard.len()
+	);
+	assert_eq!(read_header(&mut word.as_slice())?.config.model, Model::Text);
+	Ok(())
+}
+
+#[test]
+fn test_flat_model_on_random() -> AnyResult<()> {
+	// xorshift, nothing for the literal contexts to learn
+	let mut state: u64 = 0x9E3779B97F4A7C15;
+	let random: Vec<u8> = (0..200_000)
+		.map(|_| {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			(state >> 56) as u8
+		})
+		.collect();
+	let standard: Vec<u8> = compress_with(&random, &Config::new(Model::Standard))?;
+	let flat: Vec<u8> = compress_with(&random, &Config::new(Model::Flat))?;
+	assert_eq!(decompress_with_header(&flat)?, random);
+	assert!(
+		flat.len() < standard.len(),
+		"flat: {} bytes, standard: {} bytes",
+		flat.len(),
+		standard.len()
+	);
+	assert_eq!(read_header(&mut flat.as_slice())?.config.model, Model::Flat);
+	// and on data with structure as well
+	let data: Vec<u8> = generated().take(100_000).collect();
+	let flat: Vec<u8> = compress_with(&data, &Config::new(Model::Flat).with_lanes(2))?;
+	assert_eq!(decompress_with_header(&flat)?, data);
+	Ok(())
+}
+
+#[test]
+fn test_associative_models() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(200_000).collect();
+	for model in [Model::TwoWay, Model::FourWay] {
+		let compressed: Vec<u8> = compress_with(&data, &Config::new(model).with_lanes(2))?;
+		// the decoder takes the ways from the model in the header
+		assert_eq!(read_header(&mut compressed.as_slice())?.config.model, model);
+		assert!(decompress_with_header(&compressed)? == data, "{:?}", model);
+		assert!(Config::new(model)
+			.with_growth(Some(Growth::default()))
+			.check()
+			.is_err());
+	}
+	Ok(())
+}
+
+#[test]
+fn test_mix_models() -> AnyResult<()> {
+	let records: Vec<u8> = (0u32..50_000)
+		.flat_map(|index: u32| (index.wrapping_mul(2654435761) >> 12).to_le_bytes())
+		.collect();
+	let datasets: [(&str, Vec<u8>); 3] = [
+		("text", include_bytes!("../../LICENSE").to_vec()),
+		("generated", generated().take(200_000).collect()),
+		("records", records),
+	];
+	for (name, data) in &datasets {
+		let standard: Vec<u8> = compress_with(data, &Config::new(Model::Standard))?;
+		for model in [Model::XorMix, Model::RotateMix] {
+			let mixed: Vec<u8> = compress_with(data, &Config::new(model))?;
+			// the decoder takes the mix from the model in the header
+			assert_eq!(read_header(&mut mixed.as_slice())?.config.model, model);
+			assert!(
+				decompress_with_header(&mixed)? == *data,
+				"{} {:?}",
+				name,
+				model
+			);
+			// only the contexts of the second and third bits change, the ratio stays close
+			let ratio: f64 = mixed.len() as f64 / standard.len() as f64;
+			assert!(
+				(0.95..1.05).contains(&ratio),
+				"{} {:?}: {} bytes, standard: {} bytes",
+				name,
+				model,
+				mixed.len(),
+				standard.len()
+			);
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_lanes_start_alike() -> AnyResult<()> {
+	let text: &[u8] = include_bytes!("../../LICENSE");
+	let (_, _, profile): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			text,
+			Vec::new(),
+			&Config::default(),
+			None,
+			Some(DEFAULT_PROFILE_ENTRIES),
+		)?;
+	let profile: Profile = profile.unwrap();
+	for config in [
+		Config::default(),
+		Config::new(Model::FourWay),
+		Config::default().with_growth(Some(Growth::default())),
+	] {
+		// the encoder and the decoder build their lanes from the same config, every one of them
+		// from the initial seed
+		let encoder: BridgedPrimaryContext = config.primary_context();
+		let decoder: BridgedPrimaryContext = config.primary_context();
+		assert_eq!(
+			(
+				encoder.previous_byte(),
+				encoder.hash_value(),
+				encoder.word_hash()
+			),
+			(Byte::from(INITIAL_BYTE), INITIAL_HASH, 0)
+		);
+		assert!(encoder == decoder, "{}", config.name());
+		for warm_start in [None, Some(&profile)] {
+			let encoder: BridgedSecondaryContext = config.secondary_context(warm_start);
+			let decoder: BridgedSecondaryContext = config.secondary_context(warm_start);
+			assert_eq!(encoder.size(), decoder.size());
+			assert!((0..encoder.size()).all(|index| encoder.state(index) == decoder.state(index)));
+		}
+		// and the stream decodes from it, primed or not
+		for warm_start in [None, Some(&profile)] {
+			let (_, compressed, _): (&[u8], Vec<u8>, Option<Profile>) =
+				encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+					text,
+					Vec::new(),
+					&config.with_lanes(2),
+					warm_start,
+					None,
+				)?;
+			let (_, decompressed): (&[u8], Vec<u8>) = decode_with_profile::<_, _, IO_BUFFER_SIZE>(
+				compressed.as_slice(),
+				Vec::new(),
+				&config.with_lanes(2),
+				warm_start,
+			)?;
+			assert!(decompressed == text, "{}", config.name());
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_secondary_size_per_model() -> AnyResult<()> {
+	// only the text model has the literals keyed by words, the others leave them out
+	for config in Config::all() {
+		let size: usize = config.secondary_context(None).size();
+		match config.model {
+			Model::Text => assert_eq!(size, SECONDARY_CONTEXT_SIZE),
+			_ => assert!(size < SECONDARY_CONTEXT_SIZE, "{}", config.name()),
+		}
+	}
+	assert!(Config::default().estimated_memory() < Config::new(Model::Text).estimated_memory());
+	// a profile of the text model warms a smaller table up, its text literals left out
+	let text: &[u8] = include_bytes!("../../LICENSE");
+	let text_config: Config = Config::new(Model::Text);
+	let (_, _, profile): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			text,
+			Vec::new(),
+			&text_config,
+			None,
+			Some(DEFAULT_PROFILE_ENTRIES),
+		)?;
+	let profile: Profile = profile.unwrap();
+	assert!(Config::default().secondary_context(Some(&profile)).size() < SECONDARY_CONTEXT_SIZE);
+	let (_, compressed, _): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			text,
+			Vec::new(),
+			&Config::default(),
+			Some(&profile),
+			None,
+		)?;
+	let (_, decompressed): (&[u8], Vec<u8>) = decode_with_profile::<_, _, IO_BUFFER_SIZE>(
+		compressed.as_slice(),
+		Vec::new(),
+		&Config::default(),
+		Some(&profile),
+	)?;
+	assert!(decompressed == text);
+	Ok(())
+}
+
+#[test]
+fn test_profile_warm_start() -> AnyResult<()> {
+	let text: &[u8] = include_bytes!("../../LICENSE");
+	let (first, second): (&[u8], &[u8]) = text.split_at(text.len() / 2);
+	let config: Config = Config::new(Model::Text).with_lanes(2);
+	let (_, _, profile): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			first,
+			Vec::new(),
+			&config,
+			None,
+			Some(DEFAULT_PROFILE_ENTRIES),
+		)?;
+	let profile: Profile = profile.unwrap();
+	assert!(!profile.is_empty());
+	// the profile survives its serialization, and a truncated one is refused
+	let bytes: Vec<u8> = profile.to_bytes();
+	assert_eq!(Profile::from_bytes(&bytes)?, profile);
+	assert!(Profile::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+	assert!(Profile::from_bytes(b"sRx\x00").is_err());
+
+	// the second half compresses better starting from the profile of the first one
+	let (_, cold): (&[u8], Vec<u8>) =
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(second, Vec::new(), &config)?;
+	let (_, warm, _): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			second,
+			Vec::new(),
+			&config,
+			Some(&profile),
+			None,
+		)?;
+	assert!(
+		warm.len() < cold.len(),
+		"warm: {} bytes, cold: {} bytes",
+		warm.len(),
+		cold.len()
+	);
+	let (_, output): (&[u8], Vec<u8>) = decode_with_profile::<_, _, IO_BUFFER_SIZE>(
+		warm.as_slice(),
+		Vec::new(),
+		&config,
+		Some(&profile),
+	)?;
+	assert_eq!(output, second);
+
+	// only the same profile is accepted for decoding
+	assert!(check_profile(Some(profile.id()), Some(&profile)).is_ok());
+	assert!(check_profile(Some(profile.id()), None).is_err());
+	assert!(check_profile(Some(profile.id() ^ 1), Some(&profile)).is_err());
+	assert!(check_profile(None, Some(&profile)).is_err());
+	Ok(())
+}
+
+#[test]
+fn test_profile_max_entries() -> AnyResult<()> {
+	let data: Vec<u8> = generated().take(20_000).collect();
+	let (_, _, full): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			data.as_slice(),
+			Vec::new(),
+			&Config::default(),
+			None,
+			Some(usize::MAX),
+		)?;
+	let (_, _, small): (&[u8], Vec<u8>, Option<Profile>) =
+		encode_with_profile::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			data.as_slice(),
+			Vec::new(),
+			&Config::default(),
+			None,
+			Some(100),
+		)?;
+	assert!(full.unwrap().len() > 100);
+	assert_eq!(small.unwrap().len(), 100);
+	Ok(())
+}
+
+#[test]
+fn test_count_outcomes() -> AnyResult<()> {
+	// a literal until the context is made of the byte alone, then the first rank every time
+	let repeated: Outcomes = count_outcomes(&[b'a'; 1000][..], &Config::default())?;
+	assert_eq!(
+		repeated,
+		Outcomes {
+			first: 994,
+			second: 0,
+			third: 0,
+			literal: 6,
+			literal_ones: 6 * 3,
+		}
+	);
+	assert_eq!(repeated.first_bits(), (994, 6));
+	assert_eq!(repeated.second_bits(), (6, 0));
+	assert_eq!(repeated.literal_bits(), (6 * 5, 6 * 3));
+
+	// the same context followed by a few bytes in turn, which take the ranks from each other
+	let ranked: Vec<u8> = b"abacbacbad"
+		.iter()
+		.flat_map(|&next| [b'1', b'2', b'3', b'4', b'5', b'6', next])
+		.collect();
+	let outcomes: Outcomes = count_outcomes(ranked.as_slice(), &Config::default())?;
+	assert_eq!(
+		outcomes,
+		Outcomes {
+			first: 39,
+			second: 1,
+			third: 5,
+			literal: 25,
+			literal_ones: 85,
+		}
+	);
+	assert_eq!(outcomes.bytes(), ranked.len() as u64);
+	// a bit of the first rank for every byte, then fewer and fewer, and 8 for each literal
+	let (zeros, ones): (u64, u64) = outcomes.bits();
+	assert_eq!(zeros + ones, 70 + 31 + 6 + 25 * 8);
+	assert_eq!(outcomes.third_bits(), (1, 5));
+
+	// each lane has contexts of its own
+	let laned: Outcomes = count_outcomes(&[b'a'; 1000][..], &Config::default().with_lanes(2))?;
+	assert_eq!(laned.literal, 12);
+	Ok(())
+}
+
+#[test]
+fn test_calibrate() -> AnyResult<()> {
+	// 'a' nine times out of ten, so the first rank is mostly right and its bit mostly a zero
+	let mut state: u32 = 1;
+	let biased: Vec<u8> = (0..200_000)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			match state >> 24 {
+				0..=229 => b'a',
+				_ => b'b',
+			}
+		})
+		.collect();
+	let calibration: Calibration = calibrate(biased.as_slice(), &Config::default(), None)?;
+	// every bit of the bytes, and the 10 of the end of the stream
+	let (zeros, ones): (u64, u64) = count_outcomes(biased.as_slice(), &Config::default())?.bits();
+	assert_eq!(calibration.bits(), zeros + ones + 10);
+	// most of them predicted to be zeros, as they are
+	let low: u64 = calibration.bins[..CALIBRATION_BINS / 2]
+		.iter()
+		.map(|bin| bin.bits)
+		.sum();
+	assert!(low > calibration.bits() * 3 / 4);
+	// the first rank missing one time in ten, predicted as such
+	let fullest: usize = (0..CALIBRATION_BINS)
+		.max_by_key(|&index| calibration.bins[index].bits)
+		.unwrap();
+	assert_eq!(fullest, CALIBRATION_BINS / 10);
+	// and each bin seeing enough of them gets about as many ones as it predicted
+	for bin in calibration.bins.iter().filter(|bin| bin.bits >= 1000) {
+		assert!((bin.observed() - bin.predicted()).abs() < 0.1, "{:?}", bin);
+	}
+	Ok(())
+}
@@ -0,0 +1,126 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::{run_combined_context_decoder, UntilWriter};
+use super::encoder::{run_primary_context_encoder, SecondaryContextEncoder};
+use super::lanes::{with_lanes, Lanes};
+use super::profile::Profile;
+use super::shared::{WrappedReader, WrappedWriter};
+use crate::basic::{log, AnyResult, Consumer, DirectReader, DirectWriter, Producer};
+use crate::bridged_context::BridgedSecondaryContext;
+use std::io::{Read, Write};
+
+// -----------------------------------------------
+
+// The pipeline run on the calling thread, for targets without threads such as
+// wasm32-unknown-unknown, used instead of the threaded one when the threads feature is off or
+// the config asks for it, see Config::threadless.
+// Each stage calls the next one where it would write to a pipe, so there is no message buffer,
+// and the output is the same byte for byte.
+
+pub fn encode_threadless<P: Producer<u8>, W: Write, const IO_BUFFER_SIZE: usize>(
+	producer: P,
+	writer: W,
+	config: Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(P, W, Option<Profile>)> {
+	with_lanes!(
+		config,
+		PerLane,
+		encode_lanes::<P, W, IO_BUFFER_SIZE, PerLane<BridgedSecondaryContext>>(
+			producer,
+			writer,
+			config,
+			warm_start,
+			profile_entries
+		)
+	)
+}
+
+fn encode_lanes<
+	P: Producer<u8>,
+	W: Write,
+	const IO_BUFFER_SIZE: usize,
+	L: Lanes<Context = BridgedSecondaryContext>,
+>(
+	producer: P,
+	writer: W,
+	config: Config,
+	warm_start: Option<&Profile>,
+	profile_entries: Option<usize>,
+) -> AnyResult<(P, W, Option<Profile>)> {
+	let mut secondary_context_encoder: SecondaryContextEncoder<
+		DirectWriter<u8, WrappedWriter<W>, IO_BUFFER_SIZE>,
+		L,
+	> = SecondaryContextEncoder::new(DirectWriter::new(WrappedWriter(writer)), config, warm_start);
+	let returned_producer: P = run_primary_context_encoder(
+		DirectReader::<u8, P, IO_BUFFER_SIZE>::new(producer),
+		&mut secondary_context_encoder,
+		config,
+	)?;
+	let (returned_writer, payload_length, profile): (WrappedWriter<W>, u64, Option<Profile>) =
+		secondary_context_encoder.finish(profile_entries)?;
+	log!(Debug, "Encoded into {} bytes", payload_length);
+	Ok((returned_producer, returned_writer.0, profile))
+}
+
+pub fn decode_threadless<R: Read, C: Consumer<u8>, const IO_BUFFER_SIZE: usize>(
+	reader: R,
+	consumer: C,
+	config: Config,
+	warm_start: Option<&Profile>,
+) -> AnyResult<(R, C)> {
+	let (returned_reader, returned_consumer): (WrappedReader<R>, C) = run_combined_context_decoder(
+		DirectReader::<u8, WrappedReader<R>, IO_BUFFER_SIZE>::new(WrappedReader(reader)),
+		DirectWriter::<u8, C, IO_BUFFER_SIZE>::new(consumer),
+		config,
+		warm_start,
+	)?;
+	log!(Debug, "Decoded to the end of the stream");
+	Ok((returned_reader.0, returned_consumer))
+}
+
+// the writer refuses the chunk after the one the predicate held for, which stops the decoding
+pub fn decode_until_threadless<
+	R: Read,
+	W: Write,
+	F: FnMut(&[u8]) -> bool,
+	const IO_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	mut until_writer: UntilWriter<W, F>,
+	config: Config,
+) -> AnyResult<(W, bool)> {
+	// the stages only give back what was lent to them, the writer is still there after an error
+	let result: AnyResult<()> = run_combined_context_decoder(
+		DirectReader::<u8, WrappedReader<R>, IO_BUFFER_SIZE>::new(WrappedReader(reader)),
+		DirectWriter::<u8, &mut UntilWriter<W, F>, IO_BUFFER_SIZE>::new(&mut until_writer),
+		config,
+		None,
+	)
+	.map(|_| ());
+	if until_writer.found {
+		log!(Debug, "Decoding stopped before the end of the stream");
+		return Ok((until_writer.writer, true));
+	}
+	result?;
+	log!(Debug, "Decoded to the end of the stream");
+	Ok((until_writer.writer, false))
+}
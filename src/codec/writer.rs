@@ -0,0 +1,185 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::encoder::{PrimaryContextEncoder, SecondaryContextEncoder};
+use super::header::{write_header, Header};
+use super::lanes::ManyLanes;
+use crate::basic::{log, write_fully, AnyResult, Closable, Writer};
+use crate::bridged_context::{BridgedPrimaryContext, BridgedSecondaryContext};
+use std::io;
+use std::io::Write;
+
+// -----------------------------------------------
+
+// the coded bytes go to the writer in chunks of this size, or sooner when flushed
+const CHUNK_SIZE: usize = 0x10000;
+
+struct ChunkedWriter<W: Write> {
+	writer: W,
+	chunk: Vec<u8>,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+	fn write_chunk(&mut self) -> AnyResult<()> {
+		write_fully(&mut self.writer, &self.chunk)?;
+		self.chunk.clear();
+		Ok(())
+	}
+}
+
+impl<W: Write> Writer<u8> for ChunkedWriter<W> {
+	#[inline(always)]
+	fn write(&mut self, value: u8) -> AnyResult<()> {
+		self.chunk.push(value);
+		if self.chunk.len() == CHUNK_SIZE {
+			self.write_chunk()?;
+		}
+		Ok(())
+	}
+}
+
+impl<W: Write> Closable<W> for ChunkedWriter<W> {
+	fn close(mut self) -> AnyResult<W> {
+		self.write_chunk()?;
+		self.writer.flush()?;
+		Ok(self.writer)
+	}
+}
+
+// -----------------------------------------------
+
+// Compresses what is written to it into the writer, the payload alone, without a header: the
+// output is the same byte for byte as encode given the whole input at once, for a stream only
+// coming in pieces, such as from a socket. Both stages run on the calling thread like the
+// pipeline without threads.
+//
+// A write only feeds the contexts, the coder holding back the bytes its range has not settled
+// yet: ending a write, however short, must not end the coder too, or the output would depend
+// on how the input was cut. Flushing hands every coded byte to the writer and flushes it, without
+// ending the stream either, so the output stays the same whether or not, and however often, it
+// is flushed, and the other end can decode all but the last few bytes written before the flush.
+// finish ends the stream, with the same end as encode, and has to be called: dropping the
+// encoder leaves the stream unfinished, and so does an error.
+//
+// The contexts are kept as for any number of lanes, only the pipelines have a path of their own
+// for a single lane.
+pub struct SrxEncoder<W: Write> {
+	primary: PrimaryContextEncoder<ManyLanes<BridgedPrimaryContext>>,
+	secondary: SecondaryContextEncoder<ChunkedWriter<W>, ManyLanes<BridgedSecondaryContext>>,
+}
+
+impl<W: Write> SrxEncoder<W> {
+	pub fn new(writer: W) -> AnyResult<Self> {
+		Self::with_config(writer, &Config::default())
+	}
+
+	pub fn with_config(writer: W, config: &Config) -> AnyResult<Self> {
+		config.check()?;
+		let config: Config = *config;
+		log!(Debug, "Encoding with the {} config", config.name());
+		let chunked_writer: ChunkedWriter<W> = ChunkedWriter {
+			writer,
+			chunk: Vec::with_capacity(CHUNK_SIZE),
+		};
+		Ok(Self {
+			primary: PrimaryContextEncoder::new(config),
+			secondary: SecondaryContextEncoder::new(chunked_writer, config, None),
+		})
+	}
+
+	pub fn get_ref(&self) -> &W {
+		&self.secondary.encoder().get_ref().writer
+	}
+
+	// ends the stream and returns the writer, flushed
+	pub fn finish(mut self) -> AnyResult<W> {
+		self.primary.end(&mut &mut self.secondary)?;
+		let (writer, payload_length, _): (W, u64, _) = self.secondary.finish(None)?;
+		log!(Debug, "Encoded into {} bytes", payload_length);
+		Ok(writer)
+	}
+}
+
+impl<W: Write> Write for SrxEncoder<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		let mut secondary: &mut SecondaryContextEncoder<
+			ChunkedWriter<W>,
+			ManyLanes<BridgedSecondaryContext>,
+		> = &mut self.secondary;
+		for &byte in buffer {
+			self.primary.byte(&mut secondary, byte)?;
+		}
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		let chunked_writer: &mut ChunkedWriter<W> = self.secondary.encoder_mut().get_mut();
+		chunked_writer.write_chunk()?;
+		chunked_writer.writer.flush()
+	}
+}
+
+// -----------------------------------------------
+
+// An SrxEncoder behind a header like the one of a file, for a stream that is not at hand as a
+// whole, such as the body of a response, and that is decompressed like a file. Flushing and
+// finishing it are the same as for SrxEncoder.
+pub struct SrxWriter<W: Write> {
+	encoder: SrxEncoder<W>,
+}
+
+impl<W: Write> SrxWriter<W> {
+	pub fn new(writer: W) -> AnyResult<Self> {
+		Self::with_config(writer, &Config::default())
+	}
+
+	// the header is written here, without the size of the input that is not known yet
+	pub fn with_config(mut writer: W, config: &Config) -> AnyResult<Self> {
+		config.check()?;
+		write_header(
+			&mut writer,
+			&Header {
+				config: *config,
+				..Header::default()
+			},
+		)?;
+		Ok(Self {
+			encoder: SrxEncoder::with_config(writer, config)?,
+		})
+	}
+
+	pub fn get_ref(&self) -> &W {
+		self.encoder.get_ref()
+	}
+
+	// ends the stream and returns the writer, flushed
+	pub fn finish(self) -> AnyResult<W> {
+		self.encoder.finish()
+	}
+}
+
+impl<W: Write> Write for SrxWriter<W> {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		self.encoder.write(buffer)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.encoder.flush()
+	}
+}
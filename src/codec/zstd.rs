@@ -0,0 +1,123 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::config::Config;
+use super::decoder::decode_to_consumer_with_config;
+use super::encoder::encode_producer;
+use crate::basic::{read_retrying, write_fully, AnyResult, Consumer, Producer};
+use std::io::{BufReader, Read, Write};
+use zstd::stream::read::Decoder;
+use zstd::stream::write::Encoder;
+
+// -----------------------------------------------
+
+// Decompresses a zstd stream of any number of frames as a producer, for feeding it to the
+// encoder without going through a file. A frame cut short is an error, not a shorter output.
+pub struct ZstdProducer<R: Read> {
+	decoder: Decoder<'static, BufReader<R>>,
+	decompressed_size: u64,
+}
+
+impl<R: Read> ZstdProducer<R> {
+	pub fn new(reader: R) -> AnyResult<Self> {
+		Ok(Self {
+			decoder: Decoder::new(reader)?,
+			decompressed_size: 0,
+		})
+	}
+
+	pub fn decompressed_size(&self) -> u64 {
+		self.decompressed_size
+	}
+
+	pub fn into_inner(self) -> R {
+		self.decoder.finish().into_inner()
+	}
+}
+
+impl<R: Read> Producer<u8> for ZstdProducer<R> {
+	fn produce(&mut self, buffer: &mut [u8]) -> AnyResult<usize> {
+		let length: usize = read_retrying(&mut self.decoder, buffer)?;
+		self.decompressed_size += length as u64;
+		Ok(length)
+	}
+}
+
+// -----------------------------------------------
+
+// Compresses what it is given into a zstd stream of a single frame at the default level, for
+// the output of the decoder going back to zstd without going through a file.
+pub struct ZstdConsumer<W: Write> {
+	encoder: Encoder<'static, W>,
+}
+
+impl<W: Write> ZstdConsumer<W> {
+	pub fn new(writer: W) -> AnyResult<Self> {
+		Ok(Self {
+			encoder: Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+		})
+	}
+
+	// writes the end of the frame, returns the writer
+	pub fn finish(self) -> AnyResult<W> {
+		Ok(self.encoder.finish()?)
+	}
+}
+
+impl<W: Write> Consumer<u8> for ZstdConsumer<W> {
+	fn consume(&mut self, buffer: &[u8]) -> AnyResult<usize> {
+		write_fully(&mut self.encoder, buffer)?;
+		Ok(buffer.len())
+	}
+}
+
+// -----------------------------------------------
+
+// Compresses what a zstd stream decompresses to, without a temporary file in between. Returns
+// the decompressed size along with the reader and the writer.
+pub fn recompress_zstd<
+	R: Read + Send,
+	W: Write + Send,
+	const IO_BUFFER_SIZE: usize,
+	const MESSAGE_BUFFER_SIZE: usize,
+>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W, u64)> {
+	let (producer, writer): (ZstdProducer<R>, W) = encode_producer::<
+		_,
+		_,
+		IO_BUFFER_SIZE,
+		MESSAGE_BUFFER_SIZE,
+	>(ZstdProducer::new(reader)?, writer, config)?;
+	let decompressed_size: u64 = producer.decompressed_size();
+	Ok((producer.into_inner(), writer, decompressed_size))
+}
+
+// Decodes a payload of srx straight into a zstd stream, the header being already read by the
+// caller. Returns the reader and the writer.
+pub fn decompress_to_zstd<R: Read + Send, W: Write + Send>(
+	reader: R,
+	writer: W,
+	config: &Config,
+) -> AnyResult<(R, W)> {
+	let (reader, consumer): (R, ZstdConsumer<W>) =
+		decode_to_consumer_with_config(reader, ZstdConsumer::new(writer)?, config, None)?;
+	Ok((reader, consumer.finish()?))
+}
@@ -0,0 +1,39 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Building blocks of the srx codec, for experimenting with variant compressors.
+//!
+//! Stability: none of these types follow semver. Their signatures, their memory
+//! layout and the bit streams they produce may change in any release, including
+//! patch releases. Pin an exact version of srx if you build on top of them.
+//!
+//! Built with `--cfg fuzzing`, as `cargo fuzz` does, [`PrimaryContext`] and
+//! [`SecondaryContext`] also have a `check_invariants` method that panics on a
+//! table no input can lead to, for fuzz targets to call after each step.
+
+// the pipes between the stages only exist with the threads feature
+#[cfg(feature = "threads")]
+pub use crate::basic::{pipe, PipedReader, PipedWriter};
+pub use crate::basic::{Byte, Closable, Reader, Writer};
+pub use crate::primary_context::{
+	ByteHistory, ByteMatched, FixedPrimaryContext, HistoryState, PrimaryContext, PrimaryTable,
+	INITIAL_BYTE, INITIAL_HASH,
+};
+pub use crate::secondary_context::{
+	bit_cost, prediction_to_f64, Bit, BitDecoder, BitEncoder, SecondaryContext, StateInfo,
+};
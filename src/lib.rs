@@ -0,0 +1,61 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#![allow(clippy::derivable_impls)]
+#![allow(clippy::manual_range_contains)]
+#![allow(clippy::misrefactored_assign_op)]
+#![allow(clippy::module_inception)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::upper_case_acronyms)]
+
+mod basic;
+mod bridged_context;
+mod codec;
+#[cfg(feature = "internals")]
+pub mod internals;
+mod primary_context;
+mod secondary_context;
+
+#[cfg(feature = "pipe-stats")]
+pub use crate::basic::PipeStats;
+#[cfg(feature = "sha256")]
+pub use crate::basic::Sha256;
+pub use crate::basic::{
+	volume_path, AnyError, AnyResult, Consumer, Crc32, NullConsumer, SparseReader, SparseWriter,
+	VolumeReader, VolumeWriter, XxHash64,
+};
+pub use crate::bridged_context::Model;
+pub use crate::codec::{
+	calibrate, check_profile, compress, compress_chunks, compress_iter, compress_with,
+	count_collisions, count_outcomes, decode, decode_blocks, decode_resync, decode_to_consumer,
+	decode_to_consumer_with_config, decode_until, decode_with, decode_with_config,
+	decode_with_config_profile, decode_with_profile, decompress, encode, encode_blocks,
+	encode_resync, encode_with, encode_with_config, encode_with_config_profile,
+	encode_with_profile, read_header, split_stored_tail, time_stages, write_header, Block, Buffers,
+	Calibration, CalibrationBin, Checksum, ChecksumAlgo, ChecksumHasher, ChecksumWriter,
+	Collisions, Config, Growth, Header, Outcomes, Profile, Resynced, SrxDecoder, SrxEncoder,
+	SrxWriter, Stage, StageTimes, CALIBRATION_BINS, COLLISION_SAMPLE_BITS, DEFAULT_PROFILE_ENTRIES,
+	IO_BUFFER_SIZE, MAX_BLOCKS, MAX_LANES, MAX_SEGMENT_SIZE, MESSAGE_BUFFER_SIZE,
+	MIN_MESSAGE_BUFFER_SIZE, RESYNC_MARKER, SRX_HEADER,
+};
+#[cfg(feature = "gzip")]
+pub use crate::codec::{decompress_to_gzip, recompress_gzip, GzipConsumer, GzipProducer};
+#[cfg(feature = "zstd")]
+pub use crate::codec::{decompress_to_zstd, recompress_zstd, ZstdConsumer, ZstdProducer};
+#[cfg(feature = "map-context")]
+pub use crate::codec::{map_contexts, CONTEXT_MAP_COLUMNS};
@@ -16,69 +16,137 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::{AnyError, AnyResult};
-use crate::codec::{decode, encode};
+use crate::cli::{
+	check_no_metadata, check_output, is_srx, run, run_benchmark, run_compare, run_estimate,
+	run_fds, run_preflight, run_ratio_only, run_recompress, run_test, run_tune, run_verify,
+	show_progress, time_stages_if, Options, Progress, Report, StageReport, EXIT_REFUSED,
+};
+use srx::{AnyResult, StageTimes};
 use std::env;
-use std::fs::File;
-use std::io::{Read, Seek, Write};
 use std::path::Path;
 use std::process::exit;
-use std::time::Instant;
+use std::time::Duration;
 
-mod basic;
-mod bridged_context;
-mod codec;
-mod primary_context;
-mod secondary_context;
+mod cli;
 
 // -----------------------------------------------
 
-const IO_BUFFER_SIZE: usize = 0x400000;
-const MESSAGE_BUFFER_SIZE: usize = 0x40000;
-
-// -----------------------------------------------
-
-const SRX_HEADER: &[u8; 4] = b"sRx\x00";
-
-fn run(input_path: &Path, output_path: &Path, is_compress: bool) -> AnyResult<(u64, u64, f64)> {
-	// open file
-	let mut reader: File = File::open(input_path)?;
-	let mut writer: File = File::create(output_path)?;
-
-	// start the timer
-	let start: Instant = Instant::now();
-
-	// do the compression/decompression
-	let (mut done_reader, mut done_writer): (File, File) = if is_compress {
-		writer.write_all(SRX_HEADER)?;
-		encode::<File, File, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(reader, writer)?
-	} else {
-		let mut buffer: [u8; 4] = [0; 4];
-		reader.read_exact(&mut buffer)?;
-		if !buffer.eq(SRX_HEADER) {
-			return Err(AnyError::from_string("Not a SRX compressed file!"));
-		}
-		decode::<File, File, IO_BUFFER_SIZE>(reader, writer)?
-	};
-
-	// stop the timer and calculate the duration in seconds
-	let duration: f64 = start.elapsed().as_millis() as f64 / 1000.0;
-
-	// get the input and output size
-	let input_size: u64 = done_reader.stream_position()?;
-	let output_size: u64 = done_writer.stream_position()?;
-
-	// oke
-	Ok((input_size, output_size, duration))
-}
-
 fn help() -> ! {
 	println!(
 		"\
 		srx: The fast Symbol Ranking based compressor, version {}.\n\
 		Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)\n\n\
-		To   compress: srx c <input-file> <output-file>\n\
-		To decompress: srx d <input-file> <output-file>",
+		To   compress: srx c [options] <input-file> <output-file>\n\
+		\x20             (either file may be - for the standard input or output)\n\
+		\x20             srx c --ratio-only [options] <input-file>\n\
+		\x20             (the sizes and ratio as a JSON line, nothing written)\n\
+		To decompress: srx d [options] <input-file> <output-file>\n\
+		\x20             (either file may be - too, on Unix)\n\
+		To       auto: srx auto [options] <input-file> <output-file>\n\
+		\x20             (decompress a file srx compressed, compress anything else)\n\
+		To       test: srx t [options] <input-file>\n\
+		\x20             (decompressed into nothing, its size and checksum checked)\n\
+		To   pass fds: srx c|d [options] --input-fd=N --output-fd=M\n\
+		\x20             (either one standing for its path, which is then left out)\n\
+		To  preflight: srx d --preflight [--max-memory=MIB] <input-file>\n\
+		To  benchmark: srx d --benchmark [--buffers=SIZE] <input-file>\n\
+		\x20             (decoded from memory into nothing, only the decoder timed)\n\
+		To    compare: srx compare <input-file>\n\
+		To   estimate: srx estimate [options] <input-file>\n\
+		\x20             (the ratio of a few samples of a file that can be seeked in)\n\
+		To       tune: srx tune <input-file>\n\
+		\x20             (then compresses it again and again at a prompt)\n\
+		To     verify: srx verify <compressed-file> <original-file>\n\
+		To recompress: srx recompress [options] <gzip-or-zstd-file> <output-file>\n\
+		\x20             srx recompress [options] <compressed-file> <output.gz|.zst>\n\
+		\x20             (needs the gzip or the zstd feature)\n\n\
+		-f, --force:\n\
+		\x20           overwrite an existing output file (exits with 2 if it is refused)\n\
+		--progress: keep the percentage done and the speed on the standard error\n\
+		--progress-file=PATH:\n\
+		\x20           keep the progress as a JSON line in PATH, rewritten as it changes\n\
+		--text:     add a word model, better for natural language and source code\n\
+		--tagged:   tag the primary contexts, better for binaries with many contexts\n\
+		--two-way, --four-way:\n\
+		\x20           tag the primary contexts in buckets of 2 or 4 entries, colliding\n\
+		\x20           contexts each keeping one (better for large inputs, slower)\n\
+		--flat:     code all the literals in a single context, for compressed or random data\n\
+		--xor-mix, --rotate-mix:\n\
+		\x20           key the second and third ranked bytes by other mixes of them\n\
+		--xattrs:   store/restore the extended attributes of the file (Linux only)\n\
+		--owner:    store/restore the uid/gid of the file (restoring needs root)\n\
+		--no-metadata:\n\
+		\x20           refuse the options making the output depend on more than the\n\
+		\x20           content of the input, for reproducible builds\n\
+		--lanes=K:  compress K interleaved streams with their own contexts, for records\n\
+		\x20           of K bytes (1 to 16, 1 by default, ~74 MiB of memory each, ~106 MiB\n\
+		\x20           with --text)\n\
+		--blocks=N: compress the file in N blocks at once, each with contexts of its\n\
+		\x20           own on threads of its own (1 to 256, N times the memory, somewhat\n\
+		\x20           larger output), decompressed in parallel as well\n\
+		--grow:     start with a small primary table and grow it with the input, up to\n\
+		\x20           4 times the default size (less memory for small files)\n\
+		--input-fd=N, --output-fd=N:\n\
+		\x20           read or write the descriptor N set up by a parent process instead\n\
+		\x20           of a path (Unix only, nothing stored that needs the path)\n\
+		--stdin-size=BYTES:\n\
+		\x20           the size of the standard input, stored in the header and checked\n\
+		--content-hash[=HEX]:\n\
+		\x20           store the given hash of the input in the header, or its CRC-32\n\
+		--checksum-algo=ALGO:\n\
+		\x20           store a checksum of the input verified when decompressing: crc32,\n\
+		\x20           xxhash or sha256 (needs the sha256 feature)\n\
+		--map-context=PATH:\n\
+		\x20           write the contexts of the first bytes of the input to PATH as CSV\n\
+		\x20           (needs the map-context feature)\n\
+		--map-bytes=N:\n\
+		\x20           the number of bytes --map-context covers (65536 by default)\n\
+		--collision-report:\n\
+		\x20           tell how often contexts collide in the primary table, in a pass of\n\
+		\x20           its own (--tagged keeps them apart)\n\
+		--self-profile:\n\
+		\x20           tell how the bytes matched their ranks and the bits coded for each\n\
+		\x20           decision, in a pass of its own\n\
+		--calibration:\n\
+		\x20           tell how often the bits predicted with each probability of a one\n\
+		\x20           were ones, in 16 bins, in a pass of its own\n\
+		--mmap=POLICY:\n\
+		\x20           map the input in memory when compressing: never, always or auto\n\
+		\x20           (the default, regular files from 1 MiB, reading them on failure)\n\
+		--no-mmap:  same as --mmap=never, for files that may shrink while compressed\n\
+		--profile-out=PATH:\n\
+		\x20           save a profile of the secondary contexts at the end to PATH\n\
+		--profile-in=PATH:\n\
+		\x20           start the secondary contexts from the profile in PATH, which is\n\
+		\x20           then needed again to decompress\n\
+		--preset=NAME:\n\
+		\x20           start from a profile built into srx for a kind of file: json, log\n\
+		\x20           or protobuf, with the model that suits it\n\
+		--resync=MIB:\n\
+		\x20           compress into segments of MIB MiB between resync markers, each\n\
+		\x20           decoding on its own so a damaged region only loses its segments\n\
+		--volume-size=BYTES:\n\
+		\x20           split the output into volumes of BYTES bytes, OUTPUT.001, OUTPUT.002\n\
+		\x20           and so on, decompressed from OUTPUT.001\n\
+		--buffers=SIZE:\n\
+		\x20           the buffers between the stages: small, default or large, changing\n\
+		\x20           only the speed and memory, never the output\n\
+		--max-time=MS:\n\
+		\x20           stop compressing after MS milliseconds and store the rest of the\n\
+		\x20           input as it is, still decompressing to the whole file\n\
+		--verify:   decode the output on another thread as it is written and compare it\n\
+		\x20           with the input, removing the output if they differ (twice the memory)\n\
+		--flat-profile:\n\
+		\x20           print the CPU time of each stage of the pipeline after the report\n\
+		--assume-srx:\n\
+		\x20           decompress a file whose header was zeroed or stripped off as if it\n\
+		\x20           was compressed with the defaults, a last resort checking nothing\n\
+		--integrity=strict|lenient:\n\
+		\x20           fail the decompression on any byte after the end of the payload\n\
+		\x20           (strict) or ignore them (lenient, the default), the size of the\n\
+		\x20           output being checked against the header either way\n\
+		--max-memory=MIB:\n\
+		\x20           refuse to run when the contexts would need more than MIB MiB",
 		env!("CARGO_PKG_VERSION")
 	);
 	exit(0);
@@ -87,41 +155,202 @@ fn help() -> ! {
 fn main() {
 	let args: Vec<String> = env::args().collect();
 
+	// compare all the configs on a sample of the file
+	if args.len() == 3 && args[1] == "compare" {
+		if let Err(error) = run_compare(Path::new(&args[2])) {
+			println!("Error occurred! {}", error);
+			exit(1);
+		}
+		return;
+	}
+
+	// change the config and compress the same file again without restarting
+	if args.len() == 3 && args[1] == "tune" {
+		if let Err(error) = run_tune(Path::new(&args[2])) {
+			println!("Error occurred! {}", error);
+			exit(1);
+		}
+		return;
+	}
+
+	// decode and compare against the original without writing anything
+	if args.len() == 4 && args[1] == "verify" {
+		match run_verify(Path::new(&args[2]), Path::new(&args[3])) {
+			Ok(true) => return,
+			Ok(false) => exit(1),
+			Err(error) => {
+				println!("Error occurred! {}", error);
+				exit(1);
+			}
+		}
+	}
+
 	// check and parse arguments
-	if args.len() != 4 {
+	let (options, args): (Vec<&String>, Vec<&String>) = args
+		.iter()
+		.partition(|arg: &&String| arg.starts_with("--") || *arg == "-f");
+	let options: Options = Options::parse(&options).unwrap_or_else(|| help());
+
+	// read the header of a compressed file and tell what decompressing it would take
+	if options.preflight {
+		if args.len() != 3 || args[1] != "d" {
+			help()
+		}
+		match run_preflight(Path::new(args[2]), &options) {
+			Ok(true) => return,
+			Ok(false) => exit(1),
+			Err(error) => {
+				println!("Error occurred! {}", error);
+				exit(1);
+			}
+		}
+	}
+	// compress a few samples of the file for a quick guess of its ratio
+	if args.len() == 3 && args[1] == "estimate" {
+		if let Err(error) = run_estimate(Path::new(args[2]), &options) {
+			println!("Error occurred! {}", error);
+			exit(1);
+		}
+		return;
+	}
+	// decompress into nothing, checking the file without the space for its output
+	if args.len() == 3 && args[1] == "t" {
+		if options.verify || options.no_metadata {
+			help()
+		}
+		let progress: Progress = Progress::default();
+		match show_progress(&progress, options.progress, || {
+			run_test(Path::new(args[2]), &options, &progress)
+		}) {
+			Ok(output_size) => println!("OK: {} bytes decompressed.", output_size),
+			Err(error) => {
+				println!("Error occurred! {}", error);
+				if let Some(summary) = progress.summary() {
+					eprintln!("{}", summary);
+				}
+				exit(1);
+			}
+		}
+		return;
+	}
+	// compress into nothing and only print the ratio
+	if options.ratio_only && args[1] == "c" {
+		if args.len() != 3 {
+			help()
+		}
+		let progress: Progress = Progress::default();
+		match show_progress(&progress, options.progress, || {
+			run_ratio_only(Path::new(args[2]), &options, &progress)
+		}) {
+			Ok(ratio_only) => println!("{}", ratio_only.json()),
+			// the standard output is left to the JSON line alone
+			Err(error) => {
+				eprintln!("Error occurred! {}", error);
+				exit(1);
+			}
+		}
+		return;
+	}
+	// decode into nothing and only report the time of the decoder
+	if options.benchmark {
+		if args.len() != 3 || args[1] != "d" {
+			help()
+		}
+		let (result, stage_times): (AnyResult<(u64, u64, Duration)>, Option<StageTimes>) =
+			time_stages_if(options.flat_profile, || {
+				run_benchmark(Path::new(args[2]), &options)
+			});
+		match result {
+			Ok((input_size, output_size, duration)) => {
+				println!("{}", Report::new(input_size, output_size, duration, false));
+				if let Some(times) = stage_times {
+					println!("{}", StageReport::new(times, duration));
+				}
+			}
+			Err(error) => {
+				println!("Error occurred! {}", error);
+				exit(1);
+			}
+		}
+		return;
+	}
+	// descriptors set up by a parent process stand for the paths, which are then left out
+	let is_fds: bool = options.input_fd.is_some() || options.output_fd.is_some();
+	let paths: usize = match is_fds {
+		true => usize::from(options.input_fd.is_none()) + usize::from(options.output_fd.is_none()),
+		false => 2,
+	};
+	if args.len() != 2 + paths {
 		help()
 	}
-	let is_compress: bool = match args[1].as_str() {
-		"c" => true,
-		"d" => false,
+	// decompresses what srx wrote and compresses anything else, then checked like c or d
+	let mode: &str = match args[1].as_str() {
+		"auto" if !is_fds => match is_srx(Path::new(args[2])) {
+			Ok(true) => "d",
+			Ok(false) => "c",
+			Err(error) => {
+				println!("Error occurred! {}", error);
+				exit(1);
+			}
+		},
+		mode => mode,
+	};
+	let (is_compress, is_recompress): (bool, bool) = match mode {
+		"d" if options.verify || options.no_metadata => help(),
+		"d" => (false, false),
+		// only the decompression goes without a header, or checks the end of the payload
+		_ if options.assume_srx || options.config.strict_end => help(),
+		// only the compression of a file is checked as it goes
+		"recompress" if options.verify => help(),
+		"c" => (true, false),
+		"recompress" if !is_fds => (true, true),
 		_ => help(),
 	};
-	let input_path: &Path = Path::new(&args[2]);
-	let output_path: &Path = Path::new(&args[3]);
 
-	// run the compression
-	match run(input_path, output_path, is_compress) {
+	// the paths the descriptors do not stand for
+	let mut paths = args[2..].iter().map(|path| Path::new(path.as_str()));
+	let input_path: Option<&Path> = options.input_fd.is_none().then(|| paths.next()).flatten();
+	let output_path: Option<&Path> = options.output_fd.is_none().then(|| paths.next()).flatten();
+	if let Err(error) = check_output(input_path, output_path, is_compress, &options) {
+		println!("Error occurred! {}", error);
+		exit(EXIT_REFUSED);
+	}
+
+	// run the compression, keeping how far it went to tell if it fails
+	let progress: Progress = Progress::default();
+	let (result, stage_times): (AnyResult<(u64, u64, Duration)>, Option<StageTimes>) =
+		time_stages_if(options.flat_profile, || {
+			check_no_metadata(&options)?;
+			show_progress(&progress, options.progress, || {
+				if is_fds {
+					run_fds(input_path, output_path, is_compress, &options, &progress)
+				} else {
+					let input_path: &Path = Path::new(args[2]);
+					let output_path: &Path = Path::new(args[3]);
+					match is_recompress {
+						true => run_recompress(input_path, output_path, &options, &progress),
+						false => run(input_path, output_path, is_compress, &options, &progress),
+					}
+				}
+			})
+		});
+	match result {
 		Ok((input_size, output_size, duration)) => {
 			// calculating and report
-			let (percentage, speed) = if is_compress {
-				(
-					output_size as f64 / input_size as f64 * 100.0,
-					input_size as f64 / duration / (1 << 20) as f64,
-				)
-			} else {
-				(
-					input_size as f64 / output_size as f64 * 100.0,
-					output_size as f64 / duration / (1 << 20) as f64,
-				)
-			};
 			println!(
-				"{} -> {} ({:.2}%) in {:.2} seconds ({:.2} MiB/s)",
-				input_size, output_size, percentage, duration, speed
+				"{}",
+				Report::new(input_size, output_size, duration, is_compress)
 			);
+			if let Some(times) = stage_times {
+				println!("{}", StageReport::new(times, duration));
+			}
 		}
 		Err(error) => {
-			// something unexpected happened
+			// something unexpected happened, somewhere in the input if it was read at all
 			println!("Error occurred! {}", error);
+			if let Some(summary) = progress.summary() {
+				eprintln!("{}", summary);
+			}
 			exit(1);
 		}
 	};
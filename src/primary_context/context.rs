@@ -16,56 +16,260 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::{Buffer, Byte};
 use super::history::{ByteHistory, HistoryState};
 use super::matched::ByteMatched;
+use crate::basic::{zeroed_slice, Byte};
 
 // -----------------------------------------------
 
-pub struct PrimaryContext<const SIZE: usize> {
+// Where every table starts, as if the input followed a zero byte hashed to the first entry. The
+// encoder and the decoder must start each table from exactly the same state, or the stream
+// desyncs without any error: a table starting over must go back to these, never to a seed of
+// its own.
+pub const INITIAL_BYTE: u8 = 0;
+pub const INITIAL_HASH: usize = 0;
+
+// -----------------------------------------------
+
+// The size is a power of 2, so the mask is the same as % size. The product wraps on 32-bit
+// targets for a table of more than 2^26 entries, which leaves the bits under the mask as they
+// are on 64-bit ones.
+#[inline(always)]
+pub(super) fn next_hash(hash_value: usize, next_byte: Byte, size: usize) -> usize {
+	let hash_value: usize =
+		(hash_value.wrapping_mul(5 << 5) + usize::from(next_byte) + 1) & (size - 1);
+	debug_assert!(hash_value < size);
+	hash_value
+}
+
+// hash of the current run of letters and digits (case insensitive, any non-ASCII byte counts as
+// a letter so UTF-8 words stay whole), zero outside of a word
+#[inline(always)]
+pub(super) fn next_word_hash(word_hash: usize, next_byte: Byte) -> usize {
+	let byte: u8 = u8::from(next_byte);
+	if byte.is_ascii_alphanumeric() || byte >= 0x80 {
+		(word_hash + usize::from(byte.to_ascii_lowercase()) + 1).wrapping_mul(0x2F0F3D) & 0xFFFFFFFF
+	} else {
+		0
+	}
+}
+
+// -----------------------------------------------
+
+// What the codec needs of a primary context, for the loops over the bytes to be built for each
+// kind of table: PrimaryContext, and FixedPrimaryContext for the one of the default config.
+pub trait PrimaryTable {
+	fn get_history(&self) -> ByteHistory;
+
+	fn previous_byte(&self) -> Byte;
+
+	fn hash_value(&self) -> usize;
+
+	fn word_hash(&self) -> usize;
+
+	fn matching(&mut self, current_state: HistoryState, next_byte: Byte) -> ByteMatched;
+
+	fn matched(&mut self, current_state: HistoryState, next_byte: Byte, matched: ByteMatched);
+}
+
+// -----------------------------------------------
+
+#[cfg_attr(test, derive(PartialEq))]
+pub struct PrimaryContext {
 	previous_byte: Byte,
 	hash_value: usize,
-	context: Buffer<ByteHistory, SIZE>,
+	word_hash: usize,
+	context: Box<[ByteHistory]>,
+	// the table grows 4 times larger each time as many bytes as it has entries were seen,
+	// until it reaches the limit
+	limit: usize,
+	seen: usize,
+	// the table is still growing or has tags, see adapt, so that a table of a fixed size
+	// untagged or done growing has nothing more than the hashes to do for each byte
+	adapts: bool,
+	// a tag per entry, hashed from the bytes the entry index depends on, so that a different
+	// context landing on the entry replaces its history instead of mixing with it. Empty when
+	// the table is not tagged.
+	tags: Box<[u8]>,
+	// the entries of a bucket of a tagged table, a context takes the one with its tag
+	ways: usize,
+	// the entry of the current context, the hash value unless the table is set-associative
+	entry: usize,
+	recent_bytes: u64,
+	recent_mask: u64,
 }
 
-impl<const SIZE: usize> PrimaryContext<SIZE> {
-	// assert that SIZE is power of 2
-	const _SIZE_CHECK: () = assert!(SIZE != 0 && (SIZE & (SIZE - 1)) == 0);
+impl PrimaryContext {
+	// a table of a fixed size, which must be a power of 2
+	pub fn new(size: usize) -> Self {
+		Self::growing(size, size)
+	}
+
+	// a table starting at size and growing up to limit, both must be powers of 2
+	pub fn growing(size: usize, limit: usize) -> Self {
+		assert!(size.is_power_of_two() && limit.is_power_of_two() && size <= limit);
+		Self {
+			previous_byte: Byte::from(INITIAL_BYTE),
+			hash_value: INITIAL_HASH,
+			word_hash: 0,
+			context: zeroed_slice(size),
+			limit,
+			seen: 0,
+			adapts: size < limit,
+			tags: Box::new([]),
+			ways: 1,
+			entry: INITIAL_HASH,
+			recent_bytes: 0,
+			recent_mask: 0,
+		}
+	}
 
-	pub fn new() -> Self {
+	// a table of a fixed size with a tag for every entry, split into buckets of ways entries, a
+	// power of 2: a context takes the entry of its bucket with its tag, or the least recently
+	// used one when none has it. With a single way, the entry is the one of the hash value.
+	pub fn associative(size: usize, ways: usize) -> Self {
+		assert!(ways.is_power_of_two() && ways <= size);
+		// the index only depends on the last ceil(bits / 5) bytes, the hash shifts 5 bits per byte
+		let bytes: u32 = size.trailing_zeros().div_ceil(5);
 		Self {
-			previous_byte: Byte::from(0),
-			hash_value: 0,
-			context: Buffer::new(),
+			tags: vec![0; size].into_boxed_slice(),
+			ways,
+			adapts: true,
+			entry: INITIAL_HASH & !(ways - 1),
+			recent_mask: u64::MAX.checked_shr(64 - bytes * 8).unwrap_or(0),
+			..Self::new(size)
 		}
 	}
 
-	pub fn get_history(&self) -> ByteHistory {
-		self.context[self.hash_value]
+	// bytes taken by a table of the given size
+	pub const fn memory_size(size: usize, tagged: bool) -> usize {
+		size * (std::mem::size_of::<ByteHistory>() + tagged as usize)
 	}
 
-	pub fn previous_byte(&self) -> Byte {
+	#[inline(always)]
+	fn next(&mut self, next_byte: Byte) {
+		self.previous_byte = next_byte;
+		self.hash_value = next_hash(self.hash_value, next_byte, self.context.len());
+		self.word_hash = next_word_hash(self.word_hash, next_byte);
+		self.entry = self.hash_value;
+		if self.adapts {
+			self.adapt(next_byte);
+		}
+	}
+
+	// a growing table counts the bytes until it grows, a tagged one never grows
+	#[inline(always)]
+	fn adapt(&mut self, next_byte: Byte) {
+		if self.tags.is_empty() {
+			self.seen += 1;
+			if self.seen == self.context.len() {
+				self.grow();
+			}
+		} else {
+			self.check_tag(next_byte);
+		}
+	}
+
+	#[inline(always)]
+	fn check_tag(&mut self, next_byte: Byte) {
+		self.recent_bytes =
+			(self.recent_bytes << 8 | u64::from(u8::from(next_byte))) & self.recent_mask;
+		let tag: u8 = (self.recent_bytes.wrapping_mul(0x9E3779B97F4A7C15) >> 56) as u8;
+		// the entries of a bucket are kept from the most to the least recently used one, which
+		// is started over when none has the tag, and the one taken is moved first
+		let bucket: usize = self.hash_value & !(self.ways - 1);
+		let last: usize = bucket + self.ways - 1;
+		let taken: usize = match self.tags[bucket..=last]
+			.iter()
+			.position(|entry| *entry == tag)
+		{
+			Some(way) => bucket + way,
+			None => {
+				self.tags[last] = tag;
+				self.context[last] = ByteHistory::default();
+				last
+			}
+		};
+		self.tags[bucket..=taken].rotate_right(1);
+		self.context[bucket..=taken].rotate_right(1);
+		self.entry = bucket;
+	}
+
+	// every entry of the larger table starts from the entry it was folded into, so nothing
+	// learned so far is lost, and the hashes diverge as the new high bits fill in
+	#[cold]
+	fn grow(&mut self) {
+		debug_assert!(self.tags.is_empty());
+		let size: usize = (self.context.len() * 4).min(self.limit);
+		let mask: usize = self.context.len() - 1;
+		self.context = (0..size).map(|index| self.context[index & mask]).collect();
+		// a table that can not grow stops counting, so seen never wraps on 32-bit targets
+		self.adapts = size < self.limit;
+	}
+
+	// the number of entries of the table, which changes as it grows
+	pub fn size(&self) -> usize {
+		self.context.len()
+	}
+
+	// panics if the table got into a state no input can lead to, an oracle for the fuzz targets
+	// to call after each step, so a corruption is found where it happens
+	#[cfg(any(test, fuzzing))]
+	pub fn check_invariants(&self) {
+		let size: usize = self.context.len();
+		assert!(size.is_power_of_two() && self.limit.is_power_of_two() && size <= self.limit);
+		assert!(
+			self.hash_value < size,
+			"hash {} of {}",
+			self.hash_value,
+			size
+		);
+		assert!(self.word_hash <= 0xFFFFFFFF, "word hash {}", self.word_hash);
+		// the table would have grown, and a full one stops counting
+		assert!(
+			self.seen < size || size == self.limit,
+			"seen {} of {}",
+			self.seen,
+			size
+		);
+		assert!(self.tags.is_empty() || (self.tags.len() == size && size == self.limit));
+		assert_eq!(self.adapts, size < self.limit || !self.tags.is_empty());
+		assert!(self.ways.is_power_of_two() && (self.ways == 1 || !self.tags.is_empty()));
+		assert_eq!(self.entry, self.hash_value & !(self.ways - 1));
+		assert_eq!(self.recent_bytes & !self.recent_mask, 0);
+		if let Some(index) = self.context.iter().position(|history| !history.is_valid()) {
+			panic!("entry {} has no state", index);
+		}
+	}
+}
+
+impl PrimaryTable for PrimaryContext {
+	fn get_history(&self) -> ByteHistory {
+		self.context[self.entry]
+	}
+
+	fn previous_byte(&self) -> Byte {
 		self.previous_byte
 	}
 
-	pub fn hash_value(&self) -> usize {
+	fn hash_value(&self) -> usize {
 		self.hash_value
 	}
 
-	pub fn matching(&mut self, current_state: HistoryState, next_byte: Byte) -> ByteMatched {
-		let current_history: &mut ByteHistory = &mut self.context[self.hash_value];
+	fn word_hash(&self) -> usize {
+		self.word_hash
+	}
+
+	fn matching(&mut self, current_state: HistoryState, next_byte: Byte) -> ByteMatched {
+		let current_history: &mut ByteHistory = &mut self.context[self.entry];
 		let matching_byte: ByteMatched = current_history.matching(current_state, next_byte);
-		self.previous_byte = next_byte;
-		self.hash_value = (self.hash_value * (5 << 5) + usize::from(next_byte) + 1) % SIZE;
-		debug_assert!(self.hash_value < SIZE);
+		self.next(next_byte);
 		return matching_byte;
 	}
 
-	pub fn matched(&mut self, current_state: HistoryState, next_byte: Byte, matched: ByteMatched) {
-		let current_history: &mut ByteHistory = &mut self.context[self.hash_value];
+	fn matched(&mut self, current_state: HistoryState, next_byte: Byte, matched: ByteMatched) {
+		let current_history: &mut ByteHistory = &mut self.context[self.entry];
 		current_history.matched(current_state, next_byte, matched);
-		self.previous_byte = next_byte;
-		self.hash_value = (self.hash_value * (5 << 5) + usize::from(next_byte) + 1) % SIZE;
-		debug_assert!(self.hash_value < SIZE);
+		self.next(next_byte);
 	}
 }
@@ -0,0 +1,99 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::context::{next_hash, next_word_hash, PrimaryTable, INITIAL_BYTE, INITIAL_HASH};
+use super::history::{ByteHistory, HistoryState};
+use super::matched::ByteMatched;
+use crate::basic::{zeroed_slice, Byte};
+
+// -----------------------------------------------
+
+// A table of SIZE entries, a power of 2, that neither grows nor has tags: the table of the
+// default config, with nothing but the hashes to do for each byte and the mask known at compile
+// time. It gives the same histories as PrimaryContext::new(SIZE).
+pub struct FixedPrimaryContext<const SIZE: usize> {
+	previous_byte: Byte,
+	hash_value: usize,
+	word_hash: usize,
+	context: Box<[ByteHistory; SIZE]>,
+}
+
+impl<const SIZE: usize> FixedPrimaryContext<SIZE> {
+	pub fn new() -> Self {
+		const { assert!(SIZE.is_power_of_two()) };
+		Self {
+			previous_byte: Byte::from(INITIAL_BYTE),
+			hash_value: INITIAL_HASH,
+			word_hash: 0,
+			// a slice of SIZE entries always fits the array
+			context: zeroed_slice(SIZE)
+				.try_into()
+				.unwrap_or_else(|_| unreachable!()),
+		}
+	}
+
+	#[inline(always)]
+	fn next(&mut self, next_byte: Byte) {
+		self.previous_byte = next_byte;
+		self.hash_value = next_hash(self.hash_value, next_byte, SIZE);
+		self.word_hash = next_word_hash(self.word_hash, next_byte);
+	}
+
+	// the hash value is always under SIZE, the mask only lets the bounds check go
+	#[inline(always)]
+	fn entry(&self) -> usize {
+		self.hash_value & (SIZE - 1)
+	}
+}
+
+impl<const SIZE: usize> Default for FixedPrimaryContext<SIZE> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<const SIZE: usize> PrimaryTable for FixedPrimaryContext<SIZE> {
+	fn get_history(&self) -> ByteHistory {
+		self.context[self.entry()]
+	}
+
+	fn previous_byte(&self) -> Byte {
+		self.previous_byte
+	}
+
+	fn hash_value(&self) -> usize {
+		self.hash_value
+	}
+
+	fn word_hash(&self) -> usize {
+		self.word_hash
+	}
+
+	fn matching(&mut self, current_state: HistoryState, next_byte: Byte) -> ByteMatched {
+		let current_history: &mut ByteHistory = &mut self.context[self.entry()];
+		let matching_byte: ByteMatched = current_history.matching(current_state, next_byte);
+		self.next(next_byte);
+		return matching_byte;
+	}
+
+	fn matched(&mut self, current_state: HistoryState, next_byte: Byte, matched: ByteMatched) {
+		let current_history: &mut ByteHistory = &mut self.context[self.entry()];
+		current_history.matched(current_state, next_byte, matched);
+		self.next(next_byte);
+	}
+}
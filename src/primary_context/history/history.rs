@@ -17,12 +17,12 @@
  */
 
 use super::state::{HistoryState, STATE_TABLE};
-use crate::basic::Byte;
+use crate::basic::{Byte, Zeroed};
 use crate::primary_context::ByteMatched;
 
 // -----------------------------------------------
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub struct ByteHistory(u32);
 
 impl Default for ByteHistory {
@@ -31,23 +31,37 @@ impl Default for ByteHistory {
 	}
 }
 
+// SAFETY: a plain u32, its default being 0
+unsafe impl Zeroed for ByteHistory {}
+
 impl ByteHistory {
+	#[inline]
 	pub fn first_byte(&self) -> Byte {
 		Byte::from((self.0 >> 8) & 0xFF)
 	}
 
+	#[inline]
 	pub fn second_byte(&self) -> Byte {
 		Byte::from((self.0 >> 16) & 0xFF)
 	}
 
+	#[inline]
 	pub fn third_byte(&self) -> Byte {
 		Byte::from(self.0 >> 24)
 	}
 
+	#[inline]
 	pub fn get_state(&self) -> HistoryState {
 		STATE_TABLE[(self.0 & 0xFF) as usize]
 	}
 
+	// the state is an index in the state table, which has less than 256 entries
+	#[cfg(any(test, fuzzing))]
+	pub fn is_valid(&self) -> bool {
+		((self.0 & 0xFF) as usize) < STATE_TABLE.len()
+	}
+
+	#[inline]
 	pub fn matching(&mut self, current_state: HistoryState, next_byte: Byte) -> ByteMatched {
 		let mask: u32 = self.0 ^ (0x01_01_01_00 * u32::from(next_byte));
 		let matched: ByteMatched = if (mask & 0x00_00_FF_00) == 0 {
@@ -63,6 +77,7 @@ impl ByteHistory {
 		matched
 	}
 
+	#[inline]
 	pub fn matched(&mut self, current_state: HistoryState, next_byte: Byte, matched: ByteMatched) {
 		let byte_history: u32 = self.0;
 		debug_assert!(STATE_TABLE[(byte_history & 0xFF) as usize] == current_state);
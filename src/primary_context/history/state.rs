@@ -17,6 +17,8 @@
  */
 
 use crate::primary_context::ByteMatched;
+use std::cmp::Ordering;
+use std::fmt;
 
 // -----------------------------------------------
 
@@ -24,7 +26,7 @@ include!("state_table.inc");
 
 // -----------------------------------------------
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq)]
 pub struct HistoryState(u64);
 
 impl HistoryState {
@@ -44,6 +46,7 @@ impl HistoryState {
 		)
 	}
 
+	#[inline]
 	pub fn next(&self, matched: ByteMatched) -> usize {
 		match matched {
 			ByteMatched::FIRST => (self.0 & 0xFF) as usize,
@@ -53,7 +56,48 @@ impl HistoryState {
 		}
 	}
 
+	#[inline]
 	pub fn match_count(&self) -> usize {
 		(self.0 >> 32) as usize
 	}
+
+	// the states after each kind of match, for sorting and printing
+	fn transitions(&self) -> [usize; 4] {
+		[
+			self.next(ByteMatched::FIRST),
+			self.next(ByteMatched::SECOND),
+			self.next(ByteMatched::THIRD),
+			self.next(ByteMatched::NONE),
+		]
+	}
+}
+
+// by match count first, then by the transitions
+impl Ord for HistoryState {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.match_count()
+			.cmp(&other.match_count())
+			.then_with(|| self.transitions().cmp(&other.transitions()))
+	}
+}
+
+impl PartialOrd for HistoryState {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Debug for HistoryState {
+	fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let [next_if_first, next_if_second, next_if_third, next_if_miss]: [usize; 4] =
+			self.transitions();
+		formatter
+			.debug_struct("HistoryState")
+			.field("match_count", &self.match_count())
+			.field("next_if_first", &next_if_first)
+			.field("next_if_second", &next_if_second)
+			.field("next_if_third", &next_if_third)
+			.field("next_if_miss", &next_if_miss)
+			.finish()
+	}
 }
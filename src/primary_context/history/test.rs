@@ -16,10 +16,12 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::AnyResult;
 use super::state::{HistoryState, STATE_TABLE};
+use crate::basic::AnyResult;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
 use std::fs::File;
 use std::hash::Hash;
 use std::io::{BufWriter, Write};
@@ -85,6 +87,7 @@ fn increase(mut value: u8, max: u8) -> u8 {
 	}
 }
 
+#[allow(dead_code)]
 fn decrease(mut value: u8, max: u8) -> u8 {
 	value = value.saturating_sub(1);
 	if value >= max {
@@ -109,9 +112,10 @@ fn dec_nz(mut value: u8, max: u8) -> u8 {
 
 // -----------------------------------------------
 
+// ordered, so that the generated table does not depend on the order of a hash map
 #[derive(Debug)]
 struct PrimitiveStateTable {
-	map: HashMap<StateInfo, PrimitiveState>,
+	map: BTreeMap<StateInfo, PrimitiveState>,
 }
 
 impl PrimitiveStateTable {
@@ -122,7 +126,7 @@ impl PrimitiveStateTable {
 
 	fn new() -> Self {
 		Self {
-			map: HashMap::new(),
+			map: BTreeMap::new(),
 		}
 	}
 
@@ -212,7 +216,7 @@ impl PrimitiveStateTable {
 	fn export(&self) -> AnyResult<()> {
 		let mut writer: BufWriter<File> = BufWriter::new(File::create(Path::new("map.gexf"))?);
 
-		writer.write(br#"<?xml version="1.0" encoding="UTF-8"?>
+		writer.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
 <gexf xmlns="http://gexf.net/1.3" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://gexf.net/1.3 http://gexf.net/1.3/gexf.xsd" version="1.3">
 	<meta lastmodifieddate="2009-03-20">
 		<creator>Gephi.org</creator>
@@ -235,7 +239,7 @@ impl PrimitiveStateTable {
 			let second: usize = state.second as usize;
 			let third: usize = state.third as usize;
 
-			writer.write(
+			writer.write_all(
 				format!(
 					r#"
 			<node id="{}" label="{},{},{}">
@@ -251,7 +255,7 @@ impl PrimitiveStateTable {
 			)?;
 		}
 
-		writer.write(
+		writer.write_all(
 			br#"
 		</nodes>
 		<edges>"#,
@@ -264,7 +268,7 @@ impl PrimitiveStateTable {
 			let next_if_third = state.next_if_third.id();
 			let next_if_miss = state.next_if_miss.id();
 
-			writer.write(
+			writer.write_all(
 				format!(
 					r#"
 			<edge source="{}" target="{}">
@@ -300,7 +304,7 @@ impl PrimitiveStateTable {
 			)?;
 		}
 
-		writer.write(
+		writer.write_all(
 			br#"
 		</edges>
 	</graph>
@@ -312,8 +316,8 @@ impl PrimitiveStateTable {
 
 // -----------------------------------------------
 
-#[test]
-fn test_and_generate_state_table() -> AnyResult<()> {
+// the text of state_table.inc and the table it holds
+fn generate() -> (String, Vec<HistoryState>) {
 	let mut table: PrimitiveStateTable = PrimitiveStateTable::new();
 	table.state_auto(StateInfo {
 		first: 0,
@@ -323,17 +327,17 @@ fn test_and_generate_state_table() -> AnyResult<()> {
 
 	// table.export()?;
 
-	let mut states: Vec<&PrimitiveState> = table.map.values().collect();
-	states.sort_by_key(|x| x.current_state);
+	// in the order of the map
+	let states: Vec<&PrimitiveState> = table.map.values().collect();
 
-	let mut states_index: HashMap<&StateInfo, usize> = HashMap::new();
+	let mut states_index: BTreeMap<&StateInfo, usize> = BTreeMap::new();
 	for (index, &state) in states.iter().enumerate() {
 		states_index.insert(&state.current_state, index);
 	}
 
 	// create next states array
-	println!(
-		"pub const STATE_TABLE: &[HistoryState] = &[ // length = {}",
+	let mut text: String = format!(
+		"pub const STATE_TABLE: &[HistoryState] = &[ // length = {}\n",
 		states.len()
 	);
 	let mut state_table: Vec<HistoryState> = Vec::new();
@@ -352,8 +356,8 @@ fn test_and_generate_state_table() -> AnyResult<()> {
 			next_if_third as u8,
 			next_if_miss as u8,
 		));
-		println!(
-			"\tHistoryState::new({:2}, {:3}, {:3}, {:3}, {:3}), // {:3}, {:2}, {:2}, {:2}",
+		text.push_str(&format!(
+			"\tHistoryState::new({:2}, {:3}, {:3}, {:3}, {:3}), // {:3}, {:2}, {:2}, {:2}\n",
 			first_count,
 			next_if_first,
 			next_if_second,
@@ -363,11 +367,75 @@ fn test_and_generate_state_table() -> AnyResult<()> {
 			first_count,
 			second_count,
 			third_count,
-		);
+		));
 	}
-	println!("];");
+	text.push_str("];\n");
+	(text, state_table)
+}
 
-	debug_assert!(state_table.eq(STATE_TABLE));
+// The committed state_table.inc must be exactly what this generator prints. To
+// regenerate it after changing the generator, run
+//     SRX_REGENERATE_STATE_TABLE=1 cargo test test_and_generate_state_table
+// which overwrites the file, then rebuild and run the tests again.
+#[test]
+fn test_and_generate_state_table() -> AnyResult<()> {
+	let (text, state_table): (String, Vec<HistoryState>) = generate();
+
+	if env::var_os("SRX_REGENERATE_STATE_TABLE").is_some() {
+		fs::write(
+			concat!(
+				env!("CARGO_MANIFEST_DIR"),
+				"/src/primary_context/history/state_table.inc"
+			),
+			&text,
+		)?;
+	}
+
+	assert_eq!(
+		text,
+		include_str!("state_table.inc"),
+		"state_table.inc is out of date!"
+	);
+	assert_eq!(state_table, STATE_TABLE);
 
 	Ok(())
 }
+
+#[test]
+fn test_generate_state_table_twice() {
+	let (first_text, first_table): (String, Vec<HistoryState>) = generate();
+	let (second_text, second_table): (String, Vec<HistoryState>) = generate();
+	assert_eq!(first_text, second_text);
+	assert_eq!(first_table, second_table);
+}
+
+#[test]
+fn test_history_state_debug_and_order() {
+	let state: HistoryState = HistoryState::new(3, 10, 20, 30, 40);
+	assert_eq!(
+		format!("{:?}", state),
+		"HistoryState { match_count: 3, next_if_first: 10, next_if_second: 20, \
+		next_if_third: 30, next_if_miss: 40 }"
+	);
+
+	// the match count goes before the transitions
+	let mut states: Vec<HistoryState> = vec![
+		HistoryState::new(1, 0, 0, 0, 0),
+		HistoryState::new(0, 2, 0, 0, 0),
+		HistoryState::new(0, 1, 0, 0, 9),
+	];
+	states.sort();
+	assert_eq!(
+		states,
+		vec![
+			HistoryState::new(0, 1, 0, 0, 9),
+			HistoryState::new(0, 2, 0, 0, 0),
+			HistoryState::new(1, 0, 0, 0, 0),
+		]
+	);
+	let mut sorted: Vec<HistoryState> = STATE_TABLE.to_vec();
+	sorted.sort();
+	assert!(sorted
+		.windows(2)
+		.all(|pair| pair[0].match_count() <= pair[1].match_count()));
+}
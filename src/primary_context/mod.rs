@@ -17,9 +17,15 @@
  */
 
 mod context;
+mod fixed;
 mod history;
 mod matched;
+#[cfg(test)]
+mod test;
 
-pub use self::context::PrimaryContext;
+pub use self::context::{PrimaryContext, PrimaryTable};
+#[cfg(any(test, feature = "internals"))]
+pub use self::context::{INITIAL_BYTE, INITIAL_HASH};
+pub use self::fixed::FixedPrimaryContext;
 pub use self::history::{ByteHistory, HistoryState};
 pub use self::matched::ByteMatched;
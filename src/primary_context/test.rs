@@ -0,0 +1,226 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::context::{next_hash, PrimaryContext, PrimaryTable};
+use super::fixed::FixedPrimaryContext;
+use super::history::HistoryState;
+use super::matched::ByteMatched;
+use crate::basic::{AnyResult, Byte};
+use crate::bridged_context::PRIMARY_CONTEXT_SIZE;
+
+// -----------------------------------------------
+
+fn generated() -> impl Iterator<Item = u8> {
+	(0u32..).map(|index| (index.wrapping_mul(index) >> 3) as u8 ^ (index % 7) as u8)
+}
+
+// bytes following their 3 previous bytes through a fixed random table, with some noise: 2^18
+// contexts that are each well predicted, far too many for a small table
+fn collision_prone(length: usize) -> Vec<u8> {
+	let mut state: u32 = 7;
+	let mut next = || {
+		state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+		state >> 8
+	};
+	let table: Vec<u8> = (0..1 << 18).map(|_| (next() % 64) as u8).collect();
+	let mut output: Vec<u8> = vec![0; 3];
+	while output.len() < length {
+		let context: &[u8] = &output[output.len() - 3..];
+		output.push(match next() % 16 {
+			0 => (next() % 64) as u8,
+			_ => {
+				table
+					[(context[0] as usize) << 12 | (context[1] as usize) << 6 | context[2] as usize]
+			}
+		});
+	}
+	output
+}
+
+// the bytes the history ranked first, coded with a single bit each
+fn first_hits(mut context: PrimaryContext, input: &[u8]) -> usize {
+	let mut count: usize = 0;
+	for &byte in input {
+		let state: HistoryState = context.get_history().get_state();
+		if context.matching(state, Byte::from(byte)) == ByteMatched::FIRST {
+			count += 1;
+		}
+	}
+	count
+}
+
+// mispredictions of a history that was confident, the expensive ones
+fn confident_mispredictions(mut context: PrimaryContext, input: &[u8]) -> usize {
+	let mut count: usize = 0;
+	for &byte in input {
+		let state: HistoryState = context.get_history().get_state();
+		if context.matching(state, Byte::from(byte)) != ByteMatched::FIRST
+			&& state.match_count() >= 4
+		{
+			count += 1;
+		}
+	}
+	count
+}
+
+fn check_mask_matches_modulo<const SIZE: usize>() {
+	let mut value: usize = 1;
+	for _ in 0..100_000 {
+		assert_eq!(
+			value % SIZE,
+			value & (SIZE - 1),
+			"SIZE = {}, value = {}",
+			SIZE,
+			value
+		);
+		value = value
+			.wrapping_mul(6364136223846793005)
+			.wrapping_add(1442695040888963407);
+	}
+	assert_eq!(usize::MAX % SIZE, usize::MAX & (SIZE - 1));
+}
+
+fn check_hash_chain(size: usize, limit: usize) {
+	let mut context: PrimaryContext = PrimaryContext::growing(size, limit);
+	let mut expected: usize = 0;
+	let mut expected_size: usize = size;
+	for (index, byte) in generated().take(100_000).enumerate() {
+		let state: HistoryState = context.get_history().get_state();
+		context.matching(state, Byte::from(byte));
+		expected = (expected * (5 << 5) + byte as usize + 1) % expected_size;
+		assert_eq!(context.hash_value(), expected, "byte {}", index);
+		// the table grows after as many bytes as it has entries
+		if index + 1 == expected_size {
+			expected_size = (expected_size * 4).min(limit);
+		}
+	}
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_hash_mask_matches_modulo() -> AnyResult<()> {
+	check_mask_matches_modulo::<PRIMARY_CONTEXT_SIZE>();
+	check_mask_matches_modulo::<0x1000>();
+	check_mask_matches_modulo::<1>();
+	check_hash_chain(PRIMARY_CONTEXT_SIZE, PRIMARY_CONTEXT_SIZE);
+	check_hash_chain(0x1000, 0x1000);
+	Ok(())
+}
+
+#[test]
+fn test_growth_hash_chain() -> AnyResult<()> {
+	// grows at 4 KiB, 16 KiB and 64 KiB, the last step only doubles the table
+	check_hash_chain(0x1000, 0x20000);
+	check_hash_chain(1, 0x100000);
+	Ok(())
+}
+
+#[test]
+fn test_tagged_collisions() -> AnyResult<()> {
+	let input: Vec<u8> = collision_prone(1_000_000);
+	let plain: usize = confident_mispredictions(PrimaryContext::new(1 << 12), &input);
+	let tagged: usize = confident_mispredictions(PrimaryContext::associative(1 << 12, 1), &input);
+	assert!(tagged * 10 < plain, "plain: {}, tagged: {}", plain, tagged);
+	// with few collisions the tags do not hurt either
+	let plain: usize = confident_mispredictions(PrimaryContext::new(1 << 24), &input);
+	let tagged: usize = confident_mispredictions(PrimaryContext::associative(1 << 24, 1), &input);
+	assert!(tagged <= plain, "plain: {}, tagged: {}", plain, tagged);
+	Ok(())
+}
+
+#[test]
+fn test_associative_collisions() -> AnyResult<()> {
+	// 2^18 contexts on 2^14 and 2^16 entries, the first ranked byte right more often when the
+	// colliding contexts keep entries of their own
+	let input: Vec<u8> = collision_prone(1_000_000);
+	for size in [1 << 14, 1 << 16] {
+		let direct: usize = first_hits(PrimaryContext::new(size), &input);
+		let two_way: usize = first_hits(PrimaryContext::associative(size, 2), &input);
+		assert!(
+			direct < two_way,
+			"size: {}, direct: {}, two-way: {}",
+			size,
+			direct,
+			two_way
+		);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_check_invariants() -> AnyResult<()> {
+	let input: Vec<u8> = collision_prone(100_000);
+	for mut context in [
+		PrimaryContext::new(0x1000),
+		PrimaryContext::growing(0x100, 0x10000),
+		PrimaryContext::associative(0x1000, 1),
+		PrimaryContext::associative(0x1000, 4),
+	] {
+		context.check_invariants();
+		// checked after every step, through the growth of the table and the tag replacements
+		for &byte in &input {
+			let state: HistoryState = context.get_history().get_state();
+			context.matching(state, Byte::from(byte));
+			context.check_invariants();
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_fixed_matches_new() -> AnyResult<()> {
+	let input: Vec<u8> = collision_prone(100_000);
+	let mut fixed: FixedPrimaryContext<0x1000> = FixedPrimaryContext::new();
+	let mut context: PrimaryContext = PrimaryContext::new(0x1000);
+	for (index, &byte) in input.iter().enumerate() {
+		assert!(
+			fixed.get_history() == context.get_history(),
+			"byte {}",
+			index
+		);
+		let state: HistoryState = context.get_history().get_state();
+		assert!(
+			fixed.matching(state, Byte::from(byte)) == context.matching(state, Byte::from(byte)),
+			"byte {}",
+			index
+		);
+		assert_eq!(fixed.hash_value(), context.hash_value(), "byte {}", index);
+		assert_eq!(fixed.word_hash(), context.word_hash(), "byte {}", index);
+	}
+	Ok(())
+}
+
+#[test]
+fn test_hash_wraps_like_32_bit() -> AnyResult<()> {
+	// the largest growing table has 2^28 entries, the product of its hash overflows 32 bits
+	let size: usize = 1 << 28;
+	for hash_value in [0, 1 << 26, (1 << 27) + 12345, size - 1] {
+		for byte in [0u8, 0x41, 0xFF] {
+			let expected: u32 = (hash_value as u32)
+				.wrapping_mul(5 << 5)
+				.wrapping_add(u32::from(byte) + 1)
+				& (size as u32 - 1);
+			assert_eq!(
+				next_hash(hash_value, Byte::from(byte), size),
+				expected as usize
+			);
+		}
+	}
+	Ok(())
+}
@@ -24,6 +24,7 @@ pub enum Bit {
 }
 
 impl From<Bit> for bool {
+	#[inline]
 	fn from(value: Bit) -> Self {
 		match value {
 			Bit::Zero => false,
@@ -33,6 +34,7 @@ impl From<Bit> for bool {
 }
 
 impl From<bool> for Bit {
+	#[inline]
 	fn from(value: bool) -> Self {
 		match value {
 			false => Bit::Zero,
@@ -41,10 +43,14 @@ impl From<bool> for Bit {
 	}
 }
 
+// To and from the integers, 0 being Zero and 1 being One. Any other value is a bug of the caller,
+// such as a bit unpacked from the wrong place, and fails the debug assert; release builds map
+// it to One like a bool would, all nonzero values alike, so that decoding stays defined.
 macro_rules! impl_from_for_bit {
     ($($t:ty),*) => {
         $(
             impl From<Bit> for $t {
+				#[inline]
 				fn from(value: Bit) -> Self {
 					match value {
 						Bit::Zero => 0,
@@ -53,6 +59,7 @@ macro_rules! impl_from_for_bit {
 				}
             }
             impl From<$t> for Bit {
+				#[inline]
 				fn from(value: $t) -> Self {
 					debug_assert!(value == 0 || value == 1, "Unexpected value for Bit!");
 					match value {
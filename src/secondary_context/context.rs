@@ -16,29 +16,113 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::Buffer;
 use super::bit::Bit;
 use super::state::{BitState, StateInfo};
+use crate::basic::zeroed_slice;
 
-pub struct SecondaryContext<const SIZE: usize> {
-	context: Buffer<BitState, SIZE>,
+// the entries of a literal are all within this many from its context, see literal_batch
+pub const LITERAL_WINDOW: usize = 256;
+
+pub struct SecondaryContext {
+	context: Box<[BitState]>,
 }
 
-impl<const SIZE: usize> SecondaryContext<SIZE> {
-	pub fn new() -> Self {
+impl SecondaryContext {
+	// bytes taken by a table of the given size
+	pub const fn memory_size(size: usize) -> usize {
+		size * std::mem::size_of::<BitState>()
+	}
+
+	pub fn new(size: usize) -> Self {
 		Self {
-			context: Buffer::new(),
+			context: zeroed_slice(size),
 		}
 	}
 
+	// the number of entries of the table
+	pub fn size(&self) -> usize {
+		self.context.len()
+	}
+
 	pub fn get_info(&self, context_index: usize) -> StateInfo {
-		debug_assert!(context_index < SIZE);
+		debug_assert!(context_index < self.context.len());
 		self.context[context_index].get_info()
 	}
 
+	// the state of the entry as its index in the state table, for saving and restoring it
+	pub fn state(&self, context_index: usize) -> u16 {
+		debug_assert!(context_index < self.context.len());
+		self.context[context_index].index()
+	}
+
+	pub fn set_state(&mut self, context_index: usize, state: u16) {
+		debug_assert!(context_index < self.context.len());
+		self.context[context_index] = BitState::from_index(state)
+	}
+
 	// return current prediction and then update the prediction with new bit
 	pub fn update(&mut self, current_state: StateInfo, context_index: usize, bit: Bit) {
-		debug_assert!(context_index < SIZE);
+		debug_assert!(context_index < self.context.len());
 		self.context[context_index].update(current_state, bit)
 	}
+
+	// The states of several entries within LITERAL_WINDOW of base, the bounds of the window
+	// checked once instead of on each entry. The offsets must be different from each other for
+	// update_batch to be the same as updating them one by one.
+	pub fn get_info_batch<const N: usize>(
+		&self,
+		base: usize,
+		offsets: &[usize; N],
+	) -> [StateInfo; N] {
+		let window: &[BitState; LITERAL_WINDOW] = self.context[base..base + LITERAL_WINDOW]
+			.try_into()
+			.unwrap();
+		offsets.map(|offset| {
+			debug_assert!(offset < LITERAL_WINDOW);
+			window[offset % LITERAL_WINDOW].get_info()
+		})
+	}
+
+	pub fn update_batch<const N: usize>(
+		&mut self,
+		base: usize,
+		offsets: &[usize; N],
+		current_states: &[StateInfo; N],
+		bits: &[Bit; N],
+	) {
+		let window: &mut [BitState; LITERAL_WINDOW] = (&mut self.context
+			[base..base + LITERAL_WINDOW])
+			.try_into()
+			.unwrap();
+		for index in 0..N {
+			debug_assert!(offsets[index] < LITERAL_WINDOW);
+			window[offsets[index] % LITERAL_WINDOW].update(current_states[index], bits[index]);
+		}
+	}
+}
+
+// panics if an entry is not in the state table, an oracle for the fuzz targets to call after
+// each step, so a corruption is found where it happens
+#[cfg(any(test, fuzzing))]
+impl SecondaryContext {
+	pub fn check_invariants(&self) {
+		if let Some(index) = self.context.iter().position(|state| !state.is_valid()) {
+			panic!("entry {} has no state", index);
+		}
+	}
+}
+
+// for mixers built on top of the contexts, the codec itself keeps the state it fetched
+#[cfg(any(test, feature = "internals"))]
+impl SecondaryContext {
+	// the prediction of the context, without touching it
+	pub fn predict(&self, context_index: usize) -> u32 {
+		self.get_info(context_index).prediction()
+	}
+
+	// same as update, fetching the current state itself
+	pub fn update_bit(&mut self, context_index: usize, bit: Bit) {
+		let current_state: StateInfo = self.get_info(context_index);
+		self.update(current_state, context_index, bit)
+	}
 }
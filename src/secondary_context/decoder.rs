@@ -16,24 +16,36 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::{AnyResult, Closable, PipedReader, Reader};
+use crate::basic::{AnyError, AnyResult, Closable, Reader};
+use crate::secondary_context::encoder::settled_tail;
 use crate::secondary_context::Bit;
 
 // -----------------------------------------------
 
-pub struct BitDecoder<const SIZE: usize> {
+pub struct BitDecoder<R: Reader<u8>> {
 	value: u32,
 	low: u32,
 	high: u32,
-	reader: PipedReader<u8, SIZE>,
+	// the 0xFF read past the end of the input so far
+	padding: u32,
+	reader: R,
 }
 
-impl<const SIZE: usize> BitDecoder<SIZE> {
-	pub fn new(reader: PipedReader<u8, SIZE>) -> Self {
+#[cfg(any(test, feature = "internals"))]
+impl<'a> BitDecoder<&'a [u8]> {
+	// decodes from memory, like the bytes from BitEncoder::to_vec
+	pub fn from_slice(slice: &'a [u8]) -> Self {
+		Self::new(slice)
+	}
+}
+
+impl<R: Reader<u8>> BitDecoder<R> {
+	pub fn new(reader: R) -> Self {
 		Self {
 			value: 0,
 			low: 0,
 			high: 0,
+			padding: 0,
 			reader,
 		}
 	}
@@ -46,7 +58,10 @@ impl<const SIZE: usize> BitDecoder<SIZE> {
 			// shift byte in
 			self.value = (self.value << 8)
 				| match self.reader.read()? {
-					None => 0xFF,
+					None => {
+						self.padding += 1;
+						0xFF
+					}
 					Some(byte) => byte as u32,
 				};
 			// shift new bits into high/low
@@ -85,10 +100,33 @@ impl<const SIZE: usize> BitDecoder<SIZE> {
 		// return the value
 		return Ok(bit);
 	}
+
+	// Checks that the input ends right after the tail of the stream, once the last bit is
+	// decoded: the decoder reads 4 bytes ahead, so as many of them are past the end as the
+	// tail is short of 4 bytes. The single byte tail of the streams made before settled_tail
+	// is accepted too.
+	pub fn check_end(&mut self) -> AnyResult<()> {
+		// shift in the bytes the encoder shifted out after the last bit
+		if (self.high ^ self.low) < 0x01000000 {
+			self.flush()?;
+		}
+		let expected: u32 = 4 - settled_tail(self.low, self.high).1;
+		if self.padding > expected && self.padding != 3 {
+			return Err(AnyError::truncated_input(
+				"The SRX stream ends before its tail!",
+			));
+		}
+		if self.padding < expected || (self.padding == 0 && self.reader.read()?.is_some()) {
+			return Err(AnyError::from_string(
+				"Unexpected bytes after the end of the SRX stream!",
+			));
+		}
+		Ok(())
+	}
 }
 
-impl<const SIZE: usize> Closable<()> for BitDecoder<SIZE> {
-	fn close(self) -> AnyResult<()> {
+impl<T, R: Reader<u8> + Closable<T>> Closable<T> for BitDecoder<R> {
+	fn close(self) -> AnyResult<T> {
 		self.reader.close()
 	}
 }
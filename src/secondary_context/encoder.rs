@@ -16,26 +16,71 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::basic::{AnyResult, Closable, PipedWriter, Writer};
+use crate::basic::{AnyResult, Closable, Writer};
 use crate::secondary_context::Bit;
 
 // -----------------------------------------------
 
-pub struct BitEncoder<const SIZE: usize> {
+pub struct BitEncoder<W: Writer<u8>> {
 	low: u32,
 	high: u32,
-	writer: PipedWriter<u8, SIZE>,
+	written: u64,
+	writer: W,
 }
 
-impl<const SIZE: usize> BitEncoder<SIZE> {
-	pub fn new(writer: PipedWriter<u8, SIZE>) -> Self {
+#[cfg(any(test, feature = "internals"))]
+impl BitEncoder<Vec<u8>> {
+	// encodes into memory, closing it returns the encoded bytes
+	pub fn to_vec() -> Self {
+		Self::new(Vec::new())
+	}
+}
+
+// The tail close writes after the bytes already written, as the value it starts and its length
+// in bytes: the shortest start of a value in [low, high] that stays in it whatever follows, the
+// 0xFF the decoder reads past the end of its input or any other bytes after the payload. A
+// single byte is enough unless the top bytes of low and high are close, and 4 always are.
+// Streams made before took the top byte of low alone, which only the 0xFF keep in the range.
+pub(crate) fn settled_tail(low: u32, high: u32) -> (u32, u32) {
+	for length in 1..4 {
+		let free: u64 = (1 << (32 - 8 * length)) - 1;
+		let start: u64 = (low as u64 + free) & !free;
+		if start | free <= high as u64 {
+			return (start as u32, length);
+		}
+	}
+	(low, 4)
+}
+
+impl<W: Writer<u8>> BitEncoder<W> {
+	pub fn new(writer: W) -> Self {
 		Self {
 			low: 0,
 			high: 0xFFFFFFFF,
+			written: 0,
 			writer,
 		}
 	}
 
+	// the bytes given to the writer so far, not counting the tail written by close
+	pub fn bytes_written(&self) -> u64 {
+		self.written
+	}
+
+	// the number of bytes close would add, see settled_tail
+	pub fn tail_size(&self) -> u64 {
+		settled_tail(self.low, self.high).1 as u64
+	}
+
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	// writing to it directly breaks the stream, only for flushing what it buffers
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.writer
+	}
+
 	#[cold]
 	#[inline(always)]
 	fn flush(&mut self) -> AnyResult<()> {
@@ -43,8 +88,9 @@ impl<const SIZE: usize> BitEncoder<SIZE> {
 		while {
 			// write byte
 			self.writer.write((self.low >> 24) as u8)?;
+			self.written += 1;
 			// shift new bits into high/low
-			self.low = self.low << 8;
+			self.low <<= 8;
 			self.high = (self.high << 8) | 0xFF;
 			// check condition again
 			(self.high ^ self.low) < 0x01000000
@@ -75,10 +121,13 @@ impl<const SIZE: usize> BitEncoder<SIZE> {
 	}
 }
 
-impl<const SIZE: usize> Closable<()> for BitEncoder<SIZE> {
-	fn close(mut self) -> AnyResult<()> {
-		// write byte
-		self.writer.write((self.low >> 24) as u8)?;
+impl<T, W: Writer<u8> + Closable<T>> Closable<T> for BitEncoder<W> {
+	fn close(mut self) -> AnyResult<T> {
+		// write the tail, see settled_tail
+		let (tail, length): (u32, u32) = settled_tail(self.low, self.high);
+		for index in 0..length {
+			self.writer.write((tail >> (24 - 8 * index)) as u8)?;
+		}
 		// return the writer
 		self.writer.close()
 	}
@@ -20,10 +20,16 @@ mod bit;
 mod context;
 mod decoder;
 mod encoder;
+mod probability;
 mod state;
+#[cfg(test)]
+mod test;
 
 pub use self::bit::Bit;
 pub use self::context::SecondaryContext;
-pub use self::state::StateInfo;
+pub use self::state::{state_depths, StateInfo};
 pub use self::decoder::BitDecoder;
 pub use self::encoder::BitEncoder;
+#[cfg(feature = "internals")]
+pub use self::probability::bit_cost;
+pub use self::probability::prediction_to_f64;
@@ -0,0 +1,40 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(any(test, feature = "internals"))]
+use crate::secondary_context::Bit;
+
+// -----------------------------------------------
+
+// The probability of a one given by a prediction, which is that probability scaled by 2^32
+// as StateInfo::prediction returns it and the bit coders take it.
+pub fn prediction_to_f64(prediction: u32) -> f64 {
+	prediction as f64 / (1u64 << 32) as f64
+}
+
+// The cost in bits of coding the bit with the prediction, -log2 of the probability given to
+// that bit. A prediction of exactly zero gives a one an infinite cost.
+#[cfg(any(test, feature = "internals"))]
+pub fn bit_cost(prediction: u32, bit: Bit) -> f64 {
+	let one: f64 = prediction_to_f64(prediction);
+	-match bit {
+		Bit::Zero => 1.0 - one,
+		Bit::One => one,
+	}
+	.log2()
+}
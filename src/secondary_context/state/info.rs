@@ -17,14 +17,40 @@
  */
 
 use crate::secondary_context::Bit;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
 
 // -----------------------------------------------
 
 include!("state_table.inc");
 
+// the least number of updates taking the initial state to each state, saturating at 255, a
+// measure of how many bits a context has seen
+pub fn state_depths() -> Vec<u8> {
+	let mut depths: Vec<Option<u32>> = vec![None; STATE_TABLE.len()];
+	let mut queue: VecDeque<u16> = VecDeque::from([0]);
+	depths[0] = Some(0);
+	while let Some(state) = queue.pop_front() {
+		let depth: u32 = depths[state as usize].unwrap_or_default() + 1;
+		for bit in [Bit::Zero, Bit::One] {
+			let next: u16 = STATE_TABLE[state as usize].next(bit);
+			if depths[next as usize].is_none() {
+				depths[next as usize] = Some(depth);
+				queue.push_back(next);
+			}
+		}
+	}
+	// states never reached count as the deepest ones
+	depths
+		.into_iter()
+		.map(|depth| depth.map_or(u8::MAX, |depth| depth.min(u8::MAX as u32) as u8))
+		.collect()
+}
+
 // -----------------------------------------------
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq)]
 pub struct StateInfo(u64);
 
 impl StateInfo {
@@ -32,11 +58,45 @@ impl StateInfo {
 		Self(((prediction as u64) << 32) | ((next_if_zero as u64) << 16) | (next_if_one as u64))
 	}
 
+	#[inline]
 	pub fn next(&self, bit: Bit) -> u16 {
 		(if bit.into() { self.0 } else { self.0 >> 16 }) as u16
 	}
 
+	#[inline]
 	pub fn prediction(&self) -> u32 {
 		(self.0 >> 32) as u32
 	}
+
+	// the state after a zero and after a one, for sorting and printing
+	fn transitions(&self) -> (u16, u16) {
+		((self.0 >> 16) as u16, self.0 as u16)
+	}
+}
+
+// by prediction first, then by the transitions
+impl Ord for StateInfo {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.prediction()
+			.cmp(&other.prediction())
+			.then_with(|| self.transitions().cmp(&other.transitions()))
+	}
+}
+
+impl PartialOrd for StateInfo {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Debug for StateInfo {
+	fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (next_if_zero, next_if_one): (u16, u16) = self.transitions();
+		formatter
+			.debug_struct("StateInfo")
+			.field("prediction", &self.prediction())
+			.field("next_if_zero", &next_if_zero)
+			.field("next_if_one", &next_if_one)
+			.finish()
+	}
 }
@@ -22,4 +22,4 @@ mod state;
 mod test;
 
 pub use self::state::BitState;
-pub use self::info::StateInfo;
+pub use self::info::{state_depths, StateInfo};
@@ -16,6 +16,7 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::basic::Zeroed;
 use crate::secondary_context::Bit;
 use super::info::{StateInfo, STATE_TABLE};
 
@@ -30,11 +31,30 @@ impl Default for BitState {
 	}
 }
 
+// SAFETY: a plain u16, its default being 0
+unsafe impl Zeroed for BitState {}
+
 impl BitState {
+	// the index of the state in the state table
+	pub fn from_index(index: u16) -> Self {
+		Self(index)
+	}
+
+	pub fn index(&self) -> u16 {
+		self.0
+	}
+
+	#[cfg(any(test, fuzzing))]
+	pub fn is_valid(&self) -> bool {
+		(self.0 as usize) < STATE_TABLE.len()
+	}
+
+	#[inline]
 	pub fn get_info(&self) -> StateInfo {
 		STATE_TABLE[self.0 as usize]
 	}
 
+	#[inline]
 	pub fn update(&mut self, current_state: StateInfo, bit: Bit) {
 		debug_assert!(STATE_TABLE[self.0 as usize] == current_state);
 		self.0 = current_state.next(bit);
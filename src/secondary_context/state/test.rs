@@ -16,13 +16,15 @@
  * this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use super::info::{StateInfo, STATE_TABLE};
+use super::info::{state_depths, StateInfo, STATE_TABLE};
 use crate::basic::AnyResult;
 use crate::secondary_context::Bit;
 use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::env;
 use std::fmt::Debug;
+use std::fs;
 use std::fs::File;
 use std::hash::{Hash, Hasher};
 use std::io::{BufWriter, Write};
@@ -260,7 +262,12 @@ impl From<Value> for u32 {
 			Value::Fraction(fraction) => fraction.numerator as f64 / fraction.denominator as f64,
 			Value::Prediction(value) => value,
 		};
-		(fx * (1u64 << 32) as f64).round() as u32
+		debug_assert!((0.0..=1.0).contains(&fx), "{:?}", value);
+		// a certain bit is 2^32, one past the largest prediction, so it is clamped instead of
+		// relying on the saturation of the cast
+		(fx * (1u64 << 32) as f64)
+			.round()
+			.clamp(0.0, u32::MAX as f64) as u32
 	}
 }
 
@@ -314,7 +321,7 @@ fn prediction_rescaling(numerator: u64, denominator: u64) -> f64 {
 	sqr_x / (sqr_x + sqr_x_m_1)
 }
 
-fn prediction_next(predictions: &Vec<f64>, current_state: StateIndex, bit: Bit) -> StateIndex {
+fn prediction_next(predictions: &[f64], current_state: StateIndex, bit: Bit) -> StateIndex {
 	let (count, value): (u64, f64) = match current_state.value {
 		Value::Fraction(fraction) => (current_state.count + 1, f64::from(fraction)),
 		Value::Prediction(value) => (current_state.count, value),
@@ -351,15 +358,16 @@ fn prediction_next(predictions: &Vec<f64>, current_state: StateIndex, bit: Bit)
 
 // -----------------------------------------------
 
+// ordered, so that the generated table does not depend on the order of a hash map
 #[derive(Debug)]
 struct PrimitiveStateTable {
-	map: HashMap<StateIndex, PrimitiveState>,
+	map: BTreeMap<StateIndex, PrimitiveState>,
 }
 
 impl PrimitiveStateTable {
 	fn new() -> Self {
 		Self {
-			map: HashMap::new(),
+			map: BTreeMap::new(),
 		}
 	}
 
@@ -394,7 +402,7 @@ impl PrimitiveStateTable {
 		);
 	}
 
-	fn state_manual(&mut self, prediction: &Vec<f64>, current_state: StateIndex) {
+	fn state_manual(&mut self, prediction: &[f64], current_state: StateIndex) {
 		self.state(
 			current_state,
 			prediction_next(prediction, current_state, Bit::Zero),
@@ -406,7 +414,7 @@ impl PrimitiveStateTable {
 	fn export(&self) -> AnyResult<()> {
 		let mut writer: BufWriter<File> = BufWriter::new(File::create(Path::new("map.gexf"))?);
 
-		writer.write(br#"<?xml version="1.0" encoding="UTF-8"?>
+		writer.write_all(br#"<?xml version="1.0" encoding="UTF-8"?>
 <gexf xmlns="http://gexf.net/1.3" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://gexf.net/1.3 http://gexf.net/1.3/gexf.xsd" version="1.3">
 	<meta lastmodifieddate="2009-03-20">
 		<creator>Gephi.org</creator>
@@ -430,7 +438,7 @@ impl PrimitiveStateTable {
 			let level: usize = state.count as usize;
 			let prediction: f64 = f64::from(state.value);
 
-			writer.write(
+			writer.write_all(
 				format!(
 					r#"
 			<node id="{}" label="{},{}">
@@ -445,7 +453,7 @@ impl PrimitiveStateTable {
 			)?;
 		}
 
-		writer.write(
+		writer.write_all(
 			br#"
 		</nodes>
 		<edges>"#,
@@ -464,7 +472,7 @@ impl PrimitiveStateTable {
 			state.next_if_one.hash(&mut hasher);
 			let one_id = hasher.finish();
 
-			writer.write(
+			writer.write_all(
 				format!(
 					r#"
 			<edge source="{}" target="{}">
@@ -483,7 +491,7 @@ impl PrimitiveStateTable {
 			)?;
 		}
 
-		writer.write(
+		writer.write_all(
 			br#"
 		</edges>
 	</graph>
@@ -542,8 +550,8 @@ fn table() -> AnyResult<PrimitiveStateTable> {
 
 // -----------------------------------------------
 
-#[test]
-fn test_and_generate_state_table() -> AnyResult<()> {
+// the text of state_table.inc and the table it holds
+fn generate() -> AnyResult<(String, Vec<StateInfo>)> {
 	// create table
 	let table = table()?;
 
@@ -555,34 +563,126 @@ fn test_and_generate_state_table() -> AnyResult<()> {
 	// check for valid table
 	assert_eq!(table.map.len(), 1 << 16);
 
-	// get the states as an array
-	let mut data: Vec<&PrimitiveState> = table.map.iter().map(|(_, state)| state).collect();
-	data.sort_by_key(|x| x.current_state);
+	// get the states as an array, in the order of the map
+	let data: Vec<&PrimitiveState> = table.map.values().collect();
 
 	// create index for states
-	let mut data_index: HashMap<StateIndex, usize> = HashMap::new();
+	let mut data_index: BTreeMap<StateIndex, usize> = BTreeMap::new();
 	data.iter().enumerate().for_each(|(index, state)| {
 		data_index.insert(state.current_state, index);
 	});
 
 	// create next states array
-	println!("pub const STATE_TABLE: &[StateInfo] = &[ // length = {}", data.len());
+	let mut text: String = format!(
+		"pub const STATE_TABLE: &[StateInfo] = &[ // length = {}\n",
+		data.len()
+	);
 	let mut state_table: Vec<StateInfo> = Vec::new();
-	for index in 0..65536 {
-		let state: &PrimitiveState = data[index];
+	for (index, &state) in data.iter().enumerate() {
 		let level: usize = state.current_state.count as usize;
 		let prediction: u32 = u32::from(state.current_state.value);
 		let next_if_zero: u16 = *data_index.get(&state.next_if_zero).unwrap() as u16;
 		let next_if_one: u16 = *data_index.get(&state.next_if_one).unwrap() as u16;
 		state_table.push(StateInfo::new(prediction, next_if_zero, next_if_one));
-		println!(
-			"\tStateInfo::new(0x{:08X}, 0x{:04X}, 0x{:04X}), // 0x{:04X}, {}",
+		text.push_str(&format!(
+			"\tStateInfo::new(0x{:08X}, 0x{:04X}, 0x{:04X}), // 0x{:04X}, {}\n",
 			prediction, next_if_zero, next_if_one, index, level,
-		);
+		));
+	}
+	text.push_str("];\n");
+	Ok((text, state_table))
+}
+
+// The committed state_table.inc must be exactly what this generator prints. To
+// regenerate it after changing the generator, run
+//     SRX_REGENERATE_STATE_TABLE=1 cargo test test_and_generate_state_table
+// which overwrites the file, then rebuild and run the tests again.
+#[test]
+fn test_and_generate_state_table() -> AnyResult<()> {
+	let (text, state_table): (String, Vec<StateInfo>) = generate()?;
+
+	if env::var_os("SRX_REGENERATE_STATE_TABLE").is_some() {
+		fs::write(
+			concat!(
+				env!("CARGO_MANIFEST_DIR"),
+				"/src/secondary_context/state/state_table.inc"
+			),
+			&text,
+		)?;
 	}
-	println!("];");
 
-	debug_assert!(state_table.eq(STATE_TABLE));
+	assert!(
+		text == include_str!("state_table.inc"),
+		"state_table.inc is out of date!"
+	);
+	assert_eq!(state_table, STATE_TABLE);
+
+	Ok(())
+}
 
+#[test]
+fn test_generate_state_table_twice() -> AnyResult<()> {
+	let (first_text, first_table): (String, Vec<StateInfo>) = generate()?;
+	let (second_text, second_table): (String, Vec<StateInfo>) = generate()?;
+	assert!(first_text == second_text);
+	assert_eq!(first_table, second_table);
 	Ok(())
 }
+
+#[test]
+fn test_state_info_debug_and_order() {
+	let state: StateInfo = StateInfo::new(0x12345678, 0x0ABC, 0x0DEF);
+	assert_eq!(
+		format!("{:?}", state),
+		"StateInfo { prediction: 305419896, next_if_zero: 2748, next_if_one: 3567 }"
+	);
+	assert_eq!(state.next(Bit::Zero), 0x0ABC);
+	assert_eq!(state.next(Bit::One), 0x0DEF);
+
+	// the prediction goes before the transitions
+	let mut states: Vec<StateInfo> = vec![
+		StateInfo::new(2, 0, 0),
+		StateInfo::new(1, 9, 9),
+		StateInfo::new(1, 1, 2),
+		StateInfo::new(1, 1, 1),
+	];
+	states.sort();
+	assert_eq!(
+		states,
+		vec![
+			StateInfo::new(1, 1, 1),
+			StateInfo::new(1, 1, 2),
+			StateInfo::new(1, 9, 9),
+			StateInfo::new(2, 0, 0),
+		]
+	);
+	let mut sorted: Vec<StateInfo> = STATE_TABLE.to_vec();
+	sorted.sort();
+	assert!(sorted
+		.windows(2)
+		.all(|pair| pair[0].prediction() <= pair[1].prediction()));
+}
+
+#[test]
+fn test_state_depths() {
+	let depths: Vec<u8> = state_depths();
+	assert_eq!(depths.len(), STATE_TABLE.len());
+	assert_eq!(&depths[..6], &[0, 1, 1, 2, 2, 2]);
+	// one more bit never takes a state more than one step deeper
+	for (state, info) in STATE_TABLE.iter().enumerate() {
+		for bit in [Bit::Zero, Bit::One] {
+			let next: usize = info.next(bit) as usize;
+			assert!(depths[next] <= depths[state].saturating_add(1));
+		}
+	}
+}
+
+#[test]
+fn test_value_to_prediction() {
+	assert_eq!(u32::from(Value::Prediction(1.0)), u32::MAX);
+	assert_eq!(u32::from(fraction(1, 1)), u32::MAX);
+	assert_eq!(u32::from(fraction(3, 3)), u32::MAX);
+	assert_eq!(u32::from(Value::Prediction(0.0)), 0);
+	assert_eq!(u32::from(fraction(1, 2)), 1 << 31);
+	assert_eq!(u32::from(Value::Prediction(0.25)), 1 << 30);
+}
@@ -0,0 +1,236 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use super::bit::Bit;
+use super::context::{SecondaryContext, LITERAL_WINDOW};
+use super::decoder::BitDecoder;
+use super::encoder::{settled_tail, BitEncoder};
+use super::probability::{bit_cost, prediction_to_f64};
+use super::state::StateInfo;
+use crate::basic::{AnyResult, Closable};
+
+// -----------------------------------------------
+
+// (prediction, bit) pairs, the bits mostly following the predictions
+fn generated(count: usize) -> Vec<(u32, Bit)> {
+	let mut state: u32 = 1;
+	(0..count)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			let prediction: u32 = (state >> 8).clamp(1 << 12, (1 << 24) - (1 << 12)) << 8;
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			(prediction, Bit::from(state < prediction))
+		})
+		.collect()
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_bit_from_integer() {
+	assert_eq!(Bit::from(0u32), Bit::Zero);
+	assert_eq!(Bit::from(1u32), Bit::One);
+	assert_eq!(Bit::from(0u8), Bit::Zero);
+	assert_eq!(Bit::from(1usize), Bit::One);
+	assert_eq!(Bit::from(1i64), Bit::One);
+	for bit in [Bit::Zero, Bit::One] {
+		// the packing of the messages of the encoder, see PackedMessage
+		assert_eq!(Bit::from(u32::from(bit) << 30 >> 30), bit);
+		assert_eq!(Bit::from(bool::from(bit)), bit);
+	}
+}
+
+// anything but 0 or 1 is a bug, caught by the debug assert, and One without it
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "Unexpected value for Bit!"))]
+fn test_bit_from_unexpected_integer() {
+	assert_eq!(Bit::from(2u32), Bit::One);
+	assert_eq!(Bit::from(u32::MAX), Bit::One);
+	assert_eq!(Bit::from(-1i32), Bit::One);
+}
+
+#[test]
+fn test_bit_coder_round_trip() -> AnyResult<()> {
+	for count in [0, 1, 7, 100_000] {
+		let bits: Vec<(u32, Bit)> = generated(count);
+		let mut encoder: BitEncoder<Vec<u8>> = BitEncoder::to_vec();
+		for &(prediction, bit) in &bits {
+			encoder.bit(prediction, bit)?;
+		}
+		let encoded: Vec<u8> = encoder.close()?;
+		// well predicted bits take less than a bit each
+		assert!(
+			encoded.len() <= count / 8 + 1,
+			"{} bits: {} bytes",
+			count,
+			encoded.len()
+		);
+
+		let mut decoder: BitDecoder<&[u8]> = BitDecoder::from_slice(&encoded);
+		for (index, &(prediction, bit)) in bits.iter().enumerate() {
+			assert_eq!(decoder.bit(prediction)?, bit, "bit {} of {}", index, count);
+		}
+		decoder.close()?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_bit_encoder_tail() -> AnyResult<()> {
+	for count in [0, 1, 7, 8, 9, 1000, 100_000] {
+		let bits: Vec<(u32, Bit)> = generated(count);
+		let mut encoder: BitEncoder<Vec<u8>> = BitEncoder::to_vec();
+		let mut written: u64 = 0;
+		for &(prediction, bit) in &bits {
+			encoder.bit(prediction, bit)?;
+			// bytes only ever go out, never come back
+			assert!(encoder.bytes_written() >= written);
+			written = encoder.bytes_written();
+		}
+		let tail_size: u64 = encoder.tail_size();
+		assert!((1..=4).contains(&tail_size));
+		let encoded: Vec<u8> = encoder.close()?;
+		assert_eq!(encoded.len() as u64, written + tail_size, "{} bits", count);
+
+		// whatever follows the tail changes nothing, the 0xFF the decoder reads past the end
+		// or any other bytes
+		for trailing in [&[][..], &[0xFF; 4], &[0x00; 4], &[0x00], &[0x80, 0x7F]] {
+			let padded: Vec<u8> = [&encoded[..], trailing].concat();
+			let mut decoder: BitDecoder<&[u8]> = BitDecoder::from_slice(&padded);
+			for &(prediction, bit) in &bits {
+				assert_eq!(decoder.bit(prediction)?, bit);
+			}
+		}
+	}
+	Ok(())
+}
+
+#[test]
+fn test_settled_tail() {
+	// the top bytes far apart, a single byte above low
+	assert_eq!(settled_tail(0x12345678, 0x9ABCDEF0), (0x13000000, 1));
+	assert_eq!(settled_tail(0x12000000, 0x12FFFFFF), (0x12000000, 1));
+	// the top bytes next to each other, more bytes needed
+	assert_eq!(settled_tail(0x12345678, 0x13000000), (0x12350000, 2));
+	assert_eq!(settled_tail(0x12FFFF00, 0x13000001), (0x12FFFF00, 3));
+	assert_eq!(settled_tail(0x12FFFFFF, 0x13000000), (0x12FFFFFF, 4));
+	// every value starting with the tail stays in the range
+	for (low, high) in [(0x00000000, 0xFFFFFFFF), (0x7FFFFFFE, 0x80FFFFFF)] {
+		let (tail, length): (u32, u32) = settled_tail(low, high);
+		let free: u64 = (1 << (32 - 8 * length)) - 1;
+		assert!(low <= tail && (tail as u64 | free) <= high as u64);
+	}
+}
+
+#[test]
+fn test_predict_update_bit() {
+	// the same bits on the same contexts, through both APIs
+	let mut manual: SecondaryContext = SecondaryContext::new(256);
+	let mut convenient: SecondaryContext = SecondaryContext::new(256);
+	for (index, &(_, bit)) in generated(10_000).iter().enumerate() {
+		let context_index: usize = index * 7 % 256;
+		let current_state: StateInfo = manual.get_info(context_index);
+		assert_eq!(
+			convenient.predict(context_index),
+			current_state.prediction()
+		);
+		assert_eq!(convenient.get_info(context_index), current_state);
+		manual.update(current_state, context_index, bit);
+		convenient.update_bit(context_index, bit);
+	}
+	for context_index in 0..256 {
+		assert_eq!(
+			convenient.get_info(context_index),
+			manual.get_info(context_index)
+		);
+	}
+}
+
+#[test]
+fn test_prediction_to_f64() {
+	assert!((prediction_to_f64(0x80000000) - 0.5).abs() < 1e-9);
+	assert_eq!(prediction_to_f64(0), 0.0);
+	assert!(prediction_to_f64(u32::MAX) < 1.0);
+	// an even prediction costs a bit either way
+	assert!((bit_cost(0x80000000, Bit::Zero) - 1.0).abs() < 1e-9);
+	assert!((bit_cost(0x80000000, Bit::One) - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_bit_cost() {
+	// confident and right is nearly free, confident and wrong is expensive
+	let confident: u32 = u32::MAX - (1 << 20);
+	assert!(bit_cost(confident, Bit::One) < 0.001);
+	assert!(bit_cost(!confident, Bit::Zero) < 0.001);
+	assert!(bit_cost(confident, Bit::Zero) > 10.0);
+	// the average cost of the generated bits is what the bit coder gets close to
+	let bits: Vec<(u32, Bit)> = generated(10_000);
+	let total: f64 = bits
+		.iter()
+		.map(|&(prediction, bit)| bit_cost(prediction, bit))
+		.sum();
+	assert!(total > 0.0 && total < bits.len() as f64);
+}
+
+#[test]
+fn test_check_invariants() {
+	let mut context: SecondaryContext = SecondaryContext::new(256);
+	context.check_invariants();
+	for (index, &(_, bit)) in generated(10_000).iter().enumerate() {
+		let context_index: usize = index * 7 % 256;
+		let current_state: StateInfo = context.get_info(context_index);
+		context.update(current_state, context_index, bit);
+		if index % 100 == 0 {
+			context.check_invariants();
+		}
+	}
+	context.check_invariants();
+}
+
+#[test]
+fn test_batch_matches_per_call() {
+	let mut batched: SecondaryContext = SecondaryContext::new(1024);
+	let mut per_call: SecondaryContext = SecondaryContext::new(1024);
+	let bits: Vec<(u32, Bit)> = generated(8 * 2000);
+	for (round, chunk) in bits.chunks(8).enumerate() {
+		// 8 different entries of a window, like the ones of a literal, up to its last one
+		let base: usize = round * 37 % (1024 - LITERAL_WINDOW + 1);
+		let offsets: [usize; 8] =
+			std::array::from_fn(|index| (round * 11 + index * 29) % LITERAL_WINDOW);
+		let bits: [Bit; 8] = std::array::from_fn(|index| chunk[index].1);
+		let current_states: [StateInfo; 8] = batched.get_info_batch(base, &offsets);
+		batched.update_batch(base, &offsets, &current_states, &bits);
+		for index in 0..8 {
+			let current_state: StateInfo = per_call.get_info(base + offsets[index]);
+			assert_eq!(
+				current_state.prediction(),
+				current_states[index].prediction()
+			);
+			per_call.update(current_state, base + offsets[index], bits[index]);
+		}
+	}
+	for context_index in 0..1024 {
+		assert_eq!(
+			batched.state(context_index),
+			per_call.state(context_index),
+			"{}",
+			context_index
+		);
+	}
+	batched.check_invariants();
+}
@@ -0,0 +1,72 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// -----------------------------------------------
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("srx-force-{}-{}", std::process::id(), name))
+}
+
+// runs srx, returning its exit status
+fn srx(args: &[&str], input_path: &Path, output_path: &Path) -> io::Result<Option<i32>> {
+	let status = Command::new(env!("CARGO_BIN_EXE_srx"))
+		.args(args)
+		.arg(input_path)
+		.arg(output_path)
+		.output()?
+		.status;
+	Ok(status.code())
+}
+
+#[test]
+fn test_existing_output() -> io::Result<()> {
+	let input_path: PathBuf = temp_path("input");
+	let output_path: PathBuf = temp_path("output");
+	fs::write(
+		&input_path,
+		b"not overwritten without --force. ".repeat(1000),
+	)?;
+	fs::write(&output_path, b"a previous output")?;
+
+	// refused, the output left as it was
+	assert_eq!(srx(&["c"], &input_path, &output_path)?, Some(2));
+	assert_eq!(fs::read(&output_path)?, b"a previous output");
+	// overwritten when asked to
+	assert_eq!(srx(&["c", "-f"], &input_path, &output_path)?, Some(0));
+	assert_eq!(srx(&["c", "--force"], &input_path, &output_path)?, Some(0));
+	assert_ne!(fs::read(&output_path)?, b"a previous output");
+	// never the input itself, whatever the path it is given by
+	let same_path: PathBuf = input_path
+		.parent()
+		.unwrap()
+		.join(".")
+		.join(input_path.file_name().unwrap());
+	assert_eq!(srx(&["c", "--force"], &input_path, &same_path)?, Some(2));
+	// a failure of the run itself is told apart
+	assert_eq!(srx(&["d", "--force"], &input_path, &output_path)?, Some(1));
+
+	for path in [input_path, output_path] {
+		fs::remove_file(path)?;
+	}
+	Ok(())
+}
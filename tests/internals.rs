@@ -0,0 +1,114 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#![cfg(feature = "internals")]
+
+use srx::internals::{
+	pipe, Bit, BitDecoder, BitEncoder, Byte, Closable, PipedReader, PipedWriter, Reader,
+	SecondaryContext, StateInfo, Writer,
+};
+use srx::AnyResult;
+use std::thread::scope;
+
+// -----------------------------------------------
+
+// an order-0 model: one binary tree of 255 contexts over the bits of each byte
+const ORDER0_SIZE: usize = 256;
+
+const BUFFER_SIZE: usize = 0x1000;
+
+fn encode_byte(
+	context: &mut SecondaryContext,
+	encoder: &mut BitEncoder<PipedWriter<u8, BUFFER_SIZE>>,
+	byte: Byte,
+) -> AnyResult<()> {
+	let mut node: usize = 1;
+	for shift in (0..8).rev() {
+		let bit: Bit = Bit::from((usize::from(byte) >> shift) & 1);
+		let state: StateInfo = context.get_info(node);
+		encoder.bit(state.prediction(), bit)?;
+		context.update(state, node, bit);
+		node = (node << 1) | usize::from(bit);
+	}
+	Ok(())
+}
+
+fn decode_byte(
+	context: &mut SecondaryContext,
+	decoder: &mut BitDecoder<PipedReader<u8, BUFFER_SIZE>>,
+) -> AnyResult<Byte> {
+	let mut node: usize = 1;
+	while node < 256 {
+		let state: StateInfo = context.get_info(node);
+		let bit: Bit = decoder.bit(state.prediction())?;
+		context.update(state, node, bit);
+		node = (node << 1) | usize::from(bit);
+	}
+	Ok(Byte::from(node - 256))
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_order0_codec_round_trip() -> AnyResult<()> {
+	let input: &[u8] = b"abracadabra, a custom codec built from the srx internals";
+
+	// encode everything, the byte count is known to the decoder so there is no eof marker
+	let (writer, mut reader): (PipedWriter<u8, BUFFER_SIZE>, PipedReader<u8, BUFFER_SIZE>) =
+		pipe::<u8, BUFFER_SIZE>();
+	let compressed: Vec<u8> = scope(|scope| {
+		let collector = scope.spawn(move || -> AnyResult<Vec<u8>> {
+			let mut compressed: Vec<u8> = Vec::new();
+			while let Some(byte) = reader.read()? {
+				compressed.push(byte);
+			}
+			Ok(compressed)
+		});
+		let mut context: SecondaryContext = SecondaryContext::new(ORDER0_SIZE);
+		let mut encoder: BitEncoder<PipedWriter<u8, BUFFER_SIZE>> = BitEncoder::new(writer);
+		for &byte in input {
+			encode_byte(&mut context, &mut encoder, Byte::from(byte))?;
+		}
+		encoder.close()?;
+		collector.join().unwrap()
+	})?;
+
+	// decode it back
+	let (mut writer, reader): (PipedWriter<u8, BUFFER_SIZE>, PipedReader<u8, BUFFER_SIZE>) =
+		pipe::<u8, BUFFER_SIZE>();
+	let output: Vec<u8> = scope(|scope| {
+		let feeder = scope.spawn(move || -> AnyResult<()> {
+			for &byte in compressed.iter() {
+				writer.write(byte)?;
+			}
+			writer.close()
+		});
+		let mut context: SecondaryContext = SecondaryContext::new(ORDER0_SIZE);
+		let mut decoder: BitDecoder<PipedReader<u8, BUFFER_SIZE>> = BitDecoder::new(reader);
+		let mut output: Vec<u8> = Vec::new();
+		for _ in 0..input.len() {
+			output.push(u8::from(decode_byte(&mut context, &mut decoder)?));
+		}
+		decoder.close()?;
+		feeder.join().unwrap()?;
+		Ok::<Vec<u8>, srx::AnyError>(output)
+	})?;
+
+	assert_eq!(output, input);
+	Ok(())
+}
@@ -0,0 +1,123 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use srx::{
+	decode_with, encode_with, read_header, write_header, AnyResult, Config, Header, IO_BUFFER_SIZE,
+	MESSAGE_BUFFER_SIZE,
+};
+use std::io;
+use std::io::{Read, Write};
+
+// -----------------------------------------------
+
+// a little more than 4 GiB, so that any size kept in 32 bits wraps
+const LARGE_SIZE: u64 = (4 << 30) + (3 << 20) + 17;
+
+// Generates the same bytes every time without holding them: a slowly changing text-like
+// pattern, cheap to compress so that the test mostly exercises the size accounting.
+struct SyntheticReader {
+	position: u64,
+	size: u64,
+}
+
+impl SyntheticReader {
+	fn new(size: u64) -> Self {
+		Self { position: 0, size }
+	}
+
+	fn byte_at(position: u64) -> u8 {
+		let line: u64 = position / 64;
+		match position % 64 {
+			63 => b'\n',
+			column => b'a' + ((column + line / 4096) % 26) as u8,
+		}
+	}
+}
+
+impl Read for SyntheticReader {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let length: usize = buffer.len().min((self.size - self.position) as usize);
+		for (offset, slot) in buffer[..length].iter_mut().enumerate() {
+			*slot = Self::byte_at(self.position + offset as u64);
+		}
+		self.position += length as u64;
+		Ok(length)
+	}
+}
+
+// Compares the decoded bytes with the synthetic ones as they come, counting them in a u64.
+struct CheckingWriter {
+	position: u64,
+}
+
+impl Write for CheckingWriter {
+	fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+		for (offset, &value) in buffer.iter().enumerate() {
+			let position: u64 = self.position + offset as u64;
+			if value != SyntheticReader::byte_at(position) {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("Mismatch at offset {}!", position),
+				));
+			}
+		}
+		self.position += buffer.len() as u64;
+		Ok(buffer.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// -----------------------------------------------
+
+// Takes a few minutes even in release builds, run it with
+//     cargo test --release --test large_file -- --ignored
+#[test]
+#[ignore]
+fn test_larger_than_4_gib() -> AnyResult<()> {
+	let config: Config = Config::default();
+	let mut compressed: Vec<u8> = Vec::new();
+	write_header(
+		&mut compressed,
+		&Header {
+			config,
+			original_size: Some(LARGE_SIZE),
+			..Header::default()
+		},
+	)?;
+	let (reader, compressed): (SyntheticReader, Vec<u8>) =
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			SyntheticReader::new(LARGE_SIZE),
+			compressed,
+			&config,
+		)?;
+	assert_eq!(reader.position, LARGE_SIZE);
+
+	let mut payload: &[u8] = &compressed;
+	let header: Header = read_header(&mut payload)?;
+	assert_eq!(header.original_size, Some(LARGE_SIZE));
+	let (_, writer): (&[u8], CheckingWriter) = decode_with::<_, _, IO_BUFFER_SIZE>(
+		payload,
+		CheckingWriter { position: 0 },
+		&header.config,
+	)?;
+	assert_eq!(writer.position, LARGE_SIZE);
+	Ok(())
+}
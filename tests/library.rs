@@ -0,0 +1,38 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+// Uses srx the way a downstream crate does, through the paths of the public API alone, so that
+// a change to them breaks this test before it breaks someone else's build.
+
+// -----------------------------------------------
+
+#[test]
+fn test_encode_decode_from_outside() -> srx::AnyResult<()> {
+	let input: Vec<u8> = b"used from another crate, with its own buffer sizes. ".repeat(1000);
+	let (reader, compressed): (&[u8], Vec<u8>) =
+		srx::encode::<_, _, 0x400000, 0x40000>(input.as_slice(), Vec::new())?;
+	assert!(reader.is_empty());
+	assert!(compressed.len() < input.len() / 10);
+	let (_, output): (&[u8], Vec<u8>) =
+		srx::decode::<_, _, 0x400000>(compressed.as_slice(), Vec::new())?;
+	assert!(output == input);
+	// the errors are the library's own type, usable with the ? of the caller
+	let error: srx::AnyError = srx::AnyError::from_string("Not an SRX stream!");
+	assert_eq!(error.to_string(), "Not an SRX stream!");
+	Ok(())
+}
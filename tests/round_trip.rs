@@ -0,0 +1,201 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Round trips of arbitrary data: generated inputs of every shape srx may be given, each
+//! compressed with one of the configs and decompressed back to the same bytes. The inputs are
+//! seeded, so a failing case prints what it takes to generate it again.
+
+use srx::{compress_with, decompress, AnyResult, Config, Growth, Model};
+
+// -----------------------------------------------
+
+// every case allocates the tables of its config twice, which takes most of the time
+const CASES: u64 = 64;
+
+// xorshift, the same sequence for the same seed
+struct Rng(u64);
+
+impl Rng {
+	fn new(seed: u64) -> Self {
+		Self(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+	}
+
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, bound: u64) -> u64 {
+		self.next() % bound
+	}
+
+	fn byte(&mut self) -> u8 {
+		(self.next() >> 32) as u8
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Shape {
+	// the same byte over and over
+	Same,
+	Random,
+	// records of a few fields, like a table or a log
+	Records,
+	// words from a small vocabulary, like text
+	Words,
+	// runs of random bytes and random lengths
+	Runs,
+	// mostly zeros, like a sparse file
+	Sparse,
+	// a few bytes of a tiny alphabet, ranking and reranking the same symbols all along
+	Alphabet,
+}
+
+impl Shape {
+	const ALL: [Shape; 7] = [
+		Shape::Same,
+		Shape::Random,
+		Shape::Records,
+		Shape::Words,
+		Shape::Runs,
+		Shape::Sparse,
+		Shape::Alphabet,
+	];
+
+	fn generate(self, rng: &mut Rng, length: usize) -> Vec<u8> {
+		let mut data: Vec<u8> = Vec::with_capacity(length + 64);
+		let same: u8 = rng.byte();
+		let alphabet: [u8; 3] = [rng.byte(), rng.byte(), rng.byte()];
+		while data.len() < length {
+			match self {
+				Shape::Same => data.push(same),
+				Shape::Random => data.push(rng.byte()),
+				Shape::Records => {
+					let record: String = format!(
+						"{},{},{}\n",
+						rng.below(1000),
+						["get", "put", "delete"][rng.below(3) as usize],
+						rng.below(1 << 20)
+					);
+					data.extend_from_slice(record.as_bytes());
+				}
+				Shape::Words => {
+					let words: [&str; 6] = ["the ", "of ", "srx ", "symbol ", "rank ", ".\n"];
+					data.extend_from_slice(words[rng.below(6) as usize].as_bytes());
+				}
+				Shape::Runs => {
+					let value: u8 = rng.byte();
+					let run: usize = 1 + rng.below(40) as usize;
+					data.resize(data.len() + run, value);
+				}
+				Shape::Sparse => data.push(match rng.below(50) {
+					0 => rng.byte(),
+					_ => 0,
+				}),
+				Shape::Alphabet => data.push(alphabet[rng.below(3) as usize]),
+			}
+		}
+		data.truncate(length);
+		data
+	}
+}
+
+// mostly short, sometimes empty, and now and then long enough to fill the buffers
+fn length(rng: &mut Rng) -> usize {
+	match rng.below(8) {
+		0 => rng.below(3) as usize,
+		1..=4 => rng.below(300) as usize,
+		5 | 6 => rng.below(20_000) as usize,
+		_ => rng.below(300_000) as usize,
+	}
+}
+
+// every model, with lanes and growth now and then
+fn config(rng: &mut Rng) -> Config {
+	let model: Model = Model::ALL[rng.below(Model::ALL.len() as u64) as usize];
+	let config: Config = Config::new(model).with_lanes(1 + rng.below(4) as u8 / 3);
+	match model.ways().is_none() && rng.below(4) == 0 {
+		true => config.with_growth(Some(Growth::default())),
+		false => config,
+	}
+}
+
+fn check(data: &[u8], config: &Config, case: &str) -> AnyResult<()> {
+	let compressed: Vec<u8> = compress_with(data, config)?;
+	let decompressed: Vec<u8> = decompress(&compressed)?;
+	assert!(
+		decompressed == data,
+		"{}: {} bytes with {}, decompressed to {} bytes",
+		case,
+		data.len(),
+		config.name(),
+		decompressed.len()
+	);
+	Ok(())
+}
+
+// -----------------------------------------------
+
+#[test]
+fn test_arbitrary_round_trip() -> AnyResult<()> {
+	for seed in 0..CASES {
+		let mut rng: Rng = Rng::new(seed);
+		let shape: Shape = Shape::ALL[seed as usize % Shape::ALL.len()];
+		let length: usize = length(&mut rng);
+		let config: Config = config(&mut rng);
+		let data: Vec<u8> = shape.generate(&mut rng, length);
+		check(&data, &config, &format!("seed {} {:?}", seed, shape))?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test_regression_round_trip() -> AnyResult<()> {
+	let ascending: Vec<u8> = (0..=255).collect();
+	let descending: Vec<u8> = (0..=255).rev().collect();
+	let cases: [(&str, Vec<u8>); 9] = [
+		("empty", Vec::new()),
+		// the end of the stream is a literal equal to the first ranked byte, which starts as
+		// zero, so zeros at the start and at the end must not be taken for it
+		("a zero", vec![0]),
+		("zeros", vec![0; 1000]),
+		("a zero after a one", vec![1, 0]),
+		(
+			"a literal after a long run",
+			[vec![b'a'; 5000], vec![b'b']].concat(),
+		),
+		// the first ranked byte changing on every byte
+		("alternating", b"ab".repeat(3000)),
+		("every byte", ascending.clone()),
+		("every byte twice", [ascending, descending].concat()),
+		("all ones", vec![0xFF; 70_000]),
+	];
+	// two models each, all of them over the cases
+	for (index, (case, data)) in cases.iter().enumerate() {
+		for model in [index, index + 1] {
+			check(
+				data,
+				&Config::new(Model::ALL[model % Model::ALL.len()]),
+				case,
+			)?;
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,136 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+//! The stage times of --flat-profile against the clock, in a test binary of its own so that no
+//! other test competes with the stages for the cores while they are timed.
+
+#![cfg(all(feature = "threads", unix))]
+
+use srx::{
+	decode_with, encode_with, time_stages, AnyResult, Config, Stage, StageTimes, IO_BUFFER_SIZE,
+	MESSAGE_BUFFER_SIZE,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------
+
+// what was read from and what was written to
+type Coded<'a> = AnyResult<(&'a [u8], Vec<u8>)>;
+
+// a few words over and over in a slowly changing order, about 8 MiB of it
+fn medium_input() -> Vec<u8> {
+	const WORDS: [&[u8]; 8] = [
+		b"stage ",
+		b"timer ",
+		b"primary ",
+		b"secondary ",
+		b"reader ",
+		b"writer ",
+		b"of ",
+		b"the ",
+	];
+	let mut state: u64 = 0x9E3779B97F4A7C15;
+	let mut input: Vec<u8> = Vec::new();
+	while input.len() < 8 << 20 {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		input.extend_from_slice(WORDS[(state >> 61) as usize]);
+	}
+	input
+}
+
+// the stages work side by side on more than one core, so their sum is at most that many times
+// the elapsed time. How much of it they spend waiting for each other or for a busy machine
+// depends on the scheduler, so only the upper bound is checked.
+fn check_sum(times: &StageTimes, elapsed: Duration) {
+	let cores: u32 = thread::available_parallelism().map_or(1, |cores| cores.get() as u32);
+	let total: Duration = times.total();
+	assert!(total > Duration::ZERO, "No time spent in the stages");
+	assert!(
+		total <= elapsed * cores + elapsed / 10,
+		"{:?} of stages in {:?} on {} cores",
+		total,
+		elapsed,
+		cores
+	);
+}
+
+#[test]
+fn test_stage_times() -> AnyResult<()> {
+	let input: Vec<u8> = medium_input();
+	let config: Config = Config::default();
+
+	let start: Instant = Instant::now();
+	let (encoded, times): (Coded, StageTimes) = time_stages(|| {
+		encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+			input.as_slice(),
+			Vec::new(),
+			&config,
+		)
+	});
+	let elapsed: Duration = start.elapsed();
+	let (_, compressed): (&[u8], Vec<u8>) = encoded?;
+	for stage in [
+		Stage::Reader,
+		Stage::PrimaryEncoder,
+		Stage::SecondaryEncoder,
+		Stage::Writer,
+	] {
+		assert!(!times.get(stage).is_zero(), "{:?} was not timed", stage);
+		// every stage reads from or writes to a pipe, up to its end at least
+		#[cfg(feature = "pipe-stats")]
+		assert!(
+			times.pipe_stats(stage).syncs > 0,
+			"{:?} did not sync",
+			stage
+		);
+	}
+	assert!(times.get(Stage::Decoder).is_zero());
+	check_sum(&times, elapsed);
+
+	let start: Instant = Instant::now();
+	let (decoded, times): (Coded, StageTimes) = time_stages(|| {
+		decode_with::<_, _, IO_BUFFER_SIZE>(compressed.as_slice(), Vec::new(), &config)
+	});
+	let elapsed: Duration = start.elapsed();
+	assert_eq!(decoded?.1, input);
+	assert!(!times.get(Stage::Decoder).is_zero());
+	assert!(times.get(Stage::PrimaryEncoder).is_zero());
+	check_sum(&times, elapsed);
+
+	// a pipeline started on another thread is not timed by this one
+	let (spawned, times): (AnyResult<()>, StageTimes) = time_stages(|| {
+		thread::scope(|scope| {
+			scope
+				.spawn(|| {
+					encode_with::<_, _, IO_BUFFER_SIZE, MESSAGE_BUFFER_SIZE>(
+						&b"on another thread"[..],
+						Vec::new(),
+						&config,
+					)
+					.map(|_| ())
+				})
+				.join()
+				.unwrap()
+		})
+	});
+	spawned?;
+	assert!(times.total().is_zero());
+	Ok(())
+}
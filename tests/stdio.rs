@@ -0,0 +1,74 @@
+/*
+ * srx: The fast Symbol Ranking based compressor.
+ * Copyright (C) 2023  Mai Thanh Minh (a.k.a. thanhminhmr)
+ *
+ * This program is free software: you can redistribute it and/or modify it under
+ * the terms of the GNU General Public License as published by the Free Software
+ * Foundation, either  version 3 of the  License,  or (at your option) any later
+ * version.
+ *
+ * This program  is distributed in the hope  that it will be useful, but WITHOUT
+ * ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+ * FOR  A PARTICULAR PURPOSE. See  the  GNU  General  Public   License  for more
+ * details.
+ *
+ * You should have received a copy of the GNU General Public License along with
+ * this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+// The binary in a shell pipeline, - standing for the standard input and output, on Unix only.
+#![cfg(unix)]
+
+use std::io;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+// -----------------------------------------------
+
+// runs srx with the input piped in, returning what it wrote to the standard output and error
+fn piped(args: &[&str], input: Vec<u8>) -> io::Result<Output> {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_srx"))
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+	let mut stdin = child.stdin.take().unwrap();
+	let feeding = thread::spawn(move || stdin.write_all(&input));
+	let output: Output = child.wait_with_output()?;
+	feeding.join().unwrap()?;
+	Ok(output)
+}
+
+#[test]
+fn test_standard_streams() -> io::Result<()> {
+	let input: Vec<u8> = b"cat foo | srx c - - > foo.srx, then back again. ".repeat(3000);
+	let compressed: Output = piped(&["c", "-", "-"], input.clone())?;
+	assert!(compressed.status.success());
+	// the output alone on the standard output, the report with the sizes counted on the error
+	assert!(compressed.stdout.len() < input.len() / 10);
+	let report: String = String::from_utf8_lossy(&compressed.stderr).into_owned();
+	assert!(
+		report.starts_with(&format!("{} -> {} ", input.len(), compressed.stdout.len())),
+		"{}",
+		report
+	);
+
+	let decompressed: Output = piped(&["d", "-", "-"], compressed.stdout.clone())?;
+	assert!(decompressed.status.success());
+	assert!(decompressed.stdout == input);
+	let report: String = String::from_utf8_lossy(&decompressed.stderr).into_owned();
+	assert!(
+		report.starts_with(&format!("{} -> {} ", compressed.stdout.len(), input.len())),
+		"{}",
+		report
+	);
+
+	// a failure is told on the error too, nothing going to the output
+	let failed: Output = piped(&["d", "-", "-"], b"not an srx stream".to_vec())?;
+	assert!(!failed.status.success());
+	assert!(failed.stdout.is_empty());
+	assert!(String::from_utf8_lossy(&failed.stderr).contains("Error occurred!"));
+	Ok(())
+}